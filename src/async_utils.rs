@@ -2,9 +2,49 @@
 
 #[cfg(any(feature = "async-monoio", feature = "async-pollster"))]
 mod pollster_utils;
+mod block_on_all;
+mod blocking_drop;
+mod cancel;
+mod executor_handle;
+#[cfg(feature = "futures-pool")]
+mod futures_pool;
+#[cfg(feature = "io-compat")]
+pub mod io_compat;
+mod local_executor;
+pub mod mpsc;
+mod poll_ext;
+mod race;
+mod scope;
+mod timer;
+mod waker;
 
 pub use futures::future::{join_all, select_all};
 
+pub use crate::poll_ready;
+
+pub use crate::async_utils::block_on_all::{block_on_all, block_on_both};
+pub use crate::async_utils::blocking_drop::{SpawnOnDrop, block_on_drop};
+pub use crate::async_utils::cancel::{Cancelled, CancellationToken, WaitForCancellation};
+#[cfg(feature = "either")]
+pub use crate::async_utils::cancel::RunUntilCancelled;
+pub use crate::async_utils::executor_handle::{ExecutorHandle, ExecutorKind, current};
+#[cfg(feature = "futures-pool")]
+pub use crate::async_utils::futures_pool::{FuturesPool, JoinError, PoolJoinHandle};
+pub use crate::async_utils::local_executor::{LocalJoinHandle, block_on_local, spawn_local};
+pub use crate::async_utils::poll_ext::{PollExt, PollFn, poll_fn};
+pub use crate::async_utils::race::{Race, RaceOk, race, race_ok};
+pub use crate::async_utils::scope::{Scope, ScopeFuture, scope};
+pub use crate::async_utils::timer::{
+    Elapsed,
+    Sleep,
+    Timeout,
+    sleep,
+    sleep_until,
+    timeout,
+    timeout_at
+};
+pub use crate::async_utils::waker::{noop_context, noop_waker, waker_from_fn, waker_from_unpark};
+
 #[cfg(feature = "async-tokio")]
 pub use tokio::{
     sync::{
@@ -0,0 +1,173 @@
+//! Blocking on several futures at once, polling them concurrently on the current thread
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::{Context, Poll, Waker};
+use std::thread::{self, Thread};
+
+use crate::async_utils::race::PinnedBuffer;
+use crate::async_utils::waker::waker_from_fn;
+
+fn flag_waker(flag: Arc<AtomicBool>, thread: Thread) -> Waker {
+    waker_from_fn(move || {
+        flag.store(true, Ordering::SeqCst);
+        thread.unpark();
+    })
+}
+
+/// Block on all `futs`, returning their outputs in the same order
+///
+/// The futures are polled round-robin on the current thread, each one only when it has been
+/// woken, and the thread parks when none of them can make progress. This allows futures
+/// waiting on each other (for example via a channel) to complete, without spawning any task.
+pub fn block_on_all<F: Future>(futs: Vec<F>) -> Vec<F::Output> {
+    let count: usize = futs.len();
+    let mut buffer: PinnedBuffer<F> = PinnedBuffer::new(futs);
+    let mut outputs: Vec<Option<F::Output>> = (0..count).map(|_| None).collect();
+    let mut remaining: usize = count;
+
+    let flags: Vec<Arc<AtomicBool>> = (0..count)
+        .map(|_| Arc::new(AtomicBool::new(true)))
+        .collect();
+    let wakers: Vec<Waker> = flags.iter()
+        .map(|flag| flag_waker(flag.clone(), thread::current()))
+        .collect();
+
+    while remaining != 0 {
+        let mut polled: bool = false;
+        for idx in 0..buffer.len() {
+            if !flags[idx].swap(false, Ordering::SeqCst) {
+                continue;
+            }
+            polled = true;
+            let mut cx: Context<'_> = Context::from_waker(&wakers[idx]);
+            if let Some(Poll::Ready(output)) = buffer.poll_at(idx, &mut cx) {
+                outputs[idx] = Some(output);
+                remaining -= 1;
+            }
+        }
+        if !polled {
+            thread::park();
+        }
+    }
+
+    outputs.into_iter().map(Option::unwrap).collect()
+}
+
+/// Block on both `a` and `b`, polling them concurrently on the current thread
+///
+/// This is the heterogeneous counterpart of `block_on_all`.
+pub fn block_on_both<A: Future, B: Future>(a: A, b: B) -> (A::Output, B::Output) {
+    let mut a: Pin<&mut A> = std::pin::pin!(a);
+    let mut b: Pin<&mut B> = std::pin::pin!(b);
+    let (mut a_output, mut b_output): (Option<A::Output>, Option<B::Output>) = (None, None);
+
+    let a_flag: Arc<AtomicBool> = Arc::new(AtomicBool::new(true));
+    let b_flag: Arc<AtomicBool> = Arc::new(AtomicBool::new(true));
+    let a_waker: Waker = flag_waker(a_flag.clone(), thread::current());
+    let b_waker: Waker = flag_waker(b_flag.clone(), thread::current());
+
+    loop {
+        let mut polled: bool = false;
+        if a_output.is_none() && a_flag.swap(false, Ordering::SeqCst) {
+            polled = true;
+            if let Poll::Ready(output) = a.as_mut().poll(&mut Context::from_waker(&a_waker)) {
+                a_output = Some(output);
+            }
+        }
+        if b_output.is_none() && b_flag.swap(false, Ordering::SeqCst) {
+            polled = true;
+            if let Poll::Ready(output) = b.as_mut().poll(&mut Context::from_waker(&b_waker)) {
+                b_output = Some(output);
+            }
+        }
+
+        if a_output.is_some() && b_output.is_some() {
+            break;
+        }
+        if !polled {
+            thread::park();
+        }
+    }
+
+    (a_output.unwrap(), b_output.unwrap())
+}
+
+#[cfg(test)]
+mod test {
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::thread;
+    use std::time::Duration;
+
+    use crate::async_utils::{block_on_all, block_on_both, yield_now};
+    use crate::async_utils::mpsc::{Receiver, Sender, channel};
+
+    #[test]
+    fn test_block_on_all_order() {
+        let outputs: Vec<usize> = block_on_all((0..5).map(|n| async move {
+            for _ in 0..(5 - n) {
+                yield_now().await;
+            }
+            n * 2
+        }).collect());
+        assert_eq!(outputs, vec![0, 2, 4, 6, 8]);
+    }
+
+    #[test]
+    fn test_block_on_all_ping_pong() {
+        let (ping_tx, mut ping_rx): (Sender<u32>, Receiver<u32>) = channel(1);
+        let (pong_tx, mut pong_rx): (Sender<u32>, Receiver<u32>) = channel(1);
+
+        let futs: Vec<Pin<Box<dyn Future<Output = u32>>>> = vec![
+            Box::pin(async move {
+                let mut last: u32 = 0;
+                for i in 0..100 {
+                    ping_tx.send(i).await.unwrap();
+                    last = pong_rx.recv().await.unwrap();
+                }
+                last
+            }),
+            Box::pin(async move {
+                let mut count: u32 = 0;
+                while let Some(i) = ping_rx.recv().await {
+                    pong_tx.send(i + 1).await.unwrap();
+                    count += 1;
+                }
+                count
+            })
+        ];
+        assert_eq!(block_on_all(futs), vec![100, 100]);
+    }
+
+    #[test]
+    fn test_block_on_both() {
+        let (tx, mut rx): (Sender<String>, Receiver<String>) = channel(1);
+        let (a, b) = block_on_both(
+            async move { rx.recv().await.unwrap() },
+            async move {
+                tx.send("114514".to_string()).await.unwrap();
+                1919810
+            }
+        );
+        assert_eq!(a, "114514");
+        assert_eq!(b, 1919810);
+    }
+
+    #[test]
+    fn test_block_on_both_cross_thread() {
+        let (tx, mut rx): (Sender<i32>, Receiver<i32>) = channel(1);
+        let (a, ()) = block_on_both(
+            async move { rx.recv().await.unwrap() },
+            async move {
+                thread::spawn(move || {
+                    thread::sleep(Duration::from_millis(50));
+                    tx.try_send(893).unwrap();
+                });
+            }
+        );
+        assert_eq!(a, 893);
+    }
+}
@@ -0,0 +1,234 @@
+//! Running asynchronous cleanup from synchronous `Drop` implementations
+
+use std::future::Future;
+use std::ops::{Deref, DerefMut};
+
+use crate::async_utils::local_executor::{in_block_on_local, spawn_local};
+use crate::diag_intern::diagnostic;
+#[cfg(not(feature = "async-astd"))]
+use crate::async_utils::local_executor::block_on_local;
+
+#[cfg(feature = "async-tokio")]
+use tokio::runtime::{Handle, RuntimeFlavor};
+
+fn blocking_impossible() {
+    diagnostic("block_on_drop called where blocking would deadlock, cleanup future dropped");
+}
+
+/// Block on the given cleanup `Future`, intended specifically for `Drop` implementations
+///
+/// Depending on the calling context, this function:
+/// - uses `tokio::task::block_in_place` together with the runtime handle, if called within a
+///   multi-threaded `tokio` runtime;
+/// - prints a warning and drops `fut` **without running it**, if called within a current-thread
+///   `tokio` runtime or within `block_on_local`, since blocking there would deadlock;
+/// - otherwise blocks with `block_on_local` (or `async_std::task::block_on` for `async-std`).
+pub fn block_on_drop<F: Future<Output = ()>>(fut: F) {
+    #[cfg(feature = "async-tokio")]
+    if let Ok(handle) = Handle::try_current() {
+        match handle.runtime_flavor() {
+            RuntimeFlavor::CurrentThread => blocking_impossible(),
+            _ => tokio::task::block_in_place(|| handle.block_on(fut))
+        }
+        return;
+    }
+
+    if in_block_on_local() {
+        blocking_impossible();
+        return;
+    }
+
+    #[cfg(feature = "async-astd")]
+    async_std::task::block_on(fut);
+    #[cfg(not(feature = "async-astd"))]
+    block_on_local(fut);
+}
+
+fn spawn_cleanup<F>(fut: F)
+    where F: Future<Output = ()> + Send + 'static
+{
+    // the innermost executor is `block_on_local`, even if it runs within a `tokio` runtime
+    if in_block_on_local() {
+        drop(spawn_local(fut));
+        return;
+    }
+
+    #[cfg(feature = "async-tokio")]
+    if let Ok(handle) = Handle::try_current() {
+        drop(handle.spawn(fut));
+        return;
+    }
+
+    #[cfg(feature = "async-astd")]
+    drop(async_std::task::spawn(fut));
+    #[cfg(not(feature = "async-astd"))]
+    block_on_drop(fut);
+}
+
+/// Wrapper running an asynchronous cleanup when dropped, detaching it if possible
+///
+/// On drop, the wrapped value is passed to the cleanup function, and the resulting future gets
+/// spawned as a detached task if a runtime is available. If no runtime is available, the cleanup
+/// future is run with `block_on_drop` instead.
+pub struct SpawnOnDrop<T, C, F>
+    where C: FnOnce(T) -> F,
+          F: Future<Output = ()> + Send + 'static
+{
+    value: Option<T>,
+    cleanup: Option<C>
+}
+
+impl<T, C, F> SpawnOnDrop<T, C, F>
+    where C: FnOnce(T) -> F,
+          F: Future<Output = ()> + Send + 'static
+{
+    pub fn new(value: T, cleanup: C) -> Self {
+        Self { value: Some(value), cleanup: Some(cleanup) }
+    }
+
+    /// Take the wrapped value out, without running the cleanup
+    pub fn into_inner(mut self) -> T {
+        self.cleanup = None;
+        self.value.take().unwrap()
+    }
+}
+
+impl<T, C, F> Deref for SpawnOnDrop<T, C, F>
+    where C: FnOnce(T) -> F,
+          F: Future<Output = ()> + Send + 'static
+{
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        self.value.as_ref().unwrap()
+    }
+}
+
+impl<T, C, F> DerefMut for SpawnOnDrop<T, C, F>
+    where C: FnOnce(T) -> F,
+          F: Future<Output = ()> + Send + 'static
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.value.as_mut().unwrap()
+    }
+}
+
+impl<T, C, F> Drop for SpawnOnDrop<T, C, F>
+    where C: FnOnce(T) -> F,
+          F: Future<Output = ()> + Send + 'static
+{
+    fn drop(&mut self) {
+        if let (Some(value), Some(cleanup)) = (self.value.take(), self.cleanup.take()) {
+            spawn_cleanup(cleanup(value));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use crate::async_utils::{SpawnOnDrop, block_on_drop, block_on_local, yield_now};
+
+    struct AsyncResource {
+        flushed: Arc<AtomicBool>
+    }
+
+    impl Drop for AsyncResource {
+        fn drop(&mut self) {
+            let flushed: Arc<AtomicBool> = self.flushed.clone();
+            block_on_drop(async move {
+                yield_now().await;
+                flushed.store(true, Ordering::SeqCst);
+            });
+        }
+    }
+
+    #[test]
+    fn test_block_on_drop_no_runtime() {
+        let flushed: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+        drop(AsyncResource { flushed: flushed.clone() });
+        assert!(flushed.load(Ordering::SeqCst));
+    }
+
+    #[cfg(feature = "async-tokio")]
+    #[test]
+    fn test_block_on_drop_tokio_multi_thread() {
+        let flushed: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+        let flushed2: Arc<AtomicBool> = flushed.clone();
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        runtime.block_on(async move {
+            tokio::spawn(async move {
+                drop(AsyncResource { flushed: flushed2 });
+            }).await.unwrap();
+        });
+        assert!(flushed.load(Ordering::SeqCst));
+    }
+
+    #[cfg(feature = "async-tokio")]
+    #[test]
+    fn test_spawn_on_drop_tokio() {
+        use std::time::Duration;
+
+        let flushed: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+        let flushed2: Arc<AtomicBool> = flushed.clone();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        runtime.block_on(async move {
+            let wrapper = SpawnOnDrop::new(flushed2, |flushed: Arc<AtomicBool>| async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                flushed.store(true, Ordering::SeqCst);
+            });
+            assert!(!wrapper.load(Ordering::SeqCst));
+            drop(wrapper);
+            while !flushed.load(Ordering::SeqCst) {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        });
+    }
+
+    #[test]
+    fn test_spawn_on_drop_no_runtime() {
+        let flushed: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+        let wrapper = SpawnOnDrop::new(flushed.clone(), |flushed: Arc<AtomicBool>| async move {
+            flushed.store(true, Ordering::SeqCst);
+        });
+        drop(wrapper);
+        // `async-std` always has a global runtime, so the cleanup gets detached there
+        #[cfg(feature = "async-astd")]
+        while !flushed.load(Ordering::SeqCst) {
+            std::thread::yield_now();
+        }
+        assert!(flushed.load(Ordering::SeqCst));
+
+        let wrapper = SpawnOnDrop::new(flushed.clone(), |flushed: Arc<AtomicBool>| async move {
+            flushed.store(false, Ordering::SeqCst);
+        });
+        let _inner: Arc<AtomicBool> = wrapper.into_inner();
+        assert!(flushed.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_spawn_on_drop_block_on_local() {
+        let flushed: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+        let flushed2: Arc<AtomicBool> = flushed.clone();
+        block_on_local(async move {
+            let wrapper = SpawnOnDrop::new(flushed2, |flushed: Arc<AtomicBool>| async move {
+                yield_now().await;
+                flushed.store(true, Ordering::SeqCst);
+            });
+            drop(wrapper);
+            // the cleanup is spawned onto this loop, so it cannot have run yet
+            assert!(!flushed.load(Ordering::SeqCst));
+            while !flushed.load(Ordering::SeqCst) {
+                yield_now().await;
+            }
+        });
+    }
+}
@@ -0,0 +1,281 @@
+//! Portable cancellation tokens, working on every backend
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, Weak};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::{Context, Poll, Waker};
+
+#[cfg(feature = "either")] use crate::either::Either;
+
+#[derive(Default)]
+struct TokenState {
+    waiters: Vec<Option<Waker>>,
+    free_slots: Vec<usize>,
+    children: Vec<Weak<TokenNode>>
+}
+
+#[derive(Default)]
+struct TokenNode {
+    cancelled: AtomicBool,
+    state: Mutex<TokenState>
+}
+
+impl TokenNode {
+    fn cancel(&self) {
+        let state: TokenState = {
+            let mut state = self.state.lock().unwrap();
+            if self.cancelled.swap(true, Ordering::SeqCst) {
+                return;
+            }
+            std::mem::take(&mut *state)
+        };
+
+        for waker in state.waiters.into_iter().flatten() {
+            waker.wake();
+        }
+        for child in state.children {
+            if let Some(child) = child.upgrade() {
+                child.cancel();
+            }
+        }
+    }
+}
+
+/// A token for signalling cancellation to any number of waiters
+///
+/// Clones of a token share the same cancellation state. A child token created with
+/// `child_token` gets cancelled together with its parent, but cancelling the child does not
+/// affect the parent.
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    node: Arc<TokenNode>
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel this token and all its children, waking all waiters
+    ///
+    /// Cancelling an already cancelled token is a no-op.
+    pub fn cancel(&self) {
+        self.node.cancel()
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.node.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Create a child token, which is already cancelled if this token is
+    pub fn child_token(&self) -> CancellationToken {
+        let child: CancellationToken = CancellationToken::new();
+        let mut state = self.node.state.lock().unwrap();
+        if self.is_cancelled() {
+            child.node.cancelled.store(true, Ordering::SeqCst);
+        } else {
+            // prune children dropped meanwhile, so that long-lived parents do not leak
+            state.children.retain(|child| child.strong_count() != 0);
+            state.children.push(Arc::downgrade(&child.node));
+        }
+        child
+    }
+
+    /// Wait until this token gets cancelled
+    pub fn cancelled(&self) -> WaitForCancellation<'_> {
+        WaitForCancellation { token: self, slot: None }
+    }
+
+    /// Run `fut` until it completes or this token gets cancelled, whichever comes first
+    ///
+    /// If the token is already cancelled, `fut` is not polled at all.
+    #[cfg(feature = "either")]
+    pub fn run_until_cancelled<F: Future>(&self, fut: F) -> RunUntilCancelled<'_, F> {
+        RunUntilCancelled { cancelled: self.cancelled(), fut }
+    }
+}
+
+/// Future returned by `CancellationToken::cancelled`
+pub struct WaitForCancellation<'a> {
+    token: &'a CancellationToken,
+    slot: Option<usize>
+}
+
+impl<'a> Future for WaitForCancellation<'a> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let token: &'a CancellationToken = self.token;
+        if token.is_cancelled() {
+            return Poll::Ready(());
+        }
+
+        let node: &TokenNode = &token.node;
+        let mut state = node.state.lock().unwrap();
+        if node.cancelled.load(Ordering::SeqCst) {
+            return Poll::Ready(());
+        }
+
+        let waker: Waker = cx.waker().clone();
+        match self.slot {
+            Some(slot) => state.waiters[slot] = Some(waker),
+            None => {
+                let slot: usize = if let Some(slot) = state.free_slots.pop() {
+                    state.waiters[slot] = Some(waker);
+                    slot
+                } else {
+                    state.waiters.push(Some(waker));
+                    state.waiters.len() - 1
+                };
+                self.slot = Some(slot);
+            }
+        }
+        Poll::Pending
+    }
+}
+
+impl<'a> Drop for WaitForCancellation<'a> {
+    fn drop(&mut self) {
+        if let Some(slot) = self.slot {
+            let node: &TokenNode = &self.token.node;
+            let mut state = node.state.lock().unwrap();
+            // waiters have all been taken away by `cancel` otherwise
+            if !node.cancelled.load(Ordering::SeqCst) {
+                state.waiters[slot] = None;
+                state.free_slots.push(slot);
+            }
+        }
+    }
+}
+
+/// Marker for futures stopped by `CancellationToken::run_until_cancelled`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Cancelled;
+
+/// Future returned by `CancellationToken::run_until_cancelled`
+#[cfg(feature = "either")]
+pub struct RunUntilCancelled<'a, F> {
+    cancelled: WaitForCancellation<'a>,
+    fut: F
+}
+
+#[cfg(feature = "either")]
+impl<'a, F: Future> Future for RunUntilCancelled<'a, F> {
+    type Output = Either<F::Output, Cancelled>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // SAFETY: `fut` is never moved out of a pinned `RunUntilCancelled`, and
+        // `WaitForCancellation` is `Unpin`
+        let this: &mut Self = unsafe { self.get_unchecked_mut() };
+        if Pin::new(&mut this.cancelled).poll(cx).is_ready() {
+            return Poll::Ready(Either::Right(Cancelled));
+        }
+        let fut: Pin<&mut F> = unsafe { Pin::new_unchecked(&mut this.fut) };
+        fut.poll(cx).map(Either::Left)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::task::{Context, Wake, Waker};
+
+    use crate::async_utils::{CancellationToken, block_on_local, spawn_local, yield_now};
+
+    struct CountingWaker(AtomicUsize);
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn test_propagation() {
+        let parent: CancellationToken = CancellationToken::new();
+        let child: CancellationToken = parent.child_token();
+        let grandchild: CancellationToken = child.child_token();
+        let child2: CancellationToken = parent.child_token();
+
+        child2.cancel();
+        assert!(child2.is_cancelled());
+        assert!(!parent.is_cancelled());
+        assert!(!child.is_cancelled());
+
+        let child3 = child.clone();
+        block_on_local(async move {
+            let handle = spawn_local(async move { grandchild.cancelled().await });
+            yield_now().await;
+            parent.cancel();
+            handle.await;
+            assert!(child3.is_cancelled());
+            assert!(parent.child_token().is_cancelled());
+        });
+        assert!(child.is_cancelled());
+    }
+
+    #[test]
+    fn test_woken_once() {
+        let token: CancellationToken = CancellationToken::new();
+        let counter: Arc<CountingWaker> = Arc::new(CountingWaker(AtomicUsize::new(0)));
+        let waker: Waker = Waker::from(counter.clone());
+        let mut cx: Context<'_> = Context::from_waker(&waker);
+
+        let mut fut1 = token.cancelled();
+        let mut fut2 = token.cancelled();
+        assert!(Pin::new(&mut fut1).poll(&mut cx).is_pending());
+        assert!(Pin::new(&mut fut1).poll(&mut cx).is_pending());
+        assert!(Pin::new(&mut fut2).poll(&mut cx).is_pending());
+
+        token.cancel();
+        token.cancel();
+        assert_eq!(counter.0.load(Ordering::SeqCst), 2);
+        assert!(Pin::new(&mut fut1).poll(&mut cx).is_ready());
+        assert!(Pin::new(&mut fut2).poll(&mut cx).is_ready());
+    }
+
+    #[test]
+    fn test_no_leak() {
+        let token: CancellationToken = CancellationToken::new();
+        let waker: Waker = Waker::from(Arc::new(CountingWaker(AtomicUsize::new(0))));
+        let mut cx: Context<'_> = Context::from_waker(&waker);
+
+        for _ in 0..16 {
+            let mut fut = token.cancelled();
+            assert!(Pin::new(&mut fut).poll(&mut cx).is_pending());
+            drop(token.child_token());
+        }
+        let state = token.node.state.lock().unwrap();
+        assert_eq!(state.waiters.len(), 1);
+        assert!(state.waiters[0].is_none());
+        assert_eq!(state.children.len(), 1);
+    }
+
+    #[cfg(feature = "either")]
+    #[test]
+    fn test_run_until_cancelled() {
+        use crate::async_utils::Cancelled;
+        use crate::either::Either;
+
+        let token: CancellationToken = CancellationToken::new();
+        block_on_local(async move {
+            let r = token.run_until_cancelled(async { 114 }).await;
+            assert!(matches!(r, Either::Left(114)));
+
+            let token2: CancellationToken = token.clone();
+            drop(spawn_local(async move {
+                yield_now().await;
+                token2.cancel();
+            }));
+            let r = token.run_until_cancelled(std::future::pending::<()>()).await;
+            assert!(matches!(r, Either::Right(Cancelled)));
+
+            let r = token.run_until_cancelled(async { 514 }).await;
+            assert!(matches!(r, Either::Right(Cancelled)));
+        });
+    }
+}
@@ -0,0 +1,174 @@
+//! Detecting the ambient executor at runtime
+
+use std::future::Future;
+use std::sync::Arc;
+
+use crate::async_utils::local_executor::{ReadyQueue, current_ready_queue};
+
+/// Kind of executor detected by `current`
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum ExecutorKind {
+    /// The crate's own `block_on_local` loop
+    Local,
+    /// A `tokio` runtime
+    Tokio,
+    /// The global `async-std` executor
+    AsyncStd
+}
+
+#[derive(Clone)]
+enum HandleInner {
+    Local(Arc<ReadyQueue>),
+    #[cfg(feature = "async-tokio")]
+    Tokio(tokio::runtime::Handle),
+    #[cfg(feature = "async-astd")]
+    AsyncStd
+}
+
+/// Handle to an executor, obtained with `current`
+///
+/// The handle may be sent to, and used from any thread, including threads not running any
+/// executor at all.
+#[derive(Clone)]
+pub struct ExecutorHandle {
+    inner: HandleInner
+}
+
+impl ExecutorHandle {
+    /// Kind of the executor this handle refers to
+    pub fn kind(&self) -> ExecutorKind {
+        match &self.inner {
+            HandleInner::Local(_) => ExecutorKind::Local,
+            #[cfg(feature = "async-tokio")]
+            HandleInner::Tokio(_) => ExecutorKind::Tokio,
+            #[cfg(feature = "async-astd")]
+            HandleInner::AsyncStd => ExecutorKind::AsyncStd
+        }
+    }
+
+    /// Spawn a detached task onto the executor this handle refers to
+    ///
+    /// For `ExecutorKind::Local`, tasks spawned after the corresponding `block_on_local` returns
+    /// are silently dropped.
+    pub fn spawn<F>(&self, fut: F)
+        where F: Future<Output = ()> + Send + 'static
+    {
+        match &self.inner {
+            HandleInner::Local(queue) => queue.spawn_remote(Box::pin(fut)),
+            #[cfg(feature = "async-tokio")]
+            HandleInner::Tokio(handle) => drop(handle.spawn(fut)),
+            #[cfg(feature = "async-astd")]
+            HandleInner::AsyncStd => drop(async_std::task::spawn(fut))
+        }
+    }
+}
+
+/// Detect the executor driving current thread
+///
+/// Detection order is: `block_on_local` running on current thread, ambient `tokio` runtime
+/// (via `tokio::runtime::Handle::try_current`), and the `async-std` global executor, which is
+/// considered always available. `monoio` contexts cannot be detected and yield `None`.
+pub fn current() -> Option<ExecutorHandle> {
+    if let Some(queue) = current_ready_queue() {
+        return Some(ExecutorHandle { inner: HandleInner::Local(queue) });
+    }
+
+    #[cfg(feature = "async-tokio")]
+    if let Ok(handle) = tokio::runtime::Handle::try_current() {
+        return Some(ExecutorHandle { inner: HandleInner::Tokio(handle) });
+    }
+
+    #[cfg(feature = "async-astd")]
+    return Some(ExecutorHandle { inner: HandleInner::AsyncStd });
+
+    #[cfg(not(feature = "async-astd"))]
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::mpsc;
+    use std::thread;
+
+    use crate::async_utils::{ExecutorHandle, ExecutorKind, block_on_local, current};
+
+    #[test]
+    fn test_detect_local() {
+        #[cfg(not(feature = "async-astd"))]
+        assert!(current().is_none());
+
+        let (tx, rx) = mpsc::channel::<i32>();
+        block_on_local(async move {
+            let handle: ExecutorHandle = current().unwrap();
+            assert_eq!(handle.kind(), ExecutorKind::Local);
+
+            let (tx2, rx2) = mpsc::channel::<()>();
+            thread::spawn(move || {
+                handle.spawn(async move {
+                    tx.send(114514).unwrap();
+                    tx2.send(()).unwrap();
+                });
+            });
+            std::future::poll_fn(|cx| {
+                if rx2.try_recv().is_ok() {
+                    std::task::Poll::Ready(())
+                } else {
+                    cx.waker().wake_by_ref();
+                    std::task::Poll::Pending
+                }
+            }).await;
+        });
+        assert_eq!(rx.recv().unwrap(), 114514);
+    }
+
+    #[test]
+    fn test_local_drop_respawning_task() {
+        struct Respawn(ExecutorHandle);
+
+        impl Drop for Respawn {
+            fn drop(&mut self) {
+                self.0.spawn(async {});
+            }
+        }
+
+        block_on_local(async {
+            let handle: ExecutorHandle = current().unwrap();
+            let respawn: Respawn = Respawn(handle.clone());
+            // the main future completes before this task is ever picked up
+            handle.spawn(async move {
+                let _respawn: Respawn = respawn;
+            });
+        });
+    }
+
+    #[cfg(feature = "async-tokio")]
+    #[test]
+    fn test_detect_tokio() {
+        let runtime = tokio::runtime::Builder::new_multi_thread().build().unwrap();
+        let handle: ExecutorHandle = runtime.block_on(async { current() }).unwrap();
+        assert_eq!(handle.kind(), ExecutorKind::Tokio);
+
+        let (tx, rx) = mpsc::channel::<i32>();
+        thread::spawn(move || {
+            handle.spawn(async move {
+                tx.send(1919810).unwrap();
+            });
+        }).join().unwrap();
+        assert_eq!(rx.recv().unwrap(), 1919810);
+    }
+
+    #[cfg(feature = "async-astd")]
+    #[test]
+    fn test_detect_astd() {
+        let handle: ExecutorHandle = current().unwrap();
+        assert_eq!(handle.kind(), ExecutorKind::AsyncStd);
+
+        let (tx, rx) = mpsc::channel::<i32>();
+        thread::spawn(move || {
+            handle.spawn(async move {
+                tx.send(893).unwrap();
+            });
+        }).join().unwrap();
+        assert_eq!(rx.recv().unwrap(), 893);
+    }
+}
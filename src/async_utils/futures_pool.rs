@@ -0,0 +1,185 @@
+//! Executor backed by `futures::executor::ThreadPool`
+
+use std::any::Any;
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+use std::future::Future;
+use std::io;
+use std::panic::AssertUnwindSafe;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::FutureExt;
+use futures::channel::oneshot;
+use futures::executor::ThreadPool;
+
+/// Error returned by awaiting a `PoolJoinHandle`
+pub enum JoinError {
+    /// The task panicked, carrying the panic payload
+    Panicked(Box<dyn Any + Send + 'static>),
+    /// The task was dropped before completion
+    Cancelled
+}
+
+impl JoinError {
+    pub fn is_panic(&self) -> bool {
+        matches!(self, JoinError::Panicked(_))
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        matches!(self, JoinError::Cancelled)
+    }
+}
+
+impl Debug for JoinError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JoinError::Panicked(_) => write!(f, "JoinError::Panicked(..)"),
+            JoinError::Cancelled => write!(f, "JoinError::Cancelled")
+        }
+    }
+}
+
+impl Display for JoinError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JoinError::Panicked(_) => write!(f, "task panicked"),
+            JoinError::Cancelled => write!(f, "task cancelled")
+        }
+    }
+}
+
+impl Error for JoinError {}
+
+/// Handle of a task spawned onto a `FuturesPool`
+///
+/// Dropping the handle detaches the task, it keeps running.
+pub struct PoolJoinHandle<T> {
+    rx: oneshot::Receiver<std::thread::Result<T>>
+}
+
+impl<T> Future for PoolJoinHandle<T> {
+    type Output = Result<T, JoinError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match Pin::new(&mut self.rx).poll(cx) {
+            Poll::Ready(Ok(Ok(output))) => Poll::Ready(Ok(output)),
+            Poll::Ready(Ok(Err(payload))) => Poll::Ready(Err(JoinError::Panicked(payload))),
+            Poll::Ready(Err(oneshot::Canceled)) => Poll::Ready(Err(JoinError::Cancelled)),
+            Poll::Pending => Poll::Pending
+        }
+    }
+}
+
+/// A cloneable executor running tasks on a `futures::executor::ThreadPool`
+#[derive(Clone)]
+pub struct FuturesPool {
+    pool: ThreadPool
+}
+
+impl FuturesPool {
+    /// Create a pool with one thread per CPU core
+    pub fn new() -> io::Result<Self> {
+        Ok(Self { pool: ThreadPool::new()? })
+    }
+
+    /// Create a pool with exactly `size` threads
+    pub fn with_size(size: usize) -> io::Result<Self> {
+        Ok(Self { pool: ThreadPool::builder().pool_size(size).create()? })
+    }
+
+    /// Spawn a task onto the pool, returning a handle for its output
+    ///
+    /// Panics in the task are caught and reported as `JoinError::Panicked`.
+    pub fn spawn<F>(&self, fut: F) -> PoolJoinHandle<F::Output>
+        where F: Future + Send + 'static,
+              F::Output: Send + 'static
+    {
+        let (tx, rx) = oneshot::channel();
+        self.pool.spawn_ok(async move {
+            let output = AssertUnwindSafe(fut).catch_unwind().await;
+            let _ = tx.send(output);
+        });
+        PoolJoinHandle { rx }
+    }
+
+    /// Block current thread on given `Future` with `futures::executor::block_on`
+    ///
+    /// The future is run on current thread, not on the pool.
+    pub fn block_on<F: Future>(&self, fut: F) -> F::Output {
+        futures::executor::block_on(fut)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use futures::channel::oneshot;
+
+    use crate::async_utils::futures_pool::{FuturesPool, JoinError, PoolJoinHandle};
+
+    #[test]
+    fn test_spawn_join() {
+        let pool: FuturesPool = FuturesPool::with_size(4).unwrap();
+        let handles = (0..16).map(|i| pool.spawn(async move { i * 2 })).collect::<Vec<_>>();
+        let results: Vec<i32> = pool.block_on(async move {
+            let mut results: Vec<i32> = Vec::new();
+            for handle in handles {
+                results.push(handle.await.unwrap());
+            }
+            results
+        });
+        assert_eq!(results, (0..16).map(|i| i * 2).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_spawn_panic() {
+        let pool: FuturesPool = FuturesPool::with_size(1).unwrap();
+        let handle = pool.spawn(async { panic!("114514") });
+        let err: JoinError = pool.block_on(handle).unwrap_err();
+        assert!(err.is_panic());
+        if let JoinError::Panicked(payload) = err {
+            assert_eq!(*payload.downcast::<&'static str>().unwrap(), "114514");
+        }
+
+        // the pool survives a panicking task
+        assert_eq!(pool.block_on(pool.spawn(async { 1919810 })).unwrap(), 1919810);
+    }
+
+    #[test]
+    fn test_cancelled() {
+        // `ThreadPool` never drops a spawned task on its own, so drop the task's sending half
+        // directly, as dropping the task itself would
+        let (tx, rx) = oneshot::channel::<std::thread::Result<i32>>();
+        let handle: PoolJoinHandle<i32> = PoolJoinHandle { rx };
+        drop(tx);
+        let err: JoinError = futures::executor::block_on(handle).unwrap_err();
+        assert!(err.is_cancelled() && !err.is_panic());
+        assert!(matches!(err, JoinError::Cancelled));
+        assert_eq!(err.to_string(), "task cancelled");
+    }
+
+    #[test]
+    fn test_detached() {
+        let pool: FuturesPool = FuturesPool::with_size(2).unwrap();
+        let counter: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(0));
+        let counter2: Arc<AtomicUsize> = counter.clone();
+        drop(pool.spawn(async move { counter2.fetch_add(1, Ordering::SeqCst); }));
+        while counter.load(Ordering::SeqCst) == 0 {
+            std::thread::yield_now();
+        }
+    }
+
+    #[test]
+    fn test_spawn_from_pooled_task() {
+        let pool: FuturesPool = FuturesPool::with_size(2).unwrap();
+        let pool2: FuturesPool = pool.clone();
+        let handle = pool.spawn(async move {
+            let inner = pool2.spawn(async { 893 });
+            inner.await.unwrap() + 1
+        });
+        assert_eq!(pool.block_on(handle).unwrap(), 894);
+    }
+}
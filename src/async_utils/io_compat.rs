@@ -0,0 +1,268 @@
+//! Adapter between `tokio::io` and `futures::io` asynchronous IO traits
+//!
+//! `Compat<T>` exposes the `futures::io` traits if `T` implements the `tokio::io` ones, and the
+//! `tokio::io` traits if `T` implements the `futures::io` ones.
+
+use std::io::{self, SeekFrom};
+use std::pin::Pin;
+use std::task::{Context, Poll, ready};
+
+use tokio::io::ReadBuf;
+
+/// Wrapper translating between `tokio::io` and `futures::io` traits
+pub struct Compat<T> {
+    inner: T,
+    seek_pos: Option<SeekFrom>
+}
+
+impl<T> Compat<T> {
+    pub fn new(inner: T) -> Self {
+        Self { inner, seek_pos: None }
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn project(self: Pin<&mut Self>) -> (Pin<&mut T>, &mut Option<SeekFrom>) {
+        // SAFETY: `inner` is never moved out of a pinned `Compat`, and `seek_pos` is never pinned
+        unsafe {
+            let this: &mut Self = self.get_unchecked_mut();
+            (Pin::new_unchecked(&mut this.inner), &mut this.seek_pos)
+        }
+    }
+}
+
+impl<T: tokio::io::AsyncRead> futures::io::AsyncRead for Compat<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8]
+    ) -> Poll<io::Result<usize>> {
+        let mut read_buf: ReadBuf<'_> = ReadBuf::new(buf);
+        ready!(self.project().0.poll_read(cx, &mut read_buf))?;
+        Poll::Ready(Ok(read_buf.filled().len()))
+    }
+}
+
+impl<T: tokio::io::AsyncWrite> futures::io::AsyncWrite for Compat<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8]
+    ) -> Poll<io::Result<usize>> {
+        self.project().0.poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().0.poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().0.poll_shutdown(cx)
+    }
+}
+
+impl<T: tokio::io::AsyncBufRead> futures::io::AsyncBufRead for Compat<T> {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        self.project().0.poll_fill_buf(cx)
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        self.project().0.consume(amt)
+    }
+}
+
+impl<T: tokio::io::AsyncSeek> futures::io::AsyncSeek for Compat<T> {
+    fn poll_seek(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        pos: SeekFrom
+    ) -> Poll<io::Result<u64>> {
+        let (mut inner, seek_pos) = self.project();
+        if *seek_pos != Some(pos) {
+            // finish any seek started by a previous, abandoned call first
+            ready!(inner.as_mut().poll_complete(cx))?;
+            inner.as_mut().start_seek(pos)?;
+            *seek_pos = Some(pos);
+        }
+        let result: io::Result<u64> = ready!(inner.poll_complete(cx));
+        *seek_pos = None;
+        Poll::Ready(result)
+    }
+}
+
+impl<T: futures::io::AsyncRead> tokio::io::AsyncRead for Compat<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>
+    ) -> Poll<io::Result<()>> {
+        // the unfilled part of `buf` may be uninitialized, which `futures::io` does not allow
+        let unfilled: &mut [u8] = buf.initialize_unfilled();
+        let n: usize = ready!(self.project().0.poll_read(cx, unfilled))?;
+        buf.advance(n);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T: futures::io::AsyncWrite> tokio::io::AsyncWrite for Compat<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8]
+    ) -> Poll<io::Result<usize>> {
+        self.project().0.poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().0.poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().0.poll_close(cx)
+    }
+}
+
+impl<T: futures::io::AsyncBufRead> tokio::io::AsyncBufRead for Compat<T> {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        self.project().0.poll_fill_buf(cx)
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        self.project().0.consume(amt)
+    }
+}
+
+impl<T: futures::io::AsyncSeek> tokio::io::AsyncSeek for Compat<T> {
+    fn start_seek(self: Pin<&mut Self>, position: SeekFrom) -> io::Result<()> {
+        *self.project().1 = Some(position);
+        Ok(())
+    }
+
+    fn poll_complete(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+        let (inner, seek_pos) = self.project();
+        // without a pending seek, report the current position as `tokio` requires
+        let pos: SeekFrom = seek_pos.unwrap_or(SeekFrom::Current(0));
+        let result: io::Result<u64> = ready!(inner.poll_seek(cx, pos));
+        *seek_pos = None;
+        Poll::Ready(result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::SeekFrom;
+
+    use futures::future::join;
+    use futures::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::io::{AsyncBufReadExt, AsyncSeekExt, DuplexStream};
+
+    use crate::async_utils::io_compat::Compat;
+
+    // large enough to cross the duplex buffer many times, reduced under Miri to keep it quick
+    const DATA_LEN: usize = if cfg!(miri) { 4096 } else { 1024 * 1024 };
+
+    fn test_data(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 31 % 251) as u8).collect()
+    }
+
+    fn run<F: std::future::Future>(fut: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(fut)
+    }
+
+    #[test]
+    fn test_tokio_to_futures() {
+        let data: Vec<u8> = test_data(DATA_LEN);
+        let (client, server) = tokio::io::duplex(61);
+        let mut writer: Compat<DuplexStream> = Compat::new(client);
+        let mut reader: Compat<DuplexStream> = Compat::new(server);
+
+        let received: Vec<u8> = run(async {
+            let write = async {
+                writer.write_all(&data).await.unwrap();
+                writer.close().await.unwrap();
+            };
+            let read = async {
+                let mut received: Vec<u8> = Vec::new();
+                let mut chunk: [u8; 100] = [0; 100];
+                loop {
+                    let n: usize = reader.read(&mut chunk).await.unwrap();
+                    if n == 0 {
+                        break;
+                    }
+                    received.extend_from_slice(&chunk[..n]);
+                }
+                received
+            };
+            join(write, read).await.1
+        });
+        assert_eq!(received, data);
+    }
+
+    #[test]
+    fn test_futures_to_tokio() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let data: Vec<u8> = test_data(DATA_LEN + 7);
+        let (client, server) = tokio::io::duplex(127);
+        // crossing the trait boundary twice, the outer layer implements `tokio::io` on top of
+        // `futures::io`
+        let mut writer: Compat<Compat<DuplexStream>> = Compat::new(Compat::new(client));
+        let mut reader: Compat<Compat<DuplexStream>> = Compat::new(Compat::new(server));
+
+        let received: Vec<u8> = run(async {
+            let write = async {
+                writer.write_all(&data).await.unwrap();
+                writer.shutdown().await.unwrap();
+            };
+            let read = async {
+                let mut received: Vec<u8> = Vec::new();
+                let mut chunk: [u8; 100] = [0; 100];
+                loop {
+                    let n: usize = reader.read(&mut chunk).await.unwrap();
+                    if n == 0 {
+                        break;
+                    }
+                    received.extend_from_slice(&chunk[..n]);
+                }
+                received
+            };
+            join(write, read).await.1
+        });
+        assert_eq!(received, data);
+    }
+
+    #[test]
+    fn test_buf_read_seek() {
+        run(async {
+            let cursor = futures::io::Cursor::new(b"first line\nsecond line\n".to_vec());
+            let mut compat: Compat<futures::io::Cursor<Vec<u8>>> = Compat::new(cursor);
+
+            let mut line: String = String::new();
+            compat.read_line(&mut line).await.unwrap();
+            assert_eq!(line, "first line\n");
+            assert_eq!(compat.stream_position().await.unwrap(), 11);
+
+            assert_eq!(compat.seek(SeekFrom::Start(6)).await.unwrap(), 6);
+            line.clear();
+            compat.read_line(&mut line).await.unwrap();
+            assert_eq!(line, "line\n");
+
+            let mut back: Compat<Compat<futures::io::Cursor<Vec<u8>>>> = Compat::new(compat);
+            assert_eq!(futures::io::AsyncSeekExt::seek(&mut back, SeekFrom::End(-5)).await.unwrap(),
+                       18);
+            let mut rest: String = String::new();
+            back.read_to_string(&mut rest).await.unwrap();
+            assert_eq!(rest, "line\n");
+        });
+    }
+}
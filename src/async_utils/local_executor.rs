@@ -0,0 +1,356 @@
+//! A tiny single-threaded run loop, requiring no runtime at all
+//!
+//! `block_on_local` drives the given future to completion on the current thread, and also runs
+//! tasks submitted via `spawn_local` during its execution. The thread parks itself when there is
+//! nothing to poll, and wakers (even those woken from other threads) unpark it.
+//!
+//! When the main future completes, `block_on_local` returns immediately. Local tasks that are
+//! still pending at that time are **dropped** without being polled again.
+
+use std::cell::RefCell;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+
+type LocalTask = Pin<Box<dyn Future<Output = ()>>>;
+type RemoteTask = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+const MAIN_TASK_ID: usize = usize::MAX;
+
+pub(crate) struct ReadyQueue {
+    ready: Mutex<Vec<usize>>,
+    remote: Mutex<Option<Vec<RemoteTask>>>,
+    thread: Thread
+}
+
+impl ReadyQueue {
+    /// Submit a task from any thread, silently dropping it if the loop has already exited
+    pub(crate) fn spawn_remote(&self, task: RemoteTask) {
+        if let Some(remote) = self.remote.lock().unwrap().as_mut() {
+            remote.push(task);
+            self.thread.unpark();
+        }
+    }
+
+    fn take_remote(&self) -> Vec<RemoteTask> {
+        self.remote.lock().unwrap().as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+
+    fn push(&self, task_id: usize) {
+        self.ready.lock().unwrap().push(task_id);
+        self.thread.unpark();
+    }
+
+    fn take_ready(&self) -> Vec<usize> {
+        std::mem::take(&mut *self.ready.lock().unwrap())
+    }
+}
+
+struct TaskWaker {
+    task_id: usize,
+    queue: Arc<ReadyQueue>
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.queue.push(self.task_id);
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.queue.push(self.task_id);
+    }
+}
+
+struct LocalExecutor {
+    queue: Arc<ReadyQueue>,
+    tasks: RefCell<Vec<Option<LocalTask>>>,
+    free_slots: RefCell<Vec<usize>>
+}
+
+impl LocalExecutor {
+    fn new() -> Self {
+        Self {
+            queue: Arc::new(ReadyQueue {
+                ready: Mutex::new(Vec::new()),
+                remote: Mutex::new(Some(Vec::new())),
+                thread: thread::current()
+            }),
+            tasks: RefCell::new(Vec::new()),
+            free_slots: RefCell::new(Vec::new())
+        }
+    }
+
+    fn spawn(&self, task: LocalTask) {
+        let task_id: usize = if let Some(task_id) = self.free_slots.borrow_mut().pop() {
+            self.tasks.borrow_mut()[task_id] = Some(task);
+            task_id
+        } else {
+            let mut tasks = self.tasks.borrow_mut();
+            tasks.push(Some(task));
+            tasks.len() - 1
+        };
+        self.queue.push(task_id);
+    }
+
+    fn waker(&self, task_id: usize) -> Waker {
+        Waker::from(Arc::new(TaskWaker { task_id, queue: self.queue.clone() }))
+    }
+
+    fn run_task(&self, task_id: usize) {
+        // the task is taken out while being polled, so that it may `spawn_local` freely
+        let task: Option<LocalTask> = self.tasks.borrow_mut()
+            .get_mut(task_id)
+            .and_then(Option::take);
+        if let Some(mut task) = task {
+            let waker: Waker = self.waker(task_id);
+            let mut cx: Context<'_> = Context::from_waker(&waker);
+            if task.as_mut().poll(&mut cx).is_pending() {
+                self.tasks.borrow_mut()[task_id] = Some(task);
+            } else {
+                self.free_slots.borrow_mut().push(task_id);
+            }
+        }
+    }
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<Rc<LocalExecutor>>> = const { RefCell::new(None) };
+}
+
+/// Checks if current thread is running `block_on_local`
+pub(crate) fn in_block_on_local() -> bool {
+    CURRENT.with(|current| current.borrow().is_some())
+}
+
+/// Gets the ready queue of the `block_on_local` running on current thread
+pub(crate) fn current_ready_queue() -> Option<Arc<ReadyQueue>> {
+    CURRENT.with(|current| current.borrow().as_ref().map(|executor| executor.queue.clone()))
+}
+
+struct ExecutorGuard(Rc<LocalExecutor>);
+
+impl Drop for ExecutorGuard {
+    fn drop(&mut self) {
+        CURRENT.with(|current| current.borrow_mut().take());
+        // unfinished tasks get dropped here, after `CURRENT` has been reset and without holding
+        // any lock, since their destructors may spawn onto this executor again
+        let remote: Option<Vec<RemoteTask>> =
+            std::mem::take(&mut *self.0.queue.remote.lock().unwrap());
+        drop(remote);
+        let tasks: Vec<Option<LocalTask>> = std::mem::take(&mut *self.0.tasks.borrow_mut());
+        drop(tasks);
+    }
+}
+
+/// Block on the given `Future`, running locally spawned tasks meanwhile
+///
+/// Tasks spawned with `spawn_local` (either by `fut` or by other local tasks) are polled on the
+/// current thread as well. This function returns as soon as `fut` completes, dropping all local
+/// tasks that are still pending.
+///
+/// # Panics
+/// Panics if called inside another `block_on_local` on the same thread.
+pub fn block_on_local<F: Future>(fut: F) -> F::Output {
+    let executor: Rc<LocalExecutor> = Rc::new(LocalExecutor::new());
+    CURRENT.with(|current| {
+        let mut current = current.borrow_mut();
+        assert!(current.is_none(), "cannot call `block_on_local` within `block_on_local`");
+        *current = Some(executor.clone());
+    });
+    let _guard: ExecutorGuard = ExecutorGuard(executor.clone());
+
+    let mut fut: Pin<&mut F> = std::pin::pin!(fut);
+    let main_waker: Waker = executor.waker(MAIN_TASK_ID);
+    executor.queue.push(MAIN_TASK_ID);
+
+    loop {
+        for task in executor.queue.take_remote() {
+            executor.spawn(task);
+        }
+
+        let batch: Vec<usize> = executor.queue.take_ready();
+        if batch.is_empty() {
+            thread::park();
+            continue;
+        }
+
+        for task_id in batch {
+            if task_id == MAIN_TASK_ID {
+                let mut cx: Context<'_> = Context::from_waker(&main_waker);
+                if let Poll::Ready(output) = fut.as_mut().poll(&mut cx) {
+                    return output;
+                }
+            } else {
+                executor.run_task(task_id);
+            }
+        }
+    }
+}
+
+struct JoinState<R> {
+    output: Option<R>,
+    waker: Option<Waker>
+}
+
+/// Handle of a task spawned with `spawn_local`, which can be `.await`ed for its output
+pub struct LocalJoinHandle<R> {
+    state: Rc<RefCell<JoinState<R>>>
+}
+
+impl<R> LocalJoinHandle<R> {
+    /// Checks if the task has already completed
+    pub fn is_finished(&self) -> bool {
+        self.state.borrow().output.is_some()
+    }
+}
+
+impl<R> Future for LocalJoinHandle<R> {
+    type Output = R;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.state.borrow_mut();
+        if let Some(output) = state.output.take() {
+            Poll::Ready(output)
+        } else {
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+/// Spawn a task onto the `block_on_local` loop running on current thread
+///
+/// Dropping the returned `LocalJoinHandle` detaches the task, it keeps running.
+///
+/// # Panics
+/// Panics if not called within `block_on_local`.
+pub fn spawn_local<F>(fut: F) -> LocalJoinHandle<F::Output>
+    where F: Future + 'static,
+          F::Output: 'static
+{
+    let executor: Rc<LocalExecutor> = CURRENT.with(|current| current.borrow().clone())
+        .expect("`spawn_local` called outside of `block_on_local`");
+
+    let state: Rc<RefCell<JoinState<F::Output>>> = Rc::new(RefCell::new(JoinState {
+        output: None,
+        waker: None
+    }));
+    let task_state: Rc<RefCell<JoinState<F::Output>>> = state.clone();
+    executor.spawn(Box::pin(async move {
+        let output: F::Output = fut.await;
+        let waker: Option<Waker> = {
+            let mut state = task_state.borrow_mut();
+            state.output = Some(output);
+            state.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }));
+
+    LocalJoinHandle { state }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::rc::Rc;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::task::{Context, Poll};
+    use std::thread;
+    use std::time::Duration;
+
+    use crate::async_utils::local_executor::{block_on_local, spawn_local};
+    use crate::async_utils::yield_now;
+
+    #[test]
+    fn test_nested_spawn() {
+        let counter: Rc<Cell<u32>> = Rc::new(Cell::new(0));
+        let counter2: Rc<Cell<u32>> = counter.clone();
+        let result: u32 = block_on_local(async move {
+            let handle = spawn_local(async move {
+                let counter3: Rc<Cell<u32>> = counter2.clone();
+                let inner = spawn_local(async move {
+                    yield_now().await;
+                    counter3.set(counter3.get() + 1);
+                    514
+                });
+                counter2.set(counter2.get() + 1);
+                inner.await
+            });
+            handle.await
+        });
+
+        assert_eq!(result, 514);
+        assert_eq!(counter.get(), 2);
+    }
+
+    #[test]
+    fn test_cross_thread_wake() {
+        struct CrossThreadFuture {
+            flag: Arc<AtomicBool>,
+            started: bool
+        }
+
+        impl Future for CrossThreadFuture {
+            type Output = ();
+
+            fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+                if self.flag.load(Ordering::SeqCst) {
+                    return Poll::Ready(());
+                }
+                if !self.started {
+                    self.started = true;
+                    let flag: Arc<AtomicBool> = self.flag.clone();
+                    let waker = cx.waker().clone();
+                    thread::spawn(move || {
+                        thread::sleep(Duration::from_millis(100));
+                        flag.store(true, Ordering::SeqCst);
+                        waker.wake();
+                    });
+                }
+                Poll::Pending
+            }
+        }
+
+        block_on_local(async {
+            let handle = spawn_local(CrossThreadFuture {
+                flag: Arc::new(AtomicBool::new(false)),
+                started: false
+            });
+            handle.await;
+            CrossThreadFuture { flag: Arc::new(AtomicBool::new(false)), started: false }.await
+        });
+    }
+
+    #[test]
+    fn test_drop_unfinished() {
+        struct DropGuard(Rc<Cell<bool>>);
+
+        impl Drop for DropGuard {
+            fn drop(&mut self) {
+                self.0.set(true);
+            }
+        }
+
+        let dropped: Rc<Cell<bool>> = Rc::new(Cell::new(false));
+        let guard: DropGuard = DropGuard(dropped.clone());
+        block_on_local(async move {
+            let handle = spawn_local(async move {
+                let _guard: DropGuard = guard;
+                std::future::pending::<()>().await
+            });
+            yield_now().await;
+            assert!(!handle.is_finished());
+        });
+
+        assert!(dropped.get());
+    }
+}
@@ -0,0 +1,438 @@
+//! A small bounded multi-producer, single-consumer channel, working on every backend
+
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+
+struct State<T> {
+    buffer: VecDeque<T>,
+    capacity: usize,
+    senders: usize,
+    receiver_alive: bool,
+    recv_waker: Option<Waker>,
+    send_wakers: VecDeque<(usize, Waker)>,
+    next_waiter_id: usize
+}
+
+impl<T> State<T> {
+    fn wake_one_sender(&mut self) {
+        if let Some((_, waker)) = self.send_wakers.pop_front() {
+            waker.wake();
+        }
+    }
+
+    fn wake_receiver(&mut self) {
+        if let Some(waker) = self.recv_waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+struct Shared<T> {
+    state: Mutex<State<T>>
+}
+
+impl<T> Shared<T> {
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        self.state.lock().unwrap()
+    }
+}
+
+/// Error returned by `Sender::send` when the receiver has been dropped, carrying the value back
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub struct SendError<T>(pub T);
+
+impl<T> Debug for SendError<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SendError(..)")
+    }
+}
+
+impl<T> Display for SendError<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "channel closed")
+    }
+}
+
+impl<T> Error for SendError<T> {}
+
+/// Error returned by `Sender::try_send`, carrying the value back
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub enum TrySendError<T> {
+    Full(T),
+    Closed(T)
+}
+
+impl<T> TrySendError<T> {
+    pub fn into_inner(self) -> T {
+        match self {
+            TrySendError::Full(value) | TrySendError::Closed(value) => value
+        }
+    }
+}
+
+impl<T> Debug for TrySendError<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TrySendError::Full(_) => write!(f, "TrySendError::Full(..)"),
+            TrySendError::Closed(_) => write!(f, "TrySendError::Closed(..)")
+        }
+    }
+}
+
+impl<T> Display for TrySendError<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TrySendError::Full(_) => write!(f, "channel full"),
+            TrySendError::Closed(_) => write!(f, "channel closed")
+        }
+    }
+}
+
+impl<T> Error for TrySendError<T> {}
+
+/// Error returned by `Receiver::try_recv`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TryRecvError {
+    Empty,
+    Disconnected
+}
+
+impl Display for TryRecvError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TryRecvError::Empty => write!(f, "channel empty"),
+            TryRecvError::Disconnected => write!(f, "channel disconnected")
+        }
+    }
+}
+
+impl Error for TryRecvError {}
+
+/// Sending half of the channel, which may be cloned
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>
+}
+
+impl<T> Sender<T> {
+    /// Send a value, waiting for buffer space if the channel is full
+    pub fn send(&self, value: T) -> SendFuture<'_, T> {
+        SendFuture { sender: self, value: Some(value), waiter_id: None }
+    }
+
+    /// Send a value without waiting, never allocating
+    pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        let mut state = self.shared.lock();
+        if !state.receiver_alive {
+            Err(TrySendError::Closed(value))
+        } else if state.buffer.len() >= state.capacity {
+            Err(TrySendError::Full(value))
+        } else {
+            state.buffer.push_back(value);
+            state.wake_receiver();
+            Ok(())
+        }
+    }
+
+    /// Checks if the receiver has been dropped
+    pub fn is_closed(&self) -> bool {
+        !self.shared.lock().receiver_alive
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.shared.lock().senders += 1;
+        Self { shared: self.shared.clone() }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.senders -= 1;
+        if state.senders == 0 {
+            state.wake_receiver();
+        }
+    }
+}
+
+/// Future returned by `Sender::send`
+pub struct SendFuture<'a, T> {
+    sender: &'a Sender<T>,
+    value: Option<T>,
+    waiter_id: Option<usize>
+}
+
+impl<'a, T> Future for SendFuture<'a, T> {
+    type Output = Result<(), SendError<T>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this: &mut Self = Pin::into_inner(self);
+        let mut state = this.sender.shared.lock();
+        let value: T = this.value.take().expect("`SendFuture` polled after completion");
+
+        if !state.receiver_alive {
+            return Poll::Ready(Err(SendError(value)));
+        }
+        if state.buffer.len() < state.capacity {
+            state.buffer.push_back(value);
+            state.wake_receiver();
+            if let Some(waiter_id) = this.waiter_id.take() {
+                state.send_wakers.retain(|(id, _)| *id != waiter_id);
+            }
+            return Poll::Ready(Ok(()));
+        }
+
+        this.value = Some(value);
+        let waker: Waker = cx.waker().clone();
+        match this.waiter_id {
+            Some(waiter_id) if state.send_wakers.iter().any(|(id, _)| *id == waiter_id) => {
+                for (id, registered) in state.send_wakers.iter_mut() {
+                    if *id == waiter_id {
+                        *registered = waker;
+                        break;
+                    }
+                }
+            },
+            _ => {
+                let waiter_id: usize = state.next_waiter_id;
+                state.next_waiter_id = state.next_waiter_id.wrapping_add(1);
+                state.send_wakers.push_back((waiter_id, waker));
+                this.waiter_id = Some(waiter_id);
+            }
+        }
+        Poll::Pending
+    }
+}
+
+// the value is never pinned
+impl<'a, T> Unpin for SendFuture<'a, T> {}
+
+impl<'a, T> Drop for SendFuture<'a, T> {
+    fn drop(&mut self) {
+        if let (Some(waiter_id), Some(_)) = (self.waiter_id, self.value.as_ref()) {
+            let mut state = self.sender.shared.lock();
+            let registered: usize = state.send_wakers.len();
+            state.send_wakers.retain(|(id, _)| *id != waiter_id);
+            // if this sender has been woken but gave up, pass the wakeup on
+            if registered == state.send_wakers.len() && state.buffer.len() < state.capacity {
+                state.wake_one_sender();
+            }
+        }
+    }
+}
+
+/// Receiving half of the channel
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>
+}
+
+impl<T> Receiver<T> {
+    /// Receive a value, resolving to `None` once all senders are dropped and the buffer is empty
+    pub fn recv(&mut self) -> RecvFuture<'_, T> {
+        RecvFuture { receiver: self }
+    }
+
+    /// Receive a value without waiting
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        let mut state = self.shared.lock();
+        if let Some(value) = state.buffer.pop_front() {
+            state.wake_one_sender();
+            Ok(value)
+        } else if state.senders == 0 {
+            Err(TryRecvError::Disconnected)
+        } else {
+            Err(TryRecvError::Empty)
+        }
+    }
+
+    /// Close the channel, making all further sends fail, while keeping buffered values receivable
+    pub fn close(&mut self) {
+        let mut state = self.shared.lock();
+        state.receiver_alive = false;
+        for (_, waker) in std::mem::take(&mut state.send_wakers) {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.close();
+        // buffered values get dropped outside of the lock
+        let buffer: VecDeque<T> = std::mem::take(&mut self.shared.lock().buffer);
+        drop(buffer);
+    }
+}
+
+/// Future returned by `Receiver::recv`
+pub struct RecvFuture<'a, T> {
+    receiver: &'a mut Receiver<T>
+}
+
+impl<'a, T> Future for RecvFuture<'a, T> {
+    type Output = Option<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.receiver.shared.lock();
+        if let Some(value) = state.buffer.pop_front() {
+            state.wake_one_sender();
+            Poll::Ready(Some(value))
+        } else if state.senders == 0 {
+            Poll::Ready(None)
+        } else {
+            state.recv_waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+/// Create a bounded channel holding at most `capacity` values
+///
+/// # Panics
+/// Panics if `capacity` is zero.
+pub fn channel<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    assert_ne!(capacity, 0, "channel capacity must be positive");
+    let shared: Arc<Shared<T>> = Arc::new(Shared {
+        state: Mutex::new(State {
+            buffer: VecDeque::with_capacity(capacity),
+            capacity,
+            senders: 1,
+            receiver_alive: true,
+            recv_waker: None,
+            send_wakers: VecDeque::new(),
+            next_waiter_id: 0
+        })
+    });
+    (Sender { shared: shared.clone() }, Receiver { shared })
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use crate::async_utils::{block_on_local, spawn_local, yield_now};
+    use crate::async_utils::mpsc::{
+        Receiver,
+        SendError,
+        Sender,
+        TryRecvError,
+        TrySendError,
+        channel
+    };
+
+    #[test]
+    fn test_backpressure() {
+        block_on_local(async {
+            let (tx, mut rx): (Sender<i32>, Receiver<i32>) = channel(2);
+            let sent: Rc<Cell<i32>> = Rc::new(Cell::new(0));
+            let sent2: Rc<Cell<i32>> = sent.clone();
+            let producer = spawn_local(async move {
+                for i in 0..5 {
+                    tx.send(i).await.unwrap();
+                    sent2.set(sent2.get() + 1);
+                }
+            });
+
+            for _ in 0..4 {
+                yield_now().await;
+            }
+            assert_eq!(sent.get(), 2);
+
+            assert_eq!(rx.recv().await, Some(0));
+            for _ in 0..4 {
+                yield_now().await;
+            }
+            assert_eq!(sent.get(), 3);
+
+            for i in 1..5 {
+                assert_eq!(rx.recv().await, Some(i));
+            }
+            producer.await;
+            assert_eq!(sent.get(), 5);
+            assert_eq!(rx.recv().await, None);
+        });
+    }
+
+    #[test]
+    fn test_multi_producer() {
+        let received: Vec<(usize, usize)> = block_on_local(async {
+            let (tx, mut rx) = channel::<(usize, usize)>(3);
+            for producer in 0..3 {
+                let tx: Sender<(usize, usize)> = tx.clone();
+                drop(spawn_local(async move {
+                    for i in 0..10 {
+                        tx.send((producer, i)).await.unwrap();
+                        yield_now().await;
+                    }
+                }));
+            }
+            drop(tx);
+
+            let mut received: Vec<(usize, usize)> = Vec::new();
+            while let Some(item) = rx.recv().await {
+                received.push(item);
+            }
+            received
+        });
+
+        assert_eq!(received.len(), 30);
+        for producer in 0..3 {
+            let items: Vec<usize> = received.iter()
+                .filter(|(p, _)| *p == producer)
+                .map(|(_, i)| *i)
+                .collect();
+            assert_eq!(items, (0..10).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn test_senders_dropped() {
+        let (tx, mut rx): (Sender<i32>, Receiver<i32>) = channel(4);
+        let tx2: Sender<i32> = tx.clone();
+        tx.try_send(114).unwrap();
+        drop(tx);
+        assert_eq!(rx.try_recv(), Ok(114));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+        tx2.try_send(514).unwrap();
+        drop(tx2);
+        block_on_local(async move {
+            assert_eq!(rx.recv().await, Some(514));
+            assert_eq!(rx.recv().await, None);
+        });
+    }
+
+    #[test]
+    fn test_receiver_dropped() {
+        let (tx, rx): (Sender<String>, Receiver<String>) = channel(1);
+        tx.try_send("1919".to_string()).unwrap();
+        match tx.try_send("810".to_string()) {
+            Err(TrySendError::Full(value)) => assert_eq!(value, "810"),
+            _ => unreachable!()
+        }
+
+        block_on_local(async move {
+            let tx2: Sender<String> = tx.clone();
+            let blocked = spawn_local(async move { tx2.send("blocked".to_string()).await });
+            yield_now().await;
+            assert!(!blocked.is_finished());
+
+            drop(rx);
+            assert!(tx.is_closed());
+            let SendError(value) = blocked.await.unwrap_err();
+            assert_eq!(value, "blocked");
+            let SendError(value) = tx.send("late".to_string()).await.unwrap_err();
+            assert_eq!(value, "late");
+            match tx.try_send("later".to_string()) {
+                Err(TrySendError::Closed(value)) => assert_eq!(value, "later"),
+                _ => unreachable!()
+            }
+        });
+    }
+}
@@ -0,0 +1,140 @@
+//! Small helpers for writing `Future` implementations by hand
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Extracts the value out of a `Poll::Ready`, or returns `Poll::Pending` from current function
+///
+/// Equivalent to `std::task::ready!` and `futures::ready!`, without requiring either.
+#[macro_export] macro_rules! poll_ready {
+    ($e:expr $(,)?) => {
+        match $e {
+            ::std::task::Poll::Ready(t) => t,
+            ::std::task::Poll::Pending => return ::std::task::Poll::Pending
+        }
+    };
+}
+
+/// Extension methods for `Poll`
+pub trait PollExt<T> {
+    /// Map the ready value, same as `Poll::map`
+    fn map_ext<U>(self, f: impl FnOnce(T) -> U) -> Poll<U>;
+
+    /// Checks if the poll is ready with a value satisfying `pred`
+    fn is_ready_and(&self, pred: impl FnOnce(&T) -> bool) -> bool;
+
+    /// Extracts the ready value, or computes one with `f` if pending
+    fn ready_or_else(self, f: impl FnOnce() -> T) -> T;
+}
+
+impl<T> PollExt<T> for Poll<T> {
+    fn map_ext<U>(self, f: impl FnOnce(T) -> U) -> Poll<U> {
+        match self {
+            Poll::Ready(t) => Poll::Ready(f(t)),
+            Poll::Pending => Poll::Pending
+        }
+    }
+
+    fn is_ready_and(&self, pred: impl FnOnce(&T) -> bool) -> bool {
+        match self {
+            Poll::Ready(t) => pred(t),
+            Poll::Pending => false
+        }
+    }
+
+    fn ready_or_else(self, f: impl FnOnce() -> T) -> T {
+        match self {
+            Poll::Ready(t) => t,
+            Poll::Pending => f()
+        }
+    }
+}
+
+/// Future returned by `poll_fn`
+pub struct PollFn<F> {
+    f: F
+}
+
+impl<F> Unpin for PollFn<F> {}
+
+impl<T, F> Future for PollFn<F>
+    where F: FnMut(&mut Context<'_>) -> Poll<T>
+{
+    type Output = T;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        (self.f)(cx)
+    }
+}
+
+/// Create a `Future` out of a polling closure
+///
+/// Same as `std::future::poll_fn`, which is not available on older compilers.
+pub fn poll_fn<T, F>(f: F) -> PollFn<F>
+    where F: FnMut(&mut Context<'_>) -> Poll<T>
+{
+    PollFn { f }
+}
+
+#[cfg(test)]
+mod test {
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    use crate::async_utils::{PollExt, block_on_future, poll_fn, yield_now};
+
+    enum Countdown {
+        Counting(u32),
+        Finishing(Pin<Box<dyn Future<Output = ()> + Send>>),
+        Done
+    }
+
+    impl Countdown {
+        fn poll_step(&mut self, cx: &mut Context<'_>) -> Poll<&'static str> {
+            loop {
+                match self {
+                    Countdown::Counting(0) => {
+                        *self = Countdown::Finishing(Box::pin(yield_now()));
+                    },
+                    Countdown::Counting(n) => {
+                        *n -= 1;
+                        cx.waker().wake_by_ref();
+                        return Poll::Pending;
+                    },
+                    Countdown::Finishing(fut) => {
+                        poll_ready!(fut.as_mut().poll(cx));
+                        *self = Countdown::Done;
+                    },
+                    Countdown::Done => return Poll::Ready("liftoff")
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_poll_ext() {
+        assert!(Poll::Ready(3).is_ready_and(|x| *x > 2));
+        assert!(!Poll::Ready(1).is_ready_and(|x| *x > 2));
+        assert!(!Poll::<i32>::Pending.is_ready_and(|_| true));
+        assert_eq!(Poll::Ready(3).map_ext(|x| x * 2), Poll::Ready(6));
+        assert_eq!(Poll::Pending.ready_or_else(|| 42), 42);
+        assert_eq!(Poll::Ready(7).ready_or_else(|| 42), 7);
+    }
+
+    #[test]
+    fn test_state_machine() {
+        let result: (&'static str, u32) = block_on_future(async {
+            let mut countdown: Countdown = Countdown::Counting(5);
+            let mut polls: u32 = 0;
+            let output: &'static str = poll_fn(|cx| {
+                polls += 1;
+                let output: &'static str = poll_ready!(countdown.poll_step(cx));
+                Poll::Ready(output)
+            }).await;
+            (output, polls)
+        });
+        assert_eq!(result, ("liftoff", 7));
+    }
+}
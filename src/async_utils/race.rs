@@ -0,0 +1,236 @@
+//! N-way racing over a homogeneous collection of futures
+
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::ptr::{NonNull, slice_from_raw_parts_mut};
+use std::task::{Context, Poll};
+
+/// Pinned, fixed-size storage of futures
+///
+/// Kept as a raw pointer instead of a `Pin<Box<[_]>>`: retagging the box (by moving it, or by
+/// taking the length of the slice) would invalidate references held by self-referential futures
+/// inside the buffer, which Miri reports as undefined behavior.
+pub(crate) struct PinnedBuffer<F> {
+    ptr: NonNull<Option<F>>,
+    len: usize,
+    _phantom: PhantomData<Option<F>>
+}
+
+unsafe impl<F: Send> Send for PinnedBuffer<F> {}
+unsafe impl<F: Sync> Sync for PinnedBuffer<F> {}
+
+// the futures live on the heap, moving the buffer itself never moves them
+impl<F> Unpin for PinnedBuffer<F> {}
+
+impl<F: Future> PinnedBuffer<F> {
+    pub(crate) fn new(futures: Vec<F>) -> Self {
+        let boxed: Box<[Option<F>]> = futures.into_iter().map(Some).collect();
+        let len: usize = boxed.len();
+        let ptr: *mut Option<F> = Box::into_raw(boxed) as *mut Option<F>;
+        Self { ptr: unsafe { NonNull::new_unchecked(ptr) }, len, _phantom: PhantomData }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Poll the future at `idx`, dropping it in place once it completes
+    pub(crate) fn poll_at(
+        &mut self,
+        idx: usize,
+        cx: &mut Context<'_>
+    ) -> Option<Poll<F::Output>> {
+        assert!(idx < self.len);
+        // SAFETY: futures are never moved out of the buffer, only dropped in place
+        let slot: &mut Option<F> = unsafe { &mut *self.ptr.as_ptr().add(idx) };
+        let fut: Pin<&mut F> = unsafe { Pin::new_unchecked(slot.as_mut()?) };
+        let poll: Poll<F::Output> = fut.poll(cx);
+        if poll.is_ready() {
+            unsafe { Pin::new_unchecked(slot) }.set(None);
+        }
+        Some(poll)
+    }
+
+    fn clear(&mut self) {
+        let ptr: *mut [Option<F>] = slice_from_raw_parts_mut(self.ptr.as_ptr(), self.len);
+        self.ptr = NonNull::dangling();
+        self.len = 0;
+        drop(unsafe { Box::from_raw(ptr) });
+    }
+}
+
+impl<F> Drop for PinnedBuffer<F> {
+    fn drop(&mut self) {
+        let ptr: *mut [Option<F>] = slice_from_raw_parts_mut(self.ptr.as_ptr(), self.len);
+        drop(unsafe { Box::from_raw(ptr) });
+    }
+}
+
+/// Future returned by `race`
+pub struct Race<F> {
+    buffer: PinnedBuffer<F>
+}
+
+impl<F: Future> Future for Race<F> {
+    type Output = (usize, F::Output);
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        for idx in 0..self.buffer.len() {
+            if let Some(Poll::Ready(output)) = self.buffer.poll_at(idx, cx) {
+                self.buffer.clear();
+                return Poll::Ready((idx, output));
+            }
+        }
+        Poll::Pending
+    }
+}
+
+/// Race all `futures`, resolving to the index and output of the first one to complete
+///
+/// All other futures are dropped as soon as the winner completes. When several futures are ready
+/// at the same time, the one with the lowest index wins. An empty `futures` yields a future that
+/// never completes.
+pub fn race<F: Future>(futures: Vec<F>) -> Race<F> {
+    Race { buffer: PinnedBuffer::new(futures) }
+}
+
+/// Future returned by `race_ok`
+pub struct RaceOk<F, E> {
+    buffer: PinnedBuffer<F>,
+    errors: Vec<Option<E>>,
+    remaining: usize
+}
+
+impl<F, T, E> Future for RaceOk<F, E>
+    where F: Future<Output = Result<T, E>>
+{
+    type Output = Result<(usize, T), Vec<E>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this: &mut Self = Pin::into_inner(self);
+        for idx in 0..this.buffer.len() {
+            match this.buffer.poll_at(idx, cx) {
+                Some(Poll::Ready(Ok(output))) => {
+                    this.buffer.clear();
+                    this.errors.clear();
+                    return Poll::Ready(Ok((idx, output)));
+                },
+                Some(Poll::Ready(Err(e))) => {
+                    this.errors[idx] = Some(e);
+                    this.remaining -= 1;
+                },
+                _ => {}
+            }
+        }
+
+        if this.remaining == 0 {
+            Poll::Ready(Err(this.errors.drain(..).map(Option::unwrap).collect()))
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+// the futures are pinned in a boxed buffer, and errors are never pinned
+impl<F, E> Unpin for RaceOk<F, E> {}
+
+/// Race all `futures`, resolving to the index and output of the first one to complete with `Ok`
+///
+/// Futures completing with `Err` are skipped. If all futures fail, resolves to all errors, in the
+/// same order as `futures`. An empty `futures` resolves to an empty error list immediately.
+pub fn race_ok<F, T, E>(futures: Vec<F>) -> RaceOk<F, E>
+    where F: Future<Output = Result<T, E>>
+{
+    let remaining: usize = futures.len();
+    RaceOk {
+        buffer: PinnedBuffer::new(futures),
+        errors: (0..remaining).map(|_| None).collect(),
+        remaining
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use crate::async_utils::{block_on_local, race, race_ok, yield_now};
+
+    struct DropGuard(Rc<Cell<u32>>);
+
+    impl Drop for DropGuard {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    async fn yield_times(times: usize) {
+        for _ in 0..times {
+            yield_now().await;
+        }
+    }
+
+    #[test]
+    fn test_race_winner() {
+        let (idx, output) = block_on_local(race(
+            [5, 2, 8, 3].iter().map(|&n| async move {
+                yield_times(n).await;
+                n * 10
+            }).collect()
+        ));
+        assert_eq!(idx, 1);
+        assert_eq!(output, 20);
+    }
+
+    #[test]
+    fn test_race_losers_dropped() {
+        let dropped: Rc<Cell<u32>> = Rc::new(Cell::new(0));
+        let futures = (0..4).map(|n| {
+            let guard: DropGuard = DropGuard(dropped.clone());
+            async move {
+                let _guard: DropGuard = guard;
+                if n == 2 {
+                    yield_now().await;
+                } else {
+                    std::future::pending::<()>().await;
+                }
+                n
+            }
+        }).collect();
+
+        let dropped2: Rc<Cell<u32>> = dropped.clone();
+        block_on_local(async move {
+            let (idx, output) = race(futures).await;
+            assert_eq!((idx, output), (2, 2));
+            assert_eq!(dropped2.get(), 4);
+        });
+    }
+
+    #[test]
+    fn test_race_ok() {
+        let r: Result<(usize, i32), Vec<&'static str>> = block_on_local(race_ok(
+            (0..4).map(|n| async move {
+                if n < 3 {
+                    Err("early failure")
+                } else {
+                    yield_times(3).await;
+                    Ok(n)
+                }
+            }).collect()
+        ));
+        assert_eq!(r.unwrap(), (3, 3));
+
+        let r: Result<(usize, ()), Vec<usize>> = block_on_local(race_ok(
+            (0..3usize).map(|n| async move {
+                yield_times(3 - n).await;
+                Err(n)
+            }).collect()
+        ));
+        assert_eq!(r.unwrap_err(), vec![0, 1, 2]);
+
+        let r: Result<(usize, ()), Vec<()>> =
+            block_on_local(race_ok(Vec::<std::future::Ready<Result<(), ()>>>::new()));
+        assert!(r.unwrap_err().is_empty());
+    }
+}
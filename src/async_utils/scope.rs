@@ -0,0 +1,321 @@
+//! Structured concurrency: child futures that never outlive their scope
+//!
+//! ```
+//! # use std::cell::Cell;
+//! # use std::convert::Infallible;
+//! # use xjbutil::async_utils::{block_on_local, scope};
+//! let counter: Cell<i32> = Cell::new(0);
+//! block_on_local(scope(|s| {
+//!     let counter: &Cell<i32> = &counter;
+//!     async move {
+//!         s.spawn(async move { counter.set(counter.get() + 1); Ok(()) });
+//!         s.spawn(async move { counter.set(counter.get() + 2); Ok(()) });
+//!         Ok::<(), Infallible>(())
+//!     }
+//! })).unwrap();
+//! assert_eq!(counter.get(), 3);
+//! ```
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::future::Future;
+use std::panic::{AssertUnwindSafe, catch_unwind, resume_unwind};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll};
+
+type ChildFuture<'scope, E> = Pin<Box<dyn Future<Output = Result<(), E>> + 'scope>>;
+type BodyFuture<'scope, R, E> = Pin<Box<dyn Future<Output = Result<R, E>> + 'scope>>;
+
+/// Handle for spawning children into a `scope`
+///
+/// The handle may be cloned and moved into children, so that children can spawn grandchildren
+/// into the same scope.
+pub struct Scope<'scope, E> {
+    children: Rc<RefCell<Vec<ChildFuture<'scope, E>>>>
+}
+
+impl<'scope, E> Clone for Scope<'scope, E> {
+    fn clone(&self) -> Self {
+        Self { children: self.children.clone() }
+    }
+}
+
+impl<'scope, E> Scope<'scope, E> {
+    /// Spawn a child into this scope
+    ///
+    /// The child is polled by the scope future itself, so it may borrow anything that outlives
+    /// the scope. If the child returns an error, the whole scope fails with that error, and all
+    /// other children get cancelled (dropped).
+    pub fn spawn(&self, child: impl Future<Output = Result<(), E>> + 'scope) {
+        self.children.borrow_mut().push(Box::pin(child));
+    }
+}
+
+/// Future returned by `scope`
+pub struct ScopeFuture<'scope, R, E> {
+    body: Option<BodyFuture<'scope, R, E>>,
+    body_output: Option<R>,
+    scope: Scope<'scope, E>
+}
+
+impl<'scope, R, E> ScopeFuture<'scope, R, E> {
+    fn cancel_all(&mut self) {
+        self.body = None;
+        // children may hold clones of the scope handle, so take them out before dropping
+        let children: Vec<ChildFuture<'scope, E>> =
+            std::mem::take(&mut *self.scope.children.borrow_mut());
+        drop(children);
+    }
+
+    fn poll_children(&mut self, cx: &mut Context<'_>) -> Result<(), Result<E, Box<dyn Any + Send>>> {
+        loop {
+            let mut children: Vec<ChildFuture<'scope, E>> =
+                std::mem::take(&mut *self.scope.children.borrow_mut());
+            if children.is_empty() {
+                return Ok(());
+            }
+
+            let mut failure: Option<Result<E, Box<dyn Any + Send>>> = None;
+            let mut idx: usize = 0;
+            while idx < children.len() {
+                let child: &mut ChildFuture<'scope, E> = &mut children[idx];
+                match catch_unwind(AssertUnwindSafe(|| child.as_mut().poll(cx))) {
+                    Ok(Poll::Pending) => idx += 1,
+                    Ok(Poll::Ready(Ok(()))) => drop(children.swap_remove(idx)),
+                    Ok(Poll::Ready(Err(e))) => { failure = Some(Ok(e)); break; },
+                    Err(panic) => { failure = Some(Err(panic)); break; }
+                }
+            }
+
+            let spawned: bool = !self.scope.children.borrow().is_empty();
+            self.scope.children.borrow_mut().append(&mut children);
+            if let Some(failure) = failure {
+                return Err(failure);
+            }
+            if !spawned {
+                return Ok(());
+            }
+        }
+    }
+}
+
+impl<'scope, R, E> Future for ScopeFuture<'scope, R, E> {
+    type Output = Result<R, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this: &mut Self = Pin::into_inner(self);
+
+        if let Some(body) = this.body.as_mut() {
+            match body.as_mut().poll(cx) {
+                Poll::Ready(Ok(output)) => {
+                    this.body = None;
+                    this.body_output = Some(output);
+                },
+                Poll::Ready(Err(e)) => {
+                    this.cancel_all();
+                    return Poll::Ready(Err(e));
+                },
+                Poll::Pending => {}
+            }
+        }
+
+        match this.poll_children(cx) {
+            Ok(()) => {},
+            Err(Ok(e)) => {
+                this.cancel_all();
+                return Poll::Ready(Err(e));
+            },
+            Err(Err(panic)) => {
+                this.cancel_all();
+                resume_unwind(panic);
+            }
+        }
+
+        if this.body.is_none() && this.scope.children.borrow().is_empty() {
+            Poll::Ready(Ok(this.body_output.take().expect("`ScopeFuture` polled after completion")))
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+// `body_output` is never pinned, everything else is already boxed
+impl<'scope, R, E> Unpin for ScopeFuture<'scope, R, E> {}
+
+impl<'scope, R, E> Drop for ScopeFuture<'scope, R, E> {
+    fn drop(&mut self) {
+        self.cancel_all();
+    }
+}
+
+/// Run `body` with a `Scope`, resolving only after all children spawned into the scope complete
+///
+/// The returned future resolves when the body and all children (including children spawned by
+/// children) have completed. The first error returned by either the body or a child makes the
+/// scope resolve to that error immediately, cancelling (dropping) everything still running. A
+/// panicking child has all other children cancelled, and the panic is then propagated to whoever
+/// polls the scope. Dropping the scope future cancels all children as well, so in no case does a
+/// child outlive the scope.
+///
+/// Children are polled by the scope future itself rather than spawned onto the runtime, which
+/// makes this function work identically on every backend, and allows children to borrow from
+/// the enclosing environment. The flip side is that children run concurrently, but not in
+/// parallel.
+pub fn scope<'scope, B, F, R, E>(body: B) -> ScopeFuture<'scope, R, E>
+    where B: FnOnce(Scope<'scope, E>) -> F,
+          F: Future<Output = Result<R, E>> + 'scope
+{
+    let scope: Scope<'scope, E> = Scope { children: Rc::new(RefCell::new(Vec::new())) };
+    let body: F = body(scope.clone());
+    ScopeFuture {
+        body: Some(Box::pin(body)),
+        body_output: None,
+        scope
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+    use std::convert::Infallible;
+    use std::panic::{AssertUnwindSafe, catch_unwind};
+
+    use crate::async_utils::{block_on_local, scope, yield_now};
+
+    struct DropGuard<'a>(&'a Cell<u32>);
+
+    impl<'a> Drop for DropGuard<'a> {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[test]
+    fn test_scope_basic() {
+        let finished: Cell<u32> = Cell::new(0);
+        let dropped: Cell<u32> = Cell::new(0);
+
+        let r: Result<i32, Infallible> = block_on_local(scope(|s| {
+            let (finished, dropped) = (&finished, &dropped);
+            async move {
+                for i in 0..4 {
+                    s.spawn(async move {
+                        let _guard: DropGuard = DropGuard(dropped);
+                        for _ in 0..i {
+                            yield_now().await;
+                        }
+                        finished.set(finished.get() + 1);
+                        Ok(())
+                    });
+                }
+                Ok(42)
+            }
+        }));
+
+        assert_eq!(r.unwrap(), 42);
+        assert_eq!(finished.get(), 4);
+        assert_eq!(dropped.get(), 4);
+    }
+
+    #[test]
+    fn test_scope_grandchildren() {
+        let finished: Cell<u32> = Cell::new(0);
+
+        let r: Result<(), Infallible> = block_on_local(scope(|s| {
+            let finished = &finished;
+            async move {
+                let s2 = s.clone();
+                s.spawn(async move {
+                    yield_now().await;
+                    s2.spawn(async move {
+                        yield_now().await;
+                        finished.set(finished.get() + 1);
+                        Ok(())
+                    });
+                    finished.set(finished.get() + 1);
+                    Ok(())
+                });
+                Ok(())
+            }
+        }));
+
+        assert!(r.is_ok());
+        assert_eq!(finished.get(), 2);
+    }
+
+    #[test]
+    fn test_scope_body_fails() {
+        let dropped: Cell<u32> = Cell::new(0);
+
+        let r: Result<(), &'static str> = block_on_local(scope(|s| {
+            let dropped = &dropped;
+            async move {
+                s.spawn(async move {
+                    let _guard: DropGuard = DropGuard(dropped);
+                    std::future::pending::<()>().await;
+                    Ok(())
+                });
+                yield_now().await;
+                Err("body failed")
+            }
+        }));
+
+        assert_eq!(r.unwrap_err(), "body failed");
+        assert_eq!(dropped.get(), 1);
+    }
+
+    #[test]
+    fn test_scope_child_fails_racing_completion() {
+        let finished: Cell<u32> = Cell::new(0);
+        let dropped: Cell<u32> = Cell::new(0);
+
+        let r: Result<(), &'static str> = block_on_local(scope(|s| {
+            let (finished, dropped) = (&finished, &dropped);
+            async move {
+                s.spawn(async move {
+                    finished.set(finished.get() + 1);
+                    Ok(())
+                });
+                s.spawn(async move { Err("child failed") });
+                s.spawn(async move {
+                    let _guard: DropGuard = DropGuard(dropped);
+                    yield_now().await;
+                    finished.set(finished.get() + 1);
+                    Ok(())
+                });
+                Ok(())
+            }
+        }));
+
+        assert_eq!(r.unwrap_err(), "child failed");
+        assert_eq!(finished.get(), 1);
+        assert_eq!(dropped.get(), 1);
+    }
+
+    #[test]
+    fn test_scope_child_panics() {
+        let dropped: Cell<u32> = Cell::new(0);
+
+        let r = catch_unwind(AssertUnwindSafe(|| {
+            block_on_local(scope(|s| {
+                let dropped = &dropped;
+                async move {
+                    s.spawn(async move {
+                        let _guard: DropGuard = DropGuard(dropped);
+                        std::future::pending::<()>().await;
+                        Ok(())
+                    });
+                    s.spawn(async move {
+                        yield_now().await;
+                        panic!("child panicked")
+                    });
+                    Ok::<(), Infallible>(())
+                }
+            }))
+        }));
+
+        assert_eq!(*r.unwrap_err().downcast::<&'static str>().unwrap(), "child panicked");
+        assert_eq!(dropped.get(), 1);
+    }
+}
@@ -0,0 +1,364 @@
+//! Sleeping and timeouts, with both relative and absolute deadlines
+//!
+//! Within a `tokio` runtime, the native `tokio` timer is used. Everywhere else (including
+//! `async-std`, `monoio`, `pollster` and `block_on_local`), a single global timer thread fires
+//! the registered deadlines.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::future::Future;
+#[cfg(feature = "async-tokio")]
+use std::panic::catch_unwind;
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex, OnceLock, Weak};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use std::time::{Duration, Instant};
+
+struct TimerEntry {
+    deadline: Instant,
+    waker: Weak<Mutex<Option<Waker>>>
+}
+
+impl PartialEq for TimerEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.deadline == other.deadline
+    }
+}
+
+impl Eq for TimerEntry {}
+
+impl PartialOrd for TimerEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TimerEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // reversed, so that `BinaryHeap` pops the earliest deadline first
+        other.deadline.cmp(&self.deadline)
+    }
+}
+
+struct TimerThread {
+    entries: Mutex<BinaryHeap<TimerEntry>>,
+    condvar: Condvar
+}
+
+impl TimerThread {
+    fn get() -> &'static TimerThread {
+        static TIMER_THREAD: OnceLock<&'static TimerThread> = OnceLock::new();
+        TIMER_THREAD.get_or_init(|| {
+            let timer: &'static TimerThread = Box::leak(Box::new(TimerThread {
+                entries: Mutex::new(BinaryHeap::new()),
+                condvar: Condvar::new()
+            }));
+            thread::Builder::new()
+                .name("xjbutil-timer".into())
+                .spawn(move || timer.run())
+                .expect("failed spawning timer thread");
+            timer
+        })
+    }
+
+    fn register(&self, deadline: Instant, waker: Weak<Mutex<Option<Waker>>>) {
+        self.entries.lock().unwrap().push(TimerEntry { deadline, waker });
+        self.condvar.notify_one();
+    }
+
+    fn run(&self) {
+        let mut entries = self.entries.lock().unwrap();
+        loop {
+            let now: Instant = Instant::now();
+            while entries.peek().is_some_and(|entry| entry.deadline <= now) {
+                let entry: TimerEntry = entries.pop().unwrap();
+                // entries of dropped or reset `Sleep`s fail to upgrade, and are simply discarded
+                if let Some(waker) = entry.waker.upgrade() {
+                    if let Some(waker) = waker.lock().unwrap().take() {
+                        waker.wake();
+                    }
+                }
+            }
+
+            entries = match entries.peek() {
+                Some(entry) => {
+                    let timeout: Duration = entry.deadline.saturating_duration_since(now);
+                    self.condvar.wait_timeout(entries, timeout).unwrap().0
+                },
+                None => self.condvar.wait(entries).unwrap()
+            };
+        }
+    }
+}
+
+enum SleepInner {
+    #[cfg(feature = "async-tokio")]
+    Tokio(Pin<Box<tokio::time::Sleep>>),
+    Thread(Option<Arc<Mutex<Option<Waker>>>>)
+}
+
+/// Future returned by `sleep` and `sleep_until`
+///
+/// The future may be `reset` to a new deadline at any time, even after it has completed, which
+/// allows reusing a single timer in a select loop.
+pub struct Sleep {
+    deadline: Instant,
+    inner: SleepInner
+}
+
+impl Sleep {
+    fn new(deadline: Instant) -> Self {
+        // `tokio` offers no way to check if the runtime has its time driver enabled, creating a
+        // timer in a runtime built without `enable_time` panics instead
+        #[cfg(feature = "async-tokio")]
+        if tokio::runtime::Handle::try_current().is_ok() {
+            let tokio_deadline: tokio::time::Instant = tokio::time::Instant::from_std(deadline);
+            if let Ok(sleep) = catch_unwind(|| tokio::time::sleep_until(tokio_deadline)) {
+                return Self { deadline, inner: SleepInner::Tokio(Box::pin(sleep)) };
+            }
+        }
+
+        Self { deadline, inner: SleepInner::Thread(None) }
+    }
+
+    /// The deadline this `Sleep` completes at
+    pub fn deadline(&self) -> Instant {
+        self.deadline
+    }
+
+    /// Checks if the deadline has been reached
+    pub fn is_elapsed(&self) -> bool {
+        Instant::now() >= self.deadline
+    }
+
+    /// Re-arm this `Sleep` with a new deadline
+    ///
+    /// The `Sleep` will complete at `new_deadline`, regardless of whether it has already completed
+    /// before. Deadlines in the past make it complete immediately.
+    pub fn reset(&mut self, new_deadline: Instant) {
+        self.deadline = new_deadline;
+        match &mut self.inner {
+            #[cfg(feature = "async-tokio")]
+            SleepInner::Tokio(sleep) =>
+                sleep.as_mut().reset(tokio::time::Instant::from_std(new_deadline)),
+            // dropping the registration makes the timer thread discard the stale entry
+            SleepInner::Thread(registration) => *registration = None
+        }
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let deadline: Instant = self.deadline;
+        match &mut self.inner {
+            #[cfg(feature = "async-tokio")]
+            SleepInner::Tokio(sleep) => sleep.as_mut().poll(cx),
+            SleepInner::Thread(registration) => {
+                if Instant::now() >= deadline {
+                    *registration = None;
+                    return Poll::Ready(());
+                }
+
+                match registration {
+                    Some(waker) => *waker.lock().unwrap() = Some(cx.waker().clone()),
+                    None => {
+                        let waker = Arc::new(Mutex::new(Some(cx.waker().clone())));
+                        TimerThread::get().register(deadline, Arc::downgrade(&waker));
+                        *registration = Some(waker);
+                    }
+                }
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Deadline `duration` after now, or one that is never reached if that is not representable
+fn deadline_after(duration: Duration) -> Instant {
+    // roughly 30 years, as `tokio` does for overflowing deadlines
+    const NEVER: Duration = Duration::from_secs(86400 * 365 * 30);
+
+    let now: Instant = Instant::now();
+    now.checked_add(duration).unwrap_or_else(|| now + NEVER)
+}
+
+/// Sleep for the given duration
+///
+/// Durations too long to represent as a deadline make the `Sleep` never complete.
+pub fn sleep(duration: Duration) -> Sleep {
+    Sleep::new(deadline_after(duration))
+}
+
+/// Sleep until the given deadline, completing immediately if the deadline is in the past
+pub fn sleep_until(deadline: Instant) -> Sleep {
+    Sleep::new(deadline)
+}
+
+/// Error returned by `timeout` and `timeout_at` when the deadline is reached first
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Elapsed;
+
+impl Display for Elapsed {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "deadline has elapsed")
+    }
+}
+
+impl Error for Elapsed {}
+
+/// Future returned by `timeout` and `timeout_at`
+pub struct Timeout<F> {
+    fut: F,
+    sleep: Sleep
+}
+
+impl<F> Timeout<F> {
+    /// Gets the underlying `Sleep`, for example to `reset` the deadline
+    pub fn sleep_mut(&mut self) -> &mut Sleep {
+        &mut self.sleep
+    }
+
+    pub fn into_inner(self) -> F {
+        self.fut
+    }
+}
+
+impl<F: Future> Future for Timeout<F> {
+    type Output = Result<F::Output, Elapsed>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // SAFETY: `fut` is never moved out of a pinned `Timeout`, and `Sleep` is `Unpin`
+        let this: &mut Self = unsafe { self.get_unchecked_mut() };
+        let fut: Pin<&mut F> = unsafe { Pin::new_unchecked(&mut this.fut) };
+        if let Poll::Ready(output) = fut.poll(cx) {
+            return Poll::Ready(Ok(output));
+        }
+        Pin::new(&mut this.sleep).poll(cx).map(|_| Err(Elapsed))
+    }
+}
+
+/// Require `fut` to complete within `duration`
+pub fn timeout<F: Future>(duration: Duration, fut: F) -> Timeout<F> {
+    Timeout { fut, sleep: sleep(duration) }
+}
+
+/// Require `fut` to complete before `deadline`
+///
+/// `fut` is always polled at least once, so an already completed future still yields `Ok` even if
+/// the deadline is in the past.
+pub fn timeout_at<F: Future>(deadline: Instant, fut: F) -> Timeout<F> {
+    Timeout { fut, sleep: sleep_until(deadline) }
+}
+
+#[cfg(test)]
+mod test {
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::task::Poll;
+    use std::time::{Duration, Instant};
+
+    use crate::async_utils::{
+        Elapsed,
+        Sleep,
+        block_on_local,
+        sleep,
+        sleep_until,
+        timeout,
+        timeout_at
+    };
+
+    // interpreting under Miri is slow enough to blow a tighter margin between reset and check
+    const STEP: Duration = Duration::from_millis(if cfg!(miri) { 1000 } else { 50 });
+
+    async fn poll_once(sleep: &mut Sleep) -> bool {
+        std::future::poll_fn(|cx| Poll::Ready(Pin::new(&mut *sleep).poll(cx).is_ready())).await
+    }
+
+    async fn reset_before_fire() {
+        let start: Instant = Instant::now();
+        let mut sleep: Sleep = sleep(Duration::from_secs(30));
+        assert!(!poll_once(&mut sleep).await);
+        sleep.reset(Instant::now() + STEP);
+        sleep.await;
+        let elapsed: Duration = start.elapsed();
+        assert!(elapsed >= STEP);
+        assert!(elapsed < Duration::from_secs(10));
+    }
+
+    async fn reset_after_fire() {
+        let mut sleep: Sleep = sleep(Duration::from_millis(10));
+        (&mut sleep).await;
+        assert!(sleep.is_elapsed());
+
+        let start: Instant = Instant::now();
+        sleep.reset(start + STEP);
+        assert!(!sleep.is_elapsed());
+        assert!(!poll_once(&mut sleep).await);
+        (&mut sleep).await;
+        assert!(start.elapsed() >= STEP);
+    }
+
+    #[test]
+    fn test_past_deadline() {
+        block_on_local(async {
+            let past: Instant = Instant::now() - Duration::from_millis(100);
+            let mut sleep: Sleep = sleep_until(past);
+            assert!(sleep.is_elapsed());
+            assert!(poll_once(&mut sleep).await);
+
+            assert_eq!(timeout_at(past, std::future::pending::<()>()).await, Err(Elapsed));
+            assert_eq!(timeout_at(past, async { 114 }).await, Ok(114));
+            assert_eq!(timeout(Duration::from_millis(20), std::future::pending::<()>()).await,
+                       Err(Elapsed));
+        });
+    }
+
+    #[test]
+    fn test_overflowing_duration() {
+        block_on_local(async {
+            let mut sleep: Sleep = sleep(Duration::MAX);
+            assert!(!sleep.is_elapsed());
+            assert!(!poll_once(&mut sleep).await);
+            assert_eq!(timeout(Duration::MAX, async { 514 }).await, Ok(514));
+        });
+    }
+
+    #[test]
+    fn test_reset_before_fire() {
+        block_on_local(reset_before_fire());
+    }
+
+    #[test]
+    fn test_reset_after_fire() {
+        block_on_local(reset_after_fire());
+    }
+
+    #[cfg(feature = "async-tokio")]
+    #[test]
+    fn test_reset_tokio() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            reset_before_fire().await;
+            reset_after_fire().await;
+            assert!(!sleep(Duration::MAX).is_elapsed());
+        });
+    }
+
+    #[cfg(feature = "async-tokio")]
+    #[test]
+    fn test_tokio_without_time() {
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        runtime.block_on(async {
+            assert_eq!(timeout(STEP, std::future::pending::<()>()).await, Err(Elapsed));
+            reset_before_fire().await;
+        });
+    }
+}
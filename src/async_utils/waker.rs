@@ -0,0 +1,160 @@
+//! Constructing `Waker`s without any runtime
+
+use std::mem::ManuallyDrop;
+use std::sync::Arc;
+use std::task::{Context, RawWaker, RawWakerVTable, Waker};
+use std::thread::Thread;
+
+unsafe fn noop_clone(_: *const ()) -> RawWaker {
+    RawWaker::new(std::ptr::null(), &NOOP_VTABLE)
+}
+
+unsafe fn noop(_: *const ()) {}
+
+static NOOP_VTABLE: RawWakerVTable = RawWakerVTable::new(noop_clone, noop, noop, noop);
+
+static NOOP_WAKER: Waker = unsafe {
+    Waker::from_raw(RawWaker::new(std::ptr::null(), &NOOP_VTABLE))
+};
+
+/// A `Waker` that does nothing when woken
+pub fn noop_waker() -> Waker {
+    NOOP_WAKER.clone()
+}
+
+/// A `Context` built upon a no-op `Waker`, handy for polling futures manually in tests
+pub fn noop_context() -> Context<'static> {
+    Context::from_waker(&NOOP_WAKER)
+}
+
+struct FnWaker<F>(F);
+
+impl<F: Fn() + Send + Sync + 'static> FnWaker<F> {
+    const VTABLE: RawWakerVTable = RawWakerVTable::new(
+        Self::clone_raw,
+        Self::wake_raw,
+        Self::wake_by_ref_raw,
+        Self::drop_raw
+    );
+
+    fn into_waker(this: Arc<Self>) -> Waker {
+        let raw: RawWaker = RawWaker::new(Arc::into_raw(this) as *const (), &Self::VTABLE);
+        unsafe { Waker::from_raw(raw) }
+    }
+
+    unsafe fn clone_raw(ptr: *const ()) -> RawWaker {
+        Arc::increment_strong_count(ptr as *const Self);
+        RawWaker::new(ptr, &Self::VTABLE)
+    }
+
+    unsafe fn wake_raw(ptr: *const ()) {
+        let this: Arc<Self> = Arc::from_raw(ptr as *const Self);
+        (this.0)();
+    }
+
+    unsafe fn wake_by_ref_raw(ptr: *const ()) {
+        // borrow the `Arc` without touching the reference count
+        let this: ManuallyDrop<Arc<Self>> = ManuallyDrop::new(Arc::from_raw(ptr as *const Self));
+        (this.0)();
+    }
+
+    unsafe fn drop_raw(ptr: *const ()) {
+        drop(Arc::from_raw(ptr as *const Self));
+    }
+}
+
+/// A `Waker` calling `f` every time it gets woken
+pub fn waker_from_fn<F>(f: F) -> Waker
+    where F: Fn() + Send + Sync + 'static
+{
+    FnWaker::into_waker(Arc::new(FnWaker(f)))
+}
+
+/// A `Waker` unparking `thread` when woken, which is the building block of a parking `block_on`
+pub fn waker_from_unpark(thread: Thread) -> Waker {
+    waker_from_fn(move || thread.unpark())
+}
+
+#[cfg(test)]
+mod test {
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::task::{Context, Poll, Waker};
+    use std::thread;
+
+    use crate::async_utils::{noop_context, noop_waker, waker_from_fn, waker_from_unpark};
+    use crate::async_utils::waker::FnWaker;
+
+    #[test]
+    fn test_noop() {
+        let waker: Waker = noop_waker();
+        let waker2: Waker = waker.clone();
+        waker.wake_by_ref();
+        waker.wake();
+        drop(waker2);
+
+        let mut cx: Context<'static> = noop_context();
+        let mut fut = std::future::ready(114);
+        assert_eq!(Pin::new(&mut fut).poll(&mut cx), Poll::Ready(114));
+    }
+
+    #[test]
+    fn test_vtable_refcount() {
+        let counter: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(0));
+        let counter2: Arc<AtomicUsize> = counter.clone();
+        let inner = Arc::new(FnWaker(move || { counter2.fetch_add(1, Ordering::SeqCst); }));
+
+        let waker: Waker = FnWaker::into_waker(inner.clone());
+        assert_eq!(Arc::strong_count(&inner), 2);
+
+        let waker2: Waker = waker.clone();
+        let waker3: Waker = waker2.clone();
+        assert_eq!(Arc::strong_count(&inner), 4);
+
+        waker.wake_by_ref();
+        assert_eq!(Arc::strong_count(&inner), 4);
+        assert_eq!(counter.load(Ordering::SeqCst), 1);
+
+        waker.wake();
+        assert_eq!(Arc::strong_count(&inner), 3);
+        assert_eq!(counter.load(Ordering::SeqCst), 2);
+
+        drop(waker2);
+        assert_eq!(Arc::strong_count(&inner), 2);
+
+        assert!(waker3.will_wake(&waker3.clone()));
+        waker3.wake();
+        assert_eq!(Arc::strong_count(&inner), 1);
+        assert_eq!(counter.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_waker_from_fn_drops_closure() {
+        let counter: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(0));
+        let counter2: Arc<AtomicUsize> = counter.clone();
+        let waker: Waker = waker_from_fn(move || { counter2.fetch_add(1, Ordering::SeqCst); });
+        let waker2: Waker = waker.clone();
+        waker2.wake();
+        assert_eq!(Arc::strong_count(&counter), 2);
+        drop(waker);
+        assert_eq!(Arc::strong_count(&counter), 1);
+        assert_eq!(counter.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_waker_from_unpark() {
+        let waker: Waker = waker_from_unpark(thread::current());
+        let flag: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(0));
+        let flag2: Arc<AtomicUsize> = flag.clone();
+        let handle = thread::spawn(move || {
+            flag2.store(1, Ordering::SeqCst);
+            waker.wake();
+        });
+        while flag.load(Ordering::SeqCst) == 0 {
+            thread::park();
+        }
+        handle.join().unwrap();
+    }
+}
@@ -0,0 +1,296 @@
+//! Endian-explicit reading and writing of numbers in byte slices
+//!
+//! Checked accessors return `None` for out-of-range offsets. The unsafe `_dbg` variants only check
+//! offsets in debug builds, and cause undefined behavior on out-of-range access in release builds.
+//! Offsets need not be aligned. `u8` and `i8` accessors come without endianness suffixes.
+
+use core::mem::size_of;
+use core::ptr::{read_unaligned, write_unaligned};
+
+#[cfg(feature = "alloc")] use alloc::vec::Vec;
+
+#[inline(always)] fn in_range(len: usize, offset: usize, size: usize) -> bool {
+    matches!(offset.checked_add(size), Some(end) if end <= len)
+}
+
+#[inline(always)] fn read_array<const N: usize>(bytes: &[u8], offset: usize) -> Option<[u8; N]> {
+    if in_range(bytes.len(), offset, N) {
+        Some(unsafe { read_unaligned(bytes.as_ptr().add(offset) as *const [u8; N]) })
+    } else {
+        None
+    }
+}
+
+#[inline(always)]
+unsafe fn read_array_dbg<const N: usize>(bytes: &[u8], offset: usize) -> [u8; N] {
+    debug_assert!(in_range(bytes.len(), offset, N), "read out of range");
+    read_unaligned(bytes.as_ptr().add(offset) as *const [u8; N])
+}
+
+#[inline(always)]
+fn write_array<const N: usize>(bytes: &mut [u8], offset: usize, data: [u8; N]) -> Option<()> {
+    if in_range(bytes.len(), offset, N) {
+        unsafe { write_unaligned(bytes.as_mut_ptr().add(offset) as *mut [u8; N], data) };
+        Some(())
+    } else {
+        None
+    }
+}
+
+#[inline(always)]
+unsafe fn write_array_dbg<const N: usize>(bytes: &mut [u8], offset: usize, data: [u8; N]) {
+    debug_assert!(in_range(bytes.len(), offset, N), "write out of range");
+    write_unaligned(bytes.as_mut_ptr().add(offset) as *mut [u8; N], data)
+}
+
+macro_rules! byte_slice_ext {
+    ($(
+        $ty:ty, $from:ident, $to:ident:
+            $read:ident, $read_dbg:ident, $write:ident, $write_dbg:ident;
+    )*) => {
+        /// Reading and writing numbers at given byte offsets
+        pub trait ByteSliceExt {
+            $(
+                fn $read(&self, offset: usize) -> Option<$ty>;
+
+                /// # Safety
+                /// `offset` must be in range, which is only checked in debug builds.
+                unsafe fn $read_dbg(&self, offset: usize) -> $ty;
+
+                /// Returns `None` without writing anything if `offset` is out of range
+                fn $write(&mut self, offset: usize, value: $ty) -> Option<()>;
+
+                /// # Safety
+                /// `offset` must be in range, which is only checked in debug builds.
+                unsafe fn $write_dbg(&mut self, offset: usize, value: $ty);
+            )*
+        }
+
+        impl ByteSliceExt for [u8] {
+            $(
+                #[inline] fn $read(&self, offset: usize) -> Option<$ty> {
+                    read_array::<{ size_of::<$ty>() }>(self, offset).map(<$ty>::$from)
+                }
+
+                #[inline] unsafe fn $read_dbg(&self, offset: usize) -> $ty {
+                    <$ty>::$from(read_array_dbg::<{ size_of::<$ty>() }>(self, offset))
+                }
+
+                #[inline] fn $write(&mut self, offset: usize, value: $ty) -> Option<()> {
+                    write_array::<{ size_of::<$ty>() }>(self, offset, value.$to())
+                }
+
+                #[inline] unsafe fn $write_dbg(&mut self, offset: usize, value: $ty) {
+                    write_array_dbg::<{ size_of::<$ty>() }>(self, offset, value.$to())
+                }
+            )*
+        }
+    };
+}
+
+byte_slice_ext! {
+    u8, from_le_bytes, to_le_bytes: read_u8, read_u8_dbg, write_u8, write_u8_dbg;
+    i8, from_le_bytes, to_le_bytes: read_i8, read_i8_dbg, write_i8, write_i8_dbg;
+    u16, from_le_bytes, to_le_bytes: read_u16_le, read_u16_le_dbg, write_u16_le, write_u16_le_dbg;
+    u16, from_be_bytes, to_be_bytes: read_u16_be, read_u16_be_dbg, write_u16_be, write_u16_be_dbg;
+    i16, from_le_bytes, to_le_bytes: read_i16_le, read_i16_le_dbg, write_i16_le, write_i16_le_dbg;
+    i16, from_be_bytes, to_be_bytes: read_i16_be, read_i16_be_dbg, write_i16_be, write_i16_be_dbg;
+    u32, from_le_bytes, to_le_bytes: read_u32_le, read_u32_le_dbg, write_u32_le, write_u32_le_dbg;
+    u32, from_be_bytes, to_be_bytes: read_u32_be, read_u32_be_dbg, write_u32_be, write_u32_be_dbg;
+    i32, from_le_bytes, to_le_bytes: read_i32_le, read_i32_le_dbg, write_i32_le, write_i32_le_dbg;
+    i32, from_be_bytes, to_be_bytes: read_i32_be, read_i32_be_dbg, write_i32_be, write_i32_be_dbg;
+    u64, from_le_bytes, to_le_bytes: read_u64_le, read_u64_le_dbg, write_u64_le, write_u64_le_dbg;
+    u64, from_be_bytes, to_be_bytes: read_u64_be, read_u64_be_dbg, write_u64_be, write_u64_be_dbg;
+    i64, from_le_bytes, to_le_bytes: read_i64_le, read_i64_le_dbg, write_i64_le, write_i64_le_dbg;
+    i64, from_be_bytes, to_be_bytes: read_i64_be, read_i64_be_dbg, write_i64_be, write_i64_be_dbg;
+    f32, from_le_bytes, to_le_bytes: read_f32_le, read_f32_le_dbg, write_f32_le, write_f32_le_dbg;
+    f32, from_be_bytes, to_be_bytes: read_f32_be, read_f32_be_dbg, write_f32_be, write_f32_be_dbg;
+    f64, from_le_bytes, to_le_bytes: read_f64_le, read_f64_le_dbg, write_f64_le, write_f64_le_dbg;
+    f64, from_be_bytes, to_be_bytes: read_f64_be, read_f64_be_dbg, write_f64_be, write_f64_be_dbg;
+}
+
+#[cfg(feature = "alloc")]
+macro_rules! byte_vec_ext {
+    ($($ty:ty, $to:ident: $put:ident;)*) => {
+        /// Appending numbers to the end of a byte vector
+        pub trait ByteVecExt {
+            $(fn $put(&mut self, value: $ty);)*
+        }
+
+        impl ByteVecExt for Vec<u8> {
+            $(
+                #[inline] fn $put(&mut self, value: $ty) {
+                    self.extend_from_slice(&value.$to());
+                }
+            )*
+        }
+    };
+}
+
+#[cfg(feature = "alloc")]
+byte_vec_ext! {
+    u8, to_le_bytes: put_u8;
+    i8, to_le_bytes: put_i8;
+    u16, to_le_bytes: put_u16_le;
+    u16, to_be_bytes: put_u16_be;
+    i16, to_le_bytes: put_i16_le;
+    i16, to_be_bytes: put_i16_be;
+    u32, to_le_bytes: put_u32_le;
+    u32, to_be_bytes: put_u32_be;
+    i32, to_le_bytes: put_i32_le;
+    i32, to_be_bytes: put_i32_be;
+    u64, to_le_bytes: put_u64_le;
+    u64, to_be_bytes: put_u64_be;
+    i64, to_le_bytes: put_i64_le;
+    i64, to_be_bytes: put_i64_be;
+    f32, to_le_bytes: put_f32_le;
+    f32, to_be_bytes: put_f32_be;
+    f64, to_le_bytes: put_f64_le;
+    f64, to_be_bytes: put_f64_be;
+}
+
+#[cfg(test)]
+mod test {
+    use crate::bytes::ByteSliceExt;
+
+    const BYTES: [u8; 9] = [0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x89];
+
+    #[test]
+    fn test_read() {
+        let bytes: &[u8] = &BYTES;
+        assert_eq!(bytes.read_u8(8), Some(0x89));
+        assert_eq!(bytes.read_i8(8), Some(0x89u8 as i8));
+        assert_eq!(bytes.read_u16_le(0), Some(0x0201));
+        assert_eq!(bytes.read_u16_be(0), Some(0x0102));
+        assert_eq!(bytes.read_i16_le(7), Some(0x8908u16 as i16));
+        assert_eq!(bytes.read_i16_be(7), Some(0x0889));
+        assert_eq!(bytes.read_u32_le(1), Some(0x05040302));
+        assert_eq!(bytes.read_u32_be(1), Some(0x02030405));
+        assert_eq!(bytes.read_i32_le(5), Some(0x89080706u32 as i32));
+        assert_eq!(bytes.read_i32_be(5), Some(0x06070889));
+        assert_eq!(bytes.read_u64_le(0), Some(0x0807060504030201));
+        assert_eq!(bytes.read_u64_be(0), Some(0x0102030405060708));
+        assert_eq!(bytes.read_i64_le(1), Some(0x8908070605040302u64 as i64));
+        assert_eq!(bytes.read_i64_be(1), Some(0x0203040506070889));
+        assert_eq!(bytes.read_f32_le(1), Some(f32::from_bits(0x05040302)));
+        assert_eq!(bytes.read_f32_be(1), Some(f32::from_bits(0x02030405)));
+        assert_eq!(bytes.read_f64_le(1), Some(f64::from_bits(0x8908070605040302)));
+        assert_eq!(bytes.read_f64_be(1), Some(f64::from_bits(0x0203040506070889)));
+
+        unsafe {
+            assert_eq!(bytes.read_u8_dbg(0), 0x01);
+            assert_eq!(bytes.read_u16_be_dbg(7), 0x0889);
+            assert_eq!(bytes.read_u32_le_dbg(5), 0x89080706);
+            assert_eq!(bytes.read_i64_be_dbg(0), 0x0102030405060708);
+            assert_eq!(bytes.read_f64_le_dbg(0), f64::from_bits(0x0807060504030201));
+        }
+    }
+
+    #[test]
+    fn test_boundary() {
+        let bytes: &[u8] = &BYTES;
+        assert!(bytes.read_u8(9).is_none());
+        assert!(bytes.read_u16_le(8).is_none());
+        assert!(bytes.read_u32_be(6).is_none());
+        assert!(bytes.read_u32_be(5).is_some());
+        assert!(bytes.read_u64_le(2).is_none());
+        assert!(bytes.read_u64_le(1).is_some());
+        assert!(bytes.read_u16_le(usize::MAX).is_none());
+        assert!(bytes.read_u64_be(usize::MAX - 3).is_none());
+        assert!((&[] as &[u8]).read_u8(0).is_none());
+
+        let mut buf: [u8; 4] = [0; 4];
+        assert!(buf.write_u32_le(1, 114514).is_none());
+        assert!(buf.write_u16_be(usize::MAX, 893).is_none());
+        assert_eq!(buf, [0; 4]);
+    }
+
+    #[test]
+    fn test_write() {
+        let mut buf: [u8; 11] = [0; 11];
+        assert_eq!(buf.write_u16_le(0, 0x0102), Some(()));
+        assert_eq!(buf.write_u32_be(2, 0x03040506), Some(()));
+        assert_eq!(buf.write_i8(6, -1), Some(()));
+        assert_eq!(buf.write_i32_le(7, 0x0a090807), Some(()));
+        assert_eq!(buf, [0x02, 0x01, 0x03, 0x04, 0x05, 0x06, 0xff, 0x07, 0x08, 0x09, 0x0a]);
+
+        // unaligned round trips of all widths
+        buf.write_u64_be(1, 0x1145141919810893).unwrap();
+        assert_eq!(buf.read_u64_be(1), Some(0x1145141919810893));
+        buf.write_i64_le(3, -114514).unwrap();
+        assert_eq!(buf.read_i64_le(3), Some(-114514));
+        buf.write_i16_be(9, -893).unwrap();
+        assert_eq!(buf.read_i16_be(9), Some(-893));
+        buf.write_f32_le(5, 1.5).unwrap();
+        assert_eq!(buf.read_f32_le(5), Some(1.5));
+        buf.write_f64_be(3, -0.25).unwrap();
+        assert_eq!(buf.read_f64_be(3), Some(-0.25));
+        unsafe {
+            buf.write_u16_le_dbg(9, 0x0a0b);
+            buf.write_f32_be_dbg(1, 2.0);
+            buf.write_u8_dbg(0, 0xcc);
+            assert_eq!(buf.read_u16_le_dbg(9), 0x0a0b);
+            assert_eq!(buf.read_f32_be_dbg(1), 2.0);
+        }
+        assert_eq!(buf[0], 0xcc);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_put() {
+        use crate::bytes::ByteVecExt;
+
+        let mut v: Vec<u8> = Vec::new();
+        v.put_u8(0xcc);
+        v.put_i8(-2);
+        v.put_u16_le(0x0102);
+        v.put_u16_be(0x0102);
+        v.put_i16_le(-3);
+        v.put_i16_be(-3);
+        v.put_u32_le(0x01020304);
+        v.put_u32_be(0x01020304);
+        v.put_i32_le(-4);
+        v.put_i32_be(-4);
+        v.put_u64_le(1);
+        v.put_u64_be(1);
+        v.put_i64_le(-5);
+        v.put_i64_be(-5);
+        v.put_f32_le(0.5);
+        v.put_f32_be(0.5);
+        v.put_f64_le(0.125);
+        v.put_f64_be(0.125);
+        assert_eq!(v.len(), 2 + 2 * (2 + 2 + 4 + 4 + 8 + 8 + 4 + 8));
+
+        assert_eq!(&v[..6], &[0xcc, 0xfe, 0x02, 0x01, 0x01, 0x02]);
+        assert_eq!(v.read_i16_le(6), Some(-3));
+        assert_eq!(v.read_i16_be(8), Some(-3));
+        assert_eq!(v.read_u32_le(10), Some(0x01020304));
+        assert_eq!(v.read_u32_be(14), Some(0x01020304));
+        assert_eq!(v.read_i32_le(18), Some(-4));
+        assert_eq!(v.read_i32_be(22), Some(-4));
+        assert_eq!(v.read_u64_le(26), Some(1));
+        assert_eq!(v.read_u64_be(34), Some(1));
+        assert_eq!(v.read_i64_le(42), Some(-5));
+        assert_eq!(v.read_i64_be(50), Some(-5));
+        assert_eq!(v.read_f32_le(58), Some(0.5));
+        assert_eq!(v.read_f32_be(62), Some(0.5));
+        assert_eq!(v.read_f64_le(66), Some(0.125));
+        assert_eq!(v.read_f64_be(74), Some(0.125));
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "read out of range")]
+    fn test_read_dbg_out_of_range() {
+        let bytes: &[u8] = &BYTES;
+        let _ = unsafe { bytes.read_u32_le_dbg(6) };
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "write out of range")]
+    fn test_write_dbg_out_of_range() {
+        let mut buf: [u8; 4] = [0; 4];
+        unsafe { buf.write_u16_be_dbg(3, 0) };
+    }
+}
@@ -31,6 +31,11 @@
 //! }
 //! ```
 
+use std::borrow::Cow;
+use std::panic::Location;
+use std::thread;
+
+use crate::diag_intern::diagnostic;
 use crate::unchecked_intern::UncheckedOption;
 
 pub struct Defer<F>
@@ -87,3 +92,152 @@ impl<FN, CAP> Drop for Defer2<FN, CAP>
 }
 
 unsafe impl<F, CAP> Send for Defer2<F, CAP> where F: FnOnce(CAP) + Send, CAP: Send {}
+
+/// Guard enforcing that something gets explicitly done before it goes out of scope
+///
+/// Dropping a `DropBomb` without calling `defuse` panics in debug builds, and emits a diagnostic
+/// message in release builds or if the thread is already panicking.
+pub struct DropBomb {
+    message: Cow<'static, str>,
+    location: &'static Location<'static>,
+    defused: bool
+}
+
+impl DropBomb {
+    #[track_caller]
+    pub fn new(message: impl Into<Cow<'static, str>>) -> Self {
+        Self { message: message.into(), location: Location::caller(), defused: false }
+    }
+
+    pub fn defuse(&mut self) {
+        self.defused = true;
+    }
+
+    pub const fn is_defused(&self) -> bool {
+        self.defused
+    }
+}
+
+impl Drop for DropBomb {
+    fn drop(&mut self) {
+        if self.defused {
+            return;
+        }
+
+        let message: String = format!(
+            "DropBomb created at {} dropped without being defused: {}",
+            self.location,
+            self.message
+        );
+        if cfg!(debug_assertions) && !thread::panicking() {
+            panic!("{}", message);
+        } else {
+            diagnostic(&message);
+        }
+    }
+}
+
+/// Guard aborting the process if dropped during unwinding
+///
+/// Useful for sections where unwinding would break invariants, for example across FFI calls.
+/// Call `defuse` once the section completes successfully.
+pub struct AbortGuard {
+    location: &'static Location<'static>,
+    defused: bool
+}
+
+impl AbortGuard {
+    #[track_caller]
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self { location: Location::caller(), defused: false }
+    }
+
+    pub fn defuse(&mut self) {
+        self.defused = true;
+    }
+}
+
+impl Drop for AbortGuard {
+    fn drop(&mut self) {
+        if !self.defused && thread::panicking() {
+            diagnostic(&format!("panicked within AbortGuard created at {}, aborting", self.location));
+            std::process::abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::any::Any;
+    use std::panic::catch_unwind;
+
+    use crate::defer::{AbortGuard, DropBomb};
+
+    #[test]
+    fn test_defused() {
+        let mut bomb: DropBomb = DropBomb::new("114514");
+        assert!(!bomb.is_defused());
+        bomb.defuse();
+        assert!(bomb.is_defused());
+        drop(bomb);
+
+        let mut guard: AbortGuard = AbortGuard::new();
+        guard.defuse();
+        drop(guard);
+
+        // not defused, but there is no unwinding going on
+        let _guard: AbortGuard = AbortGuard::new();
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    fn test_drop_bomb_panic() {
+        let result: std::thread::Result<()> = catch_unwind(|| {
+            let _bomb: DropBomb = DropBomb::new(format!("commit {}", 893));
+        });
+        let location: String = format!("{}:{}:", file!(), line!() - 2);
+        let payload: Box<dyn Any + Send> = result.unwrap_err();
+        let message: &String = payload.downcast_ref::<String>().unwrap();
+        assert!(message.contains("commit 893"));
+        assert!(message.contains(&location));
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    fn test_drop_bomb_while_panicking() {
+        // a second panic would abort the process, so the bomb only reports via diagnostics
+        let result: std::thread::Result<()> = catch_unwind(|| {
+            let _bomb: DropBomb = DropBomb::new("unwinding");
+            panic!("1919810");
+        });
+        let payload: Box<dyn Any + Send> = result.unwrap_err();
+        assert_eq!(*payload.downcast_ref::<&str>().unwrap(), "1919810");
+    }
+
+    const ABORT_CHILD_ENV: &str = "XJBUTIL_ABORT_GUARD_CHILD";
+
+    #[test]
+    fn abort_guard_child() {
+        if std::env::var_os(ABORT_CHILD_ENV).is_none() {
+            return;
+        }
+        let _guard: AbortGuard = AbortGuard::new();
+        panic!("unwinding through AbortGuard");
+    }
+
+    #[cfg(all(unix, not(miri)))]
+    #[test]
+    fn test_abort_guard_aborts() {
+        use std::os::unix::process::ExitStatusExt;
+        use std::process::{Command, Output};
+
+        let output: Output = Command::new(std::env::current_exe().unwrap())
+            .args(["--exact", "defer::test::abort_guard_child", "--nocapture"])
+            .env(ABORT_CHILD_ENV, "1")
+            .output()
+            .unwrap();
+        assert_eq!(output.status.signal(), Some(6));
+        assert!(String::from_utf8_lossy(&output.stderr).contains("panicked within AbortGuard"));
+    }
+}
@@ -0,0 +1,88 @@
+//! Routing of diagnostic messages (such as potential resource leaks) emitted by this crate
+//!
+//! By default, messages are printed to standard error with `std`, and silently discarded without
+//! `std`. Install a custom handler with `set_diagnostic_handler` to route them elsewhere.
+
+use core::ptr::null_mut;
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+/// Handler receiving diagnostic messages, without the `[xjbutil]` prefix
+pub type DiagnosticHandler = fn(&str);
+
+// stored as a pointer rather than an integer, so that provenance of the function is kept
+static HANDLER: AtomicPtr<()> = AtomicPtr::new(null_mut());
+
+/// Route all further diagnostic messages to `handler`
+pub fn set_diagnostic_handler(handler: DiagnosticHandler) {
+    HANDLER.store(handler as *mut (), Ordering::SeqCst);
+}
+
+/// Restore the default diagnostic handler
+pub fn reset_diagnostic_handler() {
+    HANDLER.store(null_mut(), Ordering::SeqCst);
+}
+
+/// The default diagnostic handler
+pub fn default_diagnostic_handler(message: &str) {
+    #[cfg(feature = "std")]
+    eprintln!("[xjbutil] {}", message);
+    #[cfg(not(feature = "std"))]
+    let _ = message;
+}
+
+pub(crate) fn diagnostic(message: &str) {
+    let handler: *mut () = HANDLER.load(Ordering::SeqCst);
+    if handler.is_null() {
+        default_diagnostic_handler(message);
+    } else {
+        // SAFETY: non-null values only ever come from `set_diagnostic_handler`
+        let handler: DiagnosticHandler = unsafe {
+            core::mem::transmute::<*mut (), fn(&str)>(handler)
+        };
+        handler(message);
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use std::sync::Mutex;
+    use std::thread::{self, ThreadId};
+
+    use crate::diag_intern::{diagnostic, reset_diagnostic_handler, set_diagnostic_handler};
+
+    /// Held by tests installing their own diagnostic handler or `UncheckedOption` leak policy, so
+    /// that they do not replace each other's
+    pub(crate) static HANDLER_LOCK: Mutex<()> = Mutex::new(());
+
+    static CAPTURED: Mutex<Vec<(ThreadId, String)>> = Mutex::new(Vec::new());
+
+    /// Run `f` with diagnostics captured, returning the messages emitted by the current thread
+    ///
+    /// Other tests keep emitting diagnostics concurrently, those are discarded while capturing.
+    pub(crate) fn capture_diagnostics<R>(f: impl FnOnce() -> R) -> (R, Vec<String>) {
+        let _guard = HANDLER_LOCK.lock().unwrap();
+        set_diagnostic_handler(|message| {
+            CAPTURED.lock().unwrap().push((thread::current().id(), message.to_string()));
+        });
+        let result: R = f();
+        reset_diagnostic_handler();
+
+        let current: ThreadId = thread::current().id();
+        let messages: Vec<String> = CAPTURED.lock().unwrap()
+            .drain(..)
+            .filter(|(thread, _)| *thread == current)
+            .map(|(_, message)| message)
+            .collect();
+        (result, messages)
+    }
+
+    #[test]
+    fn test_diagnostic_handler() {
+        let ((), messages) = capture_diagnostics(|| diagnostic("114514"));
+        assert_eq!(messages, ["114514"]);
+
+        diagnostic("printed by the default handler");
+        let ((), messages) = capture_diagnostics(|| ());
+        assert!(messages.is_empty());
+    }
+}
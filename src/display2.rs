@@ -55,7 +55,7 @@ pub struct Display2Wrapper<'a, T: Display2> {
 }
 
 impl<'a, T: Display2> Display2Wrapper<'a, T> {
-    pub fn new(value: &'a T) -> Self {
+    pub const fn new(value: &'a T) -> Self {
         Self { value }
     }
 }
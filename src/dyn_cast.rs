@@ -0,0 +1,220 @@
+//! Cross-casting between trait objects
+//!
+//! Rust cannot tell whether a `dyn TraitA` also implements `TraitB`. Types registered with
+//! `impl_dyn_cast!` record the vtables of the traits they implement, and `dyn_cast` uses them to
+//! re-assemble a `dyn TraitB` fat pointer, via `WidePointer`, out of a `dyn DynCast`.
+//!
+//! ```
+//! # use xjbutil::dyn_cast::{DynCast, dyn_cast};
+//! # use xjbutil::impl_dyn_cast;
+//! trait Named { fn name(&self) -> &str; }
+//!
+//! struct Cat;
+//! impl Named for Cat { fn name(&self) -> &str { "cat" } }
+//! impl_dyn_cast!(Cat as dyn Named);
+//!
+//! let obj: &dyn DynCast = &Cat;
+//! assert_eq!(dyn_cast::<dyn Named>(obj).unwrap().name(), "cat");
+//! assert!(dyn_cast::<Cat>(obj).is_some());
+//! assert!(dyn_cast::<dyn std::fmt::Debug>(obj).is_none());
+//! ```
+
+use core::any::TypeId;
+use core::mem::size_of;
+
+#[cfg(feature = "korobka")] use core::ptr::NonNull;
+
+#[cfg(feature = "korobka")] use crate::korobka::Korobka;
+use crate::wide_ptr::WidePointer;
+
+/// Base trait of cross-castable types, implemented by `impl_dyn_cast!`
+///
+/// # Safety
+/// `dyn_cast_vtable` must only return vtables of `Self` for the requested trait object type, and
+/// `dyn_cast_type_id` must return `TypeId::of::<Self>()`. Use `impl_dyn_cast!` instead of
+/// implementing this trait manually.
+pub unsafe trait DynCast: 'static {
+    /// `TypeId` of the concrete type
+    fn dyn_cast_type_id(&self) -> TypeId;
+
+    /// Vtable of the concrete type for trait object type `target`, if registered
+    ///
+    /// The vtable is returned as a pointer, so that it keeps its provenance.
+    fn dyn_cast_vtable(&self, target: TypeId) -> Option<*const ()>;
+}
+
+/// Register a type as cross-castable to the given trait object types
+///
+/// ```rust,ignore
+/// impl_dyn_cast!(MyType as dyn TraitA, dyn TraitB);
+/// ```
+#[macro_export] macro_rules! impl_dyn_cast {
+    ($ty:ty as $($target:ty),* $(,)?) => {
+        unsafe impl $crate::dyn_cast::DynCast for $ty {
+            fn dyn_cast_type_id(&self) -> ::core::any::TypeId {
+                ::core::any::TypeId::of::<$ty>()
+            }
+
+            fn dyn_cast_vtable(
+                &self,
+                target: ::core::any::TypeId
+            ) -> ::core::option::Option<*const ()> {
+                $(
+                    if target == ::core::any::TypeId::of::<$target>() {
+                        let ptr: *const $target = self as *const $ty as *const $target;
+                        let wide_ptr: $crate::wide_ptr::WidePointer =
+                            $crate::wide_ptr::WidePointer::from_ptr(ptr);
+                        return ::core::option::Option::Some(wide_ptr.trivia);
+                    }
+                )*
+                ::core::option::Option::None
+            }
+        }
+    };
+}
+
+fn cast_ptr<T: ?Sized + 'static>(obj: &dyn DynCast, data: *const ()) -> Option<*const T> {
+    let target: TypeId = TypeId::of::<T>();
+    if size_of::<*const T>() == size_of::<*const ()>() {
+        // casting to the concrete type itself
+        if obj.dyn_cast_type_id() != target {
+            return None;
+        }
+        Some(unsafe { WidePointer::new(data, core::ptr::null()).to_ptr::<T>() })
+    } else {
+        let vtable: *const () = obj.dyn_cast_vtable(target)?;
+        Some(unsafe { WidePointer::new(data, vtable).to_ptr::<T>() })
+    }
+}
+
+/// View `obj` as a `T`, which is either a trait object type registered for the concrete type of
+/// `obj`, or the concrete type itself
+pub fn dyn_cast<T: ?Sized + 'static>(obj: &dyn DynCast) -> Option<&T> {
+    let data: *const () = obj as *const dyn DynCast as *const ();
+    cast_ptr::<T>(obj, data).map(|ptr| unsafe { &*ptr })
+}
+
+/// Mutable counterpart of `dyn_cast`
+pub fn dyn_cast_mut<T: ?Sized + 'static>(obj: &mut dyn DynCast) -> Option<&mut T> {
+    let data: *mut () = obj as *mut dyn DynCast as *mut ();
+    cast_ptr::<T>(obj, data).map(|ptr| unsafe { &mut *(ptr as *mut T) })
+}
+
+/// Owned counterpart of `dyn_cast`, giving back `obj` if the cast fails
+#[cfg(feature = "korobka")]
+pub fn dyn_cast_korobka<T: ?Sized + 'static>(
+    obj: Korobka<dyn DynCast>
+) -> Result<Korobka<T>, Korobka<dyn DynCast>> {
+    let data: *const () = obj.as_ptr() as *const ();
+    match cast_ptr::<T>(obj.as_ref(), data) {
+        Some(ptr) => {
+            let _ = Korobka::into_nonnull(obj);
+            Ok(unsafe { Korobka::from_nonnull(NonNull::new_unchecked(ptr as *mut T)) })
+        },
+        None => Err(obj)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fmt::Debug;
+
+    use crate::dyn_cast::{DynCast, dyn_cast, dyn_cast_mut};
+
+    trait Animal {
+        fn name(&self) -> String;
+    }
+
+    trait Pet {
+        fn pet(&mut self) -> u32;
+    }
+
+    trait Machine {
+        fn serial(&self) -> u64;
+    }
+
+    #[derive(Debug)]
+    struct Dog { petted: u32 }
+
+    struct Robot { serial: u64, petted: u32 }
+
+    impl Animal for Dog {
+        fn name(&self) -> String { "dog".into() }
+    }
+
+    impl Pet for Dog {
+        fn pet(&mut self) -> u32 { self.petted += 1; self.petted }
+    }
+
+    impl Pet for Robot {
+        fn pet(&mut self) -> u32 { self.petted += 10; self.petted }
+    }
+
+    impl Machine for Robot {
+        fn serial(&self) -> u64 { self.serial }
+    }
+
+    impl_dyn_cast!(Dog as dyn Animal, dyn Pet);
+    impl_dyn_cast!(Robot as dyn Pet, dyn Machine,);
+
+    #[test]
+    fn test_dyn_cast() {
+        let dog: Dog = Dog { petted: 0 };
+        let robot: Robot = Robot { serial: 114514, petted: 0 };
+        let objects: [&dyn DynCast; 2] = [&dog, &robot];
+
+        assert_eq!(dyn_cast::<dyn Animal>(objects[0]).unwrap().name(), "dog");
+        assert!(dyn_cast::<dyn Animal>(objects[1]).is_none());
+        assert!(dyn_cast::<dyn Machine>(objects[0]).is_none());
+        assert_eq!(dyn_cast::<dyn Machine>(objects[1]).unwrap().serial(), 114514);
+        assert!(dyn_cast::<dyn Pet>(objects[0]).is_some());
+        assert!(dyn_cast::<dyn Pet>(objects[1]).is_some());
+        assert!(dyn_cast::<dyn Debug>(objects[0]).is_none());
+
+        assert_eq!(dyn_cast::<Dog>(objects[0]).unwrap().petted, 0);
+        assert!(dyn_cast::<Robot>(objects[0]).is_none());
+        assert_eq!(dyn_cast::<Robot>(objects[1]).unwrap().serial, 114514);
+    }
+
+    #[test]
+    fn test_data_pointer_preserved() {
+        let robot: Robot = Robot { serial: 1919810, petted: 0 };
+        let obj: &dyn DynCast = &robot;
+        let machine: &dyn Machine = dyn_cast::<dyn Machine>(obj).unwrap();
+        assert_eq!(machine as *const dyn Machine as *const (), &robot as *const Robot as *const ());
+        let concrete: &Robot = dyn_cast::<Robot>(obj).unwrap();
+        assert!(std::ptr::eq(concrete, &robot));
+    }
+
+    #[test]
+    fn test_dyn_cast_mut() {
+        let mut dog: Dog = Dog { petted: 0 };
+        let mut robot: Robot = Robot { serial: 893, petted: 0 };
+        {
+            let mut objects: [&mut dyn DynCast; 2] = [&mut dog, &mut robot];
+            for obj in objects.iter_mut() {
+                let pet: &mut dyn Pet = dyn_cast_mut::<dyn Pet>(*obj).unwrap();
+                pet.pet();
+                pet.pet();
+            }
+            assert!(dyn_cast_mut::<dyn Machine>(objects[0]).is_none());
+        }
+        assert_eq!(dog.petted, 2);
+        assert_eq!(robot.petted, 20);
+    }
+
+    #[cfg(feature = "korobka")]
+    #[test]
+    fn test_dyn_cast_korobka() {
+        use crate::dyn_cast::dyn_cast_korobka;
+        use crate::korobka::Korobka;
+
+        let dog: Box<dyn DynCast> = Box::new(Dog { petted: 3 });
+        let obj: Korobka<dyn DynCast> = Korobka::from(dog);
+        let data: *const () = obj.as_ptr() as *const ();
+        let obj: Korobka<dyn DynCast> = dyn_cast_korobka::<dyn Machine>(obj).err().unwrap();
+        let mut pet: Korobka<dyn Pet> = dyn_cast_korobka::<dyn Pet>(obj).ok().unwrap();
+        assert_eq!(pet.as_ptr() as *const (), data);
+        assert_eq!(pet.pet(), 4);
+    }
+}
@@ -3,12 +3,118 @@
 //! This structure is in effect isomorphic with `Result`, but does not have certain traits
 //! implemented. Personally I think this is the better choice for expressing something which is not
 //! really a `Result`.
-#[derive(Debug, Clone)]
+
+#[cfg(feature = "alloc")] use alloc::vec::Vec;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "either-serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Either<T1, T2> {
     Left(T1),
     Right(T2)
 }
 
+impl<T1, T2> Either<T1, T2> {
+    pub const fn is_left(&self) -> bool {
+        matches!(self, Either::Left(_))
+    }
+
+    pub const fn is_right(&self) -> bool {
+        matches!(self, Either::Right(_))
+    }
+
+    pub const fn as_ref(&self) -> Either<&T1, &T2> {
+        match self {
+            Either::Left(l) => Either::Left(l),
+            Either::Right(r) => Either::Right(r)
+        }
+    }
+
+    const_mut_fn! {
+        pub fn as_mut(&mut self) -> Either<&mut T1, &mut T2> {
+            match self {
+                Either::Left(l) => Either::Left(l),
+                Either::Right(r) => Either::Right(r)
+            }
+        }
+    }
+}
+
+/// Split the items of `iter` into left and right collections, according to `f`
+#[cfg(feature = "alloc")]
+pub fn partition_map<I, L, R, F>(iter: I, mut f: F) -> (Vec<L>, Vec<R>)
+    where I: IntoIterator,
+          F: FnMut(I::Item) -> Either<L, R>
+{
+    let mut lefts: Vec<L> = Vec::new();
+    let mut rights: Vec<R> = Vec::new();
+    for item in iter {
+        match f(item) {
+            Either::Left(l) => lefts.push(l),
+            Either::Right(r) => rights.push(r)
+        }
+    }
+    (lefts, rights)
+}
+
+/// Fuzzer input of all zeros always generates the `Left` arm, so minimized inputs prefer it
+#[cfg(feature = "arbitrary")]
+impl<'a, T1, T2> arbitrary::Arbitrary<'a> for Either<T1, T2>
+    where T1: arbitrary::Arbitrary<'a>,
+          T2: arbitrary::Arbitrary<'a>
+{
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        if u.arbitrary::<bool>()? {
+            Ok(Either::Right(u.arbitrary()?))
+        } else {
+            Ok(Either::Left(u.arbitrary()?))
+        }
+    }
+
+    fn size_hint(depth: usize) -> (usize, Option<usize>) {
+        arbitrary::size_hint::and(
+            <bool as arbitrary::Arbitrary<'a>>::size_hint(depth),
+            arbitrary::size_hint::or(
+                <T1 as arbitrary::Arbitrary<'a>>::size_hint(depth),
+                <T2 as arbitrary::Arbitrary<'a>>::size_hint(depth)
+            )
+        )
+    }
+}
+
+/// Strategy generating `Either::Left` from `left` and `Either::Right` from `right`
+///
+/// Shrinking prefers the `Left` arm, then smaller payloads as shrunk by `left` and `right`.
+#[cfg(feature = "proptest")]
+pub fn either_strategy<SL, SR>(
+    left: SL,
+    right: SR
+) -> impl proptest::strategy::Strategy<Value = Either<SL::Value, SR::Value>>
+    where SL: proptest::strategy::Strategy,
+          SR: proptest::strategy::Strategy
+{
+    proptest::prop_oneof![left.prop_map(Either::Left), right.prop_map(Either::Right)]
+}
+
+#[cfg(feature = "proptest")]
+impl<T1, T2> proptest::arbitrary::Arbitrary for Either<T1, T2>
+    where T1: proptest::arbitrary::Arbitrary,
+          T2: proptest::arbitrary::Arbitrary,
+          T1::Strategy: 'static,
+          T2::Strategy: 'static
+{
+    type Parameters = (T1::Parameters, T2::Parameters);
+    type Strategy = proptest::strategy::BoxedStrategy<Self>;
+
+    fn arbitrary_with(args: Self::Parameters) -> Self::Strategy {
+        use proptest::strategy::Strategy;
+
+        either_strategy(
+            proptest::arbitrary::any_with::<T1>(args.0),
+            proptest::arbitrary::any_with::<T2>(args.1)
+        ).boxed()
+    }
+}
+
 #[cfg(test)]
 mod test {
     use crate::either::Either;
@@ -33,10 +139,108 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_either_accessors() {
+        let mut e1: Either<i32, String> = Either::Left(114);
+        let e2: Either<i32, String> = Either::Right("514".to_string());
+        assert!(e1.is_left() && !e1.is_right());
+        assert!(e2.is_right() && !e2.is_left());
+        assert_eq!(e2.as_ref(), Either::Right(&"514".to_string()));
+
+        if let Either::Left(num) = e1.as_mut() {
+            *num += 400;
+        }
+        assert_eq!(e1, Either::Left(514));
+    }
+
     #[test]
     fn test_either_no_debug_clone() {
+        #[allow(dead_code)]
         struct Shit(String, i32);
 
         let _x : Either<Shit, String> = Either::Right("Fuck".into());
     }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_partition_map() {
+        let (evens, odds): (Vec<i32>, Vec<String>) = crate::either::partition_map(
+            0..6,
+            |x| if x % 2 == 0 { Either::Left(x) } else { Either::Right(x.to_string()) }
+        );
+        assert_eq!(evens, vec![0, 2, 4]);
+        assert_eq!(odds, vec!["1", "3", "5"]);
+    }
+
+    #[cfg(feature = "arbitrary")]
+    #[test]
+    fn test_arbitrary() {
+        use arbitrary::{Arbitrary, Unstructured};
+
+        let mut u: Unstructured = Unstructured::new(&[0; 16]);
+        assert_eq!(Either::<u32, String>::arbitrary(&mut u).unwrap(), Either::Left(0));
+
+        let mut u: Unstructured = Unstructured::new(&[1, 0x72, 0, 0, 0]);
+        assert_eq!(Either::<u8, u32>::arbitrary(&mut u).unwrap(), Either::Right(0x72));
+
+        let mut u: Unstructured = Unstructured::new(&[1, 0, 7]);
+        let nested: Either<u8, Either<bool, u8>> = Arbitrary::arbitrary(&mut u).unwrap();
+        assert_eq!(nested, Either::Right(Either::Left(true)));
+    }
+
+    #[cfg(all(feature = "proptest", feature = "alloc"))]
+    mod prop {
+        use proptest::prelude::*;
+
+        use crate::either::{Either, either_strategy};
+
+        fn config() -> ProptestConfig {
+            ProptestConfig::with_cases(if cfg!(miri) { 4 } else { 256 })
+        }
+
+        proptest! {
+            #![proptest_config(config())]
+
+            #[test]
+            fn test_partition_map_preserves_items(
+                items in prop::collection::vec(either_strategy(any::<i32>(), ".*"), 0..32)
+            ) {
+                let (lefts, rights): (Vec<i32>, Vec<String>) =
+                    crate::either::partition_map(items.clone(), |item| item);
+                prop_assert_eq!(lefts.len() + rights.len(), items.len());
+
+                let expected_lefts: Vec<i32> = items.iter()
+                    .filter_map(|item| if let Either::Left(l) = item { Some(*l) } else { None })
+                    .collect();
+                prop_assert_eq!(lefts, expected_lefts);
+            }
+        }
+
+        #[cfg(feature = "either-serde")]
+        proptest! {
+            #![proptest_config(config())]
+
+            #[test]
+            fn test_serde_round_trip(value in any::<Either<Vec<u8>, Either<String, i64>>>()) {
+                let json: String = serde_json::to_string(&value).unwrap();
+                let back: Either<Vec<u8>, Either<String, i64>> =
+                    serde_json::from_str(&json).unwrap();
+                prop_assert_eq!(back, value);
+            }
+        }
+
+        #[test]
+        fn test_shrink_prefers_left() {
+            use proptest::strategy::ValueTree;
+            use proptest::test_runner::TestRunner;
+
+            let mut runner: TestRunner = TestRunner::deterministic();
+            let strategy = either_strategy(0..100u32, 0..100u32);
+            for _ in 0..16 {
+                let mut tree = strategy.new_tree(&mut runner).unwrap();
+                while tree.simplify() {}
+                assert_eq!(tree.current(), Either::Left(0));
+            }
+        }
+    }
 }
@@ -0,0 +1,226 @@
+//! Passing Rust objects through `void *` context pointers of C APIs
+//!
+//! The object is stored behind a header. In debug builds, the header records the `TypeId` of the
+//! object, which is checked whenever the pointer is cast back, turning wrong-type casts into
+//! panics. In release builds the header is empty, and the context pointer points to the object
+//! directly.
+//!
+//! In all builds, the object is moved out of the given `Korobka` into a new allocation along with
+//! the header, and moved again into a new `Korobka` when taken back. Its address only stays the
+//! same while it is behind the context pointer.
+//!
+//! Note that panics cannot unwind out of `extern "C"` functions, so a type mismatch detected in
+//! a callback invoked by C aborts the process.
+
+use core::ffi::c_void;
+use core::ptr::{NonNull, addr_of};
+
+use crate::korobka::Korobka;
+
+#[cfg(debug_assertions)] use core::any::{TypeId, type_name};
+
+#[repr(C)]
+struct Tagged<T> {
+    tag: Tag,
+    value: T
+}
+
+#[cfg(debug_assertions)]
+#[derive(Clone, Copy)]
+struct Tag {
+    type_id: TypeId,
+    type_name: &'static str
+}
+
+#[cfg(debug_assertions)]
+impl Tag {
+    fn of<T: 'static>() -> Self {
+        Self { type_id: TypeId::of::<T>(), type_name: type_name::<T>() }
+    }
+
+    fn check<T: 'static>(self) {
+        if self.type_id != TypeId::of::<T>() {
+            panic!(
+                "FFI context pointer type mismatch: expected `{}`, found `{}`",
+                type_name::<T>(),
+                self.type_name
+            );
+        }
+    }
+}
+
+#[cfg(not(debug_assertions))]
+#[derive(Clone, Copy)]
+struct Tag;
+
+#[cfg(not(debug_assertions))]
+impl Tag {
+    #[allow(clippy::extra_unused_type_parameters)]
+    #[inline(always)] fn of<T: 'static>() -> Self {
+        Self
+    }
+
+    #[inline(always)] fn check<T: 'static>(self) {}
+}
+
+unsafe fn check_tag<T: 'static>(ptr: *mut c_void) -> NonNull<Tagged<T>> {
+    debug_assert!(!ptr.is_null(), "null FFI context pointer");
+    // the header is at the same offset regardless of the payload type
+    let header: *const Tagged<()> = ptr as *const Tagged<()>;
+    (*addr_of!((*header).tag)).check::<T>();
+    NonNull::new_unchecked(ptr as *mut Tagged<T>)
+}
+
+/// Convert `value` into a context pointer for C APIs
+///
+/// The object is moved into a new allocation, and leaked until the pointer is passed to
+/// `from_ffi_ptr` or `drop_ffi_ptr`.
+pub fn into_ffi_ptr<T: 'static>(value: Korobka<T>) -> *mut c_void {
+    let tagged: Korobka<Tagged<T>> = Korobka::new(Tagged {
+        tag: Tag::of::<T>(),
+        value: Korobka::into_inner(value)
+    });
+    Korobka::into_nonnull(tagged).as_ptr() as *mut c_void
+}
+
+/// Take back the object behind a context pointer, moving it into a new `Korobka`
+///
+/// In debug builds, panics if `ptr` is null or was created from another type.
+///
+/// # Safety
+/// `ptr` must be returned by `into_ffi_ptr::<T>`, and must not be used after this call.
+pub unsafe fn from_ffi_ptr<T: 'static>(ptr: *mut c_void) -> Korobka<T> {
+    let tagged: Korobka<Tagged<T>> = Korobka::from_nonnull(check_tag::<T>(ptr));
+    Korobka::new(Korobka::into_inner(tagged).value)
+}
+
+/// Call `f` with the object behind a context pointer, without taking it back
+///
+/// In debug builds, panics if `ptr` is null or was created from another type.
+///
+/// # Safety
+/// `ptr` must be returned by `into_ffi_ptr::<T>` and not yet taken back. No other reference to
+/// the object may exist during the call, which also rules out re-entrant calls on the same
+/// pointer from within `f`.
+pub unsafe fn with_ffi_ptr<T: 'static, R>(ptr: *mut c_void, f: impl FnOnce(&mut T) -> R) -> R {
+    let tagged: NonNull<Tagged<T>> = check_tag::<T>(ptr);
+    f(&mut (*tagged.as_ptr()).value)
+}
+
+/// Destructor for context pointers, to be handed to C APIs as `drop_ffi_ptr::<T>`
+///
+/// Null pointers are ignored.
+///
+/// # Safety
+/// `ptr` must be null, or returned by `into_ffi_ptr::<T>` and not used after this call.
+pub unsafe extern "C" fn drop_ffi_ptr<T: 'static>(ptr: *mut c_void) {
+    if !ptr.is_null() {
+        drop(Korobka::from_nonnull(check_tag::<T>(ptr)));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+    use std::ffi::c_void;
+    use std::rc::Rc;
+
+    use crate::ffi_ctx::{drop_ffi_ptr, from_ffi_ptr, into_ffi_ptr, with_ffi_ptr};
+    use crate::korobka::Korobka;
+    use crate::test_intern::DropCounter;
+
+    /// Mimics a C library storing a callback along with its context and destructor
+    struct FakeCApi {
+        callback: extern "C" fn(*mut c_void, i32) -> i32,
+        ctx: *mut c_void,
+        dtor: unsafe extern "C" fn(*mut c_void)
+    }
+
+    impl FakeCApi {
+        fn fire(&self, arg: i32) -> i32 {
+            (self.callback)(self.ctx, arg)
+        }
+    }
+
+    impl Drop for FakeCApi {
+        fn drop(&mut self) {
+            unsafe { (self.dtor)(self.ctx) }
+        }
+    }
+
+    extern "C" fn trampoline<F: FnMut(i32) -> i32 + 'static>(ctx: *mut c_void, arg: i32) -> i32 {
+        unsafe { with_ffi_ptr::<F, _>(ctx, |f| f(arg)) }
+    }
+
+    fn register<F: FnMut(i32) -> i32 + 'static>(f: F) -> FakeCApi {
+        FakeCApi {
+            callback: trampoline::<F>,
+            ctx: into_ffi_ptr(Korobka::new(f)),
+            dtor: drop_ffi_ptr::<F>
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let ptr: *mut c_void = into_ffi_ptr(Korobka::new(String::from("114")));
+        unsafe { with_ffi_ptr::<String, _>(ptr, |s| s.push_str("514")); }
+        let len: usize = unsafe { with_ffi_ptr::<String, _>(ptr, |s| s.len()) };
+        assert_eq!(len, 6);
+        let value: Korobka<String> = unsafe { from_ffi_ptr(ptr) };
+        assert_eq!(value.as_str(), "114514");
+    }
+
+    #[test]
+    fn test_address() {
+        let ptr: *mut c_void = into_ffi_ptr(Korobka::new([114_u32, 514]));
+        let addr = |ptr: *mut c_void| unsafe {
+            with_ffi_ptr::<[u32; 2], _>(ptr, |array| array as *mut [u32; 2] as *mut c_void)
+        };
+        let first: *mut c_void = addr(ptr);
+        assert_eq!(addr(ptr), first);
+        // only debug builds put a non-empty header ahead of the object
+        assert_eq!(first == ptr, !cfg!(debug_assertions));
+        let value: Korobka<[u32; 2]> = unsafe { from_ffi_ptr(ptr) };
+        assert_eq!(*value, [114, 514]);
+    }
+
+    #[test]
+    fn test_callback() {
+        let drops: Rc<Cell<usize>> = Rc::new(Cell::new(0));
+        {
+            let guard: DropCounter = DropCounter(drops.clone(), 0);
+            let mut total: i32 = 0;
+            let api: FakeCApi = register(move |x| {
+                let _ = &guard;
+                total += x;
+                total
+            });
+            assert_eq!(api.fire(1919), 1919);
+            assert_eq!(api.fire(810), 2729);
+            assert_eq!(drops.get(), 0);
+        }
+        assert_eq!(drops.get(), 1);
+    }
+
+    #[test]
+    fn test_drop_null() {
+        unsafe { drop_ffi_ptr::<String>(std::ptr::null_mut()); }
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "FFI context pointer type mismatch")]
+    fn test_type_mismatch() {
+        /// Frees the context while unwinding from the expected panic
+        struct Context(*mut c_void);
+
+        impl Drop for Context {
+            fn drop(&mut self) {
+                unsafe { drop_ffi_ptr::<u64>(self.0) }
+            }
+        }
+
+        let ctx: Context = Context(into_ffi_ptr(Korobka::new(893_u64)));
+        unsafe { with_ffi_ptr::<String, _>(ctx.0, |_| ()); }
+    }
+}
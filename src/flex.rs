@@ -34,11 +34,12 @@
 //! Using `FlexArray` would allow you allocating `fixed` and `flex` together, avoiding a second
 //! allocation.
 
-use std::alloc::{Layout, alloc, dealloc};
+use std::alloc::{Layout, alloc, dealloc, handle_alloc_error};
 use std::marker::PhantomData;
 use std::mem::{ManuallyDrop, MaybeUninit};
 use std::ptr::{NonNull, addr_of, addr_of_mut, slice_from_raw_parts, slice_from_raw_parts_mut};
 
+use crate::korobka::Korobka;
 use crate::mem_intern::{leak_as_nonnull, reclaim_as_boxed};
 
 pub struct FlexArray<NonFlex, T: Copy> {
@@ -172,7 +173,7 @@ impl<NF, T: Copy> FlexArray<NF, T> {
         }
     }
 
-    pub fn as_ref(&self) -> FLARef<NF, T> {
+    pub fn as_ref(&self) -> FLARef<'_, NF, T> {
         let raw: *const FLABuffer<NF, T> = self.raw.as_ptr();
         let len: usize = unsafe { (*raw).len };
         if len == 0 {
@@ -193,7 +194,7 @@ impl<NF, T: Copy> FlexArray<NF, T> {
         }
     }
 
-    pub fn as_mut(&mut self) -> FLARefMut<NF, T> {
+    pub fn as_mut(&mut self) -> FLARefMut<'_, NF, T> {
         let raw: *mut FLABuffer<NF, T> = self.raw.as_ptr();
         let len: usize = unsafe { (*raw).len };
         if len == 0 {
@@ -254,16 +255,113 @@ impl<NF, T: Copy> Drop for FlexArray<NF, T> {
 unsafe impl<NF, T> Send for FlexArray<NF, T> where NF: Send, T: Copy + Send {}
 unsafe impl<NF, T> Sync for FlexArray<NF, T> where NF: Sync, T: Copy + Sync {}
 
+/// Header-plus-payload dynamically sized type, constructed with `alloc_flex`
+///
+/// Unlike `FlexArray`, the payload may be of any type, and the whole object is a plain Rust DST
+/// which can be handled with `Korobka`, `&` or `&mut`.
+#[repr(C)]
+pub struct Flex<H, T> {
+    header: H,
+    data: [T]
+}
+
+impl<H, T> Flex<H, T> {
+    pub fn header(&self) -> &H {
+        &self.header
+    }
+
+    pub fn header_mut(&mut self) -> &mut H {
+        &mut self.header
+    }
+
+    pub fn data(&self) -> &[T] {
+        &self.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut [T] {
+        &mut self.data
+    }
+}
+
+/// Cleans up a partially initialized `Flex` if the iterator panics or lies about its length
+struct FlexGuard<H, T> {
+    mem: *mut u8,
+    layout: Layout,
+    array: *mut T,
+    initialized: usize,
+    _phantom: PhantomData<H>
+}
+
+impl<H, T> Drop for FlexGuard<H, T> {
+    fn drop(&mut self) {
+        unsafe {
+            std::ptr::drop_in_place(self.mem as *mut H);
+            std::ptr::drop_in_place(slice_from_raw_parts_mut(self.array, self.initialized));
+            if self.layout.size() != 0 {
+                dealloc(self.mem, self.layout);
+            }
+        }
+    }
+}
+
+/// Allocate a `Flex` with given `header`, and all items from `iter` as payload
+///
+/// Panics if `iter` yields less items than it reports via `ExactSizeIterator::len`.
+pub fn alloc_flex<H, T, I>(header: H, iter: I) -> Korobka<Flex<H, T>>
+    where I: ExactSizeIterator<Item = T>
+{
+    let len: usize = iter.len();
+    let (layout, array_offset): (Layout, usize) = Layout::new::<H>()
+        .extend(Layout::array::<T>(len).unwrap())
+        .unwrap();
+    let layout: Layout = layout.pad_to_align();
+
+    let mem: *mut u8 = if layout.size() == 0 {
+        std::ptr::null_mut::<u8>().wrapping_add(layout.align())
+    } else {
+        let mem: *mut u8 = unsafe { alloc(layout) };
+        if mem.is_null() {
+            handle_alloc_error(layout);
+        }
+        mem
+    };
+
+    unsafe {
+        (mem as *mut H).write(header);
+        let mut guard: FlexGuard<H, T> = FlexGuard {
+            mem,
+            layout,
+            array: mem.add(array_offset) as *mut T,
+            initialized: 0,
+            _phantom: PhantomData
+        };
+        for item in iter.take(len) {
+            guard.array.add(guard.initialized).write(item);
+            guard.initialized += 1;
+        }
+        assert_eq!(guard.initialized, len, "iterator yielded less items than its `len`");
+        std::mem::forget(guard);
+
+        // the slice metadata (length) carries over to the `Flex` pointer
+        let raw: *mut Flex<H, T> = slice_from_raw_parts_mut(mem as *mut T, len) as *mut Flex<H, T>;
+        Korobka::from(Box::from_raw(raw))
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use crate::flex::{FLARef, FLARefMut, FlexArray};
+    use std::cell::Cell;
+    use std::mem::{align_of, size_of_val};
+
+    use crate::flex::{FLARef, FLARefMut, Flex, FlexArray, alloc_flex};
+    use crate::korobka::Korobka;
 
     #[test]
     fn test_zero_length() {
         let flex_array: FlexArray<String, u64> = FlexArray::new("为有牺牲多壮志".into(), &[]);
         let arr_ref: FLARef<String, u64> = flex_array.as_ref();
         assert_eq!(arr_ref.fixed, "为有牺牲多壮志");
-        assert_eq!(arr_ref.flex, &[]);
+        assert_eq!(arr_ref.flex, &[] as &[u64]);
     }
 
     #[test]
@@ -291,4 +389,85 @@ mod test {
         assert_eq!(arr_ref.fixed, "ultraman Ace, u ch u no Ace!");
         assert_eq!(arr_ref.flex, &[1, 1, 114514, 5, 1, 4]);
     }
+
+    #[test]
+    fn test_alloc_flex_big_header_align() {
+        #[repr(align(32))]
+        struct Header(u8);
+
+        let flex: Korobka<Flex<Header, u8>> = alloc_flex(Header(114), vec![1u8, 2, 3].into_iter());
+        assert_eq!(flex.header().0, 114);
+        assert_eq!(flex.data(), &[1, 2, 3]);
+        assert_eq!(flex.as_ptr() as *const u8 as usize % 32, 0);
+        assert_eq!(size_of_val(&*flex), 64);
+        assert_eq!(flex.data().as_ptr() as usize - flex.as_ptr() as *const u8 as usize, 32);
+    }
+
+    #[test]
+    fn test_alloc_flex_small_header_align() {
+        let mut flex: Korobka<Flex<u8, u64>> = alloc_flex(7u8, (0..5u32).map(|x| x as u64 * 100));
+        assert_eq!(*flex.header(), 7);
+        assert_eq!(flex.data(), &[0, 100, 200, 300, 400]);
+        assert_eq!(flex.data().as_ptr() as usize % align_of::<u64>(), 0);
+        assert_eq!(size_of_val(&*flex), 48);
+
+        *flex.header_mut() = 8;
+        flex.data_mut()[4] = 114514;
+        assert_eq!(*flex.header(), 8);
+        assert_eq!(flex.data()[4], 114514);
+    }
+
+    #[test]
+    fn test_alloc_flex_empty() {
+        let flex: Korobka<Flex<String, String>> =
+            alloc_flex("为有牺牲多壮志".into(), std::iter::empty());
+        assert_eq!(flex.header(), "为有牺牲多壮志");
+        assert!(flex.data().is_empty());
+
+        let zst: Korobka<Flex<(), ()>> = alloc_flex((), std::iter::empty());
+        assert_eq!(zst.data().len(), 0);
+        let zst: Korobka<Flex<(), ()>> = alloc_flex((), (0..3).map(|_| ()));
+        assert_eq!(zst.data().len(), 3);
+    }
+
+    #[test]
+    fn test_alloc_flex_drop() {
+        struct Counted<'a>(&'a Cell<usize>);
+
+        impl Drop for Counted<'_> {
+            fn drop(&mut self) {
+                self.0.set(self.0.get() + 1);
+            }
+        }
+
+        let headers: Cell<usize> = Cell::new(0);
+        let items: Cell<usize> = Cell::new(0);
+        let flex: Korobka<Flex<Counted, Counted>> =
+            alloc_flex(Counted(&headers), (0..10).map(|_| Counted(&items)));
+        assert_eq!((headers.get(), items.get()), (0, 0));
+        drop(flex);
+        assert_eq!((headers.get(), items.get()), (1, 10));
+    }
+
+    #[test]
+    fn test_alloc_flex_short_iterator() {
+        struct Liar(std::ops::Range<u32>);
+
+        impl Iterator for Liar {
+            type Item = String;
+
+            fn next(&mut self) -> Option<String> {
+                self.0.next().map(|x| x.to_string())
+            }
+        }
+
+        impl ExactSizeIterator for Liar {
+            fn len(&self) -> usize {
+                self.0.len() + 1
+            }
+        }
+
+        let result = std::panic::catch_unwind(|| alloc_flex("header".to_string(), Liar(0..3)));
+        assert!(result.is_err());
+    }
 }
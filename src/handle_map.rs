@@ -0,0 +1,313 @@
+//! Slot map addressed by generational handles
+//!
+//! A `Handle` packs a slot index with the generation of the slot at insertion time. Removing an
+//! object bumps the generation of its slot, so handles to removed objects are rejected instead of
+//! silently referring to whatever object reuses the slot later.
+//!
+//! When the generation of a slot reaches `u32::MAX`, the slot is retired on removal instead of
+//! wrapping around to zero, so a stale handle can never become valid again. A retired slot is
+//! never reused, which leaks one slot per `2^32` removals at the same index.
+
+use core::convert::TryFrom;
+use core::fmt::{Debug, Formatter};
+use core::iter::Enumerate;
+use core::slice;
+
+use alloc::vec::Vec;
+
+/// Handle to an object in a `HandleMap`
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct Handle(u64);
+
+impl Handle {
+    const fn new(index: u32, generation: u32) -> Self {
+        Self(((generation as u64) << 32) | (index as u64))
+    }
+
+    #[inline(always)] pub const fn index(self) -> u32 {
+        self.0 as u32
+    }
+
+    #[inline(always)] pub const fn generation(self) -> u32 {
+        (self.0 >> 32) as u32
+    }
+
+    #[inline(always)] pub const fn to_raw(self) -> u64 {
+        self.0
+    }
+
+    #[inline(always)] pub const fn from_raw(raw: u64) -> Self {
+        Self(raw)
+    }
+}
+
+impl Debug for Handle {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Handle({}v{})", self.index(), self.generation())
+    }
+}
+
+enum Entry<T> {
+    Occupied(T),
+    Vacant(Option<u32>)
+}
+
+struct Slot<T> {
+    generation: u32,
+    entry: Entry<T>
+}
+
+/// Storage of objects addressed by `Handle`s
+///
+/// Vacant slots are reused in LIFO order.
+pub struct HandleMap<T> {
+    slots: Vec<Slot<T>>,
+    free_head: Option<u32>,
+    len: usize
+}
+
+impl<T> HandleMap<T> {
+    pub const fn new() -> Self {
+        Self { slots: Vec::new(), free_head: None, len: 0 }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self { slots: Vec::with_capacity(capacity), free_head: None, len: 0 }
+    }
+
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn insert(&mut self, value: T) -> Handle {
+        self.len += 1;
+        if let Some(index) = self.free_head {
+            let slot: &mut Slot<T> = &mut self.slots[index as usize];
+            if let Entry::Vacant(next_free) = slot.entry {
+                self.free_head = next_free;
+            } else {
+                unreachable!("occupied slot in free list");
+            }
+            slot.entry = Entry::Occupied(value);
+            return Handle::new(index, slot.generation);
+        }
+
+        let index: u32 = u32::try_from(self.slots.len()).expect("handle map index overflow");
+        self.slots.push(Slot { generation: 0, entry: Entry::Occupied(value) });
+        Handle::new(index, 0)
+    }
+
+    pub fn contains(&self, handle: Handle) -> bool {
+        self.get(handle).is_some()
+    }
+
+    pub fn get(&self, handle: Handle) -> Option<&T> {
+        match self.slots.get(handle.index() as usize) {
+            Some(Slot { generation, entry: Entry::Occupied(value) })
+                if *generation == handle.generation() => Some(value),
+            _ => None
+        }
+    }
+
+    pub fn get_mut(&mut self, handle: Handle) -> Option<&mut T> {
+        match self.slots.get_mut(handle.index() as usize) {
+            Some(Slot { generation, entry: Entry::Occupied(value) })
+                if *generation == handle.generation() => Some(value),
+            _ => None
+        }
+    }
+
+    /// Get the object referred to by `handle`, without checking in release builds
+    ///
+    /// In debug builds, panics if `handle` is stale or invalid.
+    ///
+    /// # Safety
+    /// `handle` must refer to an object still in this map.
+    pub unsafe fn get_unchecked_dbg(&self, handle: Handle) -> &T {
+        #[cfg(debug_assertions)]
+        {
+            self.get(handle).expect("stale or invalid handle")
+        }
+        #[cfg(not(debug_assertions))]
+        {
+            match &self.slots.get_unchecked(handle.index() as usize).entry {
+                Entry::Occupied(value) => value,
+                Entry::Vacant(_) => core::hint::unreachable_unchecked()
+            }
+        }
+    }
+
+    /// Remove the object referred to by `handle`, invalidating all handles to it
+    pub fn remove(&mut self, handle: Handle) -> Option<T> {
+        let index: u32 = handle.index();
+        let slot: &mut Slot<T> = self.slots.get_mut(index as usize)?;
+        if slot.generation != handle.generation() || matches!(slot.entry, Entry::Vacant(_)) {
+            return None;
+        }
+
+        let retired: bool = slot.generation == u32::MAX;
+        let next_free: Option<u32> = if retired { None } else { self.free_head };
+        let value: T = match core::mem::replace(&mut slot.entry, Entry::Vacant(next_free)) {
+            Entry::Occupied(value) => value,
+            Entry::Vacant(_) => unreachable!()
+        };
+        if !retired {
+            slot.generation += 1;
+            self.free_head = Some(index);
+        }
+        self.len -= 1;
+        Some(value)
+    }
+
+    /// Iterate over handles and objects, in slot order
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter { inner: self.slots.iter().enumerate() }
+    }
+
+    /// Iterate over handles and mutable references to objects, in slot order
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        IterMut { inner: self.slots.iter_mut().enumerate() }
+    }
+}
+
+impl<T> Default for HandleMap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct Iter<'a, T> {
+    inner: Enumerate<slice::Iter<'a, Slot<T>>>
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = (Handle, &'a T);
+
+    fn next(&mut self) -> Option<(Handle, &'a T)> {
+        self.inner.find_map(|(index, slot)| match &slot.entry {
+            Entry::Occupied(value) => Some((Handle::new(index as u32, slot.generation), value)),
+            Entry::Vacant(_) => None
+        })
+    }
+}
+
+pub struct IterMut<'a, T> {
+    inner: Enumerate<slice::IterMut<'a, Slot<T>>>
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = (Handle, &'a mut T);
+
+    fn next(&mut self) -> Option<(Handle, &'a mut T)> {
+        self.inner.find_map(|(index, slot)| match &mut slot.entry {
+            Entry::Occupied(value) => Some((Handle::new(index as u32, slot.generation), value)),
+            Entry::Vacant(_) => None
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::handle_map::{Handle, HandleMap};
+
+    #[test]
+    fn test_insert_get_remove() {
+        let mut map: HandleMap<String> = HandleMap::new();
+        let h1: Handle = map.insert("114".into());
+        let h2: Handle = map.insert("514".into());
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get(h1).unwrap(), "114");
+        map.get_mut(h2).unwrap().push_str("1919");
+        assert_eq!(map.remove(h2).as_deref(), Some("5141919"));
+        assert_eq!(map.remove(h2), None);
+        assert_eq!(map.len(), 1);
+        assert_eq!(Handle::from_raw(h1.to_raw()), h1);
+        assert!(map.get(Handle::from_raw(u64::MAX)).is_none());
+    }
+
+    #[test]
+    fn test_stale_handle() {
+        let mut map: HandleMap<i32> = HandleMap::new();
+        let _h0: Handle = map.insert(0);
+        let h1: Handle = map.insert(1);
+        let h2: Handle = map.insert(2);
+        map.remove(h1);
+        map.remove(h2);
+
+        // LIFO reuse: the most recently freed slot comes first
+        let h3: Handle = map.insert(3);
+        let h4: Handle = map.insert(4);
+        assert_eq!(h3.index(), h2.index());
+        assert_eq!(h4.index(), h1.index());
+        assert_eq!(h3.generation(), h2.generation() + 1);
+
+        assert!(map.get(h1).is_none());
+        assert!(map.get_mut(h2).is_none());
+        assert!(!map.contains(h2));
+        assert!(map.remove(h1).is_none());
+        assert_eq!(map.get(h3), Some(&3));
+        assert_eq!(map.get(h4), Some(&4));
+    }
+
+    #[test]
+    fn test_generation_wrap() {
+        let mut map: HandleMap<i32> = HandleMap::new();
+        let h0: Handle = map.insert(0);
+        map.remove(h0);
+        map.slots[0].generation = u32::MAX;
+
+        let h1: Handle = map.insert(1);
+        assert_eq!(h1.index(), 0);
+        assert_eq!(h1.generation(), u32::MAX);
+        assert_eq!(map.remove(h1), Some(1));
+
+        // the slot is retired rather than wrapping back to generation 0
+        let h2: Handle = map.insert(2);
+        assert_eq!(h2.index(), 1);
+        assert!(map.get(h0).is_none());
+        assert!(map.get(h1).is_none());
+        assert_eq!(map.len(), 1);
+    }
+
+    #[test]
+    fn test_iter() {
+        let mut map: HandleMap<i32> = HandleMap::new();
+        let handles: Vec<Handle> = (0..6).map(|i| map.insert(i)).collect();
+        map.remove(handles[1]);
+        map.remove(handles[4]);
+
+        for (_, value) in map.iter_mut() {
+            *value *= 10;
+        }
+        let items: Vec<(Handle, i32)> = map.iter().map(|(h, v)| (h, *v)).collect();
+        assert_eq!(items, vec![
+            (handles[0], 0),
+            (handles[2], 20),
+            (handles[3], 30),
+            (handles[5], 50)
+        ]);
+    }
+
+    #[test]
+    fn test_get_unchecked() {
+        let mut map: HandleMap<i32> = HandleMap::new();
+        let handle: Handle = map.insert(893);
+        assert_eq!(unsafe { *map.get_unchecked_dbg(handle) }, 893);
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "stale or invalid handle")]
+    fn test_get_unchecked_stale() {
+        let mut map: HandleMap<i32> = HandleMap::new();
+        let handle: Handle = map.insert(893);
+        map.remove(handle);
+        map.insert(1919810);
+        let _ = unsafe { map.get_unchecked_dbg(handle) };
+    }
+}
@@ -0,0 +1,186 @@
+//! The Fx hash function, a fast non-cryptographic hash used in Firefox and rustc
+//!
+//! `FxHasher` is much faster than the default SipHash for small keys such as integers, pointers
+//! and short strings, but provides no protection against hash flooding. Never use it for keys
+//! controlled by untrusted input.
+//!
+//! The hasher has no random seed, so hashes are deterministic across runs on the same platform.
+//! They do differ between 32-bit and 64-bit platforms, and between little and big endian ones.
+
+use core::convert::TryInto;
+use core::hash::{BuildHasher, Hasher};
+
+#[cfg(target_pointer_width = "32")]
+const SEED: usize = 0x9e3779b9;
+#[cfg(target_pointer_width = "64")]
+const SEED: usize = 0x517cc1b727220a95;
+
+const ROTATE: u32 = 5;
+
+/// The Fx hasher
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FxHasher {
+    hash: usize
+}
+
+impl FxHasher {
+    #[inline(always)] fn add_to_hash(&mut self, word: usize) {
+        self.hash = (self.hash.rotate_left(ROTATE) ^ word).wrapping_mul(SEED);
+    }
+}
+
+impl Hasher for FxHasher {
+    #[inline]
+    fn write(&mut self, mut bytes: &[u8]) {
+        const WORD: usize = core::mem::size_of::<usize>();
+
+        while bytes.len() >= WORD {
+            let (word, rest) = bytes.split_at(WORD);
+            self.add_to_hash(usize::from_ne_bytes(word.try_into().unwrap()));
+            bytes = rest;
+        }
+        if WORD > 4 && bytes.len() >= 4 {
+            let (word, rest) = bytes.split_at(4);
+            self.add_to_hash(u32::from_ne_bytes(word.try_into().unwrap()) as usize);
+            bytes = rest;
+        }
+        for byte in bytes {
+            self.add_to_hash(*byte as usize);
+        }
+    }
+
+    #[inline] fn write_u8(&mut self, i: u8) {
+        self.add_to_hash(i as usize);
+    }
+
+    #[inline] fn write_u16(&mut self, i: u16) {
+        self.add_to_hash(i as usize);
+    }
+
+    #[inline] fn write_u32(&mut self, i: u32) {
+        self.add_to_hash(i as usize);
+    }
+
+    #[cfg(target_pointer_width = "32")]
+    #[inline] fn write_u64(&mut self, i: u64) {
+        self.add_to_hash(i as usize);
+        self.add_to_hash((i >> 32) as usize);
+    }
+
+    #[cfg(target_pointer_width = "64")]
+    #[inline] fn write_u64(&mut self, i: u64) {
+        self.add_to_hash(i as usize);
+    }
+
+    #[inline] fn write_usize(&mut self, i: usize) {
+        self.add_to_hash(i);
+    }
+
+    #[inline] fn finish(&self) -> u64 {
+        self.hash as u64
+    }
+}
+
+/// `BuildHasher` creating `FxHasher`s
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FxBuildHasher;
+
+impl BuildHasher for FxBuildHasher {
+    type Hasher = FxHasher;
+
+    #[inline] fn build_hasher(&self) -> FxHasher {
+        FxHasher::default()
+    }
+}
+
+#[cfg(feature = "std")]
+pub type FxHashMap<K, V> = std::collections::HashMap<K, V, FxBuildHasher>;
+
+#[cfg(feature = "std")]
+pub type FxHashSet<T> = std::collections::HashSet<T, FxBuildHasher>;
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+    use std::hash::{BuildHasher, Hash, Hasher};
+
+    use crate::hash::{FxBuildHasher, FxHasher};
+
+    fn fx_hash<T: Hash + ?Sized>(value: &T) -> u64 {
+        FxBuildHasher.hash_one(value)
+    }
+
+    #[cfg(all(target_pointer_width = "64", target_endian = "little"))]
+    #[test]
+    fn test_known_answers() {
+        let mut hasher: FxHasher = FxHasher::default();
+        hasher.write_u64(114514);
+        assert_eq!(hasher.finish(), 0xf034d9fef9618eba);
+
+        let mut hasher: FxHasher = FxHasher::default();
+        hasher.write_usize(1919810);
+        hasher.write_u8(7);
+        assert_eq!(hasher.finish(), 0x0d7d53845209c9a3);
+
+        let mut hasher: FxHasher = FxHasher::default();
+        hasher.write(b"hello, world");
+        assert_eq!(hasher.finish(), 0x2e725f56f4ea74d8);
+
+        assert_eq!(fx_hash(&0_u64), 0);
+        assert_eq!(fx_hash("xjbutil"), 0xd7d04a6ff8e427da);
+        assert_eq!(fx_hash(&(1_u32, 2_u16, 3_u8)), 0xfdcb2688e0760126);
+    }
+
+    #[test]
+    fn test_deterministic() {
+        let a: u64 = fx_hash(&("1919810", 893_u32));
+        let b: u64 = fx_hash(&("1919810", 893_u32));
+        assert_eq!(a, b);
+        assert_ne!(fx_hash("114514"), fx_hash("114515"));
+    }
+
+    #[test]
+    fn test_sequential_collisions() {
+        const COUNT: u64 = if cfg!(miri) { 1024 } else { 65536 };
+
+        let full: HashSet<u64> = (0..COUNT).map(|i| fx_hash(&i)).collect();
+        assert_eq!(full.len() as u64, COUNT);
+
+        // hash tables index buckets with part of the hash, which should still spread well
+        let buckets: HashSet<u64> = (0..COUNT).map(|i| fx_hash(&i) >> 48).collect();
+        assert!(buckets.len() as u64 >= COUNT / 2);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_map_equivalence() {
+        use std::collections::HashMap;
+
+        use crate::hash::{FxHashMap, FxHashSet};
+        use crate::rand_intern::Rng;
+
+        const ROUNDS: usize = if cfg!(miri) { 256 } else { 100000 };
+
+        let mut rng: Rng = Rng::new(114514);
+        let mut fx_map: FxHashMap<u64, u64> = FxHashMap::default();
+        let mut std_map: HashMap<u64, u64> = HashMap::new();
+        for _ in 0..ROUNDS {
+            let key: u64 = rng.gen_range(0..1024);
+            match rng.gen_range(0..3) {
+                0 => {
+                    let value: u64 = rng.next_u64();
+                    assert_eq!(fx_map.insert(key, value), std_map.insert(key, value));
+                },
+                1 => assert_eq!(fx_map.remove(&key), std_map.remove(&key)),
+                _ => assert_eq!(fx_map.get(&key), std_map.get(&key))
+            }
+        }
+        assert_eq!(fx_map.len(), std_map.len());
+        for (key, value) in std_map.iter() {
+            assert_eq!(fx_map.get(key), Some(value));
+        }
+
+        let set: FxHashSet<&str> = ["114", "514", "114"].iter().copied().collect();
+        assert_eq!(set.len(), 2);
+    }
+}
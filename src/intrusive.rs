@@ -0,0 +1,306 @@
+//! Intrusive data structures, whose nodes are owned elsewhere
+//!
+//! Unlike `LinkedList`, intrusive lists never allocate: each node embeds its own link, and the
+//! list only threads pointers through nodes. This suits free lists and work queues, where node
+//! memory is managed by someone else.
+
+use core::cell::Cell;
+use core::marker::PhantomData;
+use core::ptr::NonNull;
+
+/// Link embedded in nodes of `SList`
+pub struct SListLink<T> {
+    next: Cell<Option<NonNull<T>>>,
+    #[cfg(debug_assertions)]
+    linked: Cell<bool>
+}
+
+impl<T> SListLink<T> {
+    pub const fn new() -> Self {
+        Self {
+            next: Cell::new(None),
+            #[cfg(debug_assertions)]
+            linked: Cell::new(false)
+        }
+    }
+}
+
+impl<T> Default for SListLink<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Locates the `SListLink` inside a node
+///
+/// # Safety
+/// `link` must always return the same link embedded in `node`, and that link must not be used by
+/// any other list type at the same time.
+pub unsafe trait Adapter<T> {
+    fn link(node: &T) -> &SListLink<T>;
+}
+
+/// Intrusive singly linked list
+///
+/// The list does not own its nodes. `push_front` requires that a node stays valid, at a fixed
+/// address, for as long as it is in the list, and that no mutable reference to the node is
+/// created meanwhile. Under these rules `iter` can safely hand out shared references.
+pub struct SList<T, A: Adapter<T>> {
+    head: Option<NonNull<T>>,
+    len: usize,
+    _phantom: PhantomData<(*const T, A)>
+}
+
+impl<T, A: Adapter<T>> SList<T, A> {
+    pub const fn new() -> Self {
+        Self { head: None, len: 0, _phantom: PhantomData }
+    }
+
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.head.is_none()
+    }
+
+    pub const fn front(&self) -> Option<NonNull<T>> {
+        self.head
+    }
+
+    /// Insert `node` at the front of the list
+    ///
+    /// In debug builds, panics if `node` is already in a list.
+    ///
+    /// # Safety
+    /// `node` must be valid and not moved until it is removed from the list, or the list is
+    /// cleared or dropped. No mutable reference to `node` may be created during that period.
+    /// `node` must not already be in a list, which is only checked in debug builds.
+    pub unsafe fn push_front(&mut self, node: NonNull<T>) {
+        let link: &SListLink<T> = A::link(node.as_ref());
+        #[cfg(debug_assertions)]
+        {
+            assert!(!link.linked.get(), "node is already in a list");
+            link.linked.set(true);
+        }
+        link.next.set(self.head);
+        self.head = Some(node);
+        self.len += 1;
+    }
+
+    /// Remove and return the first node
+    pub fn pop_front(&mut self) -> Option<NonNull<T>> {
+        let node: NonNull<T> = self.head?;
+        let link: &SListLink<T> = unsafe { Self::link_of(node) };
+        self.head = link.next.take();
+        #[cfg(debug_assertions)]
+        link.linked.set(false);
+        self.len -= 1;
+        Some(node)
+    }
+
+    /// Remove `node` from the list, returning whether it was found
+    ///
+    /// This is `O(n)` since the list is singly linked.
+    pub fn remove(&mut self, node: NonNull<T>) -> bool {
+        if self.head == Some(node) {
+            self.pop_front();
+            return true;
+        }
+
+        let mut prev: Option<NonNull<T>> = self.head;
+        while let Some(prev_node) = prev {
+            let prev_link: &SListLink<T> = unsafe { Self::link_of(prev_node) };
+            let next: Option<NonNull<T>> = prev_link.next.get();
+            if next == Some(node) {
+                let link: &SListLink<T> = unsafe { Self::link_of(node) };
+                prev_link.next.set(link.next.take());
+                #[cfg(debug_assertions)]
+                link.linked.set(false);
+                self.len -= 1;
+                return true;
+            }
+            prev = next;
+        }
+        false
+    }
+
+    /// Unlink all nodes
+    pub fn clear(&mut self) {
+        while self.pop_front().is_some() {}
+    }
+
+    /// Iterate over pointers to nodes, from front to back
+    pub fn iter_ptr(&self) -> IterPtr<'_, T, A> {
+        IterPtr { next: self.head, _phantom: PhantomData }
+    }
+
+    /// Iterate over nodes, from front to back
+    pub fn iter(&self) -> Iter<'_, T, A> {
+        Iter { inner: self.iter_ptr() }
+    }
+
+    unsafe fn link_of<'a>(node: NonNull<T>) -> &'a SListLink<T> {
+        A::link(&*node.as_ptr())
+    }
+}
+
+impl<T, A: Adapter<T>> Default for SList<T, A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, A: Adapter<T>> Drop for SList<T, A> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+pub struct IterPtr<'a, T, A: Adapter<T>> {
+    next: Option<NonNull<T>>,
+    _phantom: PhantomData<&'a SList<T, A>>
+}
+
+impl<'a, T, A: Adapter<T>> Iterator for IterPtr<'a, T, A> {
+    type Item = NonNull<T>;
+
+    fn next(&mut self) -> Option<NonNull<T>> {
+        let node: NonNull<T> = self.next?;
+        self.next = unsafe { SList::<T, A>::link_of(node) }.next.get();
+        Some(node)
+    }
+}
+
+pub struct Iter<'a, T, A: Adapter<T>> {
+    inner: IterPtr<'a, T, A>
+}
+
+impl<'a, T, A: Adapter<T>> Iterator for Iter<'a, T, A> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.inner.next().map(|node| unsafe { &*node.as_ptr() })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::ptr::NonNull;
+
+    use crate::intrusive::{Adapter, SList, SListLink};
+
+    struct Node {
+        value: i32,
+        link: SListLink<Node>
+    }
+
+    impl Node {
+        fn new(value: i32) -> Self {
+            Self { value, link: SListLink::new() }
+        }
+    }
+
+    struct NodeAdapter;
+
+    unsafe impl Adapter<Node> for NodeAdapter {
+        fn link(node: &Node) -> &SListLink<Node> {
+            &node.link
+        }
+    }
+
+    fn values(list: &SList<Node, NodeAdapter>) -> Vec<i32> {
+        list.iter().map(|node| node.value).collect()
+    }
+
+    #[test]
+    fn test_push_pop() {
+        let nodes: Vec<Node> = (0..4).map(Node::new).collect();
+        let mut list: SList<Node, NodeAdapter> = SList::new();
+        assert!(list.is_empty());
+        assert!(list.pop_front().is_none());
+
+        for node in nodes.iter() {
+            unsafe { list.push_front(NonNull::from(node)); }
+        }
+        assert_eq!(list.len(), 4);
+        assert_eq!(values(&list), vec![3, 2, 1, 0]);
+        assert_eq!(list.iter_ptr().collect::<Vec<_>>()[1], NonNull::from(&nodes[2]));
+
+        assert_eq!(list.pop_front(), Some(NonNull::from(&nodes[3])));
+        assert_eq!(list.pop_front(), Some(NonNull::from(&nodes[2])));
+        unsafe { list.push_front(NonNull::from(&nodes[3])); }
+        assert_eq!(values(&list), vec![3, 1, 0]);
+        list.clear();
+        assert!(list.is_empty());
+        assert_eq!(list.len(), 0);
+    }
+
+    #[test]
+    fn test_remove() {
+        let nodes: Vec<Node> = (0..5).map(Node::new).collect();
+        let outsider: Node = Node::new(114514);
+        let mut list: SList<Node, NodeAdapter> = SList::new();
+        for node in nodes.iter() {
+            unsafe { list.push_front(NonNull::from(node)); }
+        }
+
+        assert!(list.remove(NonNull::from(&nodes[2])));
+        assert_eq!(values(&list), vec![4, 3, 1, 0]);
+        assert!(list.remove(NonNull::from(&nodes[4])));
+        assert!(list.remove(NonNull::from(&nodes[0])));
+        assert_eq!(values(&list), vec![3, 1]);
+        assert!(!list.remove(NonNull::from(&nodes[0])));
+        assert!(!list.remove(NonNull::from(&outsider)));
+        assert_eq!(list.len(), 2);
+
+        // removed nodes can be inserted again
+        unsafe { list.push_front(NonNull::from(&nodes[2])); }
+        assert_eq!(values(&list), vec![2, 3, 1]);
+    }
+
+    #[test]
+    fn test_reinsert_after_drop() {
+        let node: Node = Node::new(1919810);
+        {
+            let mut list: SList<Node, NodeAdapter> = SList::new();
+            unsafe { list.push_front(NonNull::from(&node)); }
+        }
+        let mut list: SList<Node, NodeAdapter> = SList::new();
+        unsafe { list.push_front(NonNull::from(&node)); }
+        assert_eq!(values(&list), vec![1919810]);
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "node is already in a list")]
+    fn test_double_insert() {
+        let node: Node = Node::new(893);
+        let mut list1: SList<Node, NodeAdapter> = SList::new();
+        let mut list2: SList<Node, NodeAdapter> = SList::new();
+        unsafe {
+            list1.push_front(NonNull::from(&node));
+            list2.push_front(NonNull::from(&node));
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_free_list() {
+        use crate::mem_intern::{move_to_heap, reclaim_as_boxed};
+
+        const COUNT: i32 = if cfg!(miri) { 16 } else { 1024 };
+
+        let mut free_list: SList<Node, NodeAdapter> = SList::new();
+        for i in 0..COUNT {
+            unsafe { free_list.push_front(move_to_heap(Node::new(i))); }
+        }
+
+        let mut sum: i32 = 0;
+        while let Some(node) = free_list.pop_front() {
+            let node: Box<Node> = unsafe { reclaim_as_boxed(node) };
+            sum += node.value;
+        }
+        assert_eq!(sum, (0..COUNT).sum::<i32>());
+    }
+}
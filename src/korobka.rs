@@ -2,22 +2,77 @@
 //!
 //! See <https://users.rust-lang.org/t/suspicious-undefined-hehaviour-report-about-stacked-borrows/62633/5>
 
-use std::borrow::{Borrow, BorrowMut};
-use std::hash::{Hash, Hasher};
-use std::marker::PhantomData;
-use std::ops::{Deref, DerefMut};
-use std::ptr::NonNull;
+use alloc::alloc::{Layout, alloc, dealloc, handle_alloc_error};
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::borrow::{Borrow, BorrowMut};
+use core::cmp::Ordering;
+use core::fmt::{Debug, Display, Formatter, Pointer};
+use core::future::Future;
+use core::hash::{Hash, Hasher};
+use core::iter::{FromIterator, FusedIterator};
+use core::marker::PhantomData;
+use core::mem::{ManuallyDrop, MaybeUninit, align_of, size_of};
+use core::ops::{Deref, DerefMut};
+use core::pin::Pin;
+use core::ptr::{NonNull, addr_of, addr_of_mut, drop_in_place};
+use core::task::{Context, Poll};
 
-use crate::mem_intern::{leak_as_nonnull, reclaim_as_boxed};
+use crate::mem_intern::{
+    alloc_uninit_boxed,
+    alloc_uninit_boxed_slice,
+    alloc_zeroed_boxed,
+    drop_in_place_and_dealloc,
+    leak_as_nonnull,
+    reclaim_as_boxed,
+    try_alloc_uninit_boxed,
+    try_alloc_uninit_boxed_slice
+};
+
+#[cfg(feature = "wide_ptr")] use crate::wide_ptr::WidePointer;
+
+mod rc;
+
+pub use rc::RcKorobka;
 
 /// The customized `Box` replacement
+///
+/// Like `Box`, a `Korobka` owns a heap allocation which never moves while the `Korobka` is alive,
+/// even if the `Korobka` itself gets moved. So `Pin<Korobka<T>>` upholds the pinning guarantees
+/// exactly as `Pin<Box<T>>` does.
+///
+/// Unlike `Box`, `Korobka<T>` is only `Unpin` if `T` is. In exchange, pinning is structural: a
+/// pinned `Korobka<T>` can be projected to its pinned content with `as_pin_mut`.
+///
+/// Zero-sized contents, including empty slices, never touch the allocator. Their pointer is
+/// dangling and only their destructor runs on drop.
+///
+/// Also like `Box`, `Korobka<T>` is `Send` or `Sync` exactly when `T` is:
+///
+/// ```compile_fail
+/// # use std::rc::Rc;
+/// # use xjbutil::korobka::Korobka;
+/// fn assert_send<T: Send>() {}
+/// assert_send::<Korobka<Rc<u8>>>();
+/// ```
+///
+/// ```compile_fail
+/// # use std::cell::Cell;
+/// # use xjbutil::korobka::Korobka;
+/// fn assert_sync<T: Sync>() {}
+/// assert_sync::<Korobka<Cell<u8>>>();
+/// ```
 #[repr(transparent)]
 pub struct Korobka<T: ?Sized>(NonNull<T>, PhantomData<T>);
 
+unsafe impl<T: ?Sized + Send> Send for Korobka<T> {}
+unsafe impl<T: ?Sized + Sync> Sync for Korobka<T> {}
+
 impl<T: ?Sized> Drop for Korobka<T> {
     fn drop(&mut self) {
-        let boxed: Box<T> = unsafe { reclaim_as_boxed(self.0) };
-        drop(boxed);
+        unsafe { drop_in_place_and_dealloc(self.0); }
     }
 }
 
@@ -25,18 +80,332 @@ impl<T: Sized> Korobka<T> {
     #[inline(always)] pub fn new(t: T) -> Self {
         Self(leak_as_nonnull(Box::new(t)), PhantomData)
     }
+
+    /// Construct a pinned `Korobka`, like `Box::pin`
+    #[inline(always)] pub fn pin(t: T) -> Pin<Self> {
+        Self::into_pin(Self::new(t))
+    }
+
+    /// Consume the `Korobka`, moving the content out and freeing the allocation
+    #[inline(always)] pub fn into_inner(this: Self) -> T {
+        *Self::into_box(this)
+    }
+
+    /// Transform the content with `f`, reusing the allocation if `T` and `U` share the layout
+    ///
+    /// If `f` panics, the allocation is freed without leaking.
+    pub fn map<U>(this: Self, f: impl FnOnce(T) -> U) -> Korobka<U> {
+        if Layout::new::<T>() != Layout::new::<U>() {
+            return Korobka::new(f(Self::into_inner(this)));
+        }
+
+        // dropping the `MaybeUninit` only frees the allocation, also when unwinding out of `f`
+        let slot: Korobka<MaybeUninit<T>> = unsafe {
+            Korobka::from_nonnull(Self::into_nonnull(this).cast())
+        };
+        let mapped: U = f(unsafe { slot.assume_init_read() });
+        let mut slot: Korobka<MaybeUninit<U>> = unsafe {
+            Korobka::from_nonnull(Korobka::into_nonnull(slot).cast())
+        };
+        slot.write(mapped);
+        unsafe { Korobka::assume_init(slot) }
+    }
+
+    /// Allocate a `T` without initializing it, so that it can be constructed in place
+    pub fn new_uninit() -> Korobka<MaybeUninit<T>> {
+        Korobka::from(alloc_uninit_boxed())
+    }
+
+    /// Allocate a `T` with all bytes set to zero, which is not necessarily a valid `T`
+    pub fn new_zeroed() -> Korobka<MaybeUninit<T>> {
+        Korobka::from(alloc_zeroed_boxed())
+    }
+
+    /// Allocate `len` elements without initializing them
+    pub fn new_uninit_slice(len: usize) -> Korobka<[MaybeUninit<T>]> {
+        Korobka::from(alloc_uninit_boxed_slice(len))
+    }
+
+    /// Move `t` to heap, giving it back if the allocation fails
+    pub fn try_new(t: T) -> Result<Self, T> {
+        match Self::try_new_uninit() {
+            Some(mut uninit) => {
+                uninit.write(t);
+                Ok(unsafe { Korobka::assume_init(uninit) })
+            },
+            None => Err(t)
+        }
+    }
+
+    /// Like `new_uninit`, but returns `None` if the allocation fails
+    pub fn try_new_uninit() -> Option<Korobka<MaybeUninit<T>>> {
+        try_alloc_uninit_boxed().map(Korobka::from)
+    }
+
+    /// Like `new_uninit_slice`, but returns `None` if the allocation fails or the size of the
+    /// slice overflows
+    pub fn try_new_uninit_slice(len: usize) -> Option<Korobka<[MaybeUninit<T>]>> {
+        try_alloc_uninit_boxed_slice(len).map(Korobka::from)
+    }
+}
+
+impl<T> Korobka<MaybeUninit<T>> {
+    /// Convert to `Korobka<T>`, reusing the allocation
+    ///
+    /// # Safety
+    /// The content must be fully initialized.
+    #[inline(always)] pub unsafe fn assume_init(this: Self) -> Korobka<T> {
+        Korobka::from_nonnull(Self::into_nonnull(this).cast())
+    }
+}
+
+impl<T> Korobka<[MaybeUninit<T>]> {
+    /// Convert to `Korobka<[T]>`, reusing the allocation
+    ///
+    /// # Safety
+    /// All elements must be fully initialized.
+    #[inline(always)] pub unsafe fn assume_init_slice(this: Self) -> Korobka<[T]> {
+        let ptr: NonNull<[MaybeUninit<T>]> = Self::into_nonnull(this);
+        Korobka::from_nonnull(NonNull::new_unchecked(ptr.as_ptr() as *mut [T]))
+    }
 }
 
 impl<T: ?Sized> Korobka<T> {
+    /// Move `value` to heap, then view it as a `T` with `unsize`
+    ///
+    /// This is the stable replacement of unsize coercions like `Box<U>` to `Box<dyn Trait>`.
+    /// `korobka_dyn!` calls this function with an identity closure, so that the allocation is
+    /// reused.
+    pub fn new_unsize<U>(value: U, unsize: fn(Box<U>) -> Box<T>) -> Self {
+        Self::from(unsize(Box::new(value)))
+    }
+
     #[inline(always)] pub const fn as_ptr(&self) -> *const T {
         self.0.as_ptr() as *const _
     }
 
+    const_mut_fn! {
+        #[inline(always)] pub fn as_mut_ptr(&mut self) -> *mut T {
+            self.0.as_ptr()
+        }
+    }
+
+    /// Get the underlying pointer
+    ///
+    /// The pointer is not derived from any reference, so it stays valid for both reads and
+    /// writes as long as the `Korobka` is alive, even after the `Korobka` gets moved. Writing
+    /// through it while a reference obtained from the `Korobka` is alive is still undefined
+    /// behavior.
     #[inline(always)] pub const fn as_nonnull(&self) -> NonNull<T> {
         self.0
     }
+
+    /// Consume the `Korobka`, returning the underlying pointer without dropping the content
+    ///
+    /// Unlike `as_nonnull`, the `Korobka` gives up the ownership, like `Box::into_raw`. Pass the
+    /// pointer to `from_nonnull` later to free the content, e.g. after handing it through FFI.
+    #[inline(always)] pub fn into_nonnull(this: Self) -> NonNull<T> {
+        let ptr: NonNull<T> = this.0;
+        core::mem::forget(this);
+        ptr
+    }
+
+    /// Re-construct a `Korobka` from a pointer returned by `into_nonnull`, like `Box::from_raw`
+    ///
+    /// # Safety
+    /// The pointer must be previously returned by `into_nonnull`, or leaked from a `Box` with
+    /// `leak_as_nonnull`. It must not be used to construct another `Korobka`.
+    #[inline(always)] pub unsafe fn from_nonnull(ptr: NonNull<T>) -> Self {
+        Self(ptr, PhantomData)
+    }
+
+    /// Convert the `Korobka` back into a `Box`, which takes over the allocation
+    #[inline(always)] pub fn into_box(this: Self) -> Box<T> {
+        unsafe { reclaim_as_boxed(Self::into_nonnull(this)) }
+    }
+
+    /// Pin the content of the `Korobka`, like `Box::into_pin`
+    #[inline(always)] pub fn into_pin(this: Self) -> Pin<Self> {
+        // SAFETY: the content never moves until the `Korobka` is dropped, see the type docs
+        unsafe { Pin::new_unchecked(this) }
+    }
+
+    /// Get a pinned mutable reference to the content of a pinned `Korobka`
+    #[inline(always)] pub fn as_pin_mut(this: Pin<&mut Self>) -> Pin<&mut T> {
+        // SAFETY: a pinned `Korobka` cannot be moved out of or mutably accessed unless `T: Unpin`,
+        // so its content stays pinned until the `Korobka` drops it in place
+        unsafe { this.map_unchecked_mut(|korobka| &mut **korobka) }
+    }
+}
+
+impl<T> Korobka<[T]> {
+    /// Take over the elements of `vec`, shrinking its buffer to fit
+    pub fn from_vec(vec: Vec<T>) -> Self {
+        Self::from(vec.into_boxed_slice())
+    }
+
+    pub fn from_slice(slice: &[T]) -> Self where T: Clone {
+        Self::from(Box::<[T]>::from(slice))
+    }
+
+    #[inline(always)] pub fn len(&self) -> usize {
+        self.as_ref().len()
+    }
+
+    #[inline(always)] pub fn is_empty(&self) -> bool {
+        self.as_ref().is_empty()
+    }
+}
+
+impl<T> FromIterator<T> for Korobka<[T]> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self::from_vec(iter.into_iter().collect())
+    }
+}
+
+impl<T> IntoIterator for Korobka<[T]> {
+    type Item = T;
+    type IntoIter = alloc::vec::IntoIter<T>;
+
+    /// Iterate the elements by value, reusing the allocation as the buffer of a `Vec`
+    fn into_iter(self) -> Self::IntoIter {
+        Self::into_box(self).into_vec().into_iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a Korobka<[T]> {
+    type Item = &'a T;
+    type IntoIter = core::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.as_ref().iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a mut Korobka<[T]> {
+    type Item = &'a mut T;
+    type IntoIter = core::slice::IterMut<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.as_mut().iter_mut()
+    }
+}
+
+impl Korobka<str> {
+    pub fn from_str_slice(s: &str) -> Self {
+        Self::from(Box::<str>::from(s))
+    }
+
+    /// Take over the content of `s`, shrinking its buffer to fit
+    pub fn from_string(s: String) -> Self {
+        Self::from(s.into_boxed_str())
+    }
+}
+
+impl<T: ?Sized> From<Korobka<T>> for Pin<Korobka<T>> {
+    fn from(korobka: Korobka<T>) -> Self {
+        Korobka::into_pin(korobka)
+    }
+}
+
+impl<F: ?Sized + Future> Future for Korobka<F> {
+    type Output = F::Output;
+
+    /// Poll the content, which is pinned structurally as described in the type docs
+    ///
+    /// Unlike `Box<F>`, this does not require `F: Unpin`. `Pin<Korobka<F>>` is also a `Future`,
+    /// through the blanket impl for `Pin`.
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Self::as_pin_mut(self).poll(cx)
+    }
+}
+
+// `?Sized` contents would conflict with `IntoIterator for Korobka<[T]>`, so trait objects are
+// covered one by one with `impl_korobka_dyn_iterator!`
+impl<I: Iterator> Iterator for Korobka<I> {
+    type Item = I::Item;
+
+    #[inline(always)] fn next(&mut self) -> Option<I::Item> {
+        self.as_mut().next()
+    }
+
+    #[inline(always)] fn size_hint(&self) -> (usize, Option<usize>) {
+        self.as_ref().size_hint()
+    }
+
+    #[inline(always)] fn nth(&mut self, n: usize) -> Option<I::Item> {
+        self.as_mut().nth(n)
+    }
+}
+
+impl<I: DoubleEndedIterator> DoubleEndedIterator for Korobka<I> {
+    #[inline(always)] fn next_back(&mut self) -> Option<I::Item> {
+        self.as_mut().next_back()
+    }
+
+    #[inline(always)] fn nth_back(&mut self, n: usize) -> Option<I::Item> {
+        self.as_mut().nth_back(n)
+    }
 }
 
+impl<I: ExactSizeIterator> ExactSizeIterator for Korobka<I> {
+    #[inline(always)] fn len(&self) -> usize {
+        self.as_ref().len()
+    }
+}
+
+impl<I: FusedIterator> FusedIterator for Korobka<I> {}
+
+macro_rules! impl_korobka_dyn_iterator {
+    (double_ended: $($iter:ty),*) => {
+        impl_korobka_dyn_iterator!($($iter),*);
+
+        $(
+            impl<'a, T> DoubleEndedIterator for Korobka<$iter> {
+                #[inline(always)] fn next_back(&mut self) -> Option<T> {
+                    self.as_mut().next_back()
+                }
+
+                #[inline(always)] fn nth_back(&mut self, n: usize) -> Option<T> {
+                    self.as_mut().nth_back(n)
+                }
+            }
+        )*
+    };
+    ($($iter:ty),*) => {
+        $(
+            impl<'a, T> Iterator for Korobka<$iter> {
+                type Item = T;
+
+                #[inline(always)] fn next(&mut self) -> Option<T> {
+                    self.as_mut().next()
+                }
+
+                #[inline(always)] fn size_hint(&self) -> (usize, Option<usize>) {
+                    self.as_ref().size_hint()
+                }
+
+                #[inline(always)] fn nth(&mut self, n: usize) -> Option<T> {
+                    self.as_mut().nth(n)
+                }
+            }
+        )*
+    };
+}
+
+impl_korobka_dyn_iterator!(
+    dyn Iterator<Item = T> + 'a,
+    dyn Iterator<Item = T> + Send + 'a,
+    dyn Iterator<Item = T> + Send + Sync + 'a
+);
+
+impl_korobka_dyn_iterator!(
+    double_ended:
+    dyn DoubleEndedIterator<Item = T> + 'a,
+    dyn DoubleEndedIterator<Item = T> + Send + 'a,
+    dyn DoubleEndedIterator<Item = T> + Send + Sync + 'a
+);
+
 impl<T: ?Sized> AsRef<T> for Korobka<T> {
     #[inline(always)] fn as_ref(&self) -> &T {
         unsafe { self.0.as_ref() }
@@ -81,6 +450,129 @@ impl<T: ?Sized> From<Box<T>> for Korobka<T> {
     }
 }
 
+impl<T: Default> Default for Korobka<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T> Default for Korobka<[T]> {
+    fn default() -> Self {
+        Self::from_vec(Vec::new())
+    }
+}
+
+impl Default for Korobka<str> {
+    fn default() -> Self {
+        Self::from_string(String::new())
+    }
+}
+
+impl<T: ?Sized + Debug> Debug for Korobka<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        Debug::fmt(self.as_ref(), f)
+    }
+}
+
+impl<T: ?Sized + Display> Display for Korobka<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        Display::fmt(self.as_ref(), f)
+    }
+}
+
+/// Forwards to the pointee, so that `Korobka<dyn Error + Send + Sync>` is an `Error` as well
+///
+/// Because of this, `Korobka<dyn Error>` cannot have a blanket `From<E: Error>` impl like
+/// `Box<dyn Error>` does: it would overlap with `From<T> for T`. Move the error into a
+/// `Korobka` first, and let `From<Korobka<E>>` erase its type.
+#[cfg(feature = "std")]
+impl<T: ?Sized + std::error::Error> std::error::Error for Korobka<T> {
+    #[allow(deprecated)]
+    fn description(&self) -> &str {
+        self.as_ref().description()
+    }
+
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.as_ref().source()
+    }
+}
+
+#[cfg(feature = "std")]
+macro_rules! impl_korobka_dyn_error_from {
+    ($($bounds:tt)*) => {
+        impl<'a, E> From<Korobka<E>> for Korobka<dyn std::error::Error $($bounds)* + 'a>
+            where E: std::error::Error $($bounds)* + 'a
+        {
+            fn from(korobka: Korobka<E>) -> Self {
+                let ptr: *mut (dyn std::error::Error $($bounds)* + 'a) =
+                    Korobka::into_nonnull(korobka).as_ptr();
+                unsafe { Korobka::from_nonnull(NonNull::new_unchecked(ptr)) }
+            }
+        }
+    };
+}
+
+#[cfg(feature = "std")] impl_korobka_dyn_error_from!();
+#[cfg(feature = "std")] impl_korobka_dyn_error_from!(+ Send);
+#[cfg(feature = "std")] impl_korobka_dyn_error_from!(+ Send + Sync);
+
+impl<T: ?Sized> Pointer for Korobka<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        Pointer::fmt(&self.as_ptr(), f)
+    }
+}
+
+impl<T: Clone> Clone for Korobka<T> {
+    fn clone(&self) -> Self {
+        Self::new(self.as_ref().clone())
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.as_mut().clone_from(source.as_ref());
+    }
+}
+
+impl<T: Clone> Clone for Korobka<[T]> {
+    fn clone(&self) -> Self {
+        Self::from_slice(self.as_ref())
+    }
+}
+
+impl Clone for Korobka<str> {
+    fn clone(&self) -> Self {
+        Self::from_str_slice(self.as_ref())
+    }
+}
+
+macro_rules! impl_korobka_downcast {
+    ($($any:ty),*) => {
+        $(
+            impl Korobka<$any> {
+                /// Attempt to downcast the `Korobka` to a concrete type, like `Box::downcast`
+                pub fn downcast<T: Any>(self) -> Result<Korobka<T>, Self> {
+                    if self.is::<T>() {
+                        let ptr: NonNull<$any> = Korobka::into_nonnull(self);
+                        // SAFETY: the content is a `T`, allocated by a `Korobka`
+                        Ok(unsafe { Korobka::from_nonnull(ptr.cast::<T>()) })
+                    } else {
+                        Err(self)
+                    }
+                }
+
+                pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+                    (**self).downcast_ref()
+                }
+
+                pub fn downcast_mut<T: Any>(&mut self) -> Option<&mut T> {
+                    (**self).downcast_mut()
+                }
+            }
+        )*
+    };
+}
+
+impl_korobka_downcast!(dyn Any, dyn Any + Send, dyn Any + Send + Sync);
+
 impl<T> Hash for Korobka<T> where T: ?Sized + Hash {
     fn hash<H: Hasher>(&self, state: &mut H) {
         unsafe {
@@ -97,21 +589,1456 @@ impl<T> PartialEq for Korobka<T> where T: ?Sized + PartialEq {
 
 impl<T> Eq for Korobka<T> where T: ?Sized + Eq + PartialEq {}
 
-#[cfg(test)]
-mod test {
-    use std::ptr::NonNull;
+impl<T> PartialOrd for Korobka<T> where T: ?Sized + PartialOrd {
+    #[inline(always)] fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        unsafe { self.0.as_ref().partial_cmp(other.0.as_ref()) }
+    }
+}
 
-    use crate::korobka::Korobka;
+impl<T> Ord for Korobka<T> where T: ?Sized + Ord {
+    #[inline(always)] fn cmp(&self, other: &Self) -> Ordering {
+        unsafe { self.0.as_ref().cmp(other.0.as_ref()) }
+    }
+}
 
-    #[test]
-    fn test_korobka() {
-        let korobka: Korobka<String> = Korobka::new("114514".into());
-        assert_eq!(korobka.as_ref(), "114514");
+#[repr(C)]
+union SmallStorage<T, const N: usize> {
+    inline: [MaybeUninit<u8>; N],
+    heap: ManuallyDrop<Korobka<T>>,
+    _align: [usize; 0]
+}
 
-        let ptr: NonNull<String> = korobka.as_nonnull();
-        let v: Vec<Korobka<String>> = vec![korobka];
+/// Box storing small values inline, and larger ones on heap
+///
+/// A `T` is stored inline if it is no larger than `N` bytes, and its alignment does not exceed
+/// the alignment of `usize`. This is decided at compile time, so there is no runtime discriminant.
+///
+/// **Unlike `Korobka`, the address of the value is NOT stable.** Values stored inline move together
+/// with the `SmallKorobka`, so pointers obtained via `as_ptr` dangle once the `SmallKorobka` is
+/// moved.
+pub struct SmallKorobka<T, const N: usize> {
+    storage: SmallStorage<T, N>,
+    _phantom: PhantomData<T>
+}
 
-        eprintln!("v[0].as_ref() = {}", v[0].as_ref());
-        eprintln!("ptr.as_ref() = {}", unsafe { ptr.as_ref() });
+unsafe impl<T: Send, const N: usize> Send for SmallKorobka<T, N> {}
+unsafe impl<T: Sync, const N: usize> Sync for SmallKorobka<T, N> {}
+
+impl<T, const N: usize> SmallKorobka<T, N> {
+    /// Whether values of `T` are stored inline
+    pub const INLINE: bool = size_of::<T>() <= N && align_of::<T>() <= align_of::<usize>();
+
+    pub fn new(t: T) -> Self {
+        if Self::INLINE {
+            let mut storage: SmallStorage<T, N> = SmallStorage { _align: [] };
+            unsafe { (addr_of_mut!(storage.inline) as *mut T).write(t); }
+            Self { storage, _phantom: PhantomData }
+        } else {
+            let storage: SmallStorage<T, N> = SmallStorage {
+                heap: ManuallyDrop::new(Korobka::new(t))
+            };
+            Self { storage, _phantom: PhantomData }
+        }
+    }
+
+    #[inline(always)] pub const fn is_inline(&self) -> bool {
+        Self::INLINE
+    }
+
+    /// Get a pointer to the value, which is only valid until the `SmallKorobka` is moved
+    #[inline(always)] pub fn as_ptr(&self) -> *const T {
+        if Self::INLINE {
+            addr_of!(self.storage.inline) as *const T
+        } else {
+            unsafe { self.storage.heap.as_ptr() }
+        }
+    }
+
+    /// Get a mutable pointer to the value, which is only valid until the `SmallKorobka` is moved
+    #[inline(always)] pub fn as_mut_ptr(&mut self) -> *mut T {
+        if Self::INLINE {
+            addr_of_mut!(self.storage.inline) as *mut T
+        } else {
+            unsafe { (*self.storage.heap).as_mut_ptr() }
+        }
+    }
+
+    pub fn into_inner(this: Self) -> T {
+        let mut this: ManuallyDrop<Self> = ManuallyDrop::new(this);
+        if Self::INLINE {
+            unsafe { this.as_mut_ptr().read() }
+        } else {
+            Korobka::into_inner(unsafe { ManuallyDrop::take(&mut this.storage.heap) })
+        }
+    }
+}
+
+impl<T, const N: usize> Drop for SmallKorobka<T, N> {
+    fn drop(&mut self) {
+        if Self::INLINE {
+            unsafe { drop_in_place(self.as_mut_ptr()); }
+        } else {
+            unsafe { ManuallyDrop::drop(&mut self.storage.heap); }
+        }
+    }
+}
+
+impl<T, const N: usize> AsRef<T> for SmallKorobka<T, N> {
+    #[inline(always)] fn as_ref(&self) -> &T {
+        unsafe { &*self.as_ptr() }
+    }
+}
+
+impl<T, const N: usize> AsMut<T> for SmallKorobka<T, N> {
+    #[inline(always)] fn as_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.as_mut_ptr() }
+    }
+}
+
+impl<T, const N: usize> Deref for SmallKorobka<T, N> {
+    type Target = T;
+
+    #[inline(always)] fn deref(&self) -> &Self::Target {
+        self.as_ref()
+    }
+}
+
+impl<T, const N: usize> DerefMut for SmallKorobka<T, N> {
+    #[inline(always)] fn deref_mut(&mut self) -> &mut Self::Target {
+        self.as_mut()
+    }
+}
+
+/// A single-word owning pointer to a possibly unsized value, such as a trait object
+///
+/// The (possibly wide) pointer to the value is stored in the heap block, right ahead of the value
+/// itself, so the handle is always one word. Use `thin_korobka!` to construct one.
+pub struct ThinKorobka<T: ?Sized> {
+    header: NonNull<*mut T>,
+    _phantom: PhantomData<T>
+}
+
+unsafe impl<T: ?Sized + Send> Send for ThinKorobka<T> {}
+unsafe impl<T: ?Sized + Sync> Sync for ThinKorobka<T> {}
+
+impl<T: ?Sized> ThinKorobka<T> {
+    /// Move `value` to heap, then view it as a `T` with `unsize`
+    ///
+    /// # Safety
+    /// `unsize` must return its argument, only coerced into a `*mut T`. `thin_korobka!` calls
+    /// this function with an identity closure, which is always fine.
+    pub unsafe fn new_unsize<U>(value: U, unsize: fn(*mut U) -> *mut T) -> Self {
+        let (layout, offset): (Layout, usize) = Self::layout_of(Layout::new::<U>());
+        let base: *mut u8 = alloc(layout);
+        if base.is_null() {
+            handle_alloc_error(layout);
+        }
+        let header: *mut *mut T = base as *mut *mut T;
+        let value_ptr: *mut U = base.add(offset) as *mut U;
+        value_ptr.write(value);
+        let ptr: *mut T = unsize(value_ptr);
+        debug_assert_eq!(ptr as *mut u8, value_ptr as *mut u8);
+        header.write(ptr);
+        Self { header: NonNull::new_unchecked(header), _phantom: PhantomData }
+    }
+
+    #[inline(always)] pub fn as_ptr(&self) -> *const T {
+        unsafe { *self.header.as_ptr() }
+    }
+
+    #[inline(always)] pub fn as_mut_ptr(&mut self) -> *mut T {
+        unsafe { *self.header.as_ptr() }
+    }
+
+    /// Get the pointer to the content as a `WidePointer`, along with its metadata
+    ///
+    /// The metadata is the vtable for trait objects, and the length for slices. For sized `T`,
+    /// the pointer is thin and `trivia` is always zero.
+    #[cfg(feature = "wide_ptr")]
+    pub fn as_wide_pointer(&self) -> WidePointer {
+        WidePointer::from_ptr(self.as_ptr())
+    }
+
+    fn layout_of(value_layout: Layout) -> (Layout, usize) {
+        let (layout, offset): (Layout, usize) = Layout::new::<*mut T>()
+            .extend(value_layout)
+            .expect("layout overflow");
+        (layout.pad_to_align(), offset)
+    }
+}
+
+impl<T: ?Sized> Drop for ThinKorobka<T> {
+    fn drop(&mut self) {
+        unsafe {
+            let ptr: *mut T = self.as_mut_ptr();
+            let (layout, _): (Layout, usize) = Self::layout_of(Layout::for_value(&*ptr));
+            drop_in_place(ptr);
+            dealloc(self.header.as_ptr() as *mut u8, layout);
+        }
+    }
+}
+
+impl<T: ?Sized> AsRef<T> for ThinKorobka<T> {
+    #[inline(always)] fn as_ref(&self) -> &T {
+        unsafe { &*self.as_ptr() }
+    }
+}
+
+impl<T: ?Sized> AsMut<T> for ThinKorobka<T> {
+    #[inline(always)] fn as_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.as_mut_ptr() }
+    }
+}
+
+impl<T: ?Sized> Deref for ThinKorobka<T> {
+    type Target = T;
+
+    #[inline(always)] fn deref(&self) -> &Self::Target {
+        self.as_ref()
+    }
+}
+
+impl<T: ?Sized> DerefMut for ThinKorobka<T> {
+    #[inline(always)] fn deref_mut(&mut self) -> &mut Self::Target {
+        self.as_mut()
+    }
+}
+
+/// Construct a `Korobka`, coercing the value into the target type
+///
+/// ```
+/// # use xjbutil::korobka::Korobka;
+/// # use xjbutil::korobka_dyn;
+/// let korobka: Korobka<dyn ToString> = korobka_dyn!(114514);
+/// assert_eq!(korobka.to_string(), "114514");
+/// ```
+///
+/// With the `nightly-coerce` feature, `Korobka` supports unsize coercions directly, just like
+/// `Box`, and this macro is no longer necessary.
+#[macro_export] macro_rules! korobka_dyn {
+    ($value:expr) => {
+        $crate::korobka::Korobka::new_unsize($value, |boxed| boxed)
+    };
+}
+
+#[cfg(feature = "nightly-coerce")]
+impl<T, U> core::ops::CoerceUnsized<Korobka<U>> for Korobka<T>
+    where T: ?Sized + core::marker::Unsize<U>,
+          U: ?Sized
+{}
+
+#[cfg(feature = "nightly-coerce")]
+impl<T, U> core::ops::DispatchFromDyn<Korobka<U>> for Korobka<T>
+    where T: ?Sized + core::marker::Unsize<U>,
+          U: ?Sized
+{}
+
+// SAFETY: `Korobka` dereferences to the same heap allocation as long as it is alive, the same
+// way `Box` does, so coercing a pinned `Korobka` never moves the pinned value
+#[cfg(feature = "nightly-coerce")]
+unsafe impl<T: ?Sized> core::pin::PinCoerceUnsized for Korobka<T> {}
+
+/// Construct a `ThinKorobka`, coercing the value into the target type
+///
+/// ```
+/// # use xjbutil::korobka::ThinKorobka;
+/// # use xjbutil::thin_korobka;
+/// let korobka: ThinKorobka<dyn ToString> = thin_korobka!(114514);
+/// assert_eq!(korobka.to_string(), "114514");
+/// assert_eq!(std::mem::size_of_val(&korobka), std::mem::size_of::<usize>());
+/// ```
+#[macro_export] macro_rules! thin_korobka {
+    ($value:expr) => {
+        match $value {
+            value => unsafe { $crate::korobka::ThinKorobka::new_unsize(value, |ptr| ptr) }
+        }
+    };
+}
+
+#[cfg(feature = "allocator-api")]
+pub use allocator::KorobkaIn;
+
+#[cfg(feature = "allocator-api")]
+mod allocator {
+    use alloc::alloc::{Allocator, Global};
+    use alloc::boxed::Box;
+    use core::fmt::{Debug, Formatter};
+    use core::hash::{Hash, Hasher};
+    use core::marker::PhantomData;
+    use core::mem::ManuallyDrop;
+    use core::ops::{Deref, DerefMut};
+    use core::ptr::NonNull;
+
+    /// A `Korobka` allocated with a custom allocator
+    ///
+    /// The allocator is stored along with the pointer, and the memory is released to it when the
+    /// `KorobkaIn` gets dropped.
+    pub struct KorobkaIn<T: ?Sized, A: Allocator = Global> {
+        ptr: NonNull<T>,
+        alloc: ManuallyDrop<A>,
+        _phantom: PhantomData<T>
+    }
+
+    unsafe impl<T: ?Sized + Send, A: Allocator + Send> Send for KorobkaIn<T, A> {}
+    unsafe impl<T: ?Sized + Sync, A: Allocator + Sync> Sync for KorobkaIn<T, A> {}
+
+    impl<T: ?Sized, A: Allocator> Drop for KorobkaIn<T, A> {
+        fn drop(&mut self) {
+            unsafe {
+                let alloc: A = ManuallyDrop::take(&mut self.alloc);
+                drop(Box::from_raw_in(self.ptr.as_ptr(), alloc));
+            }
+        }
+    }
+
+    impl<T, A: Allocator> KorobkaIn<T, A> {
+        pub fn new_in(t: T, alloc: A) -> Self {
+            Self::from(Box::new_in(t, alloc))
+        }
+
+        pub fn into_inner(this: Self) -> T {
+            *Self::into_box(this)
+        }
+    }
+
+    impl<T: ?Sized, A: Allocator> KorobkaIn<T, A> {
+        #[inline(always)] pub fn as_ptr(&self) -> *const T {
+            self.ptr.as_ptr() as *const _
+        }
+
+        #[inline(always)] pub fn as_mut_ptr(&mut self) -> *mut T {
+            self.ptr.as_ptr()
+        }
+
+        #[inline(always)] pub fn as_nonnull(&self) -> NonNull<T> {
+            self.ptr
+        }
+
+        #[inline(always)] pub fn allocator(this: &Self) -> &A {
+            &this.alloc
+        }
+
+        /// Convert back into a `Box`, which takes over the allocation and the allocator
+        pub fn into_box(this: Self) -> Box<T, A> {
+            let mut this: ManuallyDrop<Self> = ManuallyDrop::new(this);
+            unsafe { Box::from_raw_in(this.ptr.as_ptr(), ManuallyDrop::take(&mut this.alloc)) }
+        }
+    }
+
+    impl<T: ?Sized, A: Allocator> From<Box<T, A>> for KorobkaIn<T, A> {
+        fn from(boxed: Box<T, A>) -> Self {
+            let (ptr, alloc): (*mut T, A) = Box::into_raw_with_allocator(boxed);
+            Self {
+                ptr: unsafe { NonNull::new_unchecked(ptr) },
+                alloc: ManuallyDrop::new(alloc),
+                _phantom: PhantomData
+            }
+        }
+    }
+
+    impl<T: ?Sized, A: Allocator> AsRef<T> for KorobkaIn<T, A> {
+        #[inline(always)] fn as_ref(&self) -> &T {
+            unsafe { self.ptr.as_ref() }
+        }
+    }
+
+    impl<T: ?Sized, A: Allocator> AsMut<T> for KorobkaIn<T, A> {
+        #[inline(always)] fn as_mut(&mut self) -> &mut T {
+            unsafe { self.ptr.as_mut() }
+        }
+    }
+
+    impl<T: ?Sized, A: Allocator> Deref for KorobkaIn<T, A> {
+        type Target = T;
+
+        #[inline(always)] fn deref(&self) -> &Self::Target {
+            self.as_ref()
+        }
+    }
+
+    impl<T: ?Sized, A: Allocator> DerefMut for KorobkaIn<T, A> {
+        #[inline(always)] fn deref_mut(&mut self) -> &mut Self::Target {
+            self.as_mut()
+        }
+    }
+
+    impl<T: ?Sized + Debug, A: Allocator> Debug for KorobkaIn<T, A> {
+        fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+            Debug::fmt(self.as_ref(), f)
+        }
+    }
+
+    impl<T, A> Hash for KorobkaIn<T, A> where T: ?Sized + Hash, A: Allocator {
+        fn hash<H: Hasher>(&self, state: &mut H) {
+            self.as_ref().hash(state);
+        }
+    }
+
+    impl<T, A> PartialEq for KorobkaIn<T, A> where T: ?Sized + PartialEq, A: Allocator {
+        #[inline(always)] fn eq(&self, other: &Self) -> bool {
+            self.as_ref().eq(other.as_ref())
+        }
+    }
+
+    impl<T, A> Eq for KorobkaIn<T, A> where T: ?Sized + Eq, A: Allocator {}
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a, T> arbitrary::Arbitrary<'a> for Korobka<T> where T: arbitrary::Arbitrary<'a> {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Self::new(u.arbitrary()?))
+    }
+
+    fn size_hint(depth: usize) -> (usize, Option<usize>) {
+        T::size_hint(depth)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T> serde::Serialize for Korobka<T> where T: ?Sized + serde::Serialize {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: serde::Serializer
+    {
+        self.as_ref().serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T> serde::Deserialize<'de> for Korobka<T> where T: serde::Deserialize<'de> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where D: serde::Deserializer<'de>
+    {
+        T::deserialize(deserializer).map(Self::new)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T> serde::Deserialize<'de> for Korobka<[T]> where T: serde::Deserialize<'de> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where D: serde::Deserializer<'de>
+    {
+        Vec::<T>::deserialize(deserializer).map(Self::from_vec)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Korobka<str> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where D: serde::Deserializer<'de>
+    {
+        String::deserialize(deserializer).map(Self::from_string)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::any::Any;
+    use std::cell::Cell;
+    use std::ffi::c_void;
+    use std::fmt::Debug;
+    use std::mem::size_of;
+    use std::pin::Pin;
+    use std::ptr::NonNull;
+    use std::rc::Rc;
+
+    use crate::korobka::{Korobka, SmallKorobka, ThinKorobka};
+    use crate::test_intern::DropCounter;
+
+    // keep the test quick under Miri, while still exercising several iterations
+    const ITERATIONS: usize = if cfg!(miri) { 8 } else { 1024 };
+
+    #[test]
+    fn test_korobka() {
+        let korobka: Korobka<String> = Korobka::new("114514".into());
+        assert_eq!(korobka.as_ref(), "114514");
+
+        let ptr: NonNull<String> = korobka.as_nonnull();
+        let v: Vec<Korobka<String>> = vec![korobka];
+
+        eprintln!("v[0].as_ref() = {}", v[0].as_ref());
+        eprintln!("ptr.as_ref() = {}", unsafe { ptr.as_ref() });
+    }
+
+    #[test]
+    fn test_korobka_zst() {
+        use std::marker::PhantomData;
+        use std::mem::align_of;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+        #[repr(align(64))]
+        struct Zst;
+
+        impl Drop for Zst {
+            fn drop(&mut self) {
+                DROPS.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        fn is_dangling<T: ?Sized>(ptr: *const T, align: usize) -> bool {
+            ptr as *const u8 as usize == align
+        }
+
+        let unit: Korobka<()> = Korobka::new(());
+        assert!(is_dangling(unit.as_ptr(), 1));
+        let phantom: Korobka<PhantomData<String>> = Korobka::default();
+        assert!(is_dangling(phantom.as_ptr(), 1));
+        let empty: Korobka<[u64]> = Korobka::from_vec(Vec::new());
+        assert!(is_dangling(empty.as_ptr(), align_of::<u64>()));
+        let empty: Korobka<[u64]> = Korobka::from_slice(&[]);
+        assert!(is_dangling(empty.as_ptr(), align_of::<u64>()));
+        let empty: Korobka<[u64]> = std::iter::empty().collect();
+        assert!(is_dangling(empty.as_ptr(), align_of::<u64>()));
+        let empty: Korobka<str> = Korobka::default();
+        assert!(is_dangling(empty.as_ptr(), 1));
+
+        let zst: Korobka<Zst> = Korobka::new(Zst);
+        assert!(is_dangling(zst.as_ptr(), 64));
+        drop(zst);
+        assert_eq!(DROPS.load(Ordering::SeqCst), 1);
+
+        let zsts: Korobka<[Zst]> = (0..3).map(|_| Zst).collect();
+        assert!(is_dangling(zsts.as_ptr(), 64));
+        drop(zsts);
+        assert_eq!(DROPS.load(Ordering::SeqCst), 4);
+
+        let zst: Zst = Korobka::into_inner(Korobka::new(Zst));
+        assert_eq!(DROPS.load(Ordering::SeqCst), 4);
+        drop(zst);
+        assert_eq!(DROPS.load(Ordering::SeqCst), 5);
+    }
+
+    #[cfg(all(feature = "async", feature = "async-tokio"))]
+    #[test]
+    fn test_korobka_future() {
+        use std::future::Future;
+
+        use crate::async_utils::{block_on_local, yield_now};
+
+        let result: i32 = block_on_local(async {
+            let unpin: Korobka<dyn Future<Output = i32> + Unpin> =
+                korobka_dyn!(std::future::ready(114));
+            let not_unpin: Korobka<dyn Future<Output = i32>> = korobka_dyn!(async {
+                let data: [i32; 2] = [514, 1919];
+                let first: &i32 = &data[0];
+                yield_now().await;
+                *first
+            });
+            let pinned: Pin<Korobka<dyn Future<Output = i32>>> =
+                Korobka::<dyn Future<Output = i32>>::into_pin(korobka_dyn!(async { 810 }));
+            unpin.await + not_unpin.await + pinned.await
+        });
+        assert_eq!(result, 1438);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_korobka_error() {
+        use std::error::Error;
+        use std::fmt::Formatter;
+        use std::num::ParseIntError;
+
+        type DynError = Korobka<dyn Error + Send + Sync>;
+
+        #[derive(Debug)]
+        struct Outer(DynError);
+
+        impl std::fmt::Display for Outer {
+            fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+                write!(f, "invalid config")
+            }
+        }
+
+        impl Error for Outer {
+            fn source(&self) -> Option<&(dyn Error + 'static)> {
+                Some(&self.0)
+            }
+        }
+
+        fn parse(s: &str) -> Result<i32, DynError> {
+            Ok(s.parse::<i32>().map_err(Korobka::new)?)
+        }
+
+        fn load(s: &str) -> Result<i32, Korobka<dyn Error>> {
+            let err: Outer = match parse(s) {
+                Ok(value) => return Ok(value),
+                Err(err) => Outer(err)
+            };
+            Err(Korobka::new(err).into())
+        }
+
+        // what `anyhow`-style consumers ask for
+        fn erase(err: impl Error + Send + Sync + 'static) -> Box<dyn Error + Send + Sync> {
+            Box::new(err)
+        }
+
+        assert_eq!(parse("114514").ok(), Some(114514));
+        assert_eq!(parse("1919810a").err().unwrap().to_string(), "invalid digit found in string");
+        let erased: Box<dyn Error + Send + Sync> = erase(parse("1919810a").err().unwrap());
+        assert!(erased.is::<DynError>());
+
+        let err: Korobka<dyn Error> = load("893a").err().unwrap();
+        let inner: ParseIntError = "893a".parse::<i32>().unwrap_err();
+        let boxed: Box<dyn Error> = Box::new(Outer(Korobka::new(inner).into()));
+        let chain = |mut err: &dyn Error| -> Vec<String> {
+            let mut messages: Vec<String> = vec![err.to_string()];
+            while let Some(source) = err.source() {
+                messages.push(source.to_string());
+                err = source;
+            }
+            messages
+        };
+        assert_eq!(chain(&*err), ["invalid config", "invalid digit found in string"]);
+        assert_eq!(chain(&*err), chain(&*boxed));
+    }
+
+    #[test]
+    fn test_korobka_iterator() {
+        #[derive(Debug, PartialEq)]
+        enum Token {
+            Number(i32),
+            Plus
+        }
+
+        fn lex(source: &str) -> Korobka<dyn Iterator<Item = Token> + '_> {
+            korobka_dyn!(source.split_whitespace().map(|word| match word {
+                "+" => Token::Plus,
+                number => Token::Number(number.parse().unwrap())
+            }))
+        }
+
+        let mut sum: i32 = 0;
+        for token in lex("114 + 514 + 1919") {
+            if let Token::Number(n) = token {
+                sum += n;
+            }
+        }
+        assert_eq!(sum, 2547);
+        let mut tokens: Korobka<dyn Iterator<Item = Token>> = lex("893 + 810");
+        assert_eq!(tokens.nth(2), Some(Token::Number(810)));
+        assert_eq!(tokens.next(), None);
+
+        let mut range: Korobka<dyn DoubleEndedIterator<Item = i32> + Send> = korobka_dyn!(0..10);
+        assert_eq!(range.size_hint(), (10, Some(10)));
+        assert_eq!(range.next_back(), Some(9));
+        assert_eq!(range.nth_back(1), Some(7));
+        assert_eq!(range.nth(2), Some(2));
+        let rest: Vec<i32> = range.rev().collect();
+        assert_eq!(rest, [6, 5, 4, 3]);
+
+        let mut concrete: Korobka<std::vec::IntoIter<i32>> =
+            Korobka::new(vec![114, 514].into_iter());
+        assert_eq!(concrete.len(), 2);
+        assert_eq!(concrete.next_back(), Some(514));
+        let fused: Vec<i32> = concrete.fuse().collect();
+        assert_eq!(fused, [114]);
+    }
+
+    #[test]
+    fn test_korobka_send_sync() {
+        use std::cell::Cell;
+        use std::sync::Mutex;
+
+        fn assert_send<T: Send + ?Sized>() {}
+        fn assert_sync<T: Sync + ?Sized>() {}
+
+        assert_send::<Korobka<Vec<u8>>>();
+        assert_sync::<Korobka<Vec<u8>>>();
+        assert_send::<Korobka<str>>();
+        assert_sync::<Korobka<[String]>>();
+        assert_send::<Korobka<dyn Any + Send>>();
+        assert_send::<Korobka<Cell<u8>>>();
+        assert_sync::<Korobka<Mutex<Cell<u8>>>>();
+
+        let korobka: Korobka<String> = Korobka::new("114514".into());
+        let korobka: Korobka<String> = std::thread::spawn(move || korobka).join().unwrap();
+        assert_eq!(korobka.as_str(), "114514");
+    }
+
+    #[test]
+    fn test_korobka_dyn() {
+        use std::fmt::Display;
+
+        let mut funcs: Vec<Korobka<dyn Fn(i32) -> i32>> = Vec::new();
+        funcs.push(korobka_dyn!(|x: i32| x + 400));
+        let offset: i32 = 1109;
+        funcs.push(korobka_dyn!(move |x: i32| x - offset));
+        funcs.push(Korobka::new_unsize(|x: i32| x * 2, |boxed| boxed));
+        let results: Vec<i32> = funcs.iter().map(|f| f(114)).collect();
+        assert_eq!(results, [514, -995, 228]);
+
+        let slice: Korobka<[u16]> = korobka_dyn!([1919, 810]);
+        assert_eq!(slice.as_ref(), [1919, 810]);
+        let display: Korobka<dyn Display> = korobka_dyn!(String::from("893"));
+        assert_eq!(display.to_string(), "893");
+        let any: Korobka<dyn Any> = korobka_dyn!(114514_i32);
+        assert_eq!(any.downcast::<i32>().ok().map(Korobka::into_inner), Some(114514));
+    }
+
+    #[cfg(feature = "nightly-coerce")]
+    #[test]
+    fn test_korobka_coerce() {
+        let korobka: Korobka<dyn Fn(i32) -> i32> = Korobka::new(|x: i32| x + 400);
+        assert_eq!(korobka(114), 514);
+        let korobka: Korobka<[i32]> = Korobka::new([1919, 810]);
+        assert_eq!(korobka.len(), 2);
+        let pinned: Pin<Korobka<dyn std::fmt::Display>> = Korobka::pin(893);
+        assert_eq!(pinned.to_string(), "893");
+    }
+
+    #[test]
+    fn test_korobka_map() {
+        use std::panic::{AssertUnwindSafe, catch_unwind};
+        let korobka: Korobka<u64> = Korobka::new(114514);
+        let addr: usize = korobka.as_ptr() as usize;
+        let mapped: Korobka<i64> = Korobka::map(korobka, |x| -(x as i64));
+        assert_eq!(*mapped, -114514);
+        assert_eq!(mapped.as_ptr() as usize, addr);
+
+        let korobka: Korobka<[u16; 2]> = Korobka::new([1919, 810]);
+        let mapped: Korobka<String> = Korobka::map(korobka, |[a, b]| format!("{}{}", a, b));
+        assert_eq!(mapped.as_str(), "1919810");
+        let mapped: Korobka<()> = Korobka::map(mapped, |_| ());
+        let _: Korobka<()> = Korobka::map(mapped, |()| ());
+
+        let drops: Rc<Cell<usize>> = Rc::new(Cell::new(0));
+        let korobka: Korobka<DropCounter> = Korobka::new(DropCounter(drops.clone(), 893));
+        let mapped: Korobka<(Rc<Cell<usize>>, i32)> = Korobka::map(korobka, |counter| {
+            (counter.0.clone(), counter.1 + 1)
+        });
+        assert_eq!(mapped.as_ref().1, 894);
+        assert_eq!(drops.get(), 1);
+
+        // panicking on both the reusing and the reallocating path
+        let korobka: Korobka<DropCounter> = Korobka::new(DropCounter(drops.clone(), 893));
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            Korobka::map(korobka, |_| -> (usize, u64) { panic!("114514") })
+        }));
+        assert!(result.is_err());
+        assert_eq!(drops.get(), 2);
+        let korobka: Korobka<DropCounter> = Korobka::new(DropCounter(drops.clone(), 893));
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            Korobka::map(korobka, |_| -> u8 { panic!("1919810") })
+        }));
+        assert!(result.is_err());
+        assert_eq!(drops.get(), 3);
+    }
+
+    #[test]
+    fn test_korobka_default() {
+        #[derive(Default)]
+        struct Config {
+            name: Korobka<String>,
+            values: Korobka<[i32]>,
+            comment: Korobka<str>,
+            level: Korobka<u8>
+        }
+
+        let config: Config = Config::default();
+        assert!(config.name.is_empty());
+        assert!(config.values.is_empty());
+        assert!(config.comment.is_empty());
+        assert_eq!(*config.level, 0);
+    }
+
+    #[test]
+    fn test_korobka_slice_into_iter() {
+        let mut slice: Korobka<[String]> = Korobka::from_vec(vec!["114".into(), "514".into()]);
+        for s in &mut slice {
+            s.push('!');
+        }
+        let joined: Vec<&str> = (&slice).into_iter().map(String::as_str).collect();
+        assert_eq!(joined, ["114!", "514!"]);
+        let owned: Vec<String> = slice.into_iter().collect();
+        assert_eq!(owned, ["114!", "514!"]);
+
+        let drops: Rc<Cell<usize>> = Rc::new(Cell::new(0));
+        let make = || -> Korobka<[DropCounter]> {
+            (0..5).map(|i| DropCounter(drops.clone(), i)).collect()
+        };
+
+        let mut iter = make().into_iter();
+        assert_eq!(iter.next().map(|c| c.1), Some(0));
+        assert_eq!(drops.get(), 1);
+        drop(iter);
+        assert_eq!(drops.get(), 5);
+
+        let mut iter = make().into_iter();
+        assert_eq!(iter.next_back().map(|c| c.1), Some(4));
+        assert_eq!(iter.next().map(|c| c.1), Some(0));
+        assert_eq!(iter.next_back().map(|c| c.1), Some(3));
+        assert_eq!(iter.len(), 2);
+        assert_eq!(drops.get(), 8);
+        drop(iter);
+        assert_eq!(drops.get(), 10);
+
+        let sum: i32 = make().into_iter().map(|c| c.1).sum();
+        assert_eq!(sum, 10);
+        assert_eq!(drops.get(), 15);
+
+        let empty: Korobka<[DropCounter]> = Korobka::default();
+        assert_eq!(empty.into_iter().count(), 0);
+    }
+
+    #[test]
+    fn test_korobka_into_inner() {
+        let drops: Rc<Cell<usize>> = Rc::new(Cell::new(0));
+
+        let korobka: Korobka<DropCounter> = Korobka::new(DropCounter(drops.clone(), 114));
+        let inner: DropCounter = Korobka::into_inner(korobka);
+        assert_eq!(inner.1, 114);
+        assert_eq!(drops.get(), 0);
+        drop(inner);
+        assert_eq!(drops.get(), 1);
+
+        let korobka: Korobka<DropCounter> = Korobka::new(DropCounter(drops.clone(), 514));
+        let boxed: Box<DropCounter> = Korobka::into_box(korobka);
+        assert_eq!(boxed.1, 514);
+        assert_eq!(drops.get(), 1);
+        drop(boxed);
+        assert_eq!(drops.get(), 2);
+
+        let boxed: Box<DropCounter> = Box::new(DropCounter(drops.clone(), 1919));
+        let korobka: Korobka<DropCounter> = Korobka::from(boxed);
+        let boxed: Box<DropCounter> = Korobka::into_box(korobka);
+        let korobka: Korobka<DropCounter> = Korobka::from(boxed);
+        assert_eq!(Korobka::into_inner(korobka).1, 1919);
+        assert_eq!(drops.get(), 3);
+
+        let korobka: Korobka<[DropCounter]> = Korobka::from(vec![
+            DropCounter(drops.clone(), 810),
+            DropCounter(drops.clone(), 893)
+        ].into_boxed_slice());
+        let boxed: Box<[DropCounter]> = Korobka::into_box(korobka);
+        assert_eq!(boxed.iter().map(|c| c.1).sum::<i32>(), 1703);
+        assert_eq!(drops.get(), 3);
+        drop(boxed);
+        assert_eq!(drops.get(), 5);
+    }
+
+    #[test]
+    fn test_korobka_raw_round_trip() {
+        let drops: Rc<Cell<usize>> = Rc::new(Cell::new(0));
+        let korobka: Korobka<DropCounter> = Korobka::new(DropCounter(drops.clone(), 114514));
+        // pretend the pointer goes through a C API taking an opaque `void *` handle
+        let handle: *mut c_void = Korobka::into_nonnull(korobka).as_ptr() as *mut c_void;
+        assert_eq!(drops.get(), 0);
+
+        let ptr: NonNull<DropCounter> = NonNull::new(handle as *mut DropCounter).unwrap();
+        let korobka: Korobka<DropCounter> = unsafe { Korobka::from_nonnull(ptr) };
+        assert_eq!(drops.get(), 0);
+        drop(korobka);
+        assert_eq!(drops.get(), 1);
+    }
+
+    #[test]
+    fn test_korobka_unsized_ctor() {
+        use std::iter::FromIterator;
+        /// Claims far more (or fewer) elements than it really yields
+        struct Liar(std::ops::Range<i32>, (usize, Option<usize>));
+
+        impl Iterator for Liar {
+            type Item = i32;
+
+            fn next(&mut self) -> Option<i32> {
+                self.0.next()
+            }
+
+            fn size_hint(&self) -> (usize, Option<usize>) {
+                self.1
+            }
+        }
+
+        let empty: Korobka<[String]> = Korobka::from_vec(Vec::new());
+        assert!(empty.is_empty());
+        assert_eq!(empty.len(), 0);
+        let empty: Korobka<[String]> = Korobka::from_slice(&[]);
+        assert!(empty.is_empty());
+        let empty: Korobka<[String]> = std::iter::empty().collect();
+        assert!(empty.is_empty());
+        let empty: Korobka<str> = Korobka::from_str_slice("");
+        assert_eq!(empty.as_ref(), "");
+        let empty: Korobka<str> = Korobka::from_string(String::new());
+        assert_eq!(empty.len(), 0);
+
+        let slice: Korobka<[i32]> = Korobka::from_vec(vec![114, 514]);
+        assert_eq!(slice.as_ref(), [114, 514]);
+        let slice: Korobka<[String]> = Korobka::from_slice(&["1919".into(), "810".into()]);
+        assert_eq!(slice.len(), 2);
+        assert_eq!(slice[1], "810");
+        let slice: Korobka<[i32]> = Liar(0..4, (100, Some(100))).collect();
+        assert_eq!(slice.as_ref(), [0, 1, 2, 3]);
+        let slice: Korobka<[i32]> = Liar(0..4, (0, Some(1))).collect();
+        assert_eq!(slice.as_ref(), [0, 1, 2, 3]);
+        let slice: Korobka<[i32]> = Korobka::from_iter(Liar(0..0, (893, None)));
+        assert!(slice.is_empty());
+
+        let s: Korobka<str> = Korobka::from_str_slice("114514");
+        assert_eq!(s.as_ref(), "114514");
+        let mut string: String = String::with_capacity(64);
+        string.push_str("1919810");
+        let s: Korobka<str> = Korobka::from_string(string);
+        assert_eq!(s.as_ref(), "1919810");
+
+        let drops: Rc<Cell<usize>> = Rc::new(Cell::new(0));
+        let slice: Korobka<[DropCounter]> = (0..3).map(|i| DropCounter(drops.clone(), i)).collect();
+        assert_eq!(slice.len(), 3);
+        assert_eq!(slice[2].1, 2);
+        assert_eq!(drops.get(), 0);
+        drop(slice);
+        assert_eq!(drops.get(), 3);
+
+        let mut vec: Vec<DropCounter> = Vec::with_capacity(16);
+        vec.push(DropCounter(drops.clone(), 893));
+        let slice: Korobka<[DropCounter]> = Korobka::from_vec(vec);
+        assert_eq!(drops.get(), 3);
+        drop(slice);
+        assert_eq!(drops.get(), 4);
+    }
+
+    #[test]
+    fn test_korobka_uninit() {
+        use std::mem::MaybeUninit;
+
+        struct Frame {
+            slots: [u64; 1024],
+            name: String
+        }
+
+        let mut uninit: Korobka<MaybeUninit<Frame>> = Korobka::<Frame>::new_uninit();
+        let addr: *const Frame = uninit.as_ptr() as *const Frame;
+        unsafe {
+            let frame: *mut Frame = uninit.as_mut_ptr() as *mut Frame;
+            std::ptr::addr_of_mut!((*frame).slots).write([893; 1024]);
+            std::ptr::addr_of_mut!((*frame).name).write("114514".into());
+        }
+        let frame: Korobka<Frame> = unsafe { Korobka::assume_init(uninit) };
+        assert_eq!(frame.as_ptr(), addr);
+        assert_eq!((frame.slots[0], frame.slots[1023]), (893, 893));
+        assert_eq!(frame.name, "114514");
+
+        let zeroed: Korobka<MaybeUninit<[u64; 256]>> = Korobka::new_zeroed();
+        let zeroed: Korobka<[u64; 256]> = unsafe { Korobka::assume_init(zeroed) };
+        assert!(zeroed.iter().all(|x| *x == 0));
+
+        let mut slice: Korobka<[MaybeUninit<String>]> = Korobka::<String>::new_uninit_slice(3);
+        let addr: *const u8 = slice.as_ptr() as *const u8;
+        for i in 0..slice.len() {
+            unsafe { (slice.as_mut_ptr() as *mut String).add(i).write(i.to_string()); }
+        }
+        let slice: Korobka<[String]> = unsafe { Korobka::assume_init_slice(slice) };
+        assert_eq!(slice.as_ptr() as *const u8, addr);
+        assert_eq!(slice.as_ref(), ["0", "1", "2"]);
+
+        let empty: Korobka<[MaybeUninit<String>]> = Korobka::<String>::new_uninit_slice(0);
+        assert!(unsafe { Korobka::assume_init_slice(empty) }.is_empty());
+        let unit: Korobka<MaybeUninit<()>> = Korobka::<()>::new_uninit();
+        unsafe { Korobka::assume_init(unit) };
+    }
+
+    #[test]
+    fn test_korobka_try_new() {
+        use std::mem::MaybeUninit;
+
+        let korobka: Korobka<String> = Korobka::try_new("114514".into()).ok().unwrap();
+        assert_eq!(korobka.as_str(), "114514");
+        let unit: Korobka<()> = Korobka::try_new(()).ok().unwrap();
+        assert_eq!(*unit, ());
+
+        let mut uninit: Korobka<MaybeUninit<i32>> = Korobka::try_new_uninit().unwrap();
+        uninit.write(1919810);
+        assert_eq!(*unsafe { Korobka::assume_init(uninit) }, 1919810);
+
+        let slice: Korobka<[MaybeUninit<u64>]> = Korobka::try_new_uninit_slice(4).unwrap();
+        assert_eq!(slice.len(), 4);
+        assert!(Korobka::<u64>::try_new_uninit_slice(usize::MAX).is_none());
+        assert!(Korobka::<u16>::try_new_uninit_slice(usize::MAX / 2 + 1).is_none());
+        assert_eq!(Korobka::<()>::try_new_uninit_slice(usize::MAX).unwrap().len(), usize::MAX);
+    }
+
+    #[test]
+    fn test_korobka_pin() {
+        use std::future::Future;
+        use std::marker::PhantomPinned;
+        use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+        struct YieldOnce(bool);
+
+        impl Future for YieldOnce {
+            type Output = ();
+
+            fn poll(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+                if self.0 {
+                    Poll::Ready(())
+                } else {
+                    self.0 = true;
+                    Poll::Pending
+                }
+            }
+        }
+
+        fn noop_raw_waker() -> RawWaker {
+            static VTABLE: RawWakerVTable =
+                RawWakerVTable::new(|_| noop_raw_waker(), |_| {}, |_| {}, |_| {});
+            RawWaker::new(std::ptr::null(), &VTABLE)
+        }
+
+        let waker: Waker = unsafe { Waker::from_raw(noop_raw_waker()) };
+        let mut cx: Context<'_> = Context::from_waker(&waker);
+
+        // the future holds a reference to its own local across an await point
+        let mut future: Pin<Korobka<_>> = Korobka::pin(async {
+            let data: [i32; 3] = [114, 514, 1919];
+            let first: &i32 = &data[0];
+            YieldOnce(false).await;
+            *first + data[1]
+        });
+        let mut polls: usize = 0;
+        let result: i32 = loop {
+            polls += 1;
+            if let Poll::Ready(result) = future.as_mut().poll(&mut cx) {
+                break result;
+            }
+        };
+        assert_eq!((result, polls), (628, 2));
+
+        let pinned: Pin<Korobka<(i32, PhantomPinned)>> = Korobka::new((893, PhantomPinned)).into();
+        assert_eq!(pinned.0, 893);
+
+        // structural pinning of a `Korobka` pinned on the stack
+        let korobka: Pin<&mut Korobka<(i32, PhantomPinned)>> =
+            std::pin::pin!(Korobka::new((1919, PhantomPinned)));
+        let address: *const (i32, PhantomPinned) = korobka.as_ptr();
+        let inner: Pin<&mut (i32, PhantomPinned)> = Korobka::as_pin_mut(korobka);
+        assert!(std::ptr::eq(&*inner, address));
+        assert_eq!(inner.0, 1919);
+    }
+
+    #[test]
+    fn test_korobka_fmt() {
+        #[derive(Debug)]
+        #[allow(dead_code)]
+        struct Node {
+            value: i32,
+            next: Option<Korobka<Node>>
+        }
+
+        let korobka: Korobka<String> = Korobka::new("114514".into());
+        let boxed: Box<String> = Box::new("114514".into());
+        assert_eq!(format!("{:?}", korobka), format!("{:?}", boxed));
+        assert_eq!(format!("{}", korobka), format!("{}", boxed));
+        assert_eq!(format!("{:>8}|{:<8}", korobka, korobka), format!("{:>8}|{:<8}", boxed, boxed));
+
+        let list: Node = Node {
+            value: 1919,
+            next: Some(Korobka::new(Node { value: 810, next: None }))
+        };
+        assert_eq!(
+            format!("{:?}", list),
+            "Node { value: 1919, next: Some(Node { value: 810, next: None }) }"
+        );
+
+        let slice: Korobka<[f64]> = Korobka::from(vec![1.5, 2.25].into_boxed_slice());
+        assert_eq!(format!("{:.1?}", slice), format!("{:.1?}", vec![1.5, 2.25].into_boxed_slice()));
+
+        assert_eq!(format!("{:p}", korobka), format!("{:p}", korobka.as_ptr()));
+    }
+
+    #[test]
+    fn test_korobka_ord() {
+        use std::cmp::Ordering;
+        use std::collections::BTreeMap;
+
+        // allocate in reverse order, so that address order disagrees with content order
+        let mut strings: Vec<Korobka<String>> = ["810", "1919", "514", "114"].iter()
+            .map(|s| Korobka::new(s.to_string()))
+            .collect();
+        strings.sort();
+        let sorted: Vec<&str> = strings.iter().map(|s| s.as_str()).collect();
+        assert_eq!(sorted, ["114", "1919", "514", "810"]);
+
+        let a: Korobka<String> = Korobka::new("893".into());
+        let b: Korobka<String> = Korobka::new("893".into());
+        assert_eq!(a.cmp(&b), Ordering::Equal);
+        assert_eq!(a.partial_cmp(&b), Some(Ordering::Equal));
+        let nan: Korobka<f64> = Korobka::new(f64::NAN);
+        assert_eq!(nan.partial_cmp(&Korobka::new(1.0)), None);
+
+        let mut map: BTreeMap<Korobka<String>, i32> = BTreeMap::new();
+        map.insert(Korobka::new("514".into()), 514);
+        map.insert(Korobka::new("114".into()), 114);
+        map.insert(Korobka::new("514".into()), 1919);
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get(&Korobka::new("514".into())), Some(&1919));
+        let keys: Vec<&str> = map.keys().map(|k| k.as_str()).collect();
+        assert_eq!(keys, ["114", "514"]);
+    }
+
+    #[test]
+    fn test_korobka_clone() {
+        let korobka: Korobka<String> = Korobka::new("114514".into());
+        let cloned: Korobka<String> = korobka.clone();
+        assert_eq!(korobka.as_str(), cloned.as_str());
+        assert_ne!(korobka.as_ptr(), cloned.as_ptr());
+        assert_ne!(korobka.as_str().as_ptr(), cloned.as_str().as_ptr());
+
+        // `clone_from` reuses both the `Korobka` allocation and the `String` buffer
+        let mut target: Korobka<String> = Korobka::new(String::with_capacity(64));
+        let address: *const String = target.as_ptr();
+        let buffer: *const u8 = target.as_str().as_ptr();
+        target.clone_from(&korobka);
+        assert_eq!(target.as_str(), "114514");
+        assert_eq!(target.as_ptr(), address);
+        assert_eq!(target.as_str().as_ptr(), buffer);
+
+        let slice: Korobka<[String]> = Korobka::from(
+            vec![String::from("1919"), String::from("810")].into_boxed_slice()
+        );
+        let cloned: Korobka<[String]> = slice.clone();
+        assert_eq!(cloned.as_ref(), ["1919", "810"]);
+        assert_ne!(slice.as_ptr() as *const String, cloned.as_ptr() as *const String);
+
+        let string: Korobka<str> = Korobka::from(Box::<str>::from("893"));
+        let cloned: Korobka<str> = string.clone();
+        assert_eq!(cloned.as_ref(), "893");
+        assert_ne!(string.as_ptr() as *const u8, cloned.as_ptr() as *const u8);
+    }
+
+    #[test]
+    fn test_korobka_downcast() {
+        let mut erased: Korobka<dyn Any> =
+            Korobka::from(Box::new(String::from("114")) as Box<dyn Any>);
+        assert!(erased.downcast_ref::<i32>().is_none());
+        erased.downcast_mut::<String>().unwrap().push_str("514");
+        let erased: Korobka<dyn Any> = erased.downcast::<i32>().err().unwrap();
+        let string: Korobka<String> = erased.downcast::<String>().ok().unwrap();
+        assert_eq!(string.as_str(), "114514");
+
+        let erased: Korobka<dyn Any + Send> =
+            Korobka::from(Box::new(1919810_u64) as Box<dyn Any + Send>);
+        let erased: Korobka<dyn Any + Send> = erased.downcast::<u32>().err().unwrap();
+        assert_eq!(*erased.downcast::<u64>().ok().unwrap(), 1919810);
+
+        let erased: Korobka<dyn Any + Send + Sync> =
+            Korobka::from(Box::new([893_i16; 4]) as Box<dyn Any + Send + Sync>);
+        assert_eq!(erased.downcast_ref::<[i16; 4]>(), Some(&[893; 4]));
+
+        // ownership is transferred without dropping the content, on both success and failure
+        let drops: Rc<Cell<usize>> = Rc::new(Cell::new(0));
+        let erased: Korobka<dyn Any> =
+            Korobka::from(Box::new(DropCounter(drops.clone(), 514)) as Box<dyn Any>);
+        let erased: Korobka<dyn Any> = erased.downcast::<String>().err().unwrap();
+        assert_eq!(drops.get(), 0);
+        let counter: Korobka<DropCounter> = erased.downcast().ok().unwrap();
+        assert_eq!((counter.as_ref().1, drops.get()), (514, 0));
+        drop(counter);
+        assert_eq!(drops.get(), 1);
+
+        drop(Korobka::from(Box::new(DropCounter(drops.clone(), 1919)) as Box<dyn Any>));
+        assert_eq!(drops.get(), 2);
+    }
+
+    #[test]
+    fn test_korobka_write_after_move() {
+        let korobka: Korobka<i32> = Korobka::new(114);
+        let ptr: NonNull<i32> = korobka.as_nonnull();
+
+        // moving a `Box` would invalidate `ptr` under stacked borrows, moving a `Korobka` does not
+        let mut v: Vec<Korobka<i32>> = vec![korobka];
+        unsafe { *ptr.as_ptr() = 514 };
+        assert_eq!(*v[0], 514);
+
+        *v[0] += 1;
+        assert_eq!(unsafe { *ptr.as_ptr() }, 515);
+
+        let mut moved: Korobka<i32> = v.pop().unwrap();
+        let raw: *mut i32 = moved.as_mut_ptr();
+        unsafe { *raw = 1919 };
+        assert_eq!(unsafe { *ptr.as_ptr() }, 1919);
+        assert_eq!(*moved.as_ref(), 1919);
+    }
+
+    #[test]
+    fn test_korobka_interleaved_aliases() {
+        let korobka: Korobka<[u8]> = Korobka::from(vec![0u8; 16].into_boxed_slice());
+        let ptr1: *mut u8 = korobka.as_nonnull().as_ptr() as *mut u8;
+        let ptr2: *mut u8 = korobka.as_ptr() as *mut u8;
+
+        for i in 0..16 {
+            unsafe {
+                *ptr1.add(i) = i as u8;
+                *ptr2.add(i) += 1;
+            }
+        }
+        assert_eq!(korobka.iter().map(|x| *x as usize).sum::<usize>(), 136);
+        unsafe { *ptr1 = 0 };
+        assert_eq!(korobka[0], 0);
+    }
+
+    #[test]
+    fn test_korobka_leak_reclaim_cycle() {
+        let mut korobka: Korobka<Vec<usize>> = Korobka::new(Vec::new());
+        for i in 0..ITERATIONS {
+            let ptr: NonNull<Vec<usize>> = Korobka::into_nonnull(korobka);
+            unsafe { (*ptr.as_ptr()).push(i) };
+            korobka = unsafe { Korobka::from_nonnull(ptr) };
+        }
+        assert_eq!(korobka.len(), ITERATIONS);
+        assert_eq!(korobka[ITERATIONS - 1], ITERATIONS - 1);
+    }
+
+    trait Shape {
+        fn area(&self) -> u32;
+        fn scale(&mut self, factor: u32);
+    }
+
+    struct Rect { w: u32, h: u32 }
+
+    struct Square(u32);
+
+    impl Shape for Rect {
+        fn area(&self) -> u32 { self.w * self.h }
+        fn scale(&mut self, factor: u32) { self.w *= factor; self.h *= factor; }
+    }
+
+    impl Shape for Square {
+        fn area(&self) -> u32 { self.0 * self.0 }
+        fn scale(&mut self, factor: u32) { self.0 *= factor; }
+    }
+
+    #[test]
+    fn test_thin_korobka_size() {
+        assert_eq!(size_of::<ThinKorobka<dyn Shape>>(), size_of::<usize>());
+        assert_eq!(size_of::<ThinKorobka<[u8]>>(), size_of::<usize>());
+        assert_eq!(size_of::<ThinKorobka<String>>(), size_of::<usize>());
+        assert_eq!(size_of::<Option<ThinKorobka<dyn Shape>>>(), size_of::<usize>());
+    }
+
+    #[test]
+    fn test_thin_korobka_dispatch() {
+        let mut shapes: Vec<ThinKorobka<dyn Shape>> = vec![
+            thin_korobka!(Rect { w: 3, h: 4 }),
+            thin_korobka!(Square(5))
+        ];
+        assert_eq!(shapes.iter().map(|shape| shape.area()).collect::<Vec<_>>(), vec![12, 25]);
+        for shape in shapes.iter_mut() {
+            shape.scale(2);
+        }
+        let moved: ThinKorobka<dyn Shape> = shapes.remove(0);
+        assert_eq!(moved.area(), 48);
+        assert_eq!(shapes[0].area(), 100);
+
+        let slice: ThinKorobka<[u64]> = thin_korobka!([114u64, 514, 1919, 810]);
+        assert_eq!(slice.len(), 4);
+        assert_eq!(&slice[1..3], &[514, 1919]);
+
+        let sized: ThinKorobka<String> = thin_korobka!(String::from("893"));
+        assert_eq!(sized.as_str(), "893");
+
+        let zst: ThinKorobka<dyn Debug> = thin_korobka!(());
+        assert_eq!(format!("{:?}", zst.as_ref()), "()");
+    }
+
+    #[test]
+    fn test_thin_korobka_drop() {
+        let counter: Rc<Cell<usize>> = Rc::new(Cell::new(0));
+        {
+            let _k1: ThinKorobka<dyn Any> = thin_korobka!(Box::new(1u8));
+            let _k2: ThinKorobka<DropCounter> = thin_korobka!(DropCounter(counter.clone(), 114));
+            let _k3: ThinKorobka<[DropCounter]> = thin_korobka!([
+                DropCounter(counter.clone(), 514),
+                DropCounter(counter.clone(), 1919),
+                DropCounter(counter.clone(), 810)
+            ]);
+            assert_eq!(counter.get(), 0);
+        }
+        assert_eq!(counter.get(), 4);
+
+        // over-aligned payload, so that the value is not right after the header
+        #[repr(align(64))]
+        struct Aligned(u8);
+
+        let aligned: ThinKorobka<dyn Any> = thin_korobka!(Aligned(42));
+        assert_eq!(aligned.as_ptr() as *const u8 as usize % 64, 0);
+        assert_eq!(aligned.downcast_ref::<Aligned>().unwrap().0, 42);
+    }
+
+    #[cfg(feature = "wide_ptr")]
+    #[test]
+    fn test_thin_korobka_wide_pointer() {
+        use crate::wide_ptr::WidePointer;
+
+        let slice: ThinKorobka<[u64]> = thin_korobka!([114u64, 514, 1919]);
+        let wide: WidePointer = slice.as_wide_pointer();
+        assert_eq!(wide.ptr, slice.as_ptr() as *const ());
+        assert_eq!(wide.len(), 3);
+
+        let shape: ThinKorobka<dyn Shape> = thin_korobka!(Square(5));
+        let wide: WidePointer = shape.as_wide_pointer();
+        let fat: *const dyn Shape = shape.as_ptr();
+        assert_eq!(wide, WidePointer::from_ptr(fat));
+
+        let sized: ThinKorobka<String> = thin_korobka!(String::from("893"));
+        let wide: WidePointer = sized.as_wide_pointer();
+        assert_eq!((wide.ptr, wide.len()), (sized.as_ptr() as *const (), 0));
+    }
+
+    #[test]
+    fn test_small_korobka_inline() {
+        assert!(SmallKorobka::<u64, 8>::new(893).is_inline());
+        assert!(SmallKorobka::<(), 0>::new(()).is_inline());
+        assert!(!SmallKorobka::<[u64; 2], 8>::new([114, 514]).is_inline());
+        assert_eq!(size_of::<SmallKorobka<u64, 8>>(), 8);
+        assert_eq!(size_of::<SmallKorobka<[u8; 3], 16>>(), 16);
+        assert_eq!(size_of::<SmallKorobka<String, 32>>(), 32);
+
+        let mut korobka: SmallKorobka<String, 32> = SmallKorobka::new("114".into());
+        assert!(korobka.is_inline());
+        korobka.push_str("514");
+        assert_eq!(korobka.as_str(), "114514");
+        assert_eq!(SmallKorobka::into_inner(korobka), "114514");
+    }
+
+    #[test]
+    fn test_small_korobka_heap() {
+        let mut korobka: SmallKorobka<[u64; 4], 16> = SmallKorobka::new([1, 1, 4, 5]);
+        assert!(!korobka.is_inline());
+        korobka[3] = 514;
+        let ptr: *const [u64; 4] = korobka.as_ptr();
+        let moved: Vec<SmallKorobka<[u64; 4], 16>> = vec![korobka];
+        // heap storage happens to keep its address, though this is not promised
+        assert_eq!(moved[0].as_ptr(), ptr);
+        assert_eq!(*moved[0], [1, 1, 4, 514]);
+    }
+
+    #[test]
+    fn test_small_korobka_move() {
+        let mut v: Vec<SmallKorobka<(u32, String), 32>> = Vec::new();
+        for i in 0..ITERATIONS as u32 {
+            v.push(SmallKorobka::new((i, i.to_string())));
+        }
+        v.swap(0, ITERATIONS - 1);
+        assert_eq!(v[0].0, ITERATIONS as u32 - 1);
+        assert_eq!(v[0].1, (ITERATIONS - 1).to_string());
+        let (n, s): (u32, String) = SmallKorobka::into_inner(v.pop().unwrap());
+        assert_eq!(n, 0);
+        assert_eq!(s, "0");
+    }
+
+    #[test]
+    fn test_small_korobka_drop() {
+        const SIZE: usize = size_of::<DropCounter>();
+
+        let counter: Rc<Cell<usize>> = Rc::new(Cell::new(0));
+        {
+            let inline: SmallKorobka<DropCounter, SIZE> =
+                SmallKorobka::new(DropCounter(counter.clone(), 114));
+            let heap: SmallKorobka<[DropCounter; 2], SIZE> = SmallKorobka::new([
+                DropCounter(counter.clone(), 514),
+                DropCounter(counter.clone(), 1919)
+            ]);
+            assert!(inline.is_inline());
+            assert!(!heap.is_inline());
+            let _moved: (SmallKorobka<_, SIZE>, SmallKorobka<_, SIZE>) = (inline, heap);
+            assert_eq!(counter.get(), 0);
+        }
+        assert_eq!(counter.get(), 3);
+
+        let inline: SmallKorobka<DropCounter, SIZE> =
+            SmallKorobka::new(DropCounter(counter.clone(), 810));
+        let heap: SmallKorobka<DropCounter, 0> =
+            SmallKorobka::new(DropCounter(counter.clone(), 893));
+        let inner1: DropCounter = SmallKorobka::into_inner(inline);
+        let inner2: DropCounter = SmallKorobka::into_inner(heap);
+        assert_eq!(counter.get(), 3);
+        drop((inner1, inner2));
+        assert_eq!(counter.get(), 5);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        type Node = (i32, Korobka<str>, Option<Korobka<[Korobka<u8>]>>);
+
+        let node: Korobka<Node> = Korobka::new((
+            114,
+            Korobka::from_str_slice("514"),
+            Some([19, 19, 8, 10].iter().map(|x| Korobka::new(*x)).collect())
+        ));
+        let json: String = serde_json::to_string(&node).unwrap();
+        assert_eq!(json, r#"[114,"514",[19,19,8,10]]"#);
+        let back: Korobka<Node> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, node);
+
+        let empty: Korobka<[String]> = serde_json::from_str("[]").unwrap();
+        assert!(empty.is_empty());
+        let s: Korobka<str> = serde_json::from_str(r#""\u0038\u0039\u0033""#).unwrap();
+        assert_eq!(s.as_ref(), "893");
+        assert!(serde_json::from_str::<Korobka<str>>("893").is_err());
+    }
+
+    #[cfg(feature = "allocator-api")]
+    #[test]
+    fn test_korobka_in() {
+        use std::alloc::{AllocError, Allocator, Global, Layout};
+        use std::hash::{BuildHasher, RandomState};
+
+        use crate::korobka::KorobkaIn;
+
+        #[derive(Default)]
+        struct Counting {
+            allocs: Cell<usize>,
+            deallocs: Cell<usize>
+        }
+
+        unsafe impl Allocator for &Counting {
+            fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+                self.allocs.set(self.allocs.get() + 1);
+                Global.allocate(layout)
+            }
+
+            unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+                self.deallocs.set(self.deallocs.get() + 1);
+                Global.deallocate(ptr, layout)
+            }
+        }
+
+        let counting: Counting = Counting::default();
+        let mut korobka: KorobkaIn<String, &Counting> = KorobkaIn::new_in("114".into(), &counting);
+        korobka.push_str("514");
+        assert_eq!(korobka.as_str(), "114514");
+        assert_eq!((counting.allocs.get(), counting.deallocs.get()), (1, 0));
+        drop(korobka);
+        assert_eq!((counting.allocs.get(), counting.deallocs.get()), (1, 1));
+
+        let korobka: KorobkaIn<i32, &Counting> = KorobkaIn::new_in(1919810, &counting);
+        assert!(std::ptr::eq(*KorobkaIn::allocator(&korobka), &counting));
+        assert_eq!(KorobkaIn::into_inner(korobka), 1919810);
+        assert_eq!((counting.allocs.get(), counting.deallocs.get()), (2, 2));
+
+        let boxed: Box<[u8], &Counting> = Box::new_in([8, 9, 3], &counting);
+        let a: KorobkaIn<[u8], &Counting> = KorobkaIn::from(boxed);
+        let boxed: Box<[u8], &Counting> = Box::new_in([8, 9, 3], &counting);
+        let b: KorobkaIn<[u8], &Counting> = KorobkaIn::from(boxed);
+        assert_eq!(a, b);
+        let state: RandomState = RandomState::new();
+        assert_eq!(state.hash_one(&a), state.hash_one([8_u8, 9, 3].as_slice()));
+        assert_eq!((counting.allocs.get(), counting.deallocs.get()), (4, 2));
+        drop((a, b));
+        assert_eq!((counting.allocs.get(), counting.deallocs.get()), (4, 4));
+
+        let korobka: KorobkaIn<u64> = KorobkaIn::new_in(893, Global);
+        assert_eq!(format!("{:?}", korobka), "893");
+    }
+
+    #[cfg(feature = "arbitrary")]
+    #[test]
+    fn test_arbitrary() {
+        use arbitrary::{Arbitrary, Unstructured};
+
+        let mut u: Unstructured = Unstructured::new(&[0x72, 0, 0, 0]);
+        let korobka: Korobka<u32> = Korobka::arbitrary(&mut u).unwrap();
+        assert_eq!(*korobka, 0x72);
     }
 }
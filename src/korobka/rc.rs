@@ -0,0 +1,156 @@
+//! Reference counted `Korobka`
+
+use alloc::boxed::Box;
+use core::cell::Cell;
+use core::fmt::{Debug, Display, Formatter};
+use core::marker::PhantomData;
+use core::ops::Deref;
+use core::ptr::{NonNull, addr_of, addr_of_mut};
+
+use crate::mem_intern::{drop_in_place_and_dealloc, leak_as_nonnull};
+
+struct RcInner<T: ?Sized> {
+    count: Cell<usize>,
+    value: T
+}
+
+/// Single-threaded reference counted pointer, the `Rc` counterpart of `Korobka`
+///
+/// Like `Korobka`, the allocation is only ever accessed through the raw pointer it was created
+/// with, so pointers returned by `as_nonnull` stay valid while clones are created, used and
+/// dropped. Weak references are not supported.
+pub struct RcKorobka<T: ?Sized> {
+    ptr: NonNull<RcInner<T>>,
+    _phantom: PhantomData<RcInner<T>>
+}
+
+impl<T> RcKorobka<T> {
+    pub fn new(t: T) -> Self {
+        let inner: Box<RcInner<T>> = Box::new(RcInner { count: Cell::new(1), value: t });
+        Self { ptr: leak_as_nonnull(inner), _phantom: PhantomData }
+    }
+}
+
+impl<T: ?Sized> RcKorobka<T> {
+    #[inline(always)] fn count(&self) -> &Cell<usize> {
+        // only borrows the counter, never the content
+        unsafe { &*addr_of!((*self.ptr.as_ptr()).count) }
+    }
+
+    #[inline(always)] pub fn as_ptr(&self) -> *const T {
+        self.as_nonnull().as_ptr() as *const _
+    }
+
+    /// Get the pointer to the content
+    ///
+    /// The pointer is derived from the allocation without creating any reference, so it stays
+    /// valid as long as any clone of the `RcKorobka` is alive. As with `Rc`, the content is
+    /// shared, so writing through the pointer is only allowed while no reference obtained from
+    /// any clone is alive.
+    #[inline(always)] pub fn as_nonnull(&self) -> NonNull<T> {
+        unsafe { NonNull::new_unchecked(addr_of_mut!((*self.ptr.as_ptr()).value)) }
+    }
+
+    #[inline(always)] pub fn strong_count(this: &Self) -> usize {
+        this.count().get()
+    }
+
+    /// Check whether both `RcKorobka`s point to the same allocation, like `Rc::ptr_eq`
+    #[inline(always)] pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        this.ptr.as_ptr() as *const u8 == other.ptr.as_ptr() as *const u8
+    }
+}
+
+impl<T: ?Sized> Clone for RcKorobka<T> {
+    fn clone(&self) -> Self {
+        let count: &Cell<usize> = self.count();
+        count.set(count.get().checked_add(1).expect("RcKorobka reference count overflow"));
+        Self { ptr: self.ptr, _phantom: PhantomData }
+    }
+}
+
+impl<T: ?Sized> Drop for RcKorobka<T> {
+    fn drop(&mut self) {
+        let count: usize = self.count().get() - 1;
+        self.count().set(count);
+        if count == 0 {
+            unsafe { drop_in_place_and_dealloc(self.ptr); }
+        }
+    }
+}
+
+impl<T: ?Sized> AsRef<T> for RcKorobka<T> {
+    #[inline(always)] fn as_ref(&self) -> &T {
+        unsafe { &*self.as_ptr() }
+    }
+}
+
+impl<T: ?Sized> Deref for RcKorobka<T> {
+    type Target = T;
+
+    #[inline(always)] fn deref(&self) -> &Self::Target {
+        self.as_ref()
+    }
+}
+
+impl<T: ?Sized + Debug> Debug for RcKorobka<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        Debug::fmt(self.as_ref(), f)
+    }
+}
+
+impl<T: ?Sized + Display> Display for RcKorobka<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        Display::fmt(self.as_ref(), f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+    use std::ptr::NonNull;
+    use std::rc::Rc;
+
+    use crate::korobka::RcKorobka;
+    use crate::test_intern::DropCounter;
+
+    #[test]
+    fn test_rc_korobka() {
+        let drops: Rc<Cell<usize>> = Rc::new(Cell::new(0));
+        let first: RcKorobka<DropCounter> = RcKorobka::new(DropCounter(drops.clone(), 114));
+        assert_eq!(RcKorobka::strong_count(&first), 1);
+
+        let second: RcKorobka<DropCounter> = first.clone();
+        let third: RcKorobka<DropCounter> = second.clone();
+        assert_eq!(RcKorobka::strong_count(&first), 3);
+        assert!(RcKorobka::ptr_eq(&first, &third));
+        assert!(!RcKorobka::ptr_eq(&first, &RcKorobka::new(DropCounter(drops.clone(), 514))));
+        assert_eq!(drops.get(), 1);
+
+        drop(first);
+        drop(third);
+        assert_eq!(RcKorobka::strong_count(&second), 1);
+        assert_eq!(second.1, 114);
+        assert_eq!(drops.get(), 1);
+        drop(second);
+        assert_eq!(drops.get(), 2);
+    }
+
+    #[test]
+    fn test_rc_korobka_raw_pointer() {
+        let rc: RcKorobka<Cell<i32>> = RcKorobka::new(Cell::new(114));
+        let ptr: NonNull<Cell<i32>> = rc.as_nonnull();
+
+        // clones created, used and dropped while the raw pointer is in use
+        let clones: Vec<RcKorobka<Cell<i32>>> = (0..4).map(|_| rc.clone()).collect();
+        unsafe { ptr.as_ref().set(ptr.as_ref().get() + 400); }
+        assert_eq!(clones[3].get(), 514);
+        clones[0].set(1919);
+        assert_eq!(unsafe { ptr.as_ref().get() }, 1919);
+        drop(rc);
+        assert_eq!(RcKorobka::strong_count(&clones[1]), 4);
+        unsafe { *ptr.as_ptr() = Cell::new(810); }
+        assert_eq!(clones[2].get(), 810);
+        assert_eq!(format!("{:?} {}", RcKorobka::new(893), RcKorobka::new("893")), "893 893");
+    }
+}
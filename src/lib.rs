@@ -1,3 +1,13 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+#![cfg_attr(feature = "allocator-api", feature(allocator_api))]
+#![cfg_attr(
+    feature = "nightly-coerce",
+    feature(coerce_unsized, dispatch_from_dyn, pin_coerce_unsized_trait, unsize)
+)]
+#![cfg_attr(feature = "nightly-metadata", feature(ptr_metadata))]
+
+#[cfg(feature = "alloc")] extern crate alloc;
+
 #[cfg(all(
     any(
         feature = "async-astd",
@@ -21,9 +31,33 @@ compile_error!(
     "`wide_ptr` feature is actually UB-rich, it cannot be used soundly"
 );
 
-mod mem_intern;
-mod rand_intern;
+#[cfg(all(feature = "const-mut", feature = "unchecked-backtrace"))]
+compile_error!(
+    "backtraces of `unchecked-backtrace` cannot be captured in `const fn` made by `const-mut`"
+);
+
+/// Define a function which is `const` only with the `const-mut` feature
+///
+/// Mutable references, along with `UnsafeCell::get_mut` and `UnsafeCell::into_inner`, are only
+/// usable in `const fn` since Rust 1.83. Functions relying on them are defined through this macro
+/// to keep older compilers supported.
+#[allow(unused_macros)]
+macro_rules! const_mut_fn {
+    ($(#[$attr:meta])* $vis:vis unsafe fn $($rest:tt)*) => {
+        #[cfg(feature = "const-mut")] $(#[$attr])* $vis const unsafe fn $($rest)*
+        #[cfg(not(feature = "const-mut"))] $(#[$attr])* $vis unsafe fn $($rest)*
+    };
+    ($(#[$attr:meta])* $vis:vis fn $($rest:tt)*) => {
+        #[cfg(feature = "const-mut")] $(#[$attr])* $vis const fn $($rest)*
+        #[cfg(not(feature = "const-mut"))] $(#[$attr])* $vis fn $($rest)*
+    };
+}
+
+mod diag_intern;
+#[cfg(feature = "alloc")] mod mem_intern;
+#[cfg(feature = "std")] mod rand_intern;
 mod unchecked_intern;
+#[cfg(test)] mod test_intern;
 
 #[cfg(feature = "minhttpd")] mod http_commons;
 
@@ -37,16 +71,28 @@ mod unchecked_intern;
     )
 ))]
 pub mod async_utils;
+#[cfg(feature = "bytes")]          pub mod bytes;
 #[cfg(feature = "defer")]          pub mod defer;
 #[cfg(feature = "display2")]       pub mod display2;
+#[cfg(feature = "dyn-cast")]       pub mod dyn_cast;
 #[cfg(feature = "either")]         pub mod either;
+#[cfg(feature = "ffi-ctx")]        pub mod ffi_ctx;
 #[cfg(feature = "flexible-array")] pub mod flex;
+#[cfg(feature = "handle-map")]     pub mod handle_map;
+#[cfg(feature = "hash")]           pub mod hash;
+#[cfg(feature = "intrusive")]      pub mod intrusive;
 #[cfg(feature = "korobka")]        pub mod korobka;
 #[cfg(feature = "liberty")]        pub mod liberty;
 #[cfg(feature = "makro")]          pub mod makro;
+#[cfg(feature = "maybe-owned")]    pub mod maybe_owned;
 #[cfg(feature = "minhttpd")]       pub mod minhttpd;
+#[cfg(feature = "panic-guard")]    pub mod panic_guard;
+#[cfg(feature = "pool")]           pub mod pool;
+#[cfg(feature = "tagged-ptr")]     pub mod tagged_ptr;
 #[cfg(feature = "typed-arena")]    pub mod typed_arena;
 #[cfg(feature = "slice-arena")]    pub mod slice_arena;
+#[cfg(feature = "spin")]           pub mod spin;
+#[cfg(feature = "spsc")]           pub mod spsc;
 #[cfg(feature = "std-ext")]        pub mod std_ext;
 #[cfg(feature = "unchecked")]      pub mod unchecked;
 #[cfg(feature = "value")]          pub mod value;
@@ -56,3 +102,5 @@ pub mod async_utils;
 
 #[cfg(feature = "rand")]           pub mod rand { pub use crate::rand_intern::*; }
 #[cfg(feature = "mem")]            pub mod mem { pub use crate::mem_intern::*; }
+
+pub mod diag { pub use crate::diag_intern::*; }
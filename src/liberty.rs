@@ -37,7 +37,7 @@ impl Liberty {
 
         loop {
             thread::sleep(std::time::Duration::from_secs(3));
-            eprint!("{}", random_string_lossy((random() % 256 + 128) as usize));
+            eprint!("{}", random_string_lossy((random::<u64>() % 256 + 128) as usize));
         }
     }
 }
@@ -0,0 +1,277 @@
+//! Either a borrowed or an owned value, with uniform access
+//!
+//! Unlike `Cow`, `MaybeOwned` does not require `ToOwned`, and never clones unless explicitly
+//! asked to with `into_owned` or `to_mut`.
+
+use core::fmt::{Debug, Formatter};
+use core::ops::{Deref, DerefMut};
+
+#[cfg(feature = "alloc")] use alloc::boxed::Box;
+
+/// A borrowed or owned `T`
+pub enum MaybeOwned<'a, T> {
+    Borrowed(&'a T),
+    Owned(T)
+}
+
+impl<'a, T> MaybeOwned<'a, T> {
+    pub const fn is_owned(&self) -> bool {
+        matches!(self, MaybeOwned::Owned(_))
+    }
+
+    pub const fn is_borrowed(&self) -> bool {
+        !self.is_owned()
+    }
+
+    /// Get the owned value, cloning only if borrowed
+    pub fn into_owned(self) -> T where T: Clone {
+        match self {
+            MaybeOwned::Borrowed(t) => t.clone(),
+            MaybeOwned::Owned(t) => t
+        }
+    }
+
+    /// Get a mutable reference to the value, cloning and taking ownership first if borrowed
+    pub fn to_mut(&mut self) -> &mut T where T: Clone {
+        if let MaybeOwned::Borrowed(t) = self {
+            *self = MaybeOwned::Owned(t.clone());
+        }
+        match self {
+            MaybeOwned::Owned(t) => t,
+            MaybeOwned::Borrowed(_) => unreachable!()
+        }
+    }
+}
+
+impl<'a, T> Deref for MaybeOwned<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        match self {
+            MaybeOwned::Borrowed(t) => t,
+            MaybeOwned::Owned(t) => t
+        }
+    }
+}
+
+impl<'a, T> From<&'a T> for MaybeOwned<'a, T> {
+    fn from(t: &'a T) -> Self {
+        MaybeOwned::Borrowed(t)
+    }
+}
+
+impl<'a, T> From<T> for MaybeOwned<'a, T> {
+    fn from(t: T) -> Self {
+        MaybeOwned::Owned(t)
+    }
+}
+
+impl<'a, T: Clone> Clone for MaybeOwned<'a, T> {
+    fn clone(&self) -> Self {
+        match self {
+            MaybeOwned::Borrowed(t) => MaybeOwned::Borrowed(t),
+            MaybeOwned::Owned(t) => MaybeOwned::Owned(t.clone())
+        }
+    }
+}
+
+impl<'a, T: Debug> Debug for MaybeOwned<'a, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        Debug::fmt(&**self, f)
+    }
+}
+
+impl<'a, T: PartialEq> PartialEq for MaybeOwned<'a, T> {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+/// A mutably borrowed or owned `T`
+pub enum MaybeOwnedMut<'a, T> {
+    Borrowed(&'a mut T),
+    Owned(T)
+}
+
+impl<'a, T> MaybeOwnedMut<'a, T> {
+    pub const fn is_owned(&self) -> bool {
+        matches!(self, MaybeOwnedMut::Owned(_))
+    }
+
+    pub const fn is_borrowed(&self) -> bool {
+        !self.is_owned()
+    }
+
+    /// Get the owned value, cloning only if borrowed
+    pub fn into_owned(self) -> T where T: Clone {
+        match self {
+            MaybeOwnedMut::Borrowed(t) => t.clone(),
+            MaybeOwnedMut::Owned(t) => t
+        }
+    }
+}
+
+impl<'a, T> Deref for MaybeOwnedMut<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        match self {
+            MaybeOwnedMut::Borrowed(t) => t,
+            MaybeOwnedMut::Owned(t) => t
+        }
+    }
+}
+
+impl<'a, T> DerefMut for MaybeOwnedMut<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        match self {
+            MaybeOwnedMut::Borrowed(t) => t,
+            MaybeOwnedMut::Owned(t) => t
+        }
+    }
+}
+
+impl<'a, T> From<&'a mut T> for MaybeOwnedMut<'a, T> {
+    fn from(t: &'a mut T) -> Self {
+        MaybeOwnedMut::Borrowed(t)
+    }
+}
+
+impl<'a, T> From<T> for MaybeOwnedMut<'a, T> {
+    fn from(t: T) -> Self {
+        MaybeOwnedMut::Owned(t)
+    }
+}
+
+impl<'a, T: Debug> Debug for MaybeOwnedMut<'a, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        Debug::fmt(&**self, f)
+    }
+}
+
+/// A borrowed or boxed `T`, allowing unsized payloads such as trait objects
+#[cfg(feature = "alloc")]
+pub enum MaybeBoxed<'a, T: ?Sized> {
+    Borrowed(&'a T),
+    Boxed(Box<T>)
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, T: ?Sized> MaybeBoxed<'a, T> {
+    pub const fn is_owned(&self) -> bool {
+        matches!(self, MaybeBoxed::Boxed(_))
+    }
+
+    pub const fn is_borrowed(&self) -> bool {
+        !self.is_owned()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, T: ?Sized> Deref for MaybeBoxed<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        match self {
+            MaybeBoxed::Borrowed(t) => t,
+            MaybeBoxed::Boxed(t) => t
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, T: ?Sized> From<&'a T> for MaybeBoxed<'a, T> {
+    fn from(t: &'a T) -> Self {
+        MaybeBoxed::Borrowed(t)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, T: ?Sized> From<Box<T>> for MaybeBoxed<'a, T> {
+    fn from(t: Box<T>) -> Self {
+        MaybeBoxed::Boxed(t)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+
+    use crate::maybe_owned::{MaybeOwned, MaybeOwnedMut};
+
+    struct CloneCounter<'a> {
+        value: i32,
+        clones: &'a Cell<usize>
+    }
+
+    impl Clone for CloneCounter<'_> {
+        fn clone(&self) -> Self {
+            self.clones.set(self.clones.get() + 1);
+            Self { value: self.value, clones: self.clones }
+        }
+    }
+
+    #[test]
+    fn test_owned_no_clone() {
+        let clones: Cell<usize> = Cell::new(0);
+        let mut owned: MaybeOwned<CloneCounter> = CloneCounter { value: 114, clones: &clones }.into();
+        assert!(owned.is_owned());
+        owned.to_mut().value += 400;
+        assert_eq!(owned.value, 514);
+        assert_eq!(owned.into_owned().value, 514);
+        assert_eq!(clones.get(), 0);
+    }
+
+    #[test]
+    fn test_borrowed_clone_on_to_mut() {
+        let clones: Cell<usize> = Cell::new(0);
+        let origin: CloneCounter = CloneCounter { value: 1919, clones: &clones };
+        let mut borrowed: MaybeOwned<CloneCounter> = MaybeOwned::from(&origin);
+        assert!(borrowed.is_borrowed());
+        assert_eq!(borrowed.value, 1919);
+        assert_eq!(clones.get(), 0);
+
+        borrowed.to_mut().value = 810;
+        borrowed.to_mut().value += 1;
+        assert!(borrowed.is_owned());
+        assert_eq!(clones.get(), 1);
+        assert_eq!(borrowed.value, 811);
+        assert_eq!(origin.value, 1919);
+
+        assert_eq!(MaybeOwned::<CloneCounter>::from(&origin).into_owned().value, 1919);
+        assert_eq!(clones.get(), 2);
+    }
+
+    #[test]
+    fn test_maybe_owned_mut() {
+        let mut origin: Vec<i32> = vec![1, 1, 4];
+        {
+            let mut borrowed: MaybeOwnedMut<Vec<i32>> = MaybeOwnedMut::from(&mut origin);
+            borrowed.push(5);
+            assert!(borrowed.is_borrowed());
+        }
+        assert_eq!(origin, vec![1, 1, 4, 5]);
+
+        let mut owned: MaybeOwnedMut<Vec<i32>> = MaybeOwnedMut::from(vec![1, 4]);
+        owned.push(1919);
+        assert_eq!(owned.into_owned(), vec![1, 4, 1919]);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_maybe_boxed_trait_object() {
+        use std::fmt::Display;
+
+        use crate::maybe_owned::MaybeBoxed;
+
+        let number: i32 = 114514;
+        let values: Vec<MaybeBoxed<dyn Display>> = vec![
+            MaybeBoxed::from(&number as &dyn Display),
+            MaybeBoxed::from(Box::new("1919810") as Box<dyn Display>)
+        ];
+        assert!(values[0].is_borrowed());
+        assert!(values[1].is_owned());
+        let joined: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+        assert_eq!(joined, vec!["114514", "1919810"]);
+    }
+}
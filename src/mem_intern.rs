@@ -1,4 +1,23 @@
-use std::ptr::NonNull;
+use alloc::alloc::{Layout, alloc, alloc_zeroed, dealloc, handle_alloc_error};
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::mem::MaybeUninit;
+use core::ptr::{NonNull, drop_in_place, null_mut, slice_from_raw_parts_mut};
+
+/// Byte pattern written over freed objects, with the `poison-free` feature in debug builds
+#[cfg_attr(not(feature = "mem"), allow(dead_code))]
+pub const POISON_FREED: u8 = 0xDD;
+
+/// Byte pattern filling fresh allocations of the uninit helpers, with the `poison-free` feature in
+/// debug builds
+#[cfg_attr(not(feature = "mem"), allow(dead_code))]
+pub const POISON_UNINIT: u8 = 0xAA;
+
+/// Check whether `bytes` is non-empty and entirely filled with `POISON_FREED`
+#[cfg_attr(not(feature = "mem"), allow(dead_code))]
+pub fn is_poison_pattern(bytes: &[u8]) -> bool {
+    !bytes.is_empty() && bytes.iter().all(|b| *b == POISON_FREED)
+}
 
 /// Move the given object to heap, returning a pointer to it.
 ///
@@ -9,11 +28,46 @@ use std::ptr::NonNull;
 /// let ptr: *mut T = Box::into_raw(boxed);
 /// let ptr: NonNull<T> = unsafe { NonNull::new_unchecked(ptr) };
 /// ```
+#[cfg_attr(not(feature = "mem"), allow(dead_code))]
 #[inline] pub fn move_to_heap<T>(data: T) -> NonNull<T> {
     let boxed: Box<T> = Box::new(data);
     leak_as_nonnull(boxed)
 }
 
+/// Collect the given iterator to heap, returning a pointer to the slice.
+///
+/// Ownership can be taken back with `reclaim_as_boxed`, or released with
+/// `drop_in_place_and_dealloc`. An empty iterator gives a dangling pointer without allocating.
+///
+/// This function is equivalent to the following code:
+/// ```rust,ignore
+/// let boxed: Box<[T]> = iter.into_iter().collect::<Vec<T>>().into_boxed_slice();
+/// let ptr: NonNull<[T]> = leak_as_nonnull(boxed);
+/// ```
+#[cfg_attr(not(feature = "mem"), allow(dead_code))]
+#[inline] pub fn move_to_heap_slice<T>(iter: impl IntoIterator<Item = T>) -> NonNull<[T]> {
+    let vec: Vec<T> = iter.into_iter().collect();
+    leak_as_nonnull(vec.into_boxed_slice())
+}
+
+/// Copy the given slice to heap with a single allocation, returning a pointer to the copy.
+///
+/// Ownership can be taken back with `reclaim_as_boxed`, or released with
+/// `drop_in_place_and_dealloc`. An empty slice gives a dangling pointer without allocating.
+#[cfg_attr(not(feature = "mem"), allow(dead_code))]
+pub fn copy_to_heap_slice<T: Copy>(src: &[T]) -> NonNull<[T]> {
+    // cannot overflow, since `src` already occupies that much memory
+    let layout: Layout = Layout::for_value(src);
+    let ptr: NonNull<T> = match unsafe { try_alloc_with(layout, alloc) } {
+        Some(ptr) => ptr.cast(),
+        None => handle_alloc_error(layout)
+    };
+    unsafe {
+        core::ptr::copy_nonoverlapping(src.as_ptr(), ptr.as_ptr(), src.len());
+        NonNull::new_unchecked(slice_from_raw_parts_mut(ptr.as_ptr(), src.len()))
+    }
+}
+
 /// "Leak" the content in the `Box` but returns `NonNull` instead.
 ///
 /// This function is equivalent to the following code:
@@ -22,6 +76,7 @@ use std::ptr::NonNull;
 /// let ptr: *mut T = Box::into_raw(boxed);
 /// let ptr: NonNull<T> = unsafe { NonNull::new_unchecked(ptr) };
 /// ```
+#[cfg_attr(not(feature = "mem"), allow(dead_code))]
 #[inline] pub fn leak_as_nonnull<T>(boxed: Box<T>) -> NonNull<T>
     where T: ?Sized
 {
@@ -39,16 +94,209 @@ use std::ptr::NonNull;
 ///
 /// # Safety
 /// The reclaimed pointer must be previously leaked from a `Box`.
+#[cfg_attr(not(feature = "mem"), allow(dead_code))]
 #[inline] pub unsafe fn reclaim_as_boxed<T>(raw_ptr: NonNull<T>) -> Box<T>
     where T: ?Sized
 {
     Box::from_raw(raw_ptr.as_ptr())
 }
 
+/// Drop the object pointed to by `ptr`, and poison its bytes if enabled, without deallocating
+#[cfg_attr(not(feature = "mem"), allow(dead_code))]
+unsafe fn drop_and_poison<T>(ptr: NonNull<T>) -> Layout
+    where T: ?Sized
+{
+    let layout: Layout = Layout::for_value(ptr.as_ref());
+    drop_in_place(ptr.as_ptr());
+    #[cfg(all(feature = "poison-free", debug_assertions))]
+    core::ptr::write_bytes(ptr.as_ptr() as *mut u8, POISON_FREED, layout.size());
+    layout
+}
+
+/// Drop and deallocate an object previously leaked from a `Box`.
+///
+/// This function is equivalent to `drop(reclaim_as_boxed(raw_ptr))`, except that with the
+/// `poison-free` feature in debug builds, the bytes of the object are overwritten with
+/// `POISON_FREED` after running its destructor.
+///
+/// # Safety
+/// The pointer must be previously leaked from a `Box`, and must not be used after this call.
+#[cfg_attr(not(feature = "mem"), allow(dead_code))]
+#[inline] pub unsafe fn drop_in_place_and_dealloc<T>(raw_ptr: NonNull<T>)
+    where T: ?Sized
+{
+    let layout: Layout = drop_and_poison(raw_ptr);
+    if layout.size() != 0 {
+        dealloc(raw_ptr.as_ptr() as *mut u8, layout);
+    }
+}
+
+/// Allocate memory for `layout` with `allocate`, or a dangling pointer if `layout` is zero-sized
+#[cfg_attr(not(feature = "mem"), allow(dead_code))]
+unsafe fn try_alloc_with(
+    layout: Layout,
+    allocate: unsafe fn(Layout) -> *mut u8
+) -> Option<NonNull<u8>> {
+    if layout.size() == 0 {
+        // a well-aligned dangling pointer, offset from null so that no integer is cast to it
+        return Some(NonNull::new_unchecked(null_mut::<u8>().wrapping_add(layout.align())));
+    }
+    NonNull::new(allocate(layout))
+}
+
+/// Allocate memory for `layout` like `alloc`, filling it with `POISON_UNINIT` if enabled
+#[cfg_attr(not(feature = "mem"), allow(dead_code))]
+unsafe fn alloc_poisoned(layout: Layout) -> *mut u8 {
+    let ptr: *mut u8 = alloc(layout);
+    #[cfg(all(feature = "poison-free", debug_assertions))]
+    if !ptr.is_null() {
+        core::ptr::write_bytes(ptr, POISON_UNINIT, layout.size());
+    }
+    ptr
+}
+
+/// Allocate memory for a `T` with `allocate`, calling `handle_alloc_error` on failure
+#[cfg_attr(not(feature = "mem"), allow(dead_code))]
+unsafe fn alloc_boxed_with<T>(allocate: unsafe fn(Layout) -> *mut u8) -> Box<MaybeUninit<T>> {
+    let layout: Layout = Layout::new::<T>();
+    match try_alloc_with(layout, allocate) {
+        Some(ptr) => Box::from_raw(ptr.cast::<MaybeUninit<T>>().as_ptr()),
+        None => handle_alloc_error(layout)
+    }
+}
+
+/// Allocate an uninitialized `T` on heap, without building it on stack first
+///
+/// This function is equivalent to the following code on Rust 1.82 or newer:
+/// ```rust,ignore
+/// let boxed: Box<MaybeUninit<T>> = Box::new_uninit();
+/// ```
+#[cfg_attr(not(feature = "mem"), allow(dead_code))]
+pub fn alloc_uninit_boxed<T>() -> Box<MaybeUninit<T>> {
+    unsafe { alloc_boxed_with(alloc_poisoned) }
+}
+
+/// Allocate a `T` on heap with all bytes set to zero, which is not necessarily a valid `T`
+///
+/// This function is equivalent to `Box::new_zeroed`.
+#[cfg_attr(not(feature = "mem"), allow(dead_code))]
+pub fn alloc_zeroed_boxed<T>() -> Box<MaybeUninit<T>> {
+    unsafe { alloc_boxed_with(alloc_zeroed) }
+}
+
+/// Allocate an uninitialized `T` on heap, returning `None` instead of aborting on failure
+#[cfg_attr(not(feature = "mem"), allow(dead_code))]
+pub fn try_alloc_uninit_boxed<T>() -> Option<Box<MaybeUninit<T>>> {
+    let ptr: NonNull<u8> = unsafe { try_alloc_with(Layout::new::<T>(), alloc_poisoned)? };
+    Some(unsafe { Box::from_raw(ptr.cast::<MaybeUninit<T>>().as_ptr()) })
+}
+
+/// Allocate a boxed slice of `len` uninitialized elements, returning `None` if the allocation
+/// fails or the size of the slice overflows
+#[cfg_attr(not(feature = "mem"), allow(dead_code))]
+pub fn try_alloc_uninit_boxed_slice<T>(len: usize) -> Option<Box<[MaybeUninit<T>]>> {
+    let layout: Layout = Layout::array::<T>(len).ok()?;
+    let ptr: NonNull<u8> = unsafe { try_alloc_with(layout, alloc_poisoned)? };
+    let slice: *mut [MaybeUninit<T>] = slice_from_raw_parts_mut(ptr.cast().as_ptr(), len);
+    Some(unsafe { Box::from_raw(slice) })
+}
+
+/// Allocate memory for `len` elements with `allocate`, panicking if the size overflows and
+/// calling `handle_alloc_error` on failure
+#[cfg_attr(not(feature = "mem"), allow(dead_code))]
+#[track_caller]
+unsafe fn alloc_boxed_slice_with<T>(
+    len: usize,
+    allocate: unsafe fn(Layout) -> *mut u8
+) -> Box<[MaybeUninit<T>]> {
+    let layout: Layout = match Layout::array::<T>(len) {
+        Ok(layout) => layout,
+        Err(_) => panic!(
+            "capacity overflow: cannot allocate {} elements of `{}`",
+            len,
+            core::any::type_name::<T>()
+        )
+    };
+    match try_alloc_with(layout, allocate) {
+        Some(ptr) => Box::from_raw(slice_from_raw_parts_mut(ptr.cast().as_ptr(), len)),
+        None => handle_alloc_error(layout)
+    }
+}
+
+/// Allocate a boxed slice of `len` uninitialized elements, without zeroing the memory.
+///
+/// Panics if the size of the slice overflows. Zero-length slices and slices of zero-sized types
+/// do not allocate.
+///
+/// This function is equivalent to the following code on Rust 1.82 or newer:
+/// ```rust,ignore
+/// let boxed: Box<[MaybeUninit<T>]> = Box::new_uninit_slice(len);
+/// ```
+#[cfg_attr(not(feature = "mem"), allow(dead_code))]
+#[track_caller]
+pub fn alloc_uninit_boxed_slice<T>(len: usize) -> Box<[MaybeUninit<T>]> {
+    unsafe { alloc_boxed_slice_with(len, alloc_poisoned) }
+}
+
+/// Allocate a boxed slice of `len` elements with all bytes set to zero, which are not necessarily
+/// valid `T`s
+///
+/// Panics if the size of the slice overflows. This function is equivalent to
+/// `Box::new_zeroed_slice`.
+#[cfg_attr(not(feature = "mem"), allow(dead_code))]
+#[track_caller]
+pub fn alloc_zeroed_boxed_slice<T>(len: usize) -> Box<[MaybeUninit<T>]> {
+    unsafe { alloc_boxed_slice_with(len, alloc_zeroed) }
+}
+
+/// Like `alloc_uninit_boxed_slice`, but "leaks" the slice, returning `NonNull` instead.
+///
+/// Ownership can be taken back with `reclaim_as_boxed`, followed by `assume_init_boxed_slice`
+/// once all elements are initialized.
+#[cfg_attr(not(feature = "mem"), allow(dead_code))]
+#[track_caller]
+pub fn alloc_uninit_slice_ptr<T>(len: usize) -> NonNull<[MaybeUninit<T>]> {
+    leak_as_nonnull(alloc_uninit_boxed_slice(len))
+}
+
+/// Convert a boxed slice of `MaybeUninit<T>` to a boxed slice of `T`.
+///
+/// This function is equivalent to `<Box<[MaybeUninit<T>]>>::assume_init`.
+///
+/// # Safety
+/// All elements must be initialized.
+#[cfg_attr(not(feature = "mem"), allow(dead_code))]
+#[inline] pub unsafe fn assume_init_boxed_slice<T>(boxed: Box<[MaybeUninit<T>]>) -> Box<[T]> {
+    let ptr: *mut [MaybeUninit<T>] = Box::into_raw(boxed);
+    Box::from_raw(ptr as *mut [T])
+}
+
 #[cfg(test)]
 mod test {
+    use std::cell::Cell;
+    use std::mem::{MaybeUninit, size_of};
     use std::ptr::NonNull;
-    use crate::mem_intern::{move_to_heap, reclaim_as_boxed, leak_as_nonnull };
+    use std::rc::Rc;
+    use std::slice;
+
+    use crate::mem_intern::{
+        alloc_uninit_boxed,
+        alloc_uninit_boxed_slice,
+        alloc_uninit_slice_ptr,
+        alloc_zeroed_boxed,
+        alloc_zeroed_boxed_slice,
+        assume_init_boxed_slice,
+        copy_to_heap_slice,
+        try_alloc_uninit_boxed,
+        try_alloc_uninit_boxed_slice,
+        drop_and_poison,
+        drop_in_place_and_dealloc,
+        is_poison_pattern,
+        leak_as_nonnull,
+        move_to_heap,
+        move_to_heap_slice,
+        reclaim_as_boxed
+    };
 
     #[test]
     fn test_mem_intern() {
@@ -62,4 +310,216 @@ mod test {
         let boxed: Box<String> = unsafe { reclaim_as_boxed(ptr2) };
         drop(boxed);
     }
+
+    #[test]
+    fn test_leak_reclaim_cycles() {
+        let iterations: usize = if cfg!(miri) { 8 } else { 1024 };
+        let mut ptr: NonNull<Vec<String>> = move_to_heap(Vec::new());
+        for i in 0..iterations {
+            let mut boxed: Box<Vec<String>> = unsafe { reclaim_as_boxed(ptr) };
+            boxed.push(i.to_string());
+            ptr = leak_as_nonnull(boxed);
+            // writing through the leaked pointer must not disturb the next reclaim
+            unsafe { (*ptr.as_ptr()).push(String::new()) };
+        }
+        let boxed: Box<Vec<String>> = unsafe { reclaim_as_boxed(ptr) };
+        assert_eq!(boxed.len(), iterations * 2);
+    }
+
+    #[test]
+    fn test_reclaim_unsized() {
+        let ptr: NonNull<[String]> = leak_as_nonnull(vec!["114".to_string(), "514".into()].into_boxed_slice());
+        unsafe { (*ptr.as_ptr())[1].push_str("1919") };
+        let boxed: Box<[String]> = unsafe { reclaim_as_boxed(ptr) };
+        assert_eq!(&boxed[1], "5141919");
+    }
+
+    #[test]
+    fn test_heap_slice() {
+        let ptr: NonNull<[String]> = move_to_heap_slice((0..3).map(|i: i32| i.to_string()));
+        assert_eq!(unsafe { ptr.as_ref() }, &["0", "1", "2"]);
+        let boxed: Box<[String]> = unsafe { reclaim_as_boxed(ptr) };
+        assert_eq!(boxed.len(), 3);
+
+        let src: [u16; 5] = [114, 514, 1919, 810, 893];
+        let ptr: NonNull<[u16]> = copy_to_heap_slice(&src);
+        assert_ne!(ptr.as_ptr() as *mut u16, src.as_ptr() as *mut u16);
+        unsafe { (*ptr.as_ptr())[0] = 0 };
+        assert_eq!(unsafe { ptr.as_ref() }, &[0, 514, 1919, 810, 893]);
+        assert_eq!(src[0], 114);
+        let boxed: Box<[u16]> = unsafe { reclaim_as_boxed(ptr) };
+        assert_eq!(boxed.len(), 5);
+
+        let ptr: NonNull<[()]> = copy_to_heap_slice(&[(); 4]);
+        assert_eq!(unsafe { ptr.as_ref() }.len(), 4);
+        unsafe { drop_in_place_and_dealloc(ptr) };
+    }
+
+    #[test]
+    fn test_heap_slice_empty() {
+        let ptr: NonNull<[u64]> = move_to_heap_slice(std::iter::empty());
+        assert_eq!(unsafe { ptr.as_ref() }.len(), 0);
+        assert_eq!(ptr.as_ptr() as *mut u64 as usize, std::mem::align_of::<u64>());
+        drop(unsafe { reclaim_as_boxed(ptr) });
+
+        let ptr: NonNull<[u64]> = copy_to_heap_slice(&[]);
+        assert_eq!(unsafe { ptr.as_ref() }, &[] as &[u64]);
+        assert_eq!(ptr.as_ptr() as *mut u64 as usize, std::mem::align_of::<u64>());
+        unsafe { drop_in_place_and_dealloc(ptr) };
+    }
+
+    struct Payload {
+        data: [u64; 4],
+        drops: Rc<Cell<usize>>
+    }
+
+    impl Drop for Payload {
+        fn drop(&mut self) {
+            self.drops.set(self.drops.get() + 1);
+        }
+    }
+
+    #[test]
+    fn test_drop_in_place_and_dealloc() {
+        let drops: Rc<Cell<usize>> = Rc::new(Cell::new(0));
+        let ptr: NonNull<Payload> = move_to_heap(Payload { data: [114514; 4], drops: drops.clone() });
+        assert_eq!(unsafe { ptr.as_ref() }.data, [114514; 4]);
+        unsafe { drop_in_place_and_dealloc(ptr); }
+        assert_eq!(drops.get(), 1);
+
+        unsafe {
+            drop_in_place_and_dealloc(move_to_heap(()));
+            drop_in_place_and_dealloc(leak_as_nonnull(Box::<[String]>::from(["893".to_string()])));
+        }
+    }
+
+    #[test]
+    fn test_poison() {
+        let drops: Rc<Cell<usize>> = Rc::new(Cell::new(0));
+        // keeps the memory allocated after the destructor ran, so reading it back is defined
+        let mut storage: Box<MaybeUninit<Payload>> = Box::new(MaybeUninit::new(Payload {
+            data: [1919810; 4],
+            drops: drops.clone()
+        }));
+        let ptr: NonNull<Payload> = NonNull::from(&mut *storage).cast();
+        unsafe { drop_and_poison(ptr); }
+        assert_eq!(drops.get(), 1);
+
+        let bytes: &[u8] = unsafe {
+            slice::from_raw_parts(ptr.as_ptr() as *const u8, size_of::<Payload>())
+        };
+        assert_eq!(is_poison_pattern(bytes), cfg!(all(feature = "poison-free", debug_assertions)));
+        assert!(!is_poison_pattern(&[]));
+        assert!(!is_poison_pattern(&[0xDD, 0xAA]));
+    }
+
+    #[cfg(all(feature = "poison-free", debug_assertions))]
+    #[test]
+    fn test_poison_uninit() {
+        use crate::mem_intern::POISON_UNINIT;
+
+        // without the feature the memory is uninitialized, and reading it back is undefined
+        fn filled<T>(slice: &[MaybeUninit<T>]) -> bool {
+            let bytes: &[u8] = unsafe {
+                slice::from_raw_parts(slice.as_ptr() as *const u8, size_of::<T>() * slice.len())
+            };
+            bytes.iter().all(|b: &u8| *b == POISON_UNINIT)
+        }
+
+        let boxed: Box<MaybeUninit<[u64; 4]>> = alloc_uninit_boxed();
+        assert!(filled(slice::from_ref(&*boxed)));
+        let boxed: Box<MaybeUninit<String>> = try_alloc_uninit_boxed().unwrap();
+        assert!(filled(slice::from_ref(&*boxed)));
+        assert!(filled(&alloc_uninit_boxed_slice::<u32>(893)));
+        assert!(filled(&try_alloc_uninit_boxed_slice::<u16>(114).unwrap()));
+        let ptr: NonNull<[MaybeUninit<u8>]> = alloc_uninit_slice_ptr(514);
+        let boxed: Box<[MaybeUninit<u8>]> = unsafe { reclaim_as_boxed(ptr) };
+        assert!(filled(&boxed));
+
+        // zeroed allocations are left alone
+        let boxed: Box<MaybeUninit<[u8; 16]>> = alloc_zeroed_boxed();
+        assert_eq!(unsafe { boxed.assume_init_read() }, [0; 16]);
+    }
+
+    #[test]
+    fn test_alloc_uninit_boxed() {
+        let mut boxed: Box<MaybeUninit<[u64; 512]>> = alloc_uninit_boxed();
+        let array: &mut [u64; 512] = boxed.write([114514; 512]);
+        array[511] = 1919810;
+        let array: [u64; 512] = unsafe { boxed.assume_init_read() };
+        assert_eq!((array[0], array[511]), (114514, 1919810));
+
+        let boxed: Box<MaybeUninit<[u32; 64]>> = alloc_zeroed_boxed();
+        assert_eq!(unsafe { boxed.assume_init_read() }, [0; 64]);
+
+        let _: Box<MaybeUninit<()>> = alloc_uninit_boxed();
+        let _: Box<MaybeUninit<()>> = alloc_zeroed_boxed();
+    }
+
+    #[test]
+    fn test_try_alloc_uninit_boxed() {
+        let mut boxed: Box<MaybeUninit<String>> = try_alloc_uninit_boxed().unwrap();
+        boxed.write("114514".into());
+        assert_eq!(unsafe { boxed.assume_init_read() }, "114514");
+        assert!(try_alloc_uninit_boxed::<()>().is_some());
+
+        let mut boxed: Box<[MaybeUninit<u64>]> = try_alloc_uninit_boxed_slice(3).unwrap();
+        assert_eq!(boxed.len(), 3);
+        boxed[2].write(1919810);
+        assert_eq!(unsafe { boxed[2].assume_init() }, 1919810);
+        assert!(try_alloc_uninit_boxed_slice::<String>(0).unwrap().is_empty());
+        assert_eq!(try_alloc_uninit_boxed_slice::<()>(usize::MAX).unwrap().len(), usize::MAX);
+
+        // the size in bytes overflows `isize::MAX`
+        assert!(try_alloc_uninit_boxed_slice::<u8>(usize::MAX).is_none());
+        assert!(try_alloc_uninit_boxed_slice::<u64>(usize::MAX / 8).is_none());
+        assert!(try_alloc_uninit_boxed_slice::<[u8; 3]>(usize::MAX / 2).is_none());
+    }
+
+    #[test]
+    fn test_alloc_uninit_boxed_slice() {
+        let mut boxed: Box<[MaybeUninit<String>]> = alloc_uninit_boxed_slice(4);
+        assert_eq!(boxed.len(), 4);
+        for (i, slot) in boxed.iter_mut().enumerate() {
+            slot.write(i.to_string());
+        }
+        let strings: Vec<String> = boxed.iter().map(|s| unsafe { s.assume_init_read() }).collect();
+        assert_eq!(strings, ["0", "1", "2", "3"]);
+
+        assert!(alloc_uninit_boxed_slice::<u64>(0).is_empty());
+        assert_eq!(alloc_uninit_boxed_slice::<()>(114514).len(), 114514);
+    }
+
+    #[test]
+    fn test_assume_init_boxed_slice() {
+        let mut boxed: Box<[MaybeUninit<String>]> = alloc_uninit_boxed_slice(3);
+        for (i, slot) in boxed.iter_mut().enumerate() {
+            slot.write(format!("{}", 114 + i));
+        }
+        let boxed: Box<[String]> = unsafe { assume_init_boxed_slice(boxed) };
+        assert_eq!(&*boxed, &["114", "115", "116"]);
+
+        let zeroed: Box<[MaybeUninit<u32>]> = alloc_zeroed_boxed_slice(5);
+        let zeroed: Box<[u32]> = unsafe { assume_init_boxed_slice(zeroed) };
+        assert_eq!(&*zeroed, &[0; 5]);
+
+        let empty: Box<[u64]> = unsafe { assume_init_boxed_slice(alloc_zeroed_boxed_slice(0)) };
+        assert!(empty.is_empty());
+        let units: Box<[()]> = unsafe { assume_init_boxed_slice(alloc_uninit_boxed_slice(893)) };
+        assert_eq!(units.len(), 893);
+
+        let ptr: NonNull<[MaybeUninit<u16>]> = alloc_uninit_slice_ptr(4);
+        for i in 0..4 {
+            unsafe { (*ptr.as_ptr())[i].write(i as u16 * 2) };
+        }
+        let boxed: Box<[u16]> = unsafe { assume_init_boxed_slice(reclaim_as_boxed(ptr)) };
+        assert_eq!(&*boxed, &[0, 2, 4, 6]);
+    }
+
+    #[test]
+    #[should_panic(expected = "capacity overflow: cannot allocate 18446744073709551615 elements")]
+    #[cfg(target_pointer_width = "64")]
+    fn test_alloc_boxed_slice_overflow() {
+        alloc_uninit_boxed_slice::<u32>(usize::MAX);
+    }
 }
@@ -0,0 +1,156 @@
+//! Keeping panics from unwinding across `extern "C"` boundaries
+//!
+//! ```
+//! use xjbutil::panic_guard::ffi_boundary;
+//!
+//! extern "C" fn callback(divisor: i32) -> i32 {
+//!     ffi_boundary(|| 114514 / divisor)
+//! }
+//!
+//! assert_eq!(callback(2), 57257);
+//! assert_eq!(callback(0), 0);
+//! ```
+
+use std::any::Any;
+use std::fmt::{Debug, Display, Formatter};
+use std::panic::{UnwindSafe, catch_unwind};
+
+use crate::defer::AbortGuard;
+use crate::diag_intern::diagnostic;
+use crate::either::Either;
+
+/// Extract the message from a panic payload, if it is a `&str` or a `String`
+pub fn payload_to_string(payload: &dyn Any) -> Option<&str> {
+    if let Some(message) = payload.downcast_ref::<&'static str>() {
+        Some(message)
+    } else {
+        payload.downcast_ref::<String>().map(String::as_str)
+    }
+}
+
+/// A panic caught at an FFI boundary
+pub struct PanicInfo {
+    payload: Box<dyn Any + Send>
+}
+
+impl PanicInfo {
+    /// The panic message, or a placeholder if the payload is neither `&str` nor `String`
+    pub fn message(&self) -> &str {
+        payload_to_string(&*self.payload).unwrap_or("<non-string panic payload>")
+    }
+
+    pub fn into_payload(self) -> Box<dyn Any + Send> {
+        self.payload
+    }
+}
+
+impl Debug for PanicInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PanicInfo").field("message", &self.message()).finish()
+    }
+}
+
+impl Display for PanicInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "panicked: {}", self.message())
+    }
+}
+
+/// Run `f`, catching any panic instead of letting it unwind further
+pub fn try_ffi_boundary<R>(f: impl FnOnce() -> R + UnwindSafe) -> Either<R, PanicInfo> {
+    match catch_unwind(f) {
+        Ok(result) => Either::Left(result),
+        Err(payload) => Either::Right(PanicInfo { payload })
+    }
+}
+
+/// Run `f`, returning `R::default()` if it panics
+///
+/// The panic message is reported through the diagnostic handler.
+pub fn ffi_boundary<R: Default>(f: impl FnOnce() -> R + UnwindSafe) -> R {
+    match try_ffi_boundary(f) {
+        Either::Left(result) => result,
+        Either::Right(info) => {
+            diagnostic(&format!("panic caught at FFI boundary: {}", info.message()));
+            R::default()
+        }
+    }
+}
+
+/// Run `f`, aborting the process if it panics
+#[track_caller]
+pub fn abort_on_unwind<R>(f: impl FnOnce() -> R) -> R {
+    let mut guard: AbortGuard = AbortGuard::new();
+    let result: R = f();
+    guard.defuse();
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use std::any::Any;
+
+    use crate::either::Either;
+    use crate::panic_guard::{
+        PanicInfo,
+        abort_on_unwind,
+        ffi_boundary,
+        payload_to_string,
+        try_ffi_boundary
+    };
+
+    #[test]
+    fn test_no_panic() {
+        assert_eq!(ffi_boundary(|| 114514), 114514);
+        assert_eq!(abort_on_unwind(|| "1919810"), "1919810");
+        match try_ffi_boundary(|| vec![893]) {
+            Either::Left(v) => assert_eq!(v, vec![893]),
+            Either::Right(_) => unreachable!()
+        }
+    }
+
+    #[test]
+    fn test_panic_default() {
+        let value: i32 = ffi_boundary(|| -> i32 { panic!("114514") });
+        assert_eq!(value, 0);
+        let value: Option<String> = ffi_boundary(|| -> Option<String> { panic!("1919810") });
+        assert_eq!(value, None);
+    }
+
+    #[test]
+    fn test_panic_info() {
+        let result: Either<(), PanicInfo> = try_ffi_boundary(|| panic!("static message"));
+        match result {
+            Either::Left(_) => unreachable!(),
+            Either::Right(info) => {
+                assert_eq!(info.message(), "static message");
+                assert_eq!(info.to_string(), "panicked: static message");
+            }
+        }
+
+        let result: Either<(), PanicInfo> = try_ffi_boundary(|| panic!("formatted {}", 893));
+        match result {
+            Either::Left(_) => unreachable!(),
+            Either::Right(info) => assert_eq!(info.message(), "formatted 893")
+        }
+
+        let result: Either<(), PanicInfo> = try_ffi_boundary(|| std::panic::panic_any(114_i32));
+        match result {
+            Either::Left(_) => unreachable!(),
+            Either::Right(info) => {
+                assert_eq!(info.message(), "<non-string panic payload>");
+                assert_eq!(info.into_payload().downcast_ref::<i32>(), Some(&114));
+            }
+        }
+    }
+
+    #[test]
+    fn test_payload_to_string() {
+        let str_payload: Box<dyn Any + Send> = Box::new("514");
+        let string_payload: Box<dyn Any + Send> = Box::new(String::from("1919"));
+        let other_payload: Box<dyn Any + Send> = Box::new(810_u64);
+        assert_eq!(payload_to_string(&*str_payload), Some("514"));
+        assert_eq!(payload_to_string(&*string_payload), Some("1919"));
+        assert_eq!(payload_to_string(&*other_payload), None);
+    }
+}
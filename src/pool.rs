@@ -0,0 +1,290 @@
+//! Object pools recycling heap allocated objects
+//!
+//! Objects are created with a user supplied `init` closure when the pool is empty. Dropping a
+//! `Pooled` handle runs the `reset` closure on the object and puts its heap block back to the
+//! pool, instead of freeing it. Handles borrow the pool, so a pool can never be dropped with
+//! outstanding handles; dropping the pool frees all objects in it.
+
+use std::cell::{RefCell, RefMut};
+use std::mem::ManuallyDrop;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Mutex, MutexGuard};
+
+use crate::korobka::Korobka;
+
+mod sealed {
+    use crate::korobka::Korobka;
+
+    /// Pools which `Pooled` handles return their objects to
+    pub trait Recycle<T> {
+        fn recycle(&self, value: Korobka<T>);
+    }
+}
+
+use sealed::Recycle;
+
+/// Handle to an object borrowed from pool `P`
+///
+/// Handles of a `Pool` may be sent to and shared between threads like `T` itself, while handles
+/// of a `LocalPool` stay on the pool's thread.
+pub struct Pooled<'a, T, P: Recycle<T> = Pool<T>> {
+    value: ManuallyDrop<Korobka<T>>,
+    pool: &'a P
+}
+
+impl<'a, T, P: Recycle<T>> Pooled<'a, T, P> {
+    /// Take the object out of the pool permanently
+    pub fn detach(this: Self) -> Korobka<T> {
+        let mut this: ManuallyDrop<Self> = ManuallyDrop::new(this);
+        unsafe { ManuallyDrop::take(&mut this.value) }
+    }
+}
+
+impl<'a, T, P: Recycle<T>> Deref for Pooled<'a, T, P> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<'a, T, P: Recycle<T>> DerefMut for Pooled<'a, T, P> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<'a, T, P: Recycle<T>> Drop for Pooled<'a, T, P> {
+    fn drop(&mut self) {
+        let value: Korobka<T> = unsafe { ManuallyDrop::take(&mut self.value) };
+        self.pool.recycle(value);
+    }
+}
+
+/// Thread safe object pool, with a `Mutex` protected free list
+pub struct Pool<T> {
+    free: Mutex<Vec<Korobka<T>>>,
+    init: Box<dyn Fn() -> T + Send + Sync>,
+    reset: Box<dyn Fn(&mut T) + Send + Sync>
+}
+
+impl<T> Pool<T> {
+    pub fn new(
+        init: impl Fn() -> T + Send + Sync + 'static,
+        reset: impl Fn(&mut T) + Send + Sync + 'static
+    ) -> Self {
+        Self::with_capacity(0, init, reset)
+    }
+
+    /// Create a pool with `capacity` objects created in advance
+    pub fn with_capacity(
+        capacity: usize,
+        init: impl Fn() -> T + Send + Sync + 'static,
+        reset: impl Fn(&mut T) + Send + Sync + 'static
+    ) -> Self {
+        let free: Vec<Korobka<T>> = (0..capacity).map(|_| Korobka::new(init())).collect();
+        Self { free: Mutex::new(free), init: Box::new(init), reset: Box::new(reset) }
+    }
+
+    /// Get an object from the pool, creating a new one if the pool is empty
+    pub fn get(&self) -> Pooled<'_, T> {
+        let recycled: Option<Korobka<T>> = self.free.lock().unwrap().pop();
+        let value: Korobka<T> = recycled.unwrap_or_else(|| Korobka::new((self.init)()));
+        Pooled { value: ManuallyDrop::new(value), pool: self }
+    }
+
+    /// Count of objects in the pool, not including ones borrowed out
+    pub fn free_count(&self) -> usize {
+        self.free.lock().unwrap().len()
+    }
+
+    /// Free objects in the pool until at most `max_free` objects are left
+    pub fn shrink_to(&self, max_free: usize) {
+        let mut free: MutexGuard<Vec<Korobka<T>>> = self.free.lock().unwrap();
+        free.truncate(max_free);
+        free.shrink_to_fit();
+    }
+}
+
+impl<T> Recycle<T> for Pool<T> {
+    fn recycle(&self, mut value: Korobka<T>) {
+        (self.reset)(&mut value);
+        self.free.lock().unwrap().push(value);
+    }
+}
+
+/// Object pool for use within one thread, without any locking
+pub struct LocalPool<T> {
+    free: RefCell<Vec<Korobka<T>>>,
+    init: Box<dyn Fn() -> T>,
+    reset: Box<dyn Fn(&mut T)>
+}
+
+impl<T> LocalPool<T> {
+    pub fn new(init: impl Fn() -> T + 'static, reset: impl Fn(&mut T) + 'static) -> Self {
+        Self::with_capacity(0, init, reset)
+    }
+
+    /// Create a pool with `capacity` objects created in advance
+    pub fn with_capacity(
+        capacity: usize,
+        init: impl Fn() -> T + 'static,
+        reset: impl Fn(&mut T) + 'static
+    ) -> Self {
+        let free: Vec<Korobka<T>> = (0..capacity).map(|_| Korobka::new(init())).collect();
+        Self { free: RefCell::new(free), init: Box::new(init), reset: Box::new(reset) }
+    }
+
+    /// Get an object from the pool, creating a new one if the pool is empty
+    pub fn get(&self) -> Pooled<'_, T, Self> {
+        let recycled: Option<Korobka<T>> = self.free.borrow_mut().pop();
+        let value: Korobka<T> = recycled.unwrap_or_else(|| Korobka::new((self.init)()));
+        Pooled { value: ManuallyDrop::new(value), pool: self }
+    }
+
+    /// Count of objects in the pool, not including ones borrowed out
+    pub fn free_count(&self) -> usize {
+        self.free.borrow().len()
+    }
+
+    /// Free objects in the pool until at most `max_free` objects are left
+    pub fn shrink_to(&self, max_free: usize) {
+        let mut free: RefMut<Vec<Korobka<T>>> = self.free.borrow_mut();
+        free.truncate(max_free);
+        free.shrink_to_fit();
+    }
+}
+
+impl<T> Recycle<T> for LocalPool<T> {
+    fn recycle(&self, mut value: Korobka<T>) {
+        (self.reset)(&mut value);
+        self.free.borrow_mut().push(value);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+
+    use crate::korobka::Korobka;
+    use crate::pool::{LocalPool, Pool, Pooled};
+
+    struct Counted {
+        data: Vec<u8>,
+        drops: Arc<AtomicUsize>
+    }
+
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            self.drops.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn test_reuse() {
+        let pool: LocalPool<Vec<u8>> = LocalPool::new(Vec::new, Vec::clear);
+        let ptr: *const Vec<u8> = {
+            let mut obj: Pooled<Vec<u8>, LocalPool<Vec<u8>>> = pool.get();
+            obj.extend_from_slice(b"114514");
+            &*obj as *const Vec<u8>
+        };
+
+        for _ in 0..3 {
+            let obj: Pooled<Vec<u8>, LocalPool<Vec<u8>>> = pool.get();
+            assert_eq!(&*obj as *const Vec<u8>, ptr);
+            assert!(obj.is_empty());
+            assert!(obj.capacity() >= 6);
+        }
+        assert_eq!(pool.free_count(), 1);
+    }
+
+    #[test]
+    fn test_reset_invocation() {
+        let inits: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(0));
+        let resets: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(0));
+        let pool: Pool<i32> = {
+            let inits: Arc<AtomicUsize> = inits.clone();
+            let resets: Arc<AtomicUsize> = resets.clone();
+            Pool::new(
+                move || { inits.fetch_add(1, Ordering::SeqCst); 0 },
+                move |x| { resets.fetch_add(1, Ordering::SeqCst); *x = 0 }
+            )
+        };
+
+        for i in 1..=4 {
+            let mut obj: Pooled<i32> = pool.get();
+            assert_eq!(*obj, 0);
+            *obj = i;
+        }
+        assert_eq!(inits.load(Ordering::SeqCst), 1);
+        assert_eq!(resets.load(Ordering::SeqCst), 4);
+
+        let detached: Korobka<i32> = Pooled::detach(pool.get());
+        assert_eq!(*detached, 0);
+        assert_eq!(resets.load(Ordering::SeqCst), 4);
+        assert_eq!(pool.free_count(), 0);
+    }
+
+    #[test]
+    fn test_growth() {
+        let drops: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(0));
+        {
+            let drops2: Arc<AtomicUsize> = drops.clone();
+            let pool: LocalPool<Counted> = LocalPool::with_capacity(
+                2,
+                move || Counted { data: Vec::new(), drops: drops2.clone() },
+                |obj| obj.data.clear()
+            );
+            assert_eq!(pool.free_count(), 2);
+
+            let objects: Vec<Pooled<Counted, LocalPool<Counted>>> =
+                (0..5).map(|_| pool.get()).collect();
+            assert_eq!(pool.free_count(), 0);
+            drop(objects);
+            assert_eq!(pool.free_count(), 5);
+            assert_eq!(drops.load(Ordering::SeqCst), 0);
+
+            pool.shrink_to(3);
+            assert_eq!(pool.free_count(), 3);
+            assert_eq!(drops.load(Ordering::SeqCst), 2);
+        }
+        // all remaining objects are freed with the pool
+        assert_eq!(drops.load(Ordering::SeqCst), 5);
+    }
+
+    #[test]
+    fn test_threads() {
+        const THREADS: usize = 4;
+        const ROUNDS: usize = if cfg!(miri) { 8 } else { 1000 };
+
+        let pool: Arc<Pool<Vec<usize>>> = Arc::new(Pool::new(Vec::new, Vec::clear));
+        let handles: Vec<thread::JoinHandle<()>> = (0..THREADS).map(|i| {
+            let pool: Arc<Pool<Vec<usize>>> = pool.clone();
+            thread::spawn(move || {
+                for j in 0..ROUNDS {
+                    let mut obj: Pooled<Vec<usize>> = pool.get();
+                    assert!(obj.is_empty());
+                    obj.push(i * ROUNDS + j);
+                }
+            })
+        }).collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert!((1..=THREADS).contains(&pool.free_count()));
+
+        // handles themselves may be shared with and sent to other threads
+        let mut obj: Pooled<Vec<usize>> = pool.get();
+        obj.push(893);
+        thread::scope(|scope| {
+            let obj_ref: &Pooled<Vec<usize>> = &obj;
+            scope.spawn(move || assert_eq!(obj_ref[..], [893]));
+        });
+        thread::scope(|scope| {
+            scope.spawn(move || drop(obj));
+        });
+        assert!(pool.free_count() >= 1);
+    }
+}
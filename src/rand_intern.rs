@@ -1,45 +1,141 @@
+//! A small and fast pseudo random number generator, without any dependency
+//!
+//! **This generator is not cryptographically secure.** Use it for tests, fuzzing harnesses,
+//! randomized backoff and the like, never for keys, tokens or anything security related.
+
 #![allow(unused)]
 
-use std::sync::atomic::{AtomicBool, AtomicU64};
-use std::sync::atomic::Ordering::SeqCst;
+use std::cell::RefCell;
+use std::ops::Range;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::{SystemTime, UNIX_EPOCH};
 
-static SEED: AtomicU64 = AtomicU64::new(0x5bd1e995);
-static INIT: AtomicBool = AtomicBool::new(false);
+/// A `wyrand` pseudo random number generator
+///
+/// Not cryptographically secure.
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64
+}
 
-pub fn random() -> u64 {
-    let _ = INIT.fetch_update(SeqCst, SeqCst, |init| {
-        if !init {
-            let _ = SEED.fetch_update(SeqCst, SeqCst, |seed| {
-                let secs: u64 = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
-                Some(seed.wrapping_mul(secs))
-            });
-        }
-        Some(true)
-    });
-
-    SEED.fetch_update(
-        SeqCst,
-        SeqCst,
-        |seed| {
-            let new_value: u64 = seed.wrapping_mul(19260817).wrapping_add(19660813);
-            if new_value == seed {
-                let secs: u64 = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
-                Some(seed.wrapping_mul(secs))
-            } else {
-                Some(new_value)
+impl Rng {
+    /// Create a generator with given `seed`, always producing the same sequence for the same seed
+    pub const fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// Create a generator seeded from current time, mixed with some addresses and a counter so
+    /// that generators created at the same time still differ
+    pub fn from_entropy() -> Self {
+        static COUNTER: AtomicU64 = AtomicU64::new(0x5bd1e995);
+
+        let nanos: u64 = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_nanos() as u64);
+        let local: u8 = 0;
+        let addr: u64 = &local as *const u8 as usize as u64;
+        let count: u64 = COUNTER.fetch_add(0x9e3779b97f4a7c15, Ordering::Relaxed);
+
+        let mut mixer: Rng = Rng::new(nanos ^ addr.rotate_left(32) ^ count);
+        Rng::new(mixer.next_u64())
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0xa0761d6478bd642f);
+        let t: u128 = (self.state as u128).wrapping_mul((self.state ^ 0xe7037ed1a0b428db) as u128);
+        ((t >> 64) ^ t) as u64
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    /// Generate a number in `range`, without modulo bias
+    ///
+    /// Panics if `range` is empty.
+    pub fn gen_range(&mut self, range: Range<u64>) -> u64 {
+        assert!(range.start < range.end, "cannot sample from empty range");
+        let span: u64 = range.end - range.start;
+        // Lemire's multiply-and-reject method
+        let threshold: u64 = span.wrapping_neg() % span;
+        loop {
+            let m: u128 = (self.next_u64() as u128) * (span as u128);
+            if (m as u64) >= threshold {
+                return range.start + (m >> 64) as u64;
             }
         }
-    ).unwrap()
+    }
+
+    pub fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes: [u8; 8] = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    /// Shuffle `slice` in place with Fisher-Yates algorithm
+    pub fn shuffle<T>(&mut self, slice: &mut [T]) {
+        for i in (1..slice.len()).rev() {
+            let j: usize = self.gen_range(0..(i as u64 + 1)) as usize;
+            slice.swap(i, j);
+        }
+    }
+
+    pub fn gen<T: Random>(&mut self) -> T {
+        T::random(self)
+    }
+}
+
+/// Types which can be randomly generated with `Rng`
+pub trait Random {
+    fn random(rng: &mut Rng) -> Self;
+}
+
+macro_rules! impl_random_int {
+    ($($ty:ty),*) => {
+        $(
+            impl Random for $ty {
+                fn random(rng: &mut Rng) -> Self {
+                    rng.next_u64() as $ty
+                }
+            }
+        )*
+    };
+}
+
+impl_random_int!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+
+impl Random for bool {
+    fn random(rng: &mut Rng) -> Self {
+        rng.next_u64() >> 63 == 1
+    }
+}
+
+/// Uniformly distributed in `[0, 1)`
+impl Random for f64 {
+    fn random(rng: &mut Rng) -> Self {
+        (rng.next_u64() >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+    }
+}
+
+thread_local! {
+    static THREAD_RNG: RefCell<Rng> = RefCell::new(Rng::from_entropy());
+}
+
+/// Generate a random value with the thread local generator
+///
+/// Not cryptographically secure.
+pub fn random<T: Random>() -> T {
+    THREAD_RNG.with(|rng| T::random(&mut rng.borrow_mut()))
 }
 
 pub fn random_string(count: usize) -> String {
     let mut ret: Vec<u8> = Vec::with_capacity(count);
     for _ in 0..count {
-        match random() % 3 {
-            0 => ret.push(b'a' + (random() % 26) as u8),
-            1 => ret.push(b'A' + (random() % 26) as u8),
-            2 => ret.push(b'0' + (random() % 10) as u8),
+        match random::<u64>() % 3 {
+            0 => ret.push(b'a' + (random::<u64>() % 26) as u8),
+            1 => ret.push(b'A' + (random::<u64>() % 26) as u8),
+            2 => ret.push(b'0' + (random::<u64>() % 10) as u8),
             _ => unreachable!(),
         }
     }
@@ -49,20 +145,94 @@ pub fn random_string(count: usize) -> String {
 pub fn random_string_lossy(count: usize) -> String {
     let mut ret: Vec<u8> = Vec::with_capacity(count);
     for _ in 0..count {
-        ret.push((random() % 95 + 31) as u8);
+        ret.push((random::<u64>() % 95 + 31) as u8);
     }
     String::from_utf8_lossy(&ret).to_string()
 }
 
 #[cfg(test)]
 mod test {
+    use crate::rand_intern::{Rng, random};
+
+    const SAMPLES: usize = if cfg!(miri) { 200 } else { 100000 };
+
     #[test]
     fn test_rand() {
         eprintln!(
             "3d100 = ({}, {}, {})",
-            super::random() % 100 + 1,
-            super::random() % 100 + 1,
-            super::random() % 100 + 1
+            random::<u64>() % 100 + 1,
+            random::<u64>() % 100 + 1,
+            random::<u64>() % 100 + 1
         );
+        let _: (bool, i8, f64) = (random(), random(), random());
+    }
+
+    #[test]
+    fn test_determinism() {
+        let mut rng1: Rng = Rng::new(114514);
+        let mut rng2: Rng = Rng::new(114514);
+        let seq1: Vec<u64> = (0..16).map(|_| rng1.next_u64()).collect();
+        let seq2: Vec<u64> = (0..16).map(|_| rng2.next_u64()).collect();
+        assert_eq!(seq1, seq2);
+
+        let mut rng3: Rng = Rng::new(1919810);
+        assert_ne!(seq1, (0..16).map(|_| rng3.next_u64()).collect::<Vec<_>>());
+
+        assert_ne!(Rng::from_entropy().next_u64(), Rng::from_entropy().next_u64());
+    }
+
+    #[test]
+    fn test_gen_range() {
+        let mut rng: Rng = Rng::new(893);
+        let mut seen: [usize; 7] = [0; 7];
+        for _ in 0..SAMPLES {
+            let x: u64 = rng.gen_range(10..17);
+            assert!((10..17).contains(&x));
+            seen[(x - 10) as usize] += 1;
+        }
+        assert!(seen.iter().all(|count| *count > 0));
+
+        for _ in 0..SAMPLES {
+            assert!(rng.gen_range(u64::MAX - 1..u64::MAX) == u64::MAX - 1);
+            assert!(rng.gen_range(0..u64::MAX) < u64::MAX);
+        }
+
+        let f: f64 = rng.gen();
+        assert!((0.0..1.0).contains(&f));
+    }
+
+    #[test]
+    fn test_shuffle() {
+        let mut rng: Rng = Rng::new(364364);
+        let mut v: Vec<u32> = (0..100).chain(0..50).collect();
+        let mut expected: Vec<u32> = v.clone();
+        rng.shuffle(&mut v);
+        assert_ne!(v, expected);
+        v.sort_unstable();
+        expected.sort_unstable();
+        assert_eq!(v, expected);
+
+        let mut empty: [u32; 0] = [];
+        rng.shuffle(&mut empty);
+    }
+
+    #[test]
+    fn test_fill_bytes() {
+        for len in [0usize, 1, 7, 8, 9, 15, 17, 64, 67].iter() {
+            let mut rng1: Rng = Rng::new(*len as u64);
+            let mut rng2: Rng = Rng::new(*len as u64);
+            let mut buf: Vec<u8> = vec![0; *len];
+            rng1.fill_bytes(&mut buf);
+
+            let expected: Vec<u8> = (0..len.div_ceil(8))
+                .flat_map(|_| rng2.next_u64().to_le_bytes().to_vec())
+                .take(*len)
+                .collect();
+            assert_eq!(buf, expected);
+            if *len >= 17 {
+                // the partial tail is filled too
+                assert!(buf[len - 3..].iter().any(|b| *b != 0));
+            }
+        }
     }
 }
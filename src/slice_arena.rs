@@ -17,14 +17,23 @@
 //! # }
 //! ```
 
-use std::alloc::{Layout, alloc, dealloc};
+use std::alloc::{Layout, alloc, dealloc, handle_alloc_error};
 use std::cell::UnsafeCell;
 use std::mem::{align_of, size_of};
 use std::ops::Deref;
-use std::ptr::copy_nonoverlapping;
+use std::ptr::{NonNull, copy_nonoverlapping};
 
+use crate::hash::FxHashSet;
 use crate::unchecked_intern::UncheckedCellOps;
 
+fn alloc_aligned(layout: Layout) -> *mut u8 {
+    let mem: *mut u8 = unsafe { alloc(layout) };
+    if mem.is_null() {
+        handle_alloc_error(layout);
+    }
+    mem
+}
+
 struct ArenaDebris<const DEBRIS_SIZE: usize, const ALIGN: usize> {
     mem: *mut u8,
     usage: usize
@@ -37,20 +46,20 @@ impl<const DEBRIS_SIZE: usize, const ALIGN: usize> ArenaDebris<DEBRIS_SIZE, ALIG
             .align_to(ALIGN)
             .unwrap();
         Self {
-            mem: unsafe { alloc(layout) },
+            mem: alloc_aligned(layout),
             usage: 0,
         }
     }
 
     #[inline]
     fn rest<T>(&self) -> usize {
-        (DEBRIS_SIZE - self.usage) / size_of::<T>()
+        DEBRIS_SIZE.saturating_sub(self.usage) / size_of::<T>()
     }
 
     unsafe fn allocate<T>(&mut self, count: usize) -> *mut T {
-        let ret: *mut T = self.mem.offset(self.usage as isize) as *mut T;
+        let ret: *mut T = self.mem.add(self.usage) as *mut T;
         let alloc_bytes: usize = count * size_of::<T>();
-        let alloc_bytes: usize = alloc_bytes + ALIGN - alloc_bytes % ALIGN;
+        let alloc_bytes: usize = alloc_bytes.div_ceil(ALIGN) * ALIGN;
         self.usage += alloc_bytes;
         ret
     }
@@ -79,7 +88,7 @@ impl<const ALIGN: usize> FreeBlock<ALIGN> {
             .unwrap();
         Self {
             size,
-            mem: unsafe { alloc(layout) }
+            mem: alloc_aligned(layout)
         }
     }
 
@@ -101,105 +110,131 @@ impl<const ALIGN: usize> Drop for FreeBlock<ALIGN> {
 pub struct SliceArena<const DEBRIS_SIZE: usize, const ALIGN: usize> {
     debris: UnsafeCell<Vec<ArenaDebris<DEBRIS_SIZE, ALIGN>>>,
     free_blocks: UnsafeCell<Vec<FreeBlock<ALIGN>>>,
+    // slices stored here actually live as long as the arena
+    interned: Option<UnsafeCell<FxHashSet<&'static [u8]>>>
 }
 
 impl<const DEBRIS_SIZE: usize, const ALIGN: usize> SliceArena<DEBRIS_SIZE, ALIGN> {
     pub fn new() -> Self {
         Self {
             debris: UnsafeCell::new(vec![ArenaDebris::new()]),
-            free_blocks: UnsafeCell::new(Vec::new())
+            free_blocks: UnsafeCell::new(Vec::new()),
+            interned: None
         }
     }
 
-    pub fn make<T: Copy>(&self, slice: &[T]) -> &[T] {
-        assert!(align_of::<T>() <= ALIGN);
+    /// Create an arena deduplicating interned strings and byte slices
+    ///
+    /// With deduplication, `intern_str` and `intern_bytes` return the very same slice for equal
+    /// contents, so interned slices may be compared by address.
+    pub fn with_dedup() -> Self {
+        Self {
+            interned: Some(UnsafeCell::new(FxHashSet::default())),
+            ..Self::new()
+        }
+    }
 
-        let size: usize = slice.len() * size_of::<T>();
-        if size >= (DEBRIS_SIZE / 2) {
-            let free_block: FreeBlock<ALIGN> = FreeBlock::new(size);
-            let ptr: *mut u8 = free_block.as_mut_ptr();
-            unsafe { self.free_blocks.get_mut_ref_unchecked().push(free_block); }
-            let ptr: *mut T = ptr as _;
-            unsafe {
-                copy_nonoverlapping(slice.as_ptr(), ptr, slice.len());
-                std::slice::from_raw_parts(ptr, slice.len())
-            }
-        } else {
-            let debris: &mut Vec<_> = unsafe { self.debris.get_mut_ref_unchecked() };
-            for debris in debris.iter_mut().rev() {
-                if debris.rest::<T>() >= slice.len() {
-                    let ptr: *mut T = unsafe { debris.allocate::<T>(slice.len()) };
-                    unsafe {
-                        copy_nonoverlapping(slice.as_ptr(), ptr, slice.len());
-                        return std::slice::from_raw_parts(ptr, slice.len());
-                    }
-                }
-            }
+    pub fn is_dedup(&self) -> bool {
+        self.interned.is_some()
+    }
 
-            let mut new_debris: ArenaDebris<DEBRIS_SIZE, ALIGN> = ArenaDebris::new();
-            let ptr: *mut T = unsafe { new_debris.allocate::<T>(slice.len()) };
-            debris.push(new_debris);
-            unsafe {
-                copy_nonoverlapping(slice.as_ptr(), ptr, slice.len());
-                return std::slice::from_raw_parts(ptr, slice.len());
-            }
+    pub fn make<T: Copy>(&self, slice: &[T]) -> &[T] {
+        let ptr: *mut T = self.allocate::<T>(slice.len());
+        unsafe {
+            copy_nonoverlapping(slice.as_ptr(), ptr, slice.len());
+            std::slice::from_raw_parts(ptr, slice.len())
         }
     }
 
+    /// # Safety
+    /// The returned slice must not be used after the arena gets dropped.
     pub unsafe fn unsafe_make<T: Copy>(&self, slice: &[T]) -> &'static [T] {
         std::mem::transmute::<&[T], &'static [T]>(self.make(slice))
     }
 
+    /// Allocate a slice of `size` items yielded by `iterator`
+    ///
+    /// Panics if `iterator` yields less than `size` items. Extra items are ignored.
     pub fn make_from_iter<T, I, R>(&self, iterator: I, size: usize) -> &[T]
         where T: Copy,
               I: Iterator<Item = R>,
               R: Deref<Target = T>
     {
+        let ptr: *mut T = self.allocate::<T>(size);
+        let mut iterator = iterator.take(size);
+        for i in 0..size {
+            let item: R = iterator.next().expect("iterator yielded less items than `size`");
+            unsafe { ptr.add(i).write(*item); }
+        }
+        unsafe { std::slice::from_raw_parts(ptr, size) }
+    }
+
+    /// # Safety
+    /// The returned slice must not be used after the arena gets dropped.
+    pub unsafe fn unsafe_make_from_iter<T, I, R>(&self, iterator: I, size: usize) -> &'static [T]
+        where T: Copy,
+              I: Iterator<Item = R>,
+              R: Deref<Target = T>
+    {
+        std::mem::transmute::<&[T], &'static [T]>(self.make_from_iter(iterator, size))
+    }
+
+    /// Intern `bytes` into the arena, deduplicating them if the arena was created `with_dedup`
+    pub fn intern_bytes(&self, bytes: &[u8]) -> &[u8] {
+        let interned: &UnsafeCell<FxHashSet<&'static [u8]>> = match &self.interned {
+            Some(interned) => interned,
+            None => return self.make(bytes)
+        };
+        let interned: &mut FxHashSet<&'static [u8]> = unsafe { interned.get_mut_ref_unchecked() };
+        if let Some(existing) = interned.get(bytes) {
+            return existing;
+        }
+        let ret: &'static [u8] = unsafe { self.unsafe_make(bytes) };
+        interned.insert(ret);
+        ret
+    }
+
+    /// Intern `s` into the arena, deduplicating it if the arena was created `with_dedup`
+    pub fn intern_str(&self, s: &str) -> &str {
+        unsafe { std::str::from_utf8_unchecked(self.intern_bytes(s.as_bytes())) }
+    }
+
+    /// Leak the arena, turning it into an interner of process-lifetime strings
+    pub fn make_static(self) -> impl Fn(&str) -> &'static str {
+        let arena: &'static Self = Box::leak(Box::new(self));
+        move |s| arena.intern_str(s)
+    }
+
+    fn allocate<T>(&self, count: usize) -> *mut T {
         assert!(align_of::<T>() <= ALIGN);
 
-        if size >= (DEBRIS_SIZE / 2) {
+        let size: usize = count.checked_mul(size_of::<T>()).unwrap();
+        if size == 0 {
+            NonNull::dangling().as_ptr()
+        } else if size >= (DEBRIS_SIZE / 2) {
             let free_block: FreeBlock<ALIGN> = FreeBlock::new(size);
-            let ptr: *mut u8 = free_block.as_mut_ptr();
+            let ptr: *mut T = free_block.as_mut_ptr();
             unsafe { self.free_blocks.get_mut_ref_unchecked().push(free_block); }
-            let ptr: *mut T = ptr as _;
-            unsafe {
-                for (i, item) in iterator.enumerate() {
-                    ptr.add(i).write(*item);
-                }
-                std::slice::from_raw_parts(ptr, size)
-            }
+            ptr
         } else {
             let debris: &mut Vec<_> = unsafe { self.debris.get_mut_ref_unchecked() };
             for debris in debris.iter_mut().rev() {
-                if debris.rest::<T>() >= size {
-                    let ptr: *mut T = unsafe { debris.allocate::<T>(size) };
-                    unsafe {
-                        for (i, item) in iterator.enumerate() {
-                            ptr.add(i).write(*item);
-                        }
-                        return std::slice::from_raw_parts(ptr, size);
-                    }
+                if debris.rest::<T>() >= count {
+                    return unsafe { debris.allocate::<T>(count) };
                 }
             }
 
             let mut new_debris: ArenaDebris<DEBRIS_SIZE, ALIGN> = ArenaDebris::new();
-            let ptr: *mut T = unsafe { new_debris.allocate::<T>(size) };
+            let ptr: *mut T = unsafe { new_debris.allocate::<T>(count) };
             debris.push(new_debris);
-            unsafe {
-                for (i, item) in iterator.enumerate() {
-                    ptr.add(i).write(*item);
-                }
-                return std::slice::from_raw_parts(ptr, size);
-            }
+            ptr
         }
     }
+}
 
-    pub unsafe fn unsafe_make_from_iter<T, I, R>(&self, iterator: I, size: usize) -> &'static [T]
-        where T: Copy,
-              I: Iterator<Item = R>,
-              R: Deref<Target = T>
-    {
-        std::mem::transmute::<&[T], &'static [T]>(self.make_from_iter(iterator, size))
+impl<const DEBRIS_SIZE: usize, const ALIGN: usize> Default for SliceArena<DEBRIS_SIZE, ALIGN> {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -218,4 +253,66 @@ mod test {
         let string_slice: &[u8] = arena.make("Hello, world!".as_bytes());
         assert_eq!(string_slice, "Hello, world!".as_bytes());
     }
+
+    #[test]
+    fn test_dedup() {
+        let arena: SliceArena<1024, 8> = SliceArena::with_dedup();
+        let s1: &str = arena.intern_str("114514");
+        let s2: &str = arena.intern_str(&String::from("114514"));
+        let s3: &str = arena.intern_str("1919810");
+        assert!(std::ptr::eq(s1, s2));
+        assert!(!std::ptr::eq(s1, s3));
+
+        let b1: &[u8] = arena.intern_bytes(b"1919810");
+        assert!(std::ptr::eq(b1, s3.as_bytes()));
+        assert!(std::ptr::eq(arena.intern_str(""), arena.intern_str("")));
+    }
+
+    #[test]
+    fn test_no_dedup() {
+        let arena: SliceArena<1024, 8> = SliceArena::new();
+        assert!(!arena.is_dedup());
+        let s1: &str = arena.intern_str("114514");
+        let s2: &str = arena.intern_str("114514");
+        assert_eq!(s1, s2);
+        assert!(!std::ptr::eq(s1, s2));
+    }
+
+    #[test]
+    fn test_large_and_stable() {
+        let arena: SliceArena<64, 8> = SliceArena::with_dedup();
+        let mut interned: Vec<(&str, String)> = Vec::new();
+        for i in 0..200 {
+            // mix small strings living in debris and large ones getting their own blocks
+            let s: String = if i % 7 == 0 { "大".repeat(i + 20) } else { format!("s{}", i) };
+            interned.push((arena.intern_str(&s), s));
+        }
+        for (i, (interned_str, origin)) in interned.iter().enumerate() {
+            assert_eq!(interned_str, origin);
+            assert!(std::ptr::eq(*interned_str, arena.intern_str(origin)), "{}", i);
+            assert!(std::str::from_utf8(interned_str.as_bytes()).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_misaligned_tail() {
+        let arena: SliceArena<30, 8> = SliceArena::new();
+        for i in 0..64u8 {
+            let slice: &[u8] = arena.make(&[i; 7]);
+            assert_eq!(slice, &[i; 7]);
+        }
+        let empty: &[u64] = arena.make(&[]);
+        assert!(empty.is_empty());
+        let large: &[u16] = arena.make_from_iter([1u16; 100].iter(), 100);
+        assert_eq!(large, &[1u16; 100]);
+    }
+
+    #[test]
+    fn test_make_static() {
+        let intern = SliceArena::<1024, 8>::with_dedup().make_static();
+        let s1: &'static str = intern("エース");
+        let owned: String = "エース".into();
+        let s2: &'static str = intern(&owned);
+        assert!(std::ptr::eq(s1, s2));
+    }
 }
@@ -0,0 +1,517 @@
+//! Spin locks usable without `std`, for example during early bootstrap
+//!
+//! Locks are not reentrant: locking a lock already held by the current thread spins forever.
+//! There is no poisoning either, a panic while holding a guard simply releases the lock.
+//!
+//! ```
+//! use xjbutil::spin::{SpinGuard, SpinLock};
+//!
+//! static REGISTRY: SpinLock<Vec<&str>> = SpinLock::new(Vec::new());
+//!
+//! REGISTRY.lock().push("114514");
+//! let registry: SpinGuard<Vec<&str>> = REGISTRY.lock();
+//! assert_eq!(registry.as_slice(), &["114514"]);
+//! ```
+
+use core::cell::UnsafeCell;
+use core::hint::spin_loop;
+use core::mem::MaybeUninit;
+use core::ops::{Deref, DerefMut};
+use core::ptr;
+use core::sync::atomic::{fence, AtomicBool, AtomicUsize, Ordering};
+
+const MAX_BACKOFF: u32 = 64;
+
+/// Exponential backoff for spin loops
+struct Backoff(u32);
+
+impl Backoff {
+    fn new() -> Self {
+        Self(1)
+    }
+
+    fn spin(&mut self) {
+        for _ in 0..self.0 {
+            spin_loop();
+        }
+        if self.0 < MAX_BACKOFF {
+            self.0 *= 2;
+        }
+    }
+}
+
+/// Mutual exclusion lock, spinning until the lock is available
+pub struct SpinLock<T: ?Sized> {
+    locked: AtomicBool,
+    data: UnsafeCell<T>
+}
+
+unsafe impl<T: ?Sized + Send> Send for SpinLock<T> {}
+unsafe impl<T: ?Sized + Send> Sync for SpinLock<T> {}
+
+impl<T> SpinLock<T> {
+    pub const fn new(data: T) -> Self {
+        Self { locked: AtomicBool::new(false), data: UnsafeCell::new(data) }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> SpinLock<T> {
+    pub fn lock(&self) -> SpinGuard<'_, T> {
+        let mut backoff: Backoff = Backoff::new();
+        loop {
+            if let Some(guard) = self.try_lock() {
+                return guard;
+            }
+            while self.is_locked() {
+                backoff.spin();
+            }
+        }
+    }
+
+    pub fn try_lock(&self) -> Option<SpinGuard<'_, T>> {
+        self.locked
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| SpinGuard { lock: self })
+    }
+
+    /// Whether the lock is held, which may change right after this function returns
+    pub fn is_locked(&self) -> bool {
+        self.locked.load(Ordering::Relaxed)
+    }
+
+    const_mut_fn! {
+        pub fn get_mut(&mut self) -> &mut T {
+            self.data.get_mut()
+        }
+    }
+}
+
+impl<T: Default> Default for SpinLock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+pub struct SpinGuard<'a, T: ?Sized> {
+    lock: &'a SpinLock<T>
+}
+
+unsafe impl<'a, T: ?Sized + Sync> Sync for SpinGuard<'a, T> {}
+
+impl<'a, T: ?Sized> Deref for SpinGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> DerefMut for SpinGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for SpinGuard<'a, T> {
+    fn drop(&mut self) {
+        self.lock.locked.store(false, Ordering::Release);
+    }
+}
+
+const WRITER: usize = usize::MAX;
+
+/// Readers-writer lock, spinning until the lock is available
+///
+/// Readers are preferred, so a continuous stream of readers may starve writers.
+pub struct SpinRwLock<T: ?Sized> {
+    state: AtomicUsize,
+    data: UnsafeCell<T>
+}
+
+unsafe impl<T: ?Sized + Send> Send for SpinRwLock<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for SpinRwLock<T> {}
+
+impl<T> SpinRwLock<T> {
+    pub const fn new(data: T) -> Self {
+        Self { state: AtomicUsize::new(0), data: UnsafeCell::new(data) }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> SpinRwLock<T> {
+    pub fn read(&self) -> SpinReadGuard<'_, T> {
+        let mut backoff: Backoff = Backoff::new();
+        loop {
+            if let Some(guard) = self.try_read() {
+                return guard;
+            }
+            backoff.spin();
+        }
+    }
+
+    pub fn try_read(&self) -> Option<SpinReadGuard<'_, T>> {
+        let mut state: usize = self.state.load(Ordering::Relaxed);
+        while state < WRITER - 1 {
+            match self.state.compare_exchange_weak(
+                state,
+                state + 1,
+                Ordering::Acquire,
+                Ordering::Relaxed
+            ) {
+                Ok(_) => return Some(SpinReadGuard { lock: self }),
+                Err(current) => state = current
+            }
+        }
+        None
+    }
+
+    pub fn write(&self) -> SpinWriteGuard<'_, T> {
+        let mut backoff: Backoff = Backoff::new();
+        loop {
+            if let Some(guard) = self.try_write() {
+                return guard;
+            }
+            while self.is_locked() {
+                backoff.spin();
+            }
+        }
+    }
+
+    pub fn try_write(&self) -> Option<SpinWriteGuard<'_, T>> {
+        self.state
+            .compare_exchange(0, WRITER, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| SpinWriteGuard { lock: self })
+    }
+
+    /// Whether the lock is held by any reader or writer, which may change right after this
+    /// function returns
+    pub fn is_locked(&self) -> bool {
+        self.state.load(Ordering::Relaxed) != 0
+    }
+
+    const_mut_fn! {
+        pub fn get_mut(&mut self) -> &mut T {
+            self.data.get_mut()
+        }
+    }
+}
+
+impl<T: Default> Default for SpinRwLock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+pub struct SpinReadGuard<'a, T: ?Sized> {
+    lock: &'a SpinRwLock<T>
+}
+
+impl<'a, T: ?Sized> Deref for SpinReadGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for SpinReadGuard<'a, T> {
+    fn drop(&mut self) {
+        self.lock.state.fetch_sub(1, Ordering::Release);
+    }
+}
+
+pub struct SpinWriteGuard<'a, T: ?Sized> {
+    lock: &'a SpinRwLock<T>
+}
+
+impl<'a, T: ?Sized> Deref for SpinWriteGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> DerefMut for SpinWriteGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for SpinWriteGuard<'a, T> {
+    fn drop(&mut self) {
+        self.lock.state.store(0, Ordering::Release);
+    }
+}
+
+/// Sequence lock, for data written rarely and read frequently
+///
+/// Readers never write to shared memory: they copy the value optimistically, and retry if a
+/// writer was active meanwhile. The sequence counter is odd while a write is in progress, which
+/// also serves as the lock between writers, so `write` is safe to call from multiple threads.
+/// Writers are never blocked by readers, so a writer updating continuously may starve readers.
+pub struct SeqLock<T: Copy> {
+    seq: AtomicUsize,
+    data: UnsafeCell<T>
+}
+
+unsafe impl<T: Copy + Send> Send for SeqLock<T> {}
+unsafe impl<T: Copy + Send> Sync for SeqLock<T> {}
+
+impl<T: Copy> SeqLock<T> {
+    pub const fn new(data: T) -> Self {
+        Self { seq: AtomicUsize::new(0), data: UnsafeCell::new(data) }
+    }
+
+    const_mut_fn! {
+        pub fn into_inner(self) -> T {
+            self.data.into_inner()
+        }
+    }
+
+    pub fn write(&self, value: T) {
+        let mut backoff: Backoff = Backoff::new();
+        let mut seq: usize = self.seq.load(Ordering::Relaxed);
+        loop {
+            if seq & 1 == 0 {
+                match self.seq.compare_exchange_weak(
+                    seq,
+                    seq.wrapping_add(1),
+                    Ordering::Acquire,
+                    Ordering::Relaxed
+                ) {
+                    Ok(_) => break,
+                    Err(current) => seq = current
+                }
+            } else {
+                backoff.spin();
+                seq = self.seq.load(Ordering::Relaxed);
+            }
+        }
+
+        // keeps the payload write from being reordered before the odd sequence is visible
+        fence(Ordering::Release);
+        unsafe { ptr::write_volatile(self.data.get(), value); }
+        self.seq.store(seq.wrapping_add(2), Ordering::Release);
+    }
+
+    pub fn read(&self) -> T {
+        let mut backoff: Backoff = Backoff::new();
+        loop {
+            if let Some(value) = self.try_read() {
+                return value;
+            }
+            backoff.spin();
+        }
+    }
+
+    /// Try reading the value once, failing if a writer is active or interferes
+    pub fn try_read(&self) -> Option<T> {
+        let seq1: usize = self.seq.load(Ordering::Acquire);
+        if seq1 & 1 != 0 {
+            return None;
+        }
+
+        // the copy may be torn, so it must not be assumed initialized before validation
+        let value: MaybeUninit<T> = unsafe {
+            ptr::read_volatile(self.data.get() as *const MaybeUninit<T>)
+        };
+        // keeps the payload read from being reordered after the second sequence load
+        fence(Ordering::Acquire);
+        let seq2: usize = self.seq.load(Ordering::Relaxed);
+
+        if seq1 == seq2 {
+            Some(unsafe { value.assume_init() })
+        } else {
+            None
+        }
+    }
+
+    /// Call `f` with a consistent snapshot of the value
+    pub fn read_with<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        let value: T = self.read();
+        f(&value)
+    }
+
+    const_mut_fn! {
+        pub fn get_mut(&mut self) -> &mut T {
+            self.data.get_mut()
+        }
+    }
+}
+
+impl<T: Copy + Default> Default for SeqLock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::thread;
+
+    use crate::spin::{SeqLock, SpinGuard, SpinLock, SpinReadGuard, SpinRwLock, SpinWriteGuard};
+
+    const THREADS: usize = 4;
+    const ROUNDS: usize = if cfg!(miri) { 50 } else { 10000 };
+
+    #[test]
+    fn test_spin_lock_contention() {
+        // the counter is deliberately not atomic: the read-modify-write is only correct under
+        // mutual exclusion
+        let lock: Arc<SpinLock<(usize, usize)>> = Arc::new(SpinLock::new((0, 0)));
+        let handles: Vec<thread::JoinHandle<()>> = (0..THREADS).map(|_| {
+            let lock: Arc<SpinLock<(usize, usize)>> = lock.clone();
+            thread::spawn(move || {
+                for _ in 0..ROUNDS {
+                    let mut guard: SpinGuard<(usize, usize)> = lock.lock();
+                    let value: usize = guard.0;
+                    guard.0 = value + 1;
+                    guard.1 = value + 1;
+                }
+            })
+        }).collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert!(!lock.is_locked());
+        assert_eq!(*lock.lock(), (THREADS * ROUNDS, THREADS * ROUNDS));
+    }
+
+    #[test]
+    fn test_try_lock() {
+        let lock: SpinLock<i32> = SpinLock::new(114);
+        {
+            let mut guard: SpinGuard<i32> = lock.try_lock().unwrap();
+            assert!(lock.is_locked());
+            assert!(lock.try_lock().is_none());
+            *guard += 400;
+        }
+        assert!(!lock.is_locked());
+        assert_eq!(*lock.try_lock().unwrap(), 514);
+        assert_eq!(lock.into_inner(), 514);
+    }
+
+    #[test]
+    fn test_rw_lock() {
+        let lock: SpinRwLock<String> = SpinRwLock::new("1919".into());
+        {
+            let r1: SpinReadGuard<String> = lock.read();
+            let r2: SpinReadGuard<String> = lock.try_read().unwrap();
+            assert_eq!(*r1, *r2);
+            assert!(lock.try_write().is_none());
+        }
+        {
+            let mut w: SpinWriteGuard<String> = lock.write();
+            w.push_str("810");
+            assert!(lock.try_read().is_none());
+            assert!(lock.try_write().is_none());
+        }
+        assert!(!lock.is_locked());
+        assert_eq!(lock.read().as_str(), "1919810");
+    }
+
+    #[test]
+    fn test_rw_lock_contention() {
+        let lock: Arc<SpinRwLock<(usize, usize)>> = Arc::new(SpinRwLock::new((0, 0)));
+        let handles: Vec<thread::JoinHandle<()>> = (0..THREADS).map(|i| {
+            let lock: Arc<SpinRwLock<(usize, usize)>> = lock.clone();
+            thread::spawn(move || {
+                for _ in 0..ROUNDS {
+                    if i % 2 == 0 {
+                        let mut guard: SpinWriteGuard<(usize, usize)> = lock.write();
+                        guard.0 += 1;
+                        guard.1 += 1;
+                    } else {
+                        let guard: SpinReadGuard<(usize, usize)> = lock.read();
+                        assert_eq!(guard.0, guard.1);
+                    }
+                }
+            })
+        }).collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(*lock.read(), (THREADS / 2 * ROUNDS, THREADS / 2 * ROUNDS));
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    struct Snapshot {
+        a: u64,
+        b: u64,
+        c: u64,
+        d: u64
+    }
+
+    impl Snapshot {
+        fn new(x: u64) -> Self {
+            Self { a: x, b: x.wrapping_mul(3), c: !x, d: x.rotate_left(17) }
+        }
+
+        fn is_consistent(&self) -> bool {
+            *self == Self::new(self.a)
+        }
+    }
+
+    #[test]
+    fn test_seq_lock() {
+        let mut lock: SeqLock<Snapshot> = SeqLock::new(Snapshot::new(114));
+        assert_eq!(lock.read(), Snapshot::new(114));
+        assert_eq!(lock.try_read(), Some(Snapshot::new(114)));
+        lock.write(Snapshot::new(514));
+        assert_eq!(lock.read_with(|snapshot| snapshot.a), 514);
+        lock.get_mut().a = 1919;
+        assert!(!lock.read().is_consistent());
+        assert_eq!(lock.into_inner().a, 1919);
+    }
+
+    // optimistic reads race with writes by design, which Miri reports as a data race
+    #[cfg_attr(miri, ignore)]
+    #[test]
+    fn test_seq_lock_no_torn_read() {
+        const WRITES: u64 = 200000;
+        const READERS: usize = 3;
+
+        let lock: Arc<SeqLock<Snapshot>> = Arc::new(SeqLock::new(Snapshot::new(0)));
+        let readers: Vec<thread::JoinHandle<usize>> = (0..READERS).map(|_| {
+            let lock: Arc<SeqLock<Snapshot>> = lock.clone();
+            thread::spawn(move || {
+                let mut reads: usize = 0;
+                let mut last: u64 = 0;
+                while last != WRITES {
+                    let snapshot: Snapshot = lock.read();
+                    assert!(snapshot.is_consistent(), "torn read: {:?}", snapshot);
+                    assert!(snapshot.a >= last);
+                    last = snapshot.a;
+                    reads += 1;
+                }
+                reads
+            })
+        }).collect();
+
+        let writer: thread::JoinHandle<()> = {
+            let lock: Arc<SeqLock<Snapshot>> = lock.clone();
+            thread::spawn(move || {
+                for i in 1..=WRITES {
+                    lock.write(Snapshot::new(i));
+                }
+            })
+        };
+
+        writer.join().unwrap();
+        for reader in readers {
+            assert!(reader.join().unwrap() > 0);
+        }
+        assert_eq!(lock.try_read(), Some(Snapshot::new(WRITES)));
+    }
+}
@@ -0,0 +1,330 @@
+//! Wait-free single-producer single-consumer ring buffer
+//!
+//! ```
+//! use xjbutil::spsc::{Consumer, Producer, RingBuffer};
+//!
+//! let (mut producer, mut consumer): (Producer<i32>, Consumer<i32>) = RingBuffer::with_capacity(2);
+//! assert_eq!(producer.push(114), Ok(()));
+//! assert_eq!(producer.push(514), Ok(()));
+//! assert_eq!(producer.push(1919), Err(1919));
+//! assert_eq!(consumer.pop(), Some(114));
+//! ```
+
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::ptr::{copy_nonoverlapping, drop_in_place};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use crate::unchecked_intern::UncheckedCellOps;
+
+/// Storage shared by a `Producer` and a `Consumer`
+///
+/// `head` and `tail` increase monotonically and wrap around `usize`, slots are indexed by masking
+/// them with `capacity - 1`.
+pub struct RingBuffer<T> {
+    slots: Box<[UnsafeCell<MaybeUninit<T>>]>,
+    mask: usize,
+    head: AtomicUsize,
+    tail: AtomicUsize
+}
+
+impl<T> RingBuffer<T> {
+    /// Create a ring buffer holding at least `capacity` elements, rounded up to a power of two
+    #[allow(clippy::new_ret_no_self)]
+    pub fn with_capacity(capacity: usize) -> (Producer<T>, Consumer<T>) {
+        let capacity: usize = capacity.max(1).next_power_of_two();
+        let slots: Vec<UnsafeCell<MaybeUninit<T>>> =
+            (0..capacity).map(|_| UnsafeCell::new(MaybeUninit::uninit())).collect();
+        let buffer: Arc<RingBuffer<T>> = Arc::new(RingBuffer {
+            slots: slots.into_boxed_slice(),
+            mask: capacity - 1,
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0)
+        });
+        (
+            Producer { buffer: buffer.clone(), cached_head: 0 },
+            Consumer { buffer, cached_tail: 0 }
+        )
+    }
+
+    #[inline(always)] fn capacity(&self) -> usize {
+        self.mask + 1
+    }
+
+    /// Pointer to a slot, derived from the whole slot array so that batched copies may cross slots
+    #[inline(always)] fn slot_ptr(&self, index: usize) -> *mut T {
+        unsafe { (self.slots.as_ptr() as *mut T).add(index & self.mask) }
+    }
+
+    fn len(&self) -> usize {
+        let head: usize = self.head.load(Ordering::Acquire);
+        let tail: usize = self.tail.load(Ordering::Acquire);
+        tail.wrapping_sub(head)
+    }
+}
+
+impl<T> Drop for RingBuffer<T> {
+    fn drop(&mut self) {
+        let head: usize = *self.head.get_mut();
+        let tail: usize = *self.tail.get_mut();
+        let mut index: usize = head;
+        while index != tail {
+            unsafe { drop_in_place(self.slot_ptr(index)); }
+            index = index.wrapping_add(1);
+        }
+    }
+}
+
+/// The writing half of a `RingBuffer`
+pub struct Producer<T> {
+    buffer: Arc<RingBuffer<T>>,
+    cached_head: usize
+}
+
+unsafe impl<T: Send> Send for Producer<T> {}
+
+impl<T> Producer<T> {
+    pub fn capacity(&self) -> usize {
+        self.buffer.capacity()
+    }
+
+    /// Count of elements in the buffer, which may decrease right after this function returns
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Count of free slots, only reloading the consumer index if fewer than `wanted` are known
+    fn free_slots(&mut self, tail: usize, wanted: usize) -> usize {
+        let capacity: usize = self.buffer.capacity();
+        let free: usize = capacity - tail.wrapping_sub(self.cached_head);
+        if free >= wanted {
+            return free;
+        }
+        self.cached_head = self.buffer.head.load(Ordering::Acquire);
+        capacity - tail.wrapping_sub(self.cached_head)
+    }
+
+    /// Push `value` to the buffer, giving it back if the buffer is full
+    pub fn push(&mut self, value: T) -> Result<(), T> {
+        let tail: usize = self.buffer.tail.load(Ordering::Relaxed);
+        if self.free_slots(tail, 1) == 0 {
+            return Err(value);
+        }
+
+        let slot: &UnsafeCell<MaybeUninit<T>> = &self.buffer.slots[tail & self.buffer.mask];
+        unsafe { slot.get_mut_ref_unchecked().write(value); }
+        self.buffer.tail.store(tail.wrapping_add(1), Ordering::Release);
+        Ok(())
+    }
+}
+
+impl<T: Copy> Producer<T> {
+    /// Push as many elements from `values` as possible, returning the count pushed
+    pub fn push_slice(&mut self, values: &[T]) -> usize {
+        let tail: usize = self.buffer.tail.load(Ordering::Relaxed);
+        let count: usize = self.free_slots(tail, values.len()).min(values.len());
+        let start: usize = tail & self.buffer.mask;
+        let first: usize = count.min(self.buffer.capacity() - start);
+        unsafe {
+            copy_nonoverlapping(values.as_ptr(), self.buffer.slot_ptr(start), first);
+            let rest: *const T = values.as_ptr().add(first);
+            copy_nonoverlapping(rest, self.buffer.slot_ptr(0), count - first);
+        }
+        self.buffer.tail.store(tail.wrapping_add(count), Ordering::Release);
+        count
+    }
+}
+
+/// The reading half of a `RingBuffer`
+pub struct Consumer<T> {
+    buffer: Arc<RingBuffer<T>>,
+    cached_tail: usize
+}
+
+unsafe impl<T: Send> Send for Consumer<T> {}
+
+impl<T> Consumer<T> {
+    pub fn capacity(&self) -> usize {
+        self.buffer.capacity()
+    }
+
+    /// Count of elements in the buffer, which may increase right after this function returns
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Count of ready elements, only reloading the producer index if fewer than `wanted` are
+    /// known
+    fn ready_slots(&mut self, head: usize, wanted: usize) -> usize {
+        let ready: usize = self.cached_tail.wrapping_sub(head);
+        if ready >= wanted {
+            return ready;
+        }
+        self.cached_tail = self.buffer.tail.load(Ordering::Acquire);
+        self.cached_tail.wrapping_sub(head)
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        let head: usize = self.buffer.head.load(Ordering::Relaxed);
+        if self.ready_slots(head, 1) == 0 {
+            return None;
+        }
+
+        let slot: &UnsafeCell<MaybeUninit<T>> = &self.buffer.slots[head & self.buffer.mask];
+        let value: T = unsafe { slot.get_ref_unchecked().assume_init_read() };
+        self.buffer.head.store(head.wrapping_add(1), Ordering::Release);
+        Some(value)
+    }
+}
+
+impl<T: Copy> Consumer<T> {
+    /// Pop as many elements into `values` as possible, returning the count popped
+    pub fn pop_slice(&mut self, values: &mut [T]) -> usize {
+        let head: usize = self.buffer.head.load(Ordering::Relaxed);
+        let count: usize = self.ready_slots(head, values.len()).min(values.len());
+        let start: usize = head & self.buffer.mask;
+        let first: usize = count.min(self.buffer.capacity() - start);
+        unsafe {
+            copy_nonoverlapping(self.buffer.slot_ptr(start), values.as_mut_ptr(), first);
+            let rest: *mut T = values.as_mut_ptr().add(first);
+            copy_nonoverlapping(self.buffer.slot_ptr(0), rest, count - first);
+        }
+        self.buffer.head.store(head.wrapping_add(count), Ordering::Release);
+        count
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+
+    use crate::spsc::{Consumer, Producer, RingBuffer};
+
+    #[test]
+    fn test_full_empty() {
+        let (mut producer, mut consumer): (Producer<String>, Consumer<String>) =
+            RingBuffer::with_capacity(3);
+        assert_eq!(producer.capacity(), 4);
+        assert!(consumer.is_empty());
+        assert_eq!(consumer.pop(), None);
+
+        // go around the buffer a few times to cover index wrapping
+        for round in 0..3 {
+            for i in 0..4 {
+                assert!(producer.push(format!("{}-{}", round, i)).is_ok());
+            }
+            assert_eq!(producer.len(), 4);
+            assert_eq!(producer.push("893".into()), Err("893".into()));
+            assert_eq!(consumer.pop().unwrap(), format!("{}-0", round));
+            assert!(producer.push(format!("{}-4", round)).is_ok());
+            for i in 1..5 {
+                assert_eq!(consumer.pop().unwrap(), format!("{}-{}", round, i));
+            }
+            assert_eq!(consumer.pop(), None);
+            assert!(producer.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_slices() {
+        let (mut producer, mut consumer): (Producer<u32>, Consumer<u32>) =
+            RingBuffer::with_capacity(8);
+        let mut output: [u32; 8] = [0; 8];
+
+        assert_eq!(producer.push_slice(&[1, 2, 3, 4, 5]), 5);
+        assert_eq!(consumer.pop_slice(&mut output[..3]), 3);
+        assert_eq!(output[..3], [1, 2, 3]);
+        // wraps around the end of the buffer
+        assert_eq!(producer.push_slice(&[6, 7, 8, 9, 10, 11, 12]), 6);
+        assert_eq!(consumer.pop(), Some(4));
+        assert_eq!(consumer.pop_slice(&mut output), 7);
+        assert_eq!(output[..7], [5, 6, 7, 8, 9, 10, 11]);
+        assert_eq!(consumer.pop_slice(&mut output), 0);
+        assert_eq!(producer.push_slice(&[]), 0);
+    }
+
+    #[test]
+    fn test_drop_unconsumed() {
+        struct Counted(Arc<AtomicUsize>);
+
+        impl Drop for Counted {
+            fn drop(&mut self) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let drops: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(0));
+        let (mut producer, mut consumer): (Producer<Counted>, Consumer<Counted>) =
+            RingBuffer::with_capacity(4);
+        for _ in 0..4 {
+            assert!(producer.push(Counted(drops.clone())).is_ok());
+        }
+        // the rejected value is handed back and dropped by the caller
+        assert!(producer.push(Counted(drops.clone())).is_err());
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+        drop(consumer.pop());
+        assert_eq!(drops.load(Ordering::SeqCst), 2);
+
+        drop(producer);
+        assert_eq!(drops.load(Ordering::SeqCst), 2);
+        drop(consumer);
+        assert_eq!(drops.load(Ordering::SeqCst), 5);
+    }
+
+    #[test]
+    fn test_cross_thread() {
+        const COUNT: u64 = if cfg!(miri) { 1000 } else { 1000000 };
+
+        let (mut producer, mut consumer): (Producer<u64>, Consumer<u64>) =
+            RingBuffer::with_capacity(256);
+        let handle: thread::JoinHandle<()> = thread::spawn(move || {
+            let mut next: u64 = 0;
+            while next < COUNT {
+                if producer.push(next).is_ok() {
+                    next += 1;
+                } else {
+                    thread::yield_now();
+                }
+            }
+        });
+
+        let mut expected: u64 = 0;
+        let mut batch: [u64; 64] = [0; 64];
+        let mut use_batch: bool = false;
+        while expected < COUNT {
+            // alternate between single and batched pops
+            use_batch = !use_batch;
+            let count: usize = if use_batch {
+                consumer.pop_slice(&mut batch)
+            } else if let Some(value) = consumer.pop() {
+                batch[0] = value;
+                1
+            } else {
+                0
+            };
+            if count == 0 {
+                thread::yield_now();
+            }
+            for value in batch[..count].iter() {
+                assert_eq!(*value, expected);
+                expected += 1;
+            }
+        }
+        handle.join().unwrap();
+        assert!(consumer.is_empty());
+    }
+}
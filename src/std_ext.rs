@@ -1,8 +1,14 @@
 //! Extensions to standard libraries
 
-use std::mem::transmute;
-use std::ptr::NonNull;
+use std::any::Any;
+use std::fmt::Debug;
+use std::hint::unreachable_unchecked;
+use std::mem::{ManuallyDrop, transmute};
+use std::ptr::{self, NonNull};
+use std::rc::Rc;
+use std::sync::Arc;
 
+use crate::either::Either;
 use crate::mem_intern::{leak_as_nonnull, reclaim_as_boxed};
 
 /// Extensions on `Box`-like structure
@@ -40,6 +46,66 @@ impl<T: ?Sized> BoxedExt<T> for Box<T> {
     }
 }
 
+/// Extensions on `dyn Any` pointers, usually leaked by `BoxedExt::leak_as_nonnull`
+///
+/// Implemented for `NonNull<dyn Any>`, `NonNull<dyn Any + Send>` and
+/// `NonNull<dyn Any + Send + Sync>`.
+pub trait AnyPtrExt: Sized {
+    /// Whether the pointed object is a `T`
+    ///
+    /// # Safety
+    /// The pointer must point to a live object, since the check goes through a temporary shared
+    /// reference to it.
+    unsafe fn is_type<T: Any>(&self) -> bool;
+
+    /// Cast the pointer to `T` if the pointed object is a `T`, giving the original pointer back
+    /// otherwise
+    ///
+    /// # Safety
+    /// See `is_type`.
+    unsafe fn downcast_ptr<T: Any>(self) -> Result<NonNull<T>, Self>;
+
+    /// Reference the pointed object as a `T`, checking its type in debug builds only
+    ///
+    /// # Safety
+    /// The pointed object must be a live `T`, and must not be mutated while the returned
+    /// reference is in use.
+    unsafe fn downcast_ref_unchecked<T: Any>(&self) -> &T;
+}
+
+macro_rules! impl_any_ptr_ext {
+    ($($dyn_any:ty),*) => {
+        $(
+            impl AnyPtrExt for NonNull<$dyn_any> {
+                #[inline] unsafe fn is_type<T: Any>(&self) -> bool {
+                    self.as_ref().is::<T>()
+                }
+
+                #[inline] unsafe fn downcast_ptr<T: Any>(self) -> Result<NonNull<T>, Self> {
+                    if self.is_type::<T>() {
+                        Ok(self.cast::<T>())
+                    } else {
+                        Err(self)
+                    }
+                }
+
+                #[inline]
+                #[track_caller]
+                unsafe fn downcast_ref_unchecked<T: Any>(&self) -> &T {
+                    debug_assert!(
+                        self.is_type::<T>(),
+                        "`downcast_ref_unchecked` to wrong type `{}`",
+                        std::any::type_name::<T>()
+                    );
+                    &*self.cast::<T>().as_ptr()
+                }
+            }
+        )*
+    };
+}
+
+impl_any_ptr_ext!(dyn Any, dyn Any + Send, dyn Any + Send + Sync);
+
 /// Extensions on `std::vec::Vec`
 pub trait VecExt<T> {
     /// "Leak" the content in the `Vec`, returning a `NonNull` pointer to slice.
@@ -52,12 +118,371 @@ pub trait VecExt<T> {
     /// let ptr: NonNull<[T]> = unsafe { NonNull::new_unchecked(ptr) };
     /// ```
     fn into_slice_ptr(self) -> NonNull<[T]>;
+
+    /// Move the elements matching `pred` to the end of `sink`, keeping the relative order of both
+    /// the moved and the remaining elements
+    ///
+    /// If `pred` panics, the element being examined and all unexamined ones stay in `self`.
+    fn extract_if_into(&mut self, pred: impl FnMut(&mut T) -> bool, sink: &mut Vec<T>);
+
+    /// Pass every element to `f`, keeping those returned as `Either::Left` and collecting those
+    /// returned as `Either::Right`, in order
+    ///
+    /// If `f` panics, the element passed to it is dropped by the unwinding, and all elements not
+    /// yet passed to `f` stay in `self`. Extracted elements collected so far are dropped.
+    fn retain_map<U>(&mut self, f: impl FnMut(T) -> Either<T, U>) -> Vec<U>;
+}
+
+/// Closes the gap left by removed elements, even when unwinding
+struct RetainGuard<'a, T> {
+    vec: &'a mut Vec<T>,
+    original_len: usize,
+    processed: usize,
+    deleted: usize
+}
+
+impl<'a, T> RetainGuard<'a, T> {
+    fn new(vec: &'a mut Vec<T>) -> Self {
+        let original_len: usize = vec.len();
+        // elements are moved out one by one, a panic must not let `vec` drop them again
+        unsafe { vec.set_len(0); }
+        Self { vec, original_len, processed: 0, deleted: 0 }
+    }
+}
+
+impl<'a, T> Drop for RetainGuard<'a, T> {
+    fn drop(&mut self) {
+        unsafe {
+            let base: *mut T = self.vec.as_mut_ptr();
+            if self.deleted > 0 {
+                ptr::copy(
+                    base.add(self.processed),
+                    base.add(self.processed - self.deleted),
+                    self.original_len - self.processed
+                );
+            }
+            self.vec.set_len(self.original_len - self.deleted);
+        }
+    }
 }
 
 impl<T> VecExt<T> for Vec<T> {
     #[inline] fn into_slice_ptr(self) -> NonNull<[T]> {
         self.into_boxed_slice().leak_as_nonnull()
     }
+
+    fn extract_if_into(&mut self, mut pred: impl FnMut(&mut T) -> bool, sink: &mut Vec<T>) {
+        let mut guard: RetainGuard<T> = RetainGuard::new(self);
+        let base: *mut T = guard.vec.as_mut_ptr();
+        while guard.processed < guard.original_len {
+            let i: usize = guard.processed;
+            unsafe {
+                let current: *mut T = base.add(i);
+                if pred(&mut *current) {
+                    guard.processed += 1;
+                    guard.deleted += 1;
+                    sink.push(ptr::read(current));
+                } else {
+                    guard.processed += 1;
+                    if guard.deleted > 0 {
+                        ptr::copy_nonoverlapping(current, base.add(i - guard.deleted), 1);
+                    }
+                }
+            }
+        }
+    }
+
+    fn retain_map<U>(&mut self, mut f: impl FnMut(T) -> Either<T, U>) -> Vec<U> {
+        let mut extracted: Vec<U> = Vec::new();
+        let mut guard: RetainGuard<T> = RetainGuard::new(self);
+        let base: *mut T = guard.vec.as_mut_ptr();
+        while guard.processed < guard.original_len {
+            let i: usize = guard.processed;
+            let value: T = unsafe { ptr::read(base.add(i)) };
+            // the slot stays vacant if `f` panics
+            guard.processed += 1;
+            guard.deleted += 1;
+            match f(value) {
+                Either::Left(kept) => {
+                    guard.deleted -= 1;
+                    unsafe { ptr::write(base.add(i - guard.deleted), kept); }
+                },
+                Either::Right(value) => extracted.push(value)
+            }
+        }
+        extracted
+    }
+}
+
+/// Put a slice leaked by `VecExt::into_slice_ptr` back into a `Vec`
+///
+/// The capacity of the returned `Vec` equals its length, since `into_slice_ptr` goes through
+/// `into_boxed_slice`.
+///
+/// # Safety
+/// `ptr` must come from `VecExt::into_slice_ptr` or `BoxedExt::leak_as_nonnull` on a `Box<[T]>`,
+/// and must not be used after this call.
+#[inline] pub unsafe fn reclaim_slice_ptr<T>(ptr: NonNull<[T]>) -> Vec<T> {
+    ptr.reclaim_vec()
+}
+
+/// Extensions on slice pointers leaked by `VecExt::into_slice_ptr`
+pub trait SlicePtrExt<T> {
+    /// See `reclaim_slice_ptr`
+    ///
+    /// # Safety
+    /// See `reclaim_slice_ptr`.
+    unsafe fn reclaim_vec(self) -> Vec<T>;
+
+    /// Like `reclaim_vec`, but returns the `Box<[T]>` which was leaked
+    ///
+    /// # Safety
+    /// See `reclaim_slice_ptr`.
+    unsafe fn reclaim_boxed(self) -> Box<[T]>;
+}
+
+impl<T> SlicePtrExt<T> for NonNull<[T]> {
+    #[inline] unsafe fn reclaim_vec(self) -> Vec<T> {
+        self.reclaim_boxed().into_vec()
+    }
+
+    #[inline] unsafe fn reclaim_boxed(self) -> Box<[T]> {
+        reclaim_as_boxed(self)
+    }
+}
+
+/// Extensions on `std::ptr::NonNull`
+///
+/// Methods are suffixed with `_ext`, so that they are not shadowed by the inherent methods of
+/// the same names on newer compilers.
+pub trait NonNullExt<T> {
+    /// Like `<*mut T>::add`
+    ///
+    /// # Safety
+    /// Same as `<*mut T>::add`.
+    unsafe fn add_ext(self, count: usize) -> NonNull<T>;
+
+    /// Offset the pointer by `bytes` bytes, keeping its type
+    ///
+    /// # Safety
+    /// Same as `<*mut u8>::add`.
+    unsafe fn byte_add_ext(self, bytes: usize) -> NonNull<T>;
+
+    /// Like `NonNull::cast`
+    fn cast_ext<U>(self) -> NonNull<U>;
+}
+
+impl<T> NonNullExt<T> for NonNull<T> {
+    #[inline] unsafe fn add_ext(self, count: usize) -> NonNull<T> {
+        NonNull::new_unchecked(self.as_ptr().add(count))
+    }
+
+    #[inline] unsafe fn byte_add_ext(self, bytes: usize) -> NonNull<T> {
+        NonNull::new_unchecked((self.as_ptr() as *mut u8).add(bytes) as *mut T)
+    }
+
+    #[inline] fn cast_ext<U>(self) -> NonNull<U> {
+        self.cast::<U>()
+    }
+}
+
+/// Build a `NonNull<[T]>` from a data pointer and a length
+///
+/// This function is equivalent to `NonNull::slice_from_raw_parts`.
+#[inline] pub fn nonnull_slice_from_raw_parts<T>(data: NonNull<T>, len: usize) -> NonNull<[T]> {
+    let ptr: *mut [T] = ptr::slice_from_raw_parts_mut(data.as_ptr(), len);
+    unsafe { NonNull::new_unchecked(ptr) }
+}
+
+/// Length of the slice, without dereferencing the pointer
+///
+/// This function is equivalent to `NonNull::<[T]>::len`.
+#[inline] pub fn nonnull_slice_len<T>(ptr: NonNull<[T]>) -> usize {
+    ptr.len()
+}
+
+/// Extensions on `std::string::String`
+pub trait StringExt {
+    /// "Leak" the content in the `String`, returning a `NonNull` pointer to `str`.
+    ///
+    /// This goes through `into_boxed_str`, which reallocates to shrink the buffer if the capacity
+    /// exceeds the length.
+    fn into_str_ptr(self) -> NonNull<str>;
+}
+
+impl StringExt for String {
+    #[inline] fn into_str_ptr(self) -> NonNull<str> {
+        self.into_boxed_str().leak_as_nonnull()
+    }
+}
+
+/// Put a `str` leaked by `StringExt::into_str_ptr` back into a `String`
+///
+/// The capacity of the returned `String` equals its length.
+///
+/// # Safety
+/// `ptr` must come from `StringExt::into_str_ptr` or `BoxedExt::leak_as_nonnull` on a `Box<str>`,
+/// and must not be used after this call.
+#[inline] pub unsafe fn reclaim_string(ptr: NonNull<str>) -> String {
+    reclaim_as_boxed(ptr).into_string()
+}
+
+/// Extensions on `str`
+pub trait StrExt {
+    /// Leak a boxed `str`, which then lives until the end of the program
+    fn leak_to_static(self: Box<Self>) -> &'static str;
+}
+
+impl StrExt for str {
+    #[inline] fn leak_to_static(self: Box<Self>) -> &'static str {
+        Box::leak(self)
+    }
+}
+
+/// Extensions on slices
+pub trait SliceExt<T> {
+    /// Mutable references to two different elements
+    ///
+    /// Panics if `i == j` or either index is out of bounds.
+    fn get_two_mut(&mut self, i: usize, j: usize) -> (&mut T, &mut T);
+
+    /// Mutable references to two different elements, or `None` if `i == j` or either index is out
+    /// of bounds
+    fn try_get_two_mut(&mut self, i: usize, j: usize) -> Option<(&mut T, &mut T)>;
+
+    /// Mutable references to `N` pairwise different elements, or `None` if any two indices are
+    /// equal or any index is out of bounds
+    ///
+    /// Disjointness is checked pairwise, so this is meant for small `N`.
+    fn get_many_mut<const N: usize>(&mut self, indices: [usize; N]) -> Option<[&mut T; N]>;
+}
+
+impl<T> SliceExt<T> for [T] {
+    #[track_caller]
+    fn get_two_mut(&mut self, i: usize, j: usize) -> (&mut T, &mut T) {
+        assert_ne!(i, j, "`get_two_mut` called with equal indices");
+        let len: usize = self.len();
+        assert!(i < len && j < len, "indices ({}, {}) out of bounds for length {}", i, j, len);
+        let [a, b] = self.get_many_mut([i, j]).unwrap();
+        (a, b)
+    }
+
+    fn try_get_two_mut(&mut self, i: usize, j: usize) -> Option<(&mut T, &mut T)> {
+        let [a, b] = self.get_many_mut([i, j])?;
+        Some((a, b))
+    }
+
+    fn get_many_mut<const N: usize>(&mut self, indices: [usize; N]) -> Option<[&mut T; N]> {
+        for (n, &i) in indices.iter().enumerate() {
+            if i >= self.len() || indices[..n].contains(&i) {
+                return None;
+            }
+        }
+
+        let ptr: *mut T = self.as_mut_ptr();
+        // SAFETY: all indices are in bounds and pairwise different
+        Some(indices.map(|i: usize| unsafe { &mut *ptr.add(i) }))
+    }
+}
+
+/// Extensions on `std::sync::Arc`, for passing reference counted objects through raw pointers
+///
+/// A pointer from `into_nonnull` owns one strong reference. `clone_from_nonnull` creates new
+/// references from it, and `from_nonnull` takes the owned one back:
+///
+/// ```
+/// use std::ffi::c_void;
+/// use std::ptr::NonNull;
+/// use std::sync::Arc;
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+///
+/// use xjbutil::std_ext::ArcExt;
+///
+/// // the C side only stores the `void *` and hands it back on each call
+/// extern "C" fn on_event(ctx: *mut c_void) {
+///     let ctx: NonNull<AtomicUsize> = NonNull::new(ctx as *mut AtomicUsize).unwrap();
+///     let counter: Arc<AtomicUsize> = unsafe { Arc::clone_from_nonnull(ctx) };
+///     counter.fetch_add(1, Ordering::SeqCst);
+/// }
+///
+/// extern "C" fn on_unregister(ctx: *mut c_void) {
+///     let ctx: NonNull<AtomicUsize> = NonNull::new(ctx as *mut AtomicUsize).unwrap();
+///     drop(unsafe { Arc::<AtomicUsize>::from_nonnull(ctx) });
+/// }
+///
+/// let counter: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(0));
+/// let ctx: *mut c_void = counter.clone().into_nonnull().as_ptr() as *mut c_void;
+/// on_event(ctx);
+/// on_event(ctx);
+/// on_unregister(ctx);
+/// assert_eq!(counter.load(Ordering::SeqCst), 2);
+/// assert_eq!(Arc::strong_count(&counter), 1);
+/// ```
+pub trait ArcExt<T: ?Sized> {
+    /// Turn the `Arc` into a pointer owning its strong reference, like `Arc::into_raw`
+    fn into_nonnull(self) -> NonNull<T>;
+
+    /// Take back the strong reference owned by a pointer, like `Arc::from_raw`
+    ///
+    /// # Safety
+    /// `ptr` must come from `into_nonnull` on an `Arc<T>`, and each pointer may only be taken back
+    /// once.
+    unsafe fn from_nonnull(ptr: NonNull<T>) -> Self;
+
+    /// Create a new strong reference from a pointer without taking back the one it owns
+    ///
+    /// # Safety
+    /// `ptr` must come from `into_nonnull` on an `Arc<T>` and must not have been taken back yet.
+    unsafe fn clone_from_nonnull(ptr: NonNull<T>) -> Self;
+}
+
+impl<T: ?Sized> ArcExt<T> for Arc<T> {
+    #[inline] fn into_nonnull(self) -> NonNull<T> {
+        unsafe { NonNull::new_unchecked(Arc::into_raw(self) as *mut T) }
+    }
+
+    #[inline] unsafe fn from_nonnull(ptr: NonNull<T>) -> Self {
+        Arc::from_raw(ptr.as_ptr())
+    }
+
+    #[inline] unsafe fn clone_from_nonnull(ptr: NonNull<T>) -> Self {
+        let borrowed: ManuallyDrop<Arc<T>> = ManuallyDrop::new(Arc::from_raw(ptr.as_ptr()));
+        Arc::clone(&borrowed)
+    }
+}
+
+/// Extensions on `std::rc::Rc`, the single-threaded counterpart of `ArcExt`
+pub trait RcExt<T: ?Sized> {
+    /// Turn the `Rc` into a pointer owning its strong reference, like `Rc::into_raw`
+    fn into_nonnull(self) -> NonNull<T>;
+
+    /// Take back the strong reference owned by a pointer, like `Rc::from_raw`
+    ///
+    /// # Safety
+    /// `ptr` must come from `into_nonnull` on an `Rc<T>`, and each pointer may only be taken back
+    /// once.
+    unsafe fn from_nonnull(ptr: NonNull<T>) -> Self;
+
+    /// Create a new strong reference from a pointer without taking back the one it owns
+    ///
+    /// # Safety
+    /// `ptr` must come from `into_nonnull` on an `Rc<T>` and must not have been taken back yet.
+    unsafe fn clone_from_nonnull(ptr: NonNull<T>) -> Self;
+}
+
+impl<T: ?Sized> RcExt<T> for Rc<T> {
+    #[inline] fn into_nonnull(self) -> NonNull<T> {
+        unsafe { NonNull::new_unchecked(Rc::into_raw(self) as *mut T) }
+    }
+
+    #[inline] unsafe fn from_nonnull(ptr: NonNull<T>) -> Self {
+        Rc::from_raw(ptr.as_ptr())
+    }
+
+    #[inline] unsafe fn clone_from_nonnull(ptr: NonNull<T>) -> Self {
+        let borrowed: ManuallyDrop<Rc<T>> = ManuallyDrop::new(Rc::from_raw(ptr.as_ptr()));
+        Rc::clone(&borrowed)
+    }
 }
 
 /// Extension on `std::result::Result` and `std::option::Option`
@@ -90,6 +515,132 @@ impl<T> ExpectSilentExt<T> for Option<T> {
     }
 }
 
+/// Extensions on `std::option::Option`, checked in debug builds and unchecked in release builds
+pub trait OptionExt<T> {
+    /// Like `unwrap_unchecked`, but panics on `None` in debug builds
+    ///
+    /// # Safety
+    /// `self` must be `Some`.
+    unsafe fn unwrap_unchecked_dbg(self) -> T;
+
+    /// Assert that `self` is `None`, panicking otherwise in debug builds
+    ///
+    /// # Safety
+    /// `self` must be `None`.
+    unsafe fn unwrap_none_dbg(self);
+}
+
+impl<T> OptionExt<T> for Option<T> {
+    #[inline]
+    #[track_caller]
+    unsafe fn unwrap_unchecked_dbg(self) -> T {
+        debug_assert!(self.is_some(), "called `Option::unwrap_unchecked_dbg()` on a `None` value");
+        self.unwrap_unchecked()
+    }
+
+    #[inline]
+    #[track_caller]
+    unsafe fn unwrap_none_dbg(self) {
+        debug_assert!(self.is_none(), "called `Option::unwrap_none_dbg()` on a `Some` value");
+        if self.is_some() {
+            unreachable_unchecked()
+        }
+    }
+}
+
+/// Debug-checked dereferencing of raw pointers, checked in debug builds and unchecked in
+/// release builds
+///
+/// Implemented for `*const T` and `*mut T`. The mutable operations are in `PtrMutExt`.
+pub trait PtrExt<T> {
+    /// Like `&*self`, but panics on null or misaligned pointers in debug builds
+    ///
+    /// # Safety
+    /// Same as `&*self`.
+    #[allow(clippy::wrong_self_convention)]
+    unsafe fn as_ref_dbg<'a>(self) -> &'a T;
+
+    /// Like `ptr::read(self)`, but panics on null or misaligned pointers in debug builds
+    ///
+    /// # Safety
+    /// Same as `ptr::read(self)`.
+    unsafe fn read_dbg(self) -> T;
+}
+
+/// Mutable counterpart of `PtrExt`, implemented for `*mut T`
+pub trait PtrMutExt<T> {
+    /// Like `&mut *self`, but panics on null or misaligned pointers in debug builds
+    ///
+    /// # Safety
+    /// Same as `&mut *self`.
+    #[allow(clippy::wrong_self_convention)]
+    unsafe fn as_mut_dbg<'a>(self) -> &'a mut T;
+
+    /// Like `ptr::write(self, value)`, but panics on null or misaligned pointers in debug builds
+    ///
+    /// # Safety
+    /// Same as `ptr::write(self, value)`.
+    unsafe fn write_dbg(self, value: T);
+}
+
+#[inline(always)]
+#[track_caller]
+fn debug_check_ptr<T>(ptr: *const T) {
+    debug_assert!(!ptr.is_null(), "dereferencing null pointer to `{}`", std::any::type_name::<T>());
+    debug_assert!(
+        ptr.is_aligned(),
+        "dereferencing misaligned pointer {:p} to `{}`",
+        ptr,
+        std::any::type_name::<T>()
+    );
+}
+
+impl<T> PtrExt<T> for *const T {
+    #[inline]
+    #[track_caller]
+    unsafe fn as_ref_dbg<'a>(self) -> &'a T {
+        debug_check_ptr(self);
+        &*self
+    }
+
+    #[inline]
+    #[track_caller]
+    unsafe fn read_dbg(self) -> T {
+        debug_check_ptr(self);
+        ptr::read(self)
+    }
+}
+
+impl<T> PtrExt<T> for *mut T {
+    #[inline]
+    #[track_caller]
+    unsafe fn as_ref_dbg<'a>(self) -> &'a T {
+        (self as *const T).as_ref_dbg()
+    }
+
+    #[inline]
+    #[track_caller]
+    unsafe fn read_dbg(self) -> T {
+        (self as *const T).read_dbg()
+    }
+}
+
+impl<T> PtrMutExt<T> for *mut T {
+    #[inline]
+    #[track_caller]
+    unsafe fn as_mut_dbg<'a>(self) -> &'a mut T {
+        debug_check_ptr(self);
+        &mut *self
+    }
+
+    #[inline]
+    #[track_caller]
+    unsafe fn write_dbg(self, value: T) {
+        debug_check_ptr(self);
+        ptr::write(self, value)
+    }
+}
+
 /// Extensions on `std::result::Result`
 pub trait ResultExt<T, E> {
     /// Like normal `expect`, but does not require a `Debug` implementation on your `Err` variant.
@@ -105,6 +656,21 @@ pub trait ResultExt<T, E> {
     /// Like normal `unwrap_err`, but does not require a `Debug` implementation on your
     /// `Ok` variant
     fn unwrap_err_no_debug(self) -> E;
+
+    /// Like `unwrap_unchecked`, but panics with the `Err` value in debug builds
+    ///
+    /// # Safety
+    /// `self` must be `Ok`.
+    unsafe fn unwrap_unchecked_dbg(self) -> T where E: Debug;
+
+    /// Like `unwrap_err_unchecked`, but panics with the `Ok` value in debug builds
+    ///
+    /// # Safety
+    /// `self` must be `Err`.
+    unsafe fn unwrap_err_unchecked_dbg(self) -> E where T: Debug;
+
+    /// `Ok` becomes `Either::Left` and `Err` becomes `Either::Right`
+    fn into_either(self) -> Either<T, E>;
 }
 
 impl<T, E> ResultExt<T, E> for Result<T, E> {
@@ -147,17 +713,84 @@ impl<T, E> ResultExt<T, E> for Result<T, E> {
             panic!("called `Result::unwrap_err_no_debug()` on an `Ok` value")
         }
     }
+
+    #[inline]
+    #[track_caller]
+    unsafe fn unwrap_unchecked_dbg(self) -> T where E: Debug {
+        match self {
+            Ok(result) => result,
+            #[cfg(debug_assertions)]
+            Err(err) => panic!(
+                "called `Result::unwrap_unchecked_dbg()` on an `Err` value: {:?}",
+                err
+            ),
+            #[cfg(not(debug_assertions))]
+            Err(_) => unreachable_unchecked()
+        }
+    }
+
+    #[inline]
+    #[track_caller]
+    unsafe fn unwrap_err_unchecked_dbg(self) -> E where T: Debug {
+        match self {
+            Err(err) => err,
+            #[cfg(debug_assertions)]
+            Ok(result) => panic!(
+                "called `Result::unwrap_err_unchecked_dbg()` on an `Ok` value: {:?}",
+                result
+            ),
+            #[cfg(not(debug_assertions))]
+            Ok(_) => unreachable_unchecked()
+        }
+    }
+
+    #[inline] fn into_either(self) -> Either<T, E> {
+        match self {
+            Ok(result) => Either::Left(result),
+            Err(err) => Either::Right(err)
+        }
+    }
 }
 
-pub unsafe fn extend_lifetime<'a, T: ?Sized>(src: &'a T) -> &'static T {
+/// Extend the lifetime of given reference to `'static`.
+///
+/// # Safety
+/// The referenced object must outlive all usages of the returned reference.
+pub const unsafe fn extend_lifetime<'a, T: ?Sized>(src: &'a T) -> &'static T {
     transmute::<&'a T, &'static T>(src)
 }
 
 #[cfg(test)]
 mod test {
+    use std::any::Any;
+    use std::cell::Cell;
+    use std::panic::{AssertUnwindSafe, catch_unwind};
     use std::ptr::NonNull;
+    use std::rc::Rc;
+    use std::sync::Arc;
 
-    use crate::std_ext::{BoxedExt, VecExt};
+    use crate::either::Either;
+    use crate::std_ext::{
+        AnyPtrExt,
+        ArcExt,
+        BoxedExt,
+        NonNullExt,
+        OptionExt,
+        PtrExt,
+        PtrMutExt,
+        RcExt,
+        ResultExt,
+        SliceExt,
+        SlicePtrExt,
+        StrExt,
+        StringExt,
+        VecExt,
+        nonnull_slice_from_raw_parts,
+        nonnull_slice_len,
+        reclaim_slice_ptr,
+        reclaim_string
+    };
+    use crate::test_intern::DropCounter;
 
     #[test]
     fn test() {
@@ -172,4 +805,389 @@ mod test {
         let boxed: Box<[i32]> = unsafe { Box::reclaim(slice_ptr) };
         drop(boxed);
     }
+
+    struct Counted<'a>(&'a Cell<usize>, i32);
+
+    impl Drop for Counted<'_> {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    fn values(v: &[Counted]) -> Vec<i32> {
+        v.iter().map(|c: &Counted| c.1).collect()
+    }
+
+    #[test]
+    fn test_any_ptr_ext() {
+        let ptr: NonNull<dyn Any + Send> = Box::new(String::from("514")).leak_as_nonnull();
+        unsafe {
+            assert!(ptr.is_type::<String>() && !ptr.is_type::<&str>());
+            assert_eq!(ptr.downcast_ref_unchecked::<String>(), "514");
+        }
+
+        // a wrong type gives back the very same pointer
+        let original: NonNull<dyn Any + Send> = ptr;
+        let ptr: NonNull<dyn Any + Send> = unsafe { ptr.downcast_ptr::<i32>() }.unwrap_err();
+        assert_eq!(ptr.as_ptr() as *mut u8, original.as_ptr() as *mut u8);
+        let ptr: NonNull<String> = unsafe { ptr.downcast_ptr::<String>() }.unwrap();
+        let mut boxed: Box<String> = unsafe { Box::reclaim(ptr) };
+        boxed.push_str("1919");
+        assert_eq!(*boxed, "5141919");
+
+        let ptr: NonNull<dyn Any + Send + Sync> = Box::new(vec![810_u64]).leak_as_nonnull();
+        let ptr: NonNull<Vec<u64>> = unsafe { ptr.downcast_ptr() }.unwrap();
+        assert_eq!(*unsafe { Box::reclaim(ptr) }, vec![810]);
+    }
+
+    #[test]
+    fn test_any_ptr_ext_drop() {
+        let drops: Rc<Cell<usize>> = Rc::new(Cell::new(0));
+        let ptr: NonNull<dyn Any> = Box::new(DropCounter(drops.clone(), 893)).leak_as_nonnull();
+        let ptr: NonNull<dyn Any> = unsafe { ptr.downcast_ptr::<String>() }.unwrap_err();
+        assert_eq!(unsafe { ptr.downcast_ref_unchecked::<DropCounter>() }.1, 893);
+        assert_eq!(drops.get(), 0);
+
+        let ptr: NonNull<DropCounter> = unsafe { ptr.downcast_ptr() }.unwrap();
+        drop(unsafe { Box::reclaim(ptr) });
+        assert_eq!(drops.get(), 1);
+    }
+
+    #[test]
+    fn test_extract_if_into() {
+        let drops: Cell<usize> = Cell::new(0);
+        let mut v: Vec<Counted> = (0..10).map(|i: i32| Counted(&drops, i)).collect();
+        let mut sink: Vec<Counted> = vec![Counted(&drops, -1)];
+        v.extract_if_into(|c: &mut Counted| { c.1 *= 10; c.1 % 30 == 0 }, &mut sink);
+        assert_eq!(values(&v), vec![10, 20, 40, 50, 70, 80]);
+        assert_eq!(values(&sink), vec![-1, 0, 30, 60, 90]);
+        assert_eq!(drops.get(), 0);
+
+        v.extract_if_into(|_| false, &mut sink);
+        assert_eq!(v.len(), 6);
+        v.extract_if_into(|_| true, &mut sink);
+        assert!(v.is_empty());
+        assert_eq!(sink.len(), 11);
+        drop(sink);
+        assert_eq!(drops.get(), 11);
+    }
+
+    #[test]
+    fn test_extract_if_into_panic() {
+        let drops: Cell<usize> = Cell::new(0);
+        let mut v: Vec<Counted> = (0..6).map(|i: i32| Counted(&drops, i)).collect();
+        let mut sink: Vec<Counted> = Vec::new();
+        let result: std::thread::Result<()> = catch_unwind(AssertUnwindSafe(|| {
+            let pred = |c: &mut Counted| if c.1 == 3 { panic!() } else { c.1 % 2 == 0 };
+            v.extract_if_into(pred, &mut sink);
+        }));
+        assert!(result.is_err());
+        assert_eq!(values(&v), vec![1, 3, 4, 5]);
+        assert_eq!(values(&sink), vec![0, 2]);
+        assert_eq!(drops.get(), 0);
+        drop(v);
+        drop(sink);
+        assert_eq!(drops.get(), 6);
+    }
+
+    #[test]
+    fn test_retain_map() {
+        let drops: Cell<usize> = Cell::new(0);
+        let mut v: Vec<Counted> = (0..6).map(|i: i32| Counted(&drops, i)).collect();
+        let extracted: Vec<String> = v.retain_map(|c: Counted| {
+            if c.1 % 3 == 0 { Either::Right(format!("{}", c.1 * 100)) } else { Either::Left(c) }
+        });
+        assert_eq!(extracted, vec!["0", "300"]);
+        assert_eq!(values(&v), vec![1, 2, 4, 5]);
+        assert_eq!(drops.get(), 2);
+        drop(v);
+        assert_eq!(drops.get(), 6);
+    }
+
+    #[test]
+    fn test_retain_map_panic() {
+        let drops: Cell<usize> = Cell::new(0);
+        let mut v: Vec<Counted> = (0..6).map(|i: i32| Counted(&drops, i)).collect();
+        let result: std::thread::Result<Vec<i32>> = catch_unwind(AssertUnwindSafe(|| {
+            v.retain_map(|c: Counted| match c.1 {
+                1 => Either::Right(c.1),
+                3 => panic!(),
+                _ => Either::Left(c)
+            })
+        }));
+        assert!(result.is_err());
+        // 1 was extracted and 3 was dropped while unwinding
+        assert_eq!(drops.get(), 2);
+        assert_eq!(values(&v), vec![0, 2, 4, 5]);
+        drop(v);
+        assert_eq!(drops.get(), 6);
+    }
+
+    #[test]
+    fn test_get_two_mut() {
+        let mut registers: [i32; 4] = [114, 514, 1919, 810];
+        let (a, b) = registers.get_two_mut(3, 1);
+        std::mem::swap(a, b);
+        assert_eq!(registers, [114, 810, 1919, 514]);
+
+        assert!(registers.try_get_two_mut(2, 2).is_none());
+        assert!(registers.try_get_two_mut(0, 4).is_none());
+        let (a, b) = registers.try_get_two_mut(0, 2).unwrap();
+        *a += *b;
+        assert_eq!(registers[0], 2033);
+    }
+
+    #[test]
+    #[should_panic(expected = "`get_two_mut` called with equal indices")]
+    fn test_get_two_mut_equal() {
+        [1, 2, 3].get_two_mut(1, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "indices (0, 3) out of bounds for length 3")]
+    fn test_get_two_mut_out_of_bounds() {
+        [1, 2, 3].get_two_mut(0, 3);
+    }
+
+    #[test]
+    fn test_get_many_mut() {
+        let mut v: Vec<String> = vec!["114".into(), "514".into(), "1919".into(), "810".into()];
+        let [a, b, c] = v.get_many_mut([2, 0, 3]).unwrap();
+        a.push_str(b);
+        c.push_str(a);
+        b.clear();
+        assert_eq!(v, vec!["", "514", "1919114", "8101919114"]);
+
+        assert!(v.get_many_mut([0, 1, 0]).is_none());
+        assert!(v.get_many_mut([3, 4]).is_none());
+        assert!(v.get_many_mut([usize::MAX]).is_none());
+        assert!(v.get_many_mut([]).is_some());
+        assert!(Vec::<i32>::new().get_many_mut([0]).is_none());
+    }
+
+    #[test]
+    fn test_reclaim_slice_ptr() {
+        let drops: Cell<usize> = Cell::new(0);
+        let mut v: Vec<Counted> = Vec::with_capacity(8);
+        v.extend((0..3).map(|i: i32| Counted(&drops, i)));
+        let ptr: NonNull<[Counted]> = v.into_slice_ptr();
+        assert_eq!(nonnull_slice_len(ptr), 3);
+        assert_eq!(drops.get(), 0);
+
+        let v: Vec<Counted> = unsafe { reclaim_slice_ptr(ptr) };
+        assert_eq!((v.len(), v.capacity()), (3, 3));
+        assert_eq!(v.iter().map(|c: &Counted| c.1).collect::<Vec<i32>>(), vec![0, 1, 2]);
+        let ptr: NonNull<[Counted]> = v.into_slice_ptr();
+        let v: Vec<Counted> = unsafe { ptr.reclaim_vec() };
+        drop(v);
+        assert_eq!(drops.get(), 3);
+
+        let ptr: NonNull<[Counted]> = vec![Counted(&drops, 893)].into_slice_ptr();
+        let boxed: Box<[Counted]> = unsafe { ptr.reclaim_boxed() };
+        assert_eq!(boxed[0].1, 893);
+        drop(boxed);
+        assert_eq!(drops.get(), 4);
+
+        let ptr: NonNull<[Counted]> = Vec::with_capacity(4).into_slice_ptr();
+        assert_eq!(nonnull_slice_len(ptr), 0);
+        let v: Vec<Counted> = unsafe { ptr.reclaim_vec() };
+        assert_eq!((v.len(), v.capacity()), (0, 0));
+        assert_eq!(drops.get(), 4);
+    }
+
+    #[test]
+    fn test_string_ext() {
+        let mut s: String = String::with_capacity(64);
+        s.push_str("114514");
+        let ptr: NonNull<str> = s.into_str_ptr();
+        assert_eq!(unsafe { ptr.as_ref() }, "114514");
+
+        let mut s: String = unsafe { reclaim_string(ptr) };
+        assert_eq!(s, "114514");
+        assert_eq!(s.capacity(), s.len());
+        s.push_str("1919810");
+
+        let ptr: NonNull<str> = s.into_str_ptr();
+        let s: String = unsafe { reclaim_string(ptr) };
+        assert_eq!((s.as_str(), s.capacity()), ("1145141919810", 13));
+
+        let empty: String = unsafe { reclaim_string(String::new().into_str_ptr()) };
+        assert_eq!((empty.len(), empty.capacity()), (0, 0));
+    }
+
+    // leaks on purpose, which Miri reports
+    #[cfg_attr(miri, ignore)]
+    #[test]
+    fn test_leak_to_static() {
+        let leaked: &'static str = String::from("893").into_boxed_str().leak_to_static();
+        assert_eq!(leaked, "893");
+    }
+
+    #[test]
+    fn test_option_ext() {
+        let value: String = unsafe { Some(String::from("114514")).unwrap_unchecked_dbg() };
+        assert_eq!(value, "114514");
+        unsafe { None::<String>.unwrap_none_dbg() };
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "called `Option::unwrap_unchecked_dbg()` on a `None` value")]
+    fn test_unwrap_unchecked_dbg_none() {
+        unsafe { None::<i32>.unwrap_unchecked_dbg() };
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "called `Option::unwrap_none_dbg()` on a `Some` value")]
+    fn test_unwrap_none_dbg_some() {
+        unsafe { Some(1919810).unwrap_none_dbg() };
+    }
+
+    #[test]
+    fn test_result_ext_unchecked() {
+        let ok: Result<i32, String> = Ok(114);
+        let err: Result<i32, String> = Err("514".into());
+        assert_eq!(unsafe { ok.unwrap_unchecked_dbg() }, 114);
+        assert_eq!(unsafe { err.unwrap_err_unchecked_dbg() }, "514");
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "`Result::unwrap_unchecked_dbg()` on an `Err` value: \"1919\"")]
+    fn test_unwrap_unchecked_dbg_err() {
+        unsafe { Err::<i32, &str>("1919").unwrap_unchecked_dbg() };
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "`Result::unwrap_err_unchecked_dbg()` on an `Ok` value: [810]")]
+    fn test_unwrap_err_unchecked_dbg_ok() {
+        unsafe { Ok::<Vec<i32>, i32>(vec![810]).unwrap_err_unchecked_dbg() };
+    }
+
+    #[test]
+    fn test_result_into_either() {
+        let ok: Result<i32, &str> = Ok(893);
+        let err: Result<i32, &str> = Err("114514");
+        assert_eq!(ok.into_either(), Either::Left(893));
+        assert_eq!(err.into_either(), Either::Right("114514"));
+
+        // and back
+        let back: Result<i32, &str> = match err.into_either() {
+            Either::Left(result) => Ok(result),
+            Either::Right(err) => Err(err)
+        };
+        assert_eq!(back, err);
+    }
+
+    #[test]
+    fn test_ptr_ext() {
+        let mut values: [u32; 3] = [114, 514, 1919];
+        let ptr: *mut u32 = values.as_mut_ptr();
+        unsafe {
+            assert_eq!(*ptr.as_ref_dbg(), 114);
+            *ptr.add(1).as_mut_dbg() += 400;
+            ptr.add(2).write_dbg(810);
+            assert_eq!((ptr as *const u32).add(1).read_dbg(), 914);
+            assert_eq!(ptr.add(2).read_dbg(), 810);
+        }
+        assert_eq!(values, [114, 914, 810]);
+
+        let s: String = "893".into();
+        let copy: String = unsafe { (&s as *const String).read_dbg() };
+        std::mem::forget(s);
+        assert_eq!(copy, "893");
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "dereferencing null pointer to `u64`")]
+    fn test_ptr_ext_null() {
+        unsafe { std::ptr::null::<u64>().as_ref_dbg(); }
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "dereferencing misaligned pointer")]
+    fn test_ptr_ext_misaligned() {
+        let mut values: [u32; 2] = [0; 2];
+        let ptr: *mut u32 = (values.as_mut_ptr() as *mut u8).wrapping_add(1) as *mut u32;
+        unsafe { ptr.write_dbg(114514); }
+    }
+
+    #[test]
+    fn test_nonnull_ext() {
+        let mut values: [u16; 4] = [114, 514, 1919, 810];
+        let base: NonNull<u16> = NonNull::from(&mut values).cast_ext::<u16>();
+        unsafe {
+            assert_eq!(*base.add_ext(0).as_ptr(), 114);
+            *base.add_ext(2).as_ptr() += 1;
+            assert_eq!(base.byte_add_ext(6), base.add_ext(3));
+            *base.byte_add_ext(2).as_ptr() = 893;
+        }
+
+        let tail: NonNull<[u16]> = nonnull_slice_from_raw_parts(unsafe { base.add_ext(1) }, 3);
+        assert_eq!(nonnull_slice_len(tail), 3);
+        assert_eq!(unsafe { tail.as_ref() }, &[893, 1920, 810]);
+        unsafe { (*tail.as_ptr())[2] = 0 };
+        assert_eq!(values, [114, 893, 1920, 0]);
+
+        // the end pointer of a slice is valid to compute, but not to dereference
+        let end: NonNull<u16> = unsafe { base.add_ext(4) };
+        let empty: NonNull<[u16]> = nonnull_slice_from_raw_parts(end, 0);
+        assert_eq!(nonnull_slice_len(empty), 0);
+        assert!(unsafe { empty.as_ref() }.is_empty());
+
+        let dangling: NonNull<[String]> = nonnull_slice_from_raw_parts(NonNull::dangling(), 0);
+        assert_eq!(nonnull_slice_len(dangling), 0);
+        let boxed: Box<[String]> = vec!["1919".to_string()].into_boxed_slice();
+        let ptr: NonNull<[String]> = boxed.leak_as_nonnull();
+        let rebuilt: NonNull<[String]> =
+            nonnull_slice_from_raw_parts(ptr.cast::<String>(), nonnull_slice_len(ptr));
+        assert_eq!(unsafe { rebuilt.reclaim_vec() }, vec!["1919"]);
+    }
+
+    #[test]
+    fn test_arc_ext() {
+        let arc: Arc<String> = Arc::new("114514".into());
+        let ptr: NonNull<String> = arc.clone().into_nonnull();
+        assert_eq!(Arc::strong_count(&arc), 2);
+        assert_eq!(unsafe { ptr.as_ref() }, "114514");
+
+        let cloned: Arc<String> = unsafe { Arc::clone_from_nonnull(ptr) };
+        assert_eq!(Arc::strong_count(&arc), 3);
+        assert!(Arc::ptr_eq(&arc, &cloned));
+        drop(cloned);
+        assert_eq!(Arc::strong_count(&arc), 2);
+
+        let reclaimed: Arc<String> = unsafe { Arc::from_nonnull(ptr) };
+        assert!(Arc::ptr_eq(&arc, &reclaimed));
+        drop(reclaimed);
+        assert_eq!(Arc::strong_count(&arc), 1);
+
+        let slice: Arc<[i32]> = Arc::from(vec![1919, 810]);
+        let ptr: NonNull<[i32]> = slice.into_nonnull();
+        let cloned: Arc<[i32]> = unsafe { Arc::clone_from_nonnull(ptr) };
+        let reclaimed: Arc<[i32]> = unsafe { Arc::from_nonnull(ptr) };
+        assert_eq!(Arc::strong_count(&reclaimed), 2);
+        assert_eq!(&*cloned, &[1919, 810]);
+    }
+
+    #[test]
+    fn test_rc_ext() {
+        let rc: Rc<Vec<i32>> = Rc::new(vec![893]);
+        let ptr: NonNull<Vec<i32>> = rc.clone().into_nonnull();
+        assert_eq!(Rc::strong_count(&rc), 2);
+
+        let cloned: Rc<Vec<i32>> = unsafe { Rc::clone_from_nonnull(ptr) };
+        assert_eq!(Rc::strong_count(&rc), 3);
+        drop(cloned);
+
+        let reclaimed: Rc<Vec<i32>> = unsafe { Rc::from_nonnull(ptr) };
+        assert!(Rc::ptr_eq(&rc, &reclaimed));
+        drop(reclaimed);
+        assert_eq!(Rc::strong_count(&rc), 1);
+        assert_eq!(Rc::try_unwrap(rc), Ok(vec![893]));
+    }
 }
@@ -0,0 +1,274 @@
+//! Single-word pointers to either of two types, tagged with the lowest address bit
+//!
+//! Both types must be aligned to at least 2 bytes, so that bit 0 of their addresses is always
+//! zero and can hold the discriminant. This is checked at compile time:
+//!
+//! ```compile_fail
+//! # use xjbutil::tagged_ptr::TaggedRef;
+//! let byte: u8 = 0;
+//! let _ = TaggedRef::<u8, u32>::from_left(&byte);
+//! ```
+
+use core::marker::PhantomData;
+use core::mem::{align_of, forget};
+use core::ptr::NonNull;
+
+use crate::either::Either;
+use crate::korobka::Korobka;
+
+const TAG_MASK: usize = 1;
+
+struct AlignCheck<L, R>(PhantomData<(L, R)>);
+
+impl<L, R> AlignCheck<L, R> {
+    const OK: () = assert!(
+        align_of::<L>() >= 2 && align_of::<R>() >= 2,
+        "types stored in tagged pointers must have alignment of at least 2"
+    );
+}
+
+fn tag<T>(ptr: NonNull<T>, right: bool) -> NonNull<()> {
+    let ptr: *mut u8 = (ptr.as_ptr() as *mut u8).wrapping_add(right as usize);
+    unsafe { NonNull::new_unchecked(ptr as *mut ()) }
+}
+
+fn is_right(ptr: NonNull<()>) -> bool {
+    (ptr.as_ptr() as *const u8 as usize) & TAG_MASK != 0
+}
+
+fn untag<T>(ptr: NonNull<()>) -> NonNull<T> {
+    let offset: usize = is_right(ptr) as usize;
+    let ptr: *mut u8 = (ptr.as_ptr() as *mut u8).wrapping_sub(offset);
+    unsafe { NonNull::new_unchecked(ptr as *mut T) }
+}
+
+/// Owning pointer to either a `L` or a `R`, in one word
+pub struct TaggedPtr<L, R> {
+    ptr: NonNull<()>,
+    _phantom: PhantomData<Either<Korobka<L>, Korobka<R>>>
+}
+
+unsafe impl<L: Send, R: Send> Send for TaggedPtr<L, R> {}
+unsafe impl<L: Sync, R: Sync> Sync for TaggedPtr<L, R> {}
+
+impl<L, R> TaggedPtr<L, R> {
+    pub fn from_left(left: Korobka<L>) -> Self {
+        #[allow(clippy::let_unit_value)]
+        let _ = AlignCheck::<L, R>::OK;
+        Self { ptr: tag(Korobka::into_nonnull(left), false), _phantom: PhantomData }
+    }
+
+    pub fn from_right(right: Korobka<R>) -> Self {
+        #[allow(clippy::let_unit_value)]
+        let _ = AlignCheck::<L, R>::OK;
+        Self { ptr: tag(Korobka::into_nonnull(right), true), _phantom: PhantomData }
+    }
+
+    #[inline(always)] pub fn is_left(&self) -> bool {
+        !is_right(self.ptr)
+    }
+
+    #[inline(always)] pub fn is_right(&self) -> bool {
+        is_right(self.ptr)
+    }
+
+    pub fn as_either(&self) -> Either<&L, &R> {
+        if self.is_right() {
+            Either::Right(unsafe { &*untag::<R>(self.ptr).as_ptr() })
+        } else {
+            Either::Left(unsafe { &*untag::<L>(self.ptr).as_ptr() })
+        }
+    }
+
+    pub fn as_either_mut(&mut self) -> Either<&mut L, &mut R> {
+        if self.is_right() {
+            Either::Right(unsafe { &mut *untag::<R>(self.ptr).as_ptr() })
+        } else {
+            Either::Left(unsafe { &mut *untag::<L>(self.ptr).as_ptr() })
+        }
+    }
+
+    pub fn into_either(self) -> Either<Korobka<L>, Korobka<R>> {
+        let ptr: NonNull<()> = self.ptr;
+        forget(self);
+        unsafe { Self::reclaim(ptr) }
+    }
+
+    unsafe fn reclaim(ptr: NonNull<()>) -> Either<Korobka<L>, Korobka<R>> {
+        if is_right(ptr) {
+            Either::Right(Korobka::from_nonnull(untag::<R>(ptr)))
+        } else {
+            Either::Left(Korobka::from_nonnull(untag::<L>(ptr)))
+        }
+    }
+}
+
+impl<L, R> From<Either<Korobka<L>, Korobka<R>>> for TaggedPtr<L, R> {
+    fn from(either: Either<Korobka<L>, Korobka<R>>) -> Self {
+        match either {
+            Either::Left(left) => Self::from_left(left),
+            Either::Right(right) => Self::from_right(right)
+        }
+    }
+}
+
+impl<L, R> Drop for TaggedPtr<L, R> {
+    fn drop(&mut self) {
+        drop(unsafe { Self::reclaim(self.ptr) });
+    }
+}
+
+/// Borrowing counterpart of `TaggedPtr`
+pub struct TaggedRef<'a, L, R> {
+    ptr: NonNull<()>,
+    _phantom: PhantomData<Either<&'a L, &'a R>>
+}
+
+unsafe impl<'a, L: Sync, R: Sync> Send for TaggedRef<'a, L, R> {}
+unsafe impl<'a, L: Sync, R: Sync> Sync for TaggedRef<'a, L, R> {}
+
+impl<'a, L, R> Clone for TaggedRef<'a, L, R> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, L, R> Copy for TaggedRef<'a, L, R> {}
+
+impl<'a, L, R> TaggedRef<'a, L, R> {
+    pub fn from_left(left: &'a L) -> Self {
+        #[allow(clippy::let_unit_value)]
+        let _ = AlignCheck::<L, R>::OK;
+        Self { ptr: tag(NonNull::from(left), false), _phantom: PhantomData }
+    }
+
+    pub fn from_right(right: &'a R) -> Self {
+        #[allow(clippy::let_unit_value)]
+        let _ = AlignCheck::<L, R>::OK;
+        Self { ptr: tag(NonNull::from(right), true), _phantom: PhantomData }
+    }
+
+    #[inline(always)] pub fn is_left(&self) -> bool {
+        !is_right(self.ptr)
+    }
+
+    #[inline(always)] pub fn is_right(&self) -> bool {
+        is_right(self.ptr)
+    }
+
+    pub fn as_either(&self) -> Either<&'a L, &'a R> {
+        if self.is_right() {
+            Either::Right(unsafe { &*untag::<R>(self.ptr).as_ptr() })
+        } else {
+            Either::Left(unsafe { &*untag::<L>(self.ptr).as_ptr() })
+        }
+    }
+}
+
+impl<'a, L, R> From<Either<&'a L, &'a R>> for TaggedRef<'a, L, R> {
+    fn from(either: Either<&'a L, &'a R>) -> Self {
+        match either {
+            Either::Left(left) => Self::from_left(left),
+            Either::Right(right) => Self::from_right(right)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+    use std::mem::size_of;
+
+    use crate::either::Either;
+    use crate::korobka::Korobka;
+    use crate::tagged_ptr::{TaggedPtr, TaggedRef};
+
+    struct DropCounter<'a>(u16, &'a Cell<usize>);
+
+    impl Drop for DropCounter<'_> {
+        fn drop(&mut self) {
+            self.1.set(self.1.get() + 1);
+        }
+    }
+
+    #[test]
+    fn test_size() {
+        assert_eq!(size_of::<TaggedPtr<u16, String>>(), size_of::<usize>());
+        assert_eq!(size_of::<Option<TaggedPtr<u16, String>>>(), size_of::<usize>());
+        assert_eq!(size_of::<TaggedRef<u64, [u32; 3]>>(), size_of::<usize>());
+        #[cfg(target_pointer_width = "64")]
+        assert_eq!(size_of::<TaggedPtr<u16, String>>(), 8);
+    }
+
+    #[test]
+    fn test_tagged_ptr_round_trip() {
+        let mut left: TaggedPtr<u16, String> = TaggedPtr::from_left(Korobka::new(114));
+        let mut right: TaggedPtr<u16, String> = Either::Right(Korobka::new("514".into())).into();
+        assert!(left.is_left() && !left.is_right());
+        assert!(right.is_right() && !right.is_left());
+        assert_eq!(left.as_either(), Either::Left(&114));
+        assert_eq!(right.as_either(), Either::Right(&"514".to_string()));
+
+        if let Either::Left(x) = left.as_either_mut() {
+            *x += 400;
+        }
+        if let Either::Right(s) = right.as_either_mut() {
+            s.push_str("1919");
+        }
+
+        match left.into_either() {
+            Either::Left(x) => assert_eq!(*x, 514),
+            Either::Right(_) => unreachable!()
+        }
+        match right.into_either() {
+            Either::Left(_) => unreachable!(),
+            Either::Right(s) => assert_eq!(s.as_str(), "5141919")
+        }
+    }
+
+    #[test]
+    fn test_tagged_ptr_drop() {
+        let counter: Cell<usize> = Cell::new(0);
+        {
+            let _left: TaggedPtr<DropCounter, DropCounter> =
+                TaggedPtr::from_left(Korobka::new(DropCounter(1, &counter)));
+            let _right: TaggedPtr<DropCounter, DropCounter> =
+                TaggedPtr::from_right(Korobka::new(DropCounter(2, &counter)));
+        }
+        assert_eq!(counter.get(), 2);
+
+        let right: TaggedPtr<DropCounter, DropCounter> =
+            TaggedPtr::from_right(Korobka::new(DropCounter(3, &counter)));
+        let inner: Korobka<DropCounter> = match right.into_either() {
+            Either::Left(_) => unreachable!(),
+            Either::Right(inner) => inner
+        };
+        assert_eq!(counter.get(), 2);
+        assert_eq!(inner.0, 3);
+        drop(inner);
+        assert_eq!(counter.get(), 3);
+    }
+
+    #[test]
+    fn test_tagged_ref() {
+        let values: Vec<u32> = vec![1919, 810];
+        let name: String = "893".into();
+        let refs: Vec<TaggedRef<u32, String>> = vec![
+            TaggedRef::from_left(&values[0]),
+            TaggedRef::from_right(&name),
+            Either::Left(&values[1]).into()
+        ];
+        let copied: TaggedRef<u32, String> = refs[1];
+        assert_eq!(copied.as_either(), Either::Right(&name));
+        match copied.as_either() {
+            Either::Left(_) => unreachable!(),
+            Either::Right(s) => assert!(std::ptr::eq(s, &name))
+        }
+
+        let lefts: Vec<u32> = refs.iter().filter_map(|r| match r.as_either() {
+            Either::Left(x) => Some(*x),
+            Either::Right(_) => None
+        }).collect();
+        assert_eq!(lefts, vec![1919, 810]);
+    }
+}
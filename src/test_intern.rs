@@ -0,0 +1,13 @@
+//! Helpers shared by the unit tests of several modules
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+/// Counts its drops in the shared counter, and carries a value to tell instances apart
+pub(crate) struct DropCounter(pub(crate) Rc<Cell<usize>>, pub(crate) i32);
+
+impl Drop for DropCounter {
+    fn drop(&mut self) {
+        self.0.set(self.0.get() + 1);
+    }
+}
@@ -1,27 +1,38 @@
-use std::alloc::{Layout, alloc, dealloc};
+use std::alloc::{Layout, alloc, dealloc, handle_alloc_error};
 use std::cell::UnsafeCell;
+use std::ptr::NonNull;
 
 use unchecked_unwrap::UncheckedUnwrap;
 
 use crate::unchecked_intern::UncheckedCellOps;
 
-struct ArenaDebris<T, const DEBRIS_SIZE: usize> {
+/// Debris grow no larger than this, unless a single `DEBRIS_SIZE` debris is already larger
+const MAX_DEBRIS_BYTES: usize = 1 << 20;
+
+struct ArenaDebris<T> {
     mem: *mut T,
+    capacity: usize,
     usage: usize
 }
 
-impl<T, const DEBRIS_SIZE: usize> ArenaDebris<T, DEBRIS_SIZE> {
-    fn new() -> Self {
-        let layout: Layout = Layout::array::<T>(DEBRIS_SIZE)
+impl<T> ArenaDebris<T> {
+    fn new(capacity: usize) -> Self {
+        let layout: Layout = Layout::array::<T>(capacity)
             .unwrap();
-        Self {
-            mem: unsafe { alloc(layout) as _ },
-            usage: 0
-        }
+        let mem: *mut T = if layout.size() == 0 {
+            NonNull::dangling().as_ptr()
+        } else {
+            let mem: *mut T = unsafe { alloc(layout) as _ };
+            if mem.is_null() {
+                handle_alloc_error(layout);
+            }
+            mem
+        };
+        Self { mem, capacity, usage: 0 }
     }
 
     #[inline] fn has_rest(&self) -> bool {
-        self.usage < DEBRIS_SIZE
+        self.usage < self.capacity
     }
 
     unsafe fn allocate(&mut self, data: T) -> *mut T {
@@ -32,7 +43,7 @@ impl<T, const DEBRIS_SIZE: usize> ArenaDebris<T, DEBRIS_SIZE> {
     }
 }
 
-impl<T, const DEBRIS_SIZE: usize> Drop for ArenaDebris<T, DEBRIS_SIZE> {
+impl<T> Drop for ArenaDebris<T> {
     fn drop(&mut self) {
         unsafe {
             for i in 0..self.usage {
@@ -40,9 +51,11 @@ impl<T, const DEBRIS_SIZE: usize> Drop for ArenaDebris<T, DEBRIS_SIZE> {
                 ptr.drop_in_place();
             }
 
-            let layout: Layout = Layout::array::<T>(DEBRIS_SIZE)
+            let layout: Layout = Layout::array::<T>(self.capacity)
                 .unwrap();
-            dealloc(self.mem as _, layout);
+            if layout.size() != 0 {
+                dealloc(self.mem as _, layout);
+            }
         }
     }
 }
@@ -124,40 +137,88 @@ pub unsafe trait IntoArenaPtr {
     }
 }
 
+/// Arena allocating objects of type `T`, which are all dropped together with the arena
+///
+/// Objects are stored in debris, which are never moved or freed before the arena gets dropped,
+/// so addresses of allocated objects stay stable. The first debris holds `DEBRIS_SIZE` objects,
+/// and each following one doubles the capacity, up to 1 MiB per debris. Objects get dropped in
+/// allocation order.
 pub struct TypedArena<T, const DEBRIS_SIZE: usize> {
-    debris: UnsafeCell<Vec<ArenaDebris<T, DEBRIS_SIZE>>>
+    debris: UnsafeCell<Vec<ArenaDebris<T>>>
 }
 
 impl<T, const DEBRIS_SIZE: usize> TypedArena<T, DEBRIS_SIZE> {
     pub fn new() -> Self {
+        assert_ne!(DEBRIS_SIZE, 0, "DEBRIS_SIZE must not be zero");
         Self {
-            debris: UnsafeCell::new(vec![ArenaDebris::new()])
+            debris: UnsafeCell::new(vec![ArenaDebris::new(DEBRIS_SIZE)])
         }
     }
 
     pub fn make(&self, data: T) -> ArenaPtr<T> {
+        ArenaPtr {
+            ptr: self.allocate(data),
+            from_arena: unsafe { IntoArenaPtr::into(self) }
+        }
+    }
+
+    /// Move `data` into the arena, returning a reference living as long as the arena
+    pub fn alloc(&self, data: T) -> &T {
+        unsafe { &*self.allocate(data) }
+    }
+
+    /// Move all items from `iter` into the arena, returning references to them in order
+    pub fn alloc_iter<I>(&self, iter: I) -> Vec<&T>
+        where I: IntoIterator<Item = T>
+    {
+        iter.into_iter().map(|data| self.alloc(data)).collect()
+    }
+
+    /// Count of objects allocated in this arena
+    pub fn len(&self) -> usize {
+        unsafe { self.debris.get_ref_unchecked() }.iter().map(|piece| piece.usage).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn allocate(&self, data: T) -> *mut T {
         unsafe {
-            let debris: &mut Vec<ArenaDebris<T, DEBRIS_SIZE>> =
-                self.debris.get_mut_ref_unchecked();
-            let mut last_piece: &mut ArenaDebris<T, DEBRIS_SIZE> =
-                debris.last_mut().unchecked_unwrap();
+            let debris: &mut Vec<ArenaDebris<T>> = self.debris.get_mut_ref_unchecked();
+            let mut last_piece: &mut ArenaDebris<T> = debris.last_mut().unchecked_unwrap();
             if !last_piece.has_rest() {
-                debris.push(ArenaDebris::new());
+                let capacity: usize = Self::next_capacity(last_piece.capacity);
+                debris.push(ArenaDebris::new(capacity));
                 last_piece = debris.last_mut().unchecked_unwrap();
             }
-            ArenaPtr {
-                ptr: last_piece.allocate(data),
-                from_arena: IntoArenaPtr::into(self)
-            }
+            last_piece.allocate(data)
         }
     }
+
+    fn next_capacity(capacity: usize) -> usize {
+        let max_capacity: usize = match std::mem::size_of::<T>() {
+            0 => usize::MAX,
+            size => DEBRIS_SIZE.max(MAX_DEBRIS_BYTES / size)
+        };
+        capacity.saturating_mul(2).min(max_capacity)
+    }
+}
+
+impl<T, const DEBRIS_SIZE: usize> Default for TypedArena<T, DEBRIS_SIZE> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 unsafe impl<T, const DEBRIS_SIZE: usize> IntoArenaPtr for TypedArena<T, DEBRIS_SIZE> {}
 
 #[cfg(test)]
 mod test {
+    use std::cell::RefCell;
+
     use crate::rand_intern::random_string;
+    use crate::unchecked_intern::UncheckedCellOps;
     use crate::typed_arena::{ArenaPtr, TypedArena};
 
     #[test]
@@ -198,4 +259,86 @@ mod test {
         eprintln!("{:?}", r1);
         eprintln!("{:?}", r2);
     }
+
+    #[test]
+    fn test_address_stability() {
+        let arena: TypedArena<usize, 4> = TypedArena::new();
+        let refs: Vec<&usize> = (0..1000).map(|i| arena.alloc(i)).collect();
+        let addrs: Vec<*const usize> = refs.iter().map(|r| *r as *const usize).collect();
+
+        let more: Vec<&usize> = arena.alloc_iter(1000..2000);
+        assert_eq!(arena.len(), 2000);
+        for (i, r) in refs.iter().enumerate() {
+            assert_eq!(**r, i);
+            assert_eq!(*r as *const usize, addrs[i]);
+        }
+        assert_eq!(*more[999], 1999);
+    }
+
+    #[test]
+    fn test_growth() {
+        let arena: TypedArena<u64, 4> = TypedArena::new();
+        assert_eq!(TypedArena::<u64, 4>::next_capacity(4), 8);
+        assert_eq!(TypedArena::<u64, 4>::next_capacity(1 << 20), (1 << 20) / 8);
+        assert_eq!(TypedArena::<[u8; 1 << 20], 2>::next_capacity(2), 2);
+
+        for i in 0..(4 + 8 + 16 + 1) {
+            arena.alloc(i);
+        }
+        let capacities: Vec<usize> = unsafe { arena.debris.get_ref_unchecked() }
+            .iter()
+            .map(|piece| piece.capacity)
+            .collect();
+        assert_eq!(capacities, vec![4, 8, 16, 32]);
+    }
+
+    #[test]
+    fn test_drop_order() {
+        struct Tracked<'a>(usize, &'a RefCell<Vec<usize>>);
+
+        impl Drop for Tracked<'_> {
+            fn drop(&mut self) {
+                self.1.borrow_mut().push(self.0);
+            }
+        }
+
+        let dropped: RefCell<Vec<usize>> = RefCell::new(Vec::new());
+        {
+            let arena: TypedArena<Tracked, 2> = TypedArena::new();
+            for i in 0..9 {
+                arena.alloc(Tracked(i, &dropped));
+            }
+            assert!(dropped.borrow().is_empty());
+        }
+        assert_eq!(*dropped.borrow(), (0..9).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_shared_allocation() {
+        fn intern<'a>(arena: &'a TypedArena<String, 2>, s: &str) -> &'a str {
+            arena.alloc(s.to_string())
+        }
+
+        let arena: TypedArena<String, 2> = TypedArena::new();
+        let (a1, a2): (&TypedArena<String, 2>, &TypedArena<String, 2>) = (&arena, &arena);
+        let s1: &str = intern(a1, "114");
+        let s2: &str = intern(a2, "514");
+        let s3: &str = intern(a1, "1919");
+        let p: ArenaPtr<String> = a2.make("810".into());
+
+        assert_eq!([s1, s2, s3, p.get(&arena).as_str()].concat(), "1145141919810");
+        assert_eq!(arena.len(), 4);
+    }
+
+    #[test]
+    fn test_zst() {
+        #[derive(Debug, PartialEq)]
+        struct Zst;
+
+        let arena: TypedArena<Zst, 3> = TypedArena::new();
+        assert!(arena.is_empty());
+        let refs: Vec<&Zst> = arena.alloc_iter((0..10).map(|_| Zst));
+        assert_eq!(arena.len(), 10);
+        assert!(refs.iter().all(|r| **r == Zst));
+    }
 }
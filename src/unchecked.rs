@@ -1,40 +1,88 @@
 //! Unchecked counterparts to standard library components
 
-pub use crate::unchecked_intern::{UncheckedCellOps, UncheckedOption};
+use core::cell::UnsafeCell;
+#[cfg(debug_assertions)] use core::cell::{Ref, RefCell, RefMut};
+use core::fmt::{Display, Formatter};
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicU8, Ordering};
+
+pub use crate::unchecked_intern::{
+    LeakPolicy,
+    UncheckedCellOps,
+    UncheckedMaybeUninitOps,
+    UncheckedOption,
+    leak_policy,
+    set_leak_policy
+};
+#[cfg(feature = "alloc")] pub use crate::unchecked_intern::clone_slice_assume_init;
 
 /// Unchecked counterpart to `std::convert::From`
 pub trait UnsafeFrom<T> {
+    /// Performs the conversion.
+    ///
+    /// # Safety
+    /// The conversion is not checked, the implementor decides the exact invariants.
     unsafe fn unsafe_from(data: T) -> Self;
 }
 
 /// Unchecked counterpart to `std::convert::Into`
 pub trait UnsafeInto<T> {
+    /// Performs the conversion.
+    ///
+    /// # Safety
+    /// The conversion is not checked, the implementor decides the exact invariants.
     unsafe fn unsafe_into(self) -> T;
 }
 
+/// Force a value to be `Send`, for cases where sending it is made safe by other means
+///
+/// ```
+/// use std::ptr::NonNull;
+/// use std::thread;
+///
+/// use xjbutil::unchecked::UncheckedSend;
+///
+/// let mut value: Box<i32> = Box::new(114);
+/// let ptr: NonNull<i32> = NonNull::from(&mut *value);
+/// // SAFETY: `value` outlives the thread, and is not accessed until the thread is joined
+/// let send: UncheckedSend<NonNull<i32>> = unsafe { UncheckedSend::new(ptr) };
+/// thread::spawn(move || unsafe { *send.into_inner().as_ptr() += 400 }).join().unwrap();
+/// assert_eq!(*value, 514);
+/// ```
+#[repr(transparent)]
 pub struct UncheckedSend<T> { inner: T }
 
 unsafe impl<T> Send for UncheckedSend<T> {}
 
 impl<T> UncheckedSend<T> {
-    pub unsafe fn new(inner: T) -> Self {
+    /// Wrap `inner` into a `UncheckedSend`.
+    ///
+    /// # Safety
+    /// User must guarantee that it is actually safe to treat `inner` as `Send`.
+    pub const unsafe fn new(inner: T) -> Self {
         Self { inner }
     }
 
-    pub fn as_ref(&self) -> &T {
+    #[allow(clippy::should_implement_trait)]
+    pub const fn as_ref(&self) -> &T {
         &self.inner
     }
 
-    pub fn as_mut(&mut self) -> &mut T {
-        &mut self.inner
+    const_mut_fn! {
+        #[allow(clippy::should_implement_trait)]
+        pub fn as_mut(&mut self) -> &mut T {
+            &mut self.inner
+        }
     }
 
-    pub fn as_ptr(&self) -> *const T {
-        self.as_ref() as *const T
+    pub const fn as_ptr(&self) -> *const T {
+        core::ptr::addr_of!(self.inner)
     }
 
-    pub fn as_mut_ptr(&mut self) -> *mut T {
-        self.as_mut() as *mut T
+    const_mut_fn! {
+        pub fn as_mut_ptr(&mut self) -> *mut T {
+            core::ptr::addr_of_mut!(self.inner)
+        }
     }
 
     pub fn into_inner(self) -> T {
@@ -42,30 +90,56 @@ impl<T> UncheckedSend<T> {
     }
 }
 
+impl<T> Deref for UncheckedSend<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T> DerefMut for UncheckedSend<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+}
+
+/// Force a value to be `Send` and `Sync`, for cases where sharing it is made safe by other means
+#[repr(transparent)]
 pub struct UncheckedSendSync<T> { inner: T }
 
 unsafe impl<T> Send for UncheckedSendSync<T> {}
 unsafe impl<T> Sync for UncheckedSendSync<T> {}
 
 impl<T> UncheckedSendSync<T> {
-    pub unsafe fn new(inner: T) -> Self {
+    /// Wrap `inner` into a `UncheckedSendSync`.
+    ///
+    /// # Safety
+    /// User must guarantee that it is actually safe to treat `inner` as `Send` and `Sync`.
+    pub const unsafe fn new(inner: T) -> Self {
         Self { inner }
     }
 
-    pub fn as_ref(&self) -> &T {
+    #[allow(clippy::should_implement_trait)]
+    pub const fn as_ref(&self) -> &T {
         &self.inner
     }
 
-    pub fn as_mut(&mut self) -> &mut T {
-        &mut self.inner
+    const_mut_fn! {
+        #[allow(clippy::should_implement_trait)]
+        pub fn as_mut(&mut self) -> &mut T {
+            &mut self.inner
+        }
     }
 
-    pub fn as_ptr(&self) -> *const T {
-        self.as_ref() as *const T
+    pub const fn as_ptr(&self) -> *const T {
+        core::ptr::addr_of!(self.inner)
     }
 
-    pub fn as_mut_ptr(&mut self) -> *mut T {
-        self.as_mut() as *mut T
+    const_mut_fn! {
+        pub fn as_mut_ptr(&mut self) -> *mut T {
+            core::ptr::addr_of_mut!(self.inner)
+        }
     }
 
     pub fn into_inner(self) -> T {
@@ -73,9 +147,395 @@ impl<T> UncheckedSendSync<T> {
     }
 }
 
-#[cfg(feature = "async")] use std::future::Future;
-#[cfg(feature = "async")] use std::pin::Pin;
-#[cfg(feature = "async")] use std::task::{Context, Poll};
+impl<T> Deref for UncheckedSendSync<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T> DerefMut for UncheckedSendSync<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+}
+
+/// Force a value to be `Sync`, for cases where sharing it is made safe by other means
+///
+/// The wrapper is `Send` only if `T` is.
+#[repr(transparent)]
+pub struct UncheckedSync<T> { inner: T }
+
+unsafe impl<T> Sync for UncheckedSync<T> {}
+
+impl<T> UncheckedSync<T> {
+    /// Wrap `inner` into a `UncheckedSync`.
+    ///
+    /// # Safety
+    /// User must guarantee that it is actually safe to treat `inner` as `Sync`.
+    pub const unsafe fn new(inner: T) -> Self {
+        Self { inner }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T> Deref for UncheckedSync<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T> DerefMut for UncheckedSync<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+}
+
+/// An `UnsafeCell` which is `Sync`, for `static`s and values shared with external synchronization
+///
+/// ```
+/// use xjbutil::unchecked::{RacyCell, UncheckedCellOps};
+///
+/// static COUNTER: RacyCell<u32> = RacyCell::new(0);
+///
+/// // SAFETY: no other thread accesses `COUNTER`
+/// unsafe { *COUNTER.get_mut_ref_unchecked() += 114514; }
+/// assert_eq!(unsafe { *COUNTER.get_ref_unchecked() }, 114514);
+/// ```
+///
+/// `RacyCell<T>` is `Sync` if `T` is `Send`, the same bound as `Mutex<T>`. As with `UnsafeCell`,
+/// the user is responsible for ruling out data races.
+#[repr(transparent)]
+pub struct RacyCell<T> {
+    inner: UnsafeCell<T>
+}
+
+unsafe impl<T: Send> Sync for RacyCell<T> {}
+
+impl<T> RacyCell<T> {
+    pub const fn new(t: T) -> Self {
+        Self { inner: UnsafeCell::new(t) }
+    }
+
+    pub const fn get(&self) -> *mut T {
+        self.inner.get()
+    }
+
+    const_mut_fn! {
+        pub fn get_mut(&mut self) -> &mut T {
+            self.inner.get_mut()
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner()
+    }
+}
+
+impl<T: Default> Default for RacyCell<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T> UncheckedCellOps for RacyCell<T> {
+    type Target = T;
+
+    #[inline]
+    unsafe fn get_ref_unchecked(&self) -> &T {
+        self.inner.get_ref_unchecked()
+    }
+
+    #[inline]
+    #[allow(clippy::mut_from_ref)]
+    unsafe fn get_mut_ref_unchecked(&self) -> &mut T {
+        self.inner.get_mut_ref_unchecked()
+    }
+}
+
+/// Error returned by `OnceInit::init` when the value has already been initialized
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AlreadyInitialized;
+
+impl Display for AlreadyInitialized {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "already initialized")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for AlreadyInitialized {}
+
+const ONCE_INIT_EMPTY: u8 = 0;
+const ONCE_INIT_BUSY: u8 = 1;
+const ONCE_INIT_READY: u8 = 2;
+
+/// A value initialized once, then readable without any synchronization
+///
+/// Unlike `LazyLock`, `get_unchecked` does not even check whether the value has been initialized,
+/// which suits tables filled during single-threaded startup and read on hot paths afterwards. The
+/// atomic state is only used by `init` and `get`.
+pub struct OnceInit<T> {
+    inner: UnsafeCell<UncheckedOption<T>>,
+    state: AtomicU8
+}
+
+unsafe impl<T: Send + Sync> Sync for OnceInit<T> {}
+
+impl<T> OnceInit<T> {
+    pub const fn new() -> Self {
+        Self {
+            inner: UnsafeCell::new(UncheckedOption::new_none()),
+            state: AtomicU8::new(ONCE_INIT_EMPTY)
+        }
+    }
+
+    /// Initialize the value, failing if it has been, or is being, initialized
+    pub fn init(&self, value: T) -> Result<(), AlreadyInitialized> {
+        self.state.compare_exchange(
+            ONCE_INIT_EMPTY,
+            ONCE_INIT_BUSY,
+            Ordering::Acquire,
+            Ordering::Relaxed
+        ).map_err(|_| AlreadyInitialized)?;
+        unsafe { self.inner.get_mut_ref_unchecked().set(value); }
+        self.state.store(ONCE_INIT_READY, Ordering::Release);
+        Ok(())
+    }
+
+    pub fn is_initialized(&self) -> bool {
+        self.state.load(Ordering::Acquire) == ONCE_INIT_READY
+    }
+
+    /// Get the value if it has been initialized
+    pub fn get(&self) -> Option<&T> {
+        if self.is_initialized() {
+            Some(unsafe { self.inner.get_ref_unchecked().get_ref() })
+        } else {
+            None
+        }
+    }
+
+    /// Get the value without checking whether it has been initialized
+    ///
+    /// # Safety
+    /// `init` must have completed, and that completion must *happen before* this call. This holds
+    /// if `init` was called on the current thread, or before the current thread was spawned, or
+    /// if any `get` or `is_initialized` call on the current thread has observed the value. Checked
+    /// with a panic in debug builds.
+    #[inline]
+    pub unsafe fn get_unchecked(&self) -> &T {
+        debug_assert!(
+            self.state.load(Ordering::Relaxed) == ONCE_INIT_READY,
+            "OnceInit accessed before initialization"
+        );
+        self.inner.get_ref_unchecked().get_ref()
+    }
+}
+
+impl<T> Default for OnceInit<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for OnceInit<T> {
+    fn drop(&mut self) {
+        if *self.state.get_mut() == ONCE_INIT_READY {
+            drop(unsafe { self.inner.get_mut().take() });
+        }
+    }
+}
+
+/// A cell written once through a shared reference, then read without any checks
+///
+/// In debug builds, setting twice or reading before setting panics. In release builds, the cell is
+/// a bare `MaybeUninit<T>` inside an `UnsafeCell`, and never drops its value: use `take` to avoid
+/// leaking it.
+///
+/// `UncheckedOnceCell` is not `Sync`, and there is no synchronization between `set` and `get`. Use
+/// `OnceInit` for values shared between threads.
+pub struct UncheckedOnceCell<T> {
+    inner: UnsafeCell<UncheckedOption<T>>
+}
+
+impl<T> UncheckedOnceCell<T> {
+    pub const fn new() -> Self {
+        Self {
+            inner: UnsafeCell::new(UncheckedOption::new_none())
+        }
+    }
+
+    #[cfg(debug_assertions)]
+    fn is_set(&self) -> bool {
+        unsafe { self.inner.get_ref_unchecked().is_full() }
+    }
+
+    /// Set the value of the cell.
+    ///
+    /// # Safety
+    /// The cell must not have been set yet, or must have been emptied with `take`. Checked with a
+    /// panic in debug builds.
+    pub unsafe fn set(&self, t: T) {
+        #[cfg(debug_assertions)]
+        assert!(!self.is_set(), "UncheckedOnceCell set twice");
+        self.inner.get_mut_ref_unchecked().set(t);
+    }
+
+    /// Get the value of the cell.
+    ///
+    /// # Safety
+    /// The cell must have been set. Checked with a panic in debug builds.
+    pub unsafe fn get(&self) -> &T {
+        #[cfg(debug_assertions)]
+        assert!(self.is_set(), "UncheckedOnceCell accessed before set");
+        self.inner.get_ref_unchecked().get_ref()
+    }
+
+    /// Take the value out of the cell, leaving it empty.
+    ///
+    /// # Safety
+    /// The cell must have been set. Checked with a panic in debug builds.
+    pub unsafe fn take(&mut self) -> T {
+        #[cfg(debug_assertions)]
+        assert!(self.is_set(), "UncheckedOnceCell accessed before set");
+        self.inner.get_mut().take()
+    }
+}
+
+impl<T> Default for UncheckedOnceCell<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(debug_assertions)]
+impl<T> Drop for UncheckedOnceCell<T> {
+    fn drop(&mut self) {
+        if self.is_set() {
+            drop(unsafe { self.inner.get_mut().take() });
+        }
+    }
+}
+
+/// Unchecked counterpart to `RefCell`
+///
+/// In debug builds, this is a `RefCell`, and conflicting borrows panic. In release builds, this is
+/// a bare `UnsafeCell` without any borrow flag, and conflicting borrows are undefined behavior.
+///
+/// Borrows are returned as `UncheckedRef` and `UncheckedRefMut` guards, which hold the `RefCell`
+/// guards in debug builds so that borrows are tracked until the guards go out of scope, and are
+/// plain references in release builds.
+#[cfg_attr(not(debug_assertions), repr(transparent))]
+pub struct UncheckedRefCell<T> {
+    #[cfg(debug_assertions)]
+    inner: RefCell<T>,
+    #[cfg(not(debug_assertions))]
+    inner: UnsafeCell<T>
+}
+
+/// Immutable borrow of an `UncheckedRefCell`
+pub struct UncheckedRef<'a, T> {
+    #[cfg(debug_assertions)]
+    inner: Ref<'a, T>,
+    #[cfg(not(debug_assertions))]
+    inner: &'a T
+}
+
+/// Mutable borrow of an `UncheckedRefCell`
+pub struct UncheckedRefMut<'a, T> {
+    #[cfg(debug_assertions)]
+    inner: RefMut<'a, T>,
+    #[cfg(not(debug_assertions))]
+    inner: &'a mut T
+}
+
+impl<T> UncheckedRefCell<T> {
+    pub const fn new(t: T) -> Self {
+        Self {
+            #[cfg(debug_assertions)]
+            inner: RefCell::new(t),
+            #[cfg(not(debug_assertions))]
+            inner: UnsafeCell::new(t)
+        }
+    }
+
+    /// Immutably borrow the value.
+    ///
+    /// # Safety
+    /// The value must not be mutably borrowed during the lifetime of the returned guard. Checked
+    /// with a panic in debug builds.
+    #[inline]
+    pub unsafe fn borrow(&self) -> UncheckedRef<'_, T> {
+        UncheckedRef {
+            #[cfg(debug_assertions)]
+            inner: self.inner.borrow(),
+            #[cfg(not(debug_assertions))]
+            inner: self.inner.get_ref_unchecked()
+        }
+    }
+
+    /// Mutably borrow the value.
+    ///
+    /// # Safety
+    /// The value must not be borrowed in any way during the lifetime of the returned guard.
+    /// Checked with a panic in debug builds.
+    #[inline]
+    pub unsafe fn borrow_mut(&self) -> UncheckedRefMut<'_, T> {
+        UncheckedRefMut {
+            #[cfg(debug_assertions)]
+            inner: self.inner.borrow_mut(),
+            #[cfg(not(debug_assertions))]
+            inner: self.inner.get_mut_ref_unchecked()
+        }
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        self.inner.get_mut()
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner()
+    }
+}
+
+impl<T: Default> Default for UncheckedRefCell<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T> Deref for UncheckedRef<'_, T> {
+    type Target = T;
+
+    #[inline] fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T> Deref for UncheckedRefMut<'_, T> {
+    type Target = T;
+
+    #[inline] fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T> DerefMut for UncheckedRefMut<'_, T> {
+    #[inline] fn deref_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+}
+
+#[cfg(feature = "async")] use core::future::Future;
+#[cfg(feature = "async")] use core::pin::Pin;
+#[cfg(feature = "async")] use core::task::{Context, Poll};
 
 #[cfg(feature = "async")]
 pub struct UncheckedSendFut<R: 'static> {
@@ -84,6 +544,10 @@ pub struct UncheckedSendFut<R: 'static> {
 
 #[cfg(feature = "async")]
 impl<R: 'static> UncheckedSendFut<R> {
+    /// Wrap `fut` into an `UncheckedSendFut`.
+    ///
+    /// # Safety
+    /// User must guarantee that it is actually safe to send `fut` across threads.
     pub unsafe fn new(fut: impl Future<Output = R> + 'static) -> Self {
         Self { fut: Box::pin(fut) }
     }
@@ -109,6 +573,10 @@ pub struct UncheckedSendFutUnpin<FUT: Unpin> {
 
 #[cfg(feature = "async")]
 impl<FUT: Unpin> UncheckedSendFutUnpin<FUT> {
+    /// Wrap `fut` into an `UncheckedSendFutUnpin`.
+    ///
+    /// # Safety
+    /// User must guarantee that it is actually safe to send `fut` across threads.
     pub unsafe fn new(fut: FUT) -> Self {
         Self { fut }
     }
@@ -128,3 +596,246 @@ impl<F, R> Future for UncheckedSendFutUnpin<F>
 #[cfg(feature = "async")] impl<F: Unpin> Unpin for UncheckedSendFutUnpin<F> {}
 #[cfg(feature = "async")] unsafe impl<F: Unpin> Send for UncheckedSendFutUnpin<F> {}
 #[cfg(feature = "async")] unsafe impl<F: Unpin> Sync for UncheckedSendFutUnpin<F> {}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::thread;
+
+    use crate::unchecked::{
+        AlreadyInitialized,
+        OnceInit,
+        RacyCell,
+        UncheckedCellOps,
+        UncheckedOnceCell,
+        UncheckedRef,
+        UncheckedRefCell,
+        UncheckedRefMut,
+        UncheckedSend,
+        UncheckedSendSync,
+        UncheckedSync
+    };
+
+    #[test]
+    fn test_once_init() {
+        static TABLE: OnceInit<Vec<&str>> = OnceInit::new();
+
+        assert!(TABLE.get().is_none());
+        assert_eq!(TABLE.init(vec!["114", "514"]), Ok(()));
+        assert_eq!(TABLE.init(vec!["1919810"]), Err(AlreadyInitialized));
+        assert_eq!(TABLE.get().unwrap(), &["114", "514"]);
+        assert_eq!(unsafe { TABLE.get_unchecked() }, &["114", "514"]);
+    }
+
+    #[test]
+    fn test_once_init_cross_thread() {
+        let once: Arc<OnceInit<String>> = Arc::new(OnceInit::new());
+        let readers: Vec<thread::JoinHandle<String>> = (0..4).map(|_| {
+            let once: Arc<OnceInit<String>> = once.clone();
+            thread::spawn(move || {
+                let value: &String = loop {
+                    if let Some(value) = once.get() {
+                        break value;
+                    }
+                    thread::yield_now();
+                };
+                // `get` observed the value, so the unchecked getter is fine from now on
+                assert!(std::ptr::eq(value, unsafe { once.get_unchecked() }));
+                value.clone()
+            })
+        }).collect();
+
+        let writers: Vec<thread::JoinHandle<bool>> = (0..4).map(|i| {
+            let once: Arc<OnceInit<String>> = once.clone();
+            thread::spawn(move || once.init(format!("writer {}", i)).is_ok())
+        }).collect();
+
+        let succeeded: usize = writers.into_iter()
+            .map(|writer| writer.join().unwrap())
+            .filter(|ok| *ok)
+            .count();
+        assert_eq!(succeeded, 1);
+
+        let values: Vec<String> = readers.into_iter()
+            .map(|reader| reader.join().unwrap())
+            .collect();
+        assert!(values.iter().all(|value| value == once.get().unwrap()));
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "OnceInit accessed before initialization")]
+    fn test_once_init_premature_unchecked() {
+        let once: OnceInit<i32> = OnceInit::new();
+        let _ = unsafe { once.get_unchecked() };
+    }
+
+    #[test]
+    fn test_unchecked_once_cell() {
+        let mut cell: UncheckedOnceCell<String> = UncheckedOnceCell::new();
+        unsafe {
+            let shared: &UncheckedOnceCell<String> = &cell;
+            shared.set("114514".into());
+            let r1: &String = shared.get();
+            let r2: &String = shared.get();
+            assert!(std::ptr::eq(r1, r2));
+            assert_eq!(r1, "114514");
+
+            assert_eq!(cell.take(), "114514");
+            cell.set("1919810".into());
+            assert_eq!(cell.take(), "1919810");
+        }
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    fn test_unchecked_once_cell_drop() {
+        use std::rc::Rc;
+
+        let value: Rc<()> = Rc::new(());
+        let cell: UncheckedOnceCell<Rc<()>> = UncheckedOnceCell::new();
+        unsafe { cell.set(value.clone()); }
+        assert_eq!(Rc::strong_count(&value), 2);
+        drop(cell);
+        assert_eq!(Rc::strong_count(&value), 1);
+
+        // dropping an empty cell does nothing
+        drop(UncheckedOnceCell::<Rc<()>>::new());
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "UncheckedOnceCell set twice")]
+    fn test_unchecked_once_cell_set_twice() {
+        let cell: UncheckedOnceCell<i32> = UncheckedOnceCell::new();
+        unsafe {
+            cell.set(114);
+            cell.set(514);
+        }
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "UncheckedOnceCell accessed before set")]
+    fn test_unchecked_once_cell_premature_get() {
+        let cell: UncheckedOnceCell<i32> = UncheckedOnceCell::new();
+        let _ = unsafe { cell.get() };
+    }
+
+    #[test]
+    fn test_unchecked_send_sync() {
+        use std::cell::Cell;
+        use std::ptr::NonNull;
+        use std::rc::Rc;
+
+        // `Rc` is neither `Send` nor `Sync`, the thread is joined before the `Rc` is used again
+        let rc: Rc<Cell<i32>> = Rc::new(Cell::new(114));
+        let send: UncheckedSend<Rc<Cell<i32>>> = unsafe { UncheckedSend::new(rc.clone()) };
+        send.set(send.get() + 400);
+        thread::spawn(move || send.set(send.get() * 2)).join().unwrap();
+        assert_eq!(rc.get(), 1028);
+
+        let cell: Cell<i32> = Cell::new(1919);
+        let shared: UncheckedSync<NonNull<Cell<i32>>> =
+            unsafe { UncheckedSync::new(NonNull::from(&cell)) };
+        thread::scope(|scope| {
+            scope.spawn(|| unsafe { shared.as_ref().set(810) });
+        });
+        assert_eq!(cell.get(), 810);
+
+        let mut vec: UncheckedSync<Vec<i32>> = unsafe { UncheckedSync::new(vec![114]) };
+        vec.push(514);
+        assert_eq!(vec.into_inner(), [114, 514]);
+
+        let send_sync: Arc<UncheckedSendSync<Rc<i32>>> =
+            Arc::new(unsafe { UncheckedSendSync::new(Rc::new(893)) });
+        let cloned: Arc<UncheckedSendSync<Rc<i32>>> = send_sync.clone();
+        let value: i32 = thread::spawn(move || {
+            let rc: &Rc<i32> = &cloned;
+            **rc
+        }).join().unwrap();
+        assert_eq!(value, 893);
+        assert_eq!(*Arc::try_unwrap(send_sync).ok().unwrap().into_inner(), 893);
+    }
+
+    #[test]
+    fn test_unchecked_ref_cell() {
+        let mut cell: UncheckedRefCell<Vec<i32>> = UncheckedRefCell::new(vec![114]);
+        unsafe {
+            cell.borrow_mut().push(514);
+            let r1: UncheckedRef<Vec<i32>> = cell.borrow();
+            let r2: UncheckedRef<Vec<i32>> = cell.borrow();
+            assert_eq!(*r1, *r2);
+            drop((r1, r2));
+
+            let mut m: UncheckedRefMut<Vec<i32>> = cell.borrow_mut();
+            m.push(1919);
+        }
+        cell.get_mut().push(810);
+        assert_eq!(cell.into_inner(), [114, 514, 1919, 810]);
+    }
+
+    #[test]
+    fn test_unchecked_ref_cell_size() {
+        use std::mem::size_of;
+
+        #[cfg(not(debug_assertions))]
+        {
+            assert_eq!(size_of::<UncheckedRefCell<u64>>(), size_of::<u64>());
+            assert_eq!(size_of::<UncheckedRefCell<[u8; 3]>>(), size_of::<[u8; 3]>());
+            assert_eq!(size_of::<UncheckedRef<u64>>(), size_of::<&u64>());
+            assert_eq!(size_of::<UncheckedRefMut<u64>>(), size_of::<&mut u64>());
+        }
+        #[cfg(debug_assertions)]
+        assert!(size_of::<UncheckedRefCell<u64>>() > size_of::<u64>());
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "already borrowed")]
+    fn test_unchecked_ref_cell_conflict() {
+        let cell: UncheckedRefCell<i32> = UncheckedRefCell::new(114514);
+        unsafe {
+            let _r: UncheckedRef<i32> = cell.borrow();
+            let _m: UncheckedRefMut<i32> = cell.borrow_mut();
+        }
+    }
+
+    #[test]
+    fn test_racy_cell() {
+        use std::sync::Mutex;
+
+        struct State {
+            names: Vec<&'static str>,
+            count: usize
+        }
+
+        static STATE: RacyCell<State> = RacyCell::new(State { names: Vec::new(), count: 0 });
+        static LOCK: Mutex<()> = Mutex::new(());
+
+        let threads: Vec<thread::JoinHandle<()>> = ["114", "514", "1919", "810"].iter()
+            .map(|name| thread::spawn(move || {
+                let _guard = LOCK.lock().unwrap();
+                // SAFETY: all accesses to `STATE` are guarded by `LOCK`
+                let state: &mut State = unsafe { STATE.get_mut_ref_unchecked() };
+                state.names.push(name);
+                state.count += 1;
+            }))
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let _guard = LOCK.lock().unwrap();
+        let state: &State = unsafe { STATE.get_ref_unchecked() };
+        assert_eq!(state.count, 4);
+        let mut names: Vec<&str> = state.names.clone();
+        names.sort_unstable();
+        assert_eq!(names, ["114", "1919", "514", "810"]);
+
+        let mut cell: RacyCell<String> = RacyCell::new("893".into());
+        cell.get_mut().push('!');
+        unsafe { (*cell.get()).push('?'); }
+        assert_eq!(cell.into_inner(), "893!?");
+    }
+}
@@ -1,14 +1,84 @@
 #![allow(unused)]
 
-use std::cell::UnsafeCell;
+use core::any::type_name;
+use core::cell::UnsafeCell;
+use core::fmt::{Debug, Formatter};
+use core::mem::MaybeUninit;
+use core::ptr::null_mut;
+use core::sync::atomic::{AtomicPtr, AtomicU8, Ordering};
+
+#[cfg(feature = "alloc")] use alloc::vec::Vec;
+#[cfg(all(debug_assertions, feature = "unchecked-backtrace"))] use std::backtrace::Backtrace;
+
+use crate::diag_intern::diagnostic;
+
+/// What debug build `UncheckedOption`s do when dropped with a value
+#[derive(Clone, Copy, Debug)]
+pub enum LeakPolicy {
+    /// Report the leak through the diagnostic handler, which is the default
+    Warn,
+    /// Panic, unless the thread is already panicking, in which case the leak is reported as with
+    /// `Warn`
+    Panic,
+    /// Ignore the leak
+    Silent,
+    /// Call the hook with the type name of the leaked value
+    Hook(fn(&'static str))
+}
+
+const LEAK_WARN: u8 = 0;
+const LEAK_PANIC: u8 = 1;
+const LEAK_SILENT: u8 = 2;
+const LEAK_HOOK: u8 = 3;
+
+static LEAK_POLICY: AtomicU8 = AtomicU8::new(LEAK_WARN);
+// only ever set to non-null values, and always before `LEAK_POLICY` becomes `LEAK_HOOK`
+static LEAK_HOOK_FN: AtomicPtr<()> = AtomicPtr::new(null_mut());
+
+/// Set the leak policy of all `UncheckedOption`s in the process
+///
+/// `UncheckedOption`s in release builds cannot detect leaks, and never consult the policy.
+pub fn set_leak_policy(policy: LeakPolicy) {
+    let kind: u8 = match policy {
+        LeakPolicy::Warn => LEAK_WARN,
+        LeakPolicy::Panic => LEAK_PANIC,
+        LeakPolicy::Silent => LEAK_SILENT,
+        LeakPolicy::Hook(hook) => {
+            LEAK_HOOK_FN.store(hook as *mut (), Ordering::SeqCst);
+            LEAK_HOOK
+        }
+    };
+    LEAK_POLICY.store(kind, Ordering::SeqCst);
+}
+
+/// Get the current leak policy of `UncheckedOption`s
+pub fn leak_policy() -> LeakPolicy {
+    match LEAK_POLICY.load(Ordering::SeqCst) {
+        LEAK_WARN => LeakPolicy::Warn,
+        LEAK_PANIC => LeakPolicy::Panic,
+        LEAK_SILENT => LeakPolicy::Silent,
+        _ => {
+            let hook: *mut () = LEAK_HOOK_FN.load(Ordering::SeqCst);
+            // SAFETY: the hook has been set before the policy became `LEAK_HOOK`
+            LeakPolicy::Hook(unsafe { core::mem::transmute::<*mut (), fn(&'static str)>(hook) })
+        }
+    }
+}
 
 /// Provides unchecked variant of `std::option::Option`
 ///
 /// The `UncheckedOption` is provided as an unsafe counterpart to `std::option::Option`, with
 /// no checks or guarantees. User must guarantee the correctness on themselves.
+///
+/// With the `unchecked-backtrace` feature, debug builds record the backtrace of the last `take` or
+/// `set`, and attach it to misuse panics and leak diagnostics.
 #[cfg(debug_assertions)]
 pub struct UncheckedOption<T> {
-    inner: Option<T>
+    // same storage as in release build, so that the payload has an address even when empty
+    inner: MaybeUninit<T>,
+    full: bool,
+    #[cfg(feature = "unchecked-backtrace")]
+    last_access: Option<Backtrace>
 }
 
 #[cfg(debug_assertions)]
@@ -16,72 +86,253 @@ impl<T> UncheckedOption<T> {
     /// Create an `UncheckedOption` containing given value `t`.
     pub const fn new(t: T) -> Self {
         Self {
-            inner: Some(t)
+            inner: MaybeUninit::new(t),
+            full: true,
+            #[cfg(feature = "unchecked-backtrace")]
+            last_access: None
         }
     }
 
     /// Create an empty `UncheckedOption`
     pub const fn new_none() -> Self {
         Self {
-            inner: None
+            inner: MaybeUninit::uninit(),
+            full: false,
+            #[cfg(feature = "unchecked-backtrace")]
+            last_access: None
+        }
+    }
+
+    /// Whether the `UncheckedOption` contains a value, which is only tracked in debug build
+    pub(crate) const fn is_full(&self) -> bool {
+        self.full
+    }
+
+    #[cfg(feature = "unchecked-backtrace")]
+    #[inline(always)]
+    fn record_access(&mut self) {
+        self.last_access = Some(Backtrace::force_capture());
+    }
+
+    #[cfg(not(feature = "unchecked-backtrace"))]
+    const_mut_fn! {
+        #[inline(always)]
+        fn record_access(&mut self) {}
+    }
+
+    #[cfg(feature = "unchecked-backtrace")]
+    fn describe(&self, message: &str) -> String {
+        match &self.last_access {
+            Some(backtrace) => format!("{}, last take or set at:\n{}", message, backtrace),
+            None => message.to_string()
+        }
+    }
+
+    #[cfg(feature = "unchecked-backtrace")]
+    #[cold]
+    fn misuse(&self, message: &str) -> ! {
+        panic!("{}", self.describe(message))
+    }
+
+    #[cfg(not(feature = "unchecked-backtrace"))]
+    #[cold]
+    const fn misuse(&self, message: &str) -> ! {
+        panic!("{}", message)
+    }
+
+    const_mut_fn! {
+        fn occupied(&self) -> &T {
+            if !self.full {
+                self.misuse("UncheckedOption accessed while empty");
+            }
+            unsafe { &*self.inner.as_ptr() }
+        }
+    }
+
+    const_mut_fn! {
+        fn occupied_mut(&mut self) -> &mut T {
+            if !self.full {
+                self.misuse("UncheckedOption accessed while empty");
+            }
+            unsafe { &mut *self.inner.as_mut_ptr() }
         }
     }
 
-    /// Assuming the `UncheckedOption` containing a value, take out the item stored in
-    /// `UncheckedOption`.
+    const_mut_fn! {
+        /// Assuming the `UncheckedOption` containing a value, take out the item stored in
+        /// `UncheckedOption`.
+        ///
+        /// # Safety
+        /// The `UncheckedOption` must really contains a `T`. If not, this function will panic in
+        /// debug build, cause undefined behavior in release build.
+        pub unsafe fn take(&mut self) -> T {
+            if !self.full {
+                self.misuse("UncheckedOption accessed while empty");
+            }
+            self.record_access();
+            self.full = false;
+            core::mem::replace(&mut self.inner, MaybeUninit::uninit()).assume_init()
+        }
+    }
+
+    const_mut_fn! {
+        /// Assuming the `UncheckedOption` containing a value, get an immutable reference to the
+        /// item stored in `UncheckedOption`.
+        ///
+        /// # Safety
+        /// The `UncheckedOption` must really contains a `T`. If not, this function will panic in
+        /// debug build, cause undefined behavior in release build.
+        pub unsafe fn get_ref(&self) -> &T {
+            self.occupied()
+        }
+    }
+
+    const_mut_fn! {
+        /// Assuming the `UncheckedOption` containing a value, get an mutable reference to the
+        /// item stored in `UncheckedOption`.
+        ///
+        /// # Safety
+        /// The `UncheckedOption` must really contains a `T`. If not, this function will panic in
+        /// debug build, cause undefined behavior in release build.
+        pub unsafe fn get_mut(&mut self) -> &mut T {
+            self.occupied_mut()
+        }
+    }
+
+    /// Assuming the `UncheckedOption` containing a value, consume the `UncheckedOption` and take
+    /// out the item stored in it.
     ///
     /// # Safety
     /// The `UncheckedOption` must really contains a `T`. If not, this function will panic in
     /// debug build, cause undefined behavior in release build.
-    pub unsafe fn take(&mut self) -> T {
-        self.inner.take().unwrap()
+    pub unsafe fn into_inner(mut self) -> T {
+        // the option is left empty, so dropping it afterwards does not report a leak
+        self.take()
+    }
+
+    /// Get a raw pointer to the item stored in `UncheckedOption`, without checking occupancy.
+    ///
+    /// The pointer always points to the payload storage, in both debug and release build. It may
+    /// only be dereferenced while the `UncheckedOption` contains a value.
+    pub fn as_ptr(&self) -> *const T {
+        self.inner.as_ptr()
+    }
+
+    /// Get a mutable raw pointer to the item stored in `UncheckedOption`, without checking
+    /// occupancy.
+    ///
+    /// The pointer always points to the payload storage, in both debug and release build. It may
+    /// only be dereferenced while the `UncheckedOption` contains a value.
+    pub fn as_mut_ptr(&mut self) -> *mut T {
+        self.inner.as_mut_ptr()
     }
 
-    /// Assuming the `UncheckedOption` containing a value, get an immutable reference to the item
-    /// stored in `UncheckedOption`.
+    const_mut_fn! {
+        /// Assuming the `UncheckedOption` is empty, put a `T` object into it.
+        ///
+        /// # Safety
+        /// The `UncheckedOption` must be empty. If not, this function will panic in
+        /// debug build, or cause potential resource leaks in release build. This function does not
+        /// have UB, but still marked as `unsafe` in order to remind user.
+        pub unsafe fn set(&mut self, t: T) {
+            if self.full {
+                self.misuse("UncheckedOption set while occupied");
+            }
+            self.record_access();
+            self.inner = MaybeUninit::new(t);
+            self.full = true;
+        }
+    }
+
+    /// Assuming the `UncheckedOption` containing a value, put a `T` object into it and take out
+    /// the previous one.
     ///
     /// # Safety
     /// The `UncheckedOption` must really contains a `T`. If not, this function will panic in
     /// debug build, cause undefined behavior in release build.
-    pub unsafe fn get_ref(&self) -> &T {
-        self.inner.as_ref().unwrap()
+    pub unsafe fn replace(&mut self, t: T) -> T {
+        core::mem::replace(self.occupied_mut(), t)
     }
 
-    /// Assuming the `UncheckedOption` containing a value, get an mutable reference to the item
-    /// stored in `UncheckedOption`.
+    /// Assuming both `UncheckedOption`s containing a value, exchange their items.
+    ///
+    /// # Safety
+    /// Both `UncheckedOption`s must really contain a `T`. If not, this function will panic in
+    /// debug build, cause undefined behavior in release build.
+    pub unsafe fn swap(&mut self, other: &mut UncheckedOption<T>) {
+        core::mem::swap(self.occupied_mut(), other.occupied_mut());
+    }
+
+    /// Assuming the `UncheckedOption` containing a value, transform the item stored in
+    /// `UncheckedOption` with `f`.
+    ///
+    /// If `f` panics, the item has been moved into `f` and is dropped during unwinding, and the
+    /// `UncheckedOption` is left empty.
     ///
     /// # Safety
     /// The `UncheckedOption` must really contains a `T`. If not, this function will panic in
     /// debug build, cause undefined behavior in release build.
-    pub unsafe fn get_mut(&mut self) -> &mut T {
-        self.inner.as_mut().unwrap()
+    pub unsafe fn map_in_place(&mut self, f: impl FnOnce(T) -> T) {
+        let t: T = self.take();
+        self.set(f(t));
     }
 
-    /// Assuming the `UncheckedOption` is empty, put a `T` object into it.
+    /// Assuming the `UncheckedOption` containing a value, transform the item stored in
+    /// `UncheckedOption` with `f` into a new `UncheckedOption`.
     ///
     /// # Safety
-    /// The `UncheckedOption` must be empty. If not, this function will panic in
-    /// debug build, or cause potential resource leaks in release build. This function does not have
-    /// UB, but still marked as `unsafe` in order to remind user.
-    pub unsafe fn set(&mut self, t: T) {
-        let origin: Option<T> = self.inner.replace(t);
-        assert!(origin.is_none());
+    /// The `UncheckedOption` must really contains a `T`. If not, this function will panic in
+    /// debug build, cause undefined behavior in release build.
+    pub unsafe fn map_into<U>(self, f: impl FnOnce(T) -> U) -> UncheckedOption<U> {
+        UncheckedOption::new(f(self.into_inner()))
+    }
+}
+
+#[cfg(debug_assertions)]
+impl<T: Debug> Debug for UncheckedOption<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let inner: Option<&T> = if self.full { Some(self.occupied()) } else { None };
+        f.debug_tuple("UncheckedOption").field(&inner).finish()
     }
 }
 
 #[cfg(debug_assertions)]
 impl<T> Drop for UncheckedOption<T> {
     fn drop(&mut self) {
-        if self.inner.is_some() {
-            eprintln!("[xjbutil] UncheckedOption dropped with value, potential resource leak");
+        // like in release build, the value is not dropped
+        let leaked: bool = self.full;
+        if !leaked {
+            return;
+        }
+
+        let message: &str = "UncheckedOption dropped with value, potential resource leak";
+        let policy: LeakPolicy = leak_policy();
+        #[cfg(feature = "std")]
+        let policy: LeakPolicy = match policy {
+            LeakPolicy::Panic if std::thread::panicking() => LeakPolicy::Warn,
+            policy => policy
+        };
+        match policy {
+            LeakPolicy::Warn => {
+                #[cfg(feature = "unchecked-backtrace")]
+                diagnostic(&self.describe(message));
+                #[cfg(not(feature = "unchecked-backtrace"))]
+                diagnostic(message);
+            },
+            LeakPolicy::Panic => {
+                #[cfg(feature = "unchecked-backtrace")]
+                panic!("{}", self.describe(&format!("{}, type `{}`", message, type_name::<T>())));
+                #[cfg(not(feature = "unchecked-backtrace"))]
+                panic!("{}, type `{}`", message, type_name::<T>());
+            },
+            LeakPolicy::Silent => {},
+            LeakPolicy::Hook(hook) => hook(type_name::<T>())
         }
-        // ensure consistent behavior
-        std::mem::forget(self.inner.take());
     }
 }
 
 #[cfg(not(debug_assertions))]
-use std::mem::{MaybeUninit, replace};
+use core::mem::replace;
 
 #[cfg(not(debug_assertions))]
 pub struct UncheckedOption<T> {
@@ -104,25 +355,102 @@ impl<T> UncheckedOption<T> {
         }
     }
 
+    const_mut_fn! {
+        #[inline]
+        pub unsafe fn take(&mut self) -> T {
+            let ret: MaybeUninit<T> = replace(&mut self.inner, MaybeUninit::uninit());
+            ret.assume_init()
+        }
+    }
+
+    const_mut_fn! {
+        #[inline]
+        pub unsafe fn get_ref(&self) -> &T {
+            &*self.inner.as_ptr()
+        }
+    }
+
+    const_mut_fn! {
+        #[inline]
+        pub unsafe fn get_mut(&mut self) -> &mut T {
+            &mut *self.inner.as_mut_ptr()
+        }
+    }
+
     #[inline]
-    pub unsafe fn take(&mut self) -> T {
-        let ret: MaybeUninit<T> = replace(&mut self.inner, MaybeUninit::uninit());
-        ret.assume_init()
+    pub unsafe fn into_inner(self) -> T {
+        self.inner.assume_init()
     }
 
     #[inline]
-    pub unsafe fn get_ref(&self) -> &T {
-        &*self.inner.as_ptr()
+    pub fn as_ptr(&self) -> *const T {
+        self.inner.as_ptr()
     }
 
     #[inline]
-    pub unsafe fn get_mut(&mut self) -> &mut T {
-        &mut *self.inner.as_mut_ptr()
+    pub fn as_mut_ptr(&mut self) -> *mut T {
+        self.inner.as_mut_ptr()
+    }
+
+    const_mut_fn! {
+        #[inline]
+        pub unsafe fn set(&mut self, t: T) {
+            let _ = replace(&mut self.inner, MaybeUninit::new(t));
+        }
+    }
+
+    #[inline]
+    pub unsafe fn replace(&mut self, t: T) -> T {
+        replace(&mut self.inner, MaybeUninit::new(t)).assume_init()
     }
 
     #[inline]
-    pub unsafe fn set(&mut self, t: T) {
-        let _ = replace(&mut self.inner, MaybeUninit::new(t));
+    pub unsafe fn swap(&mut self, other: &mut UncheckedOption<T>) {
+        core::mem::swap(&mut self.inner, &mut other.inner);
+    }
+
+    #[inline]
+    pub unsafe fn map_in_place(&mut self, f: impl FnOnce(T) -> T) {
+        // `MaybeUninit` never drops its content, so the slot simply counts as empty if `f` panics
+        let t: T = self.inner.as_ptr().read();
+        self.inner.as_mut_ptr().write(f(t));
+    }
+
+    #[inline]
+    pub unsafe fn map_into<U>(self, f: impl FnOnce(T) -> U) -> UncheckedOption<U> {
+        UncheckedOption::new(f(self.into_inner()))
+    }
+}
+
+impl<T: Clone> UncheckedOption<T> {
+    /// Assuming the `UncheckedOption` containing a value, create a new `UncheckedOption`
+    /// containing a clone of the item.
+    ///
+    /// # Safety
+    /// The `UncheckedOption` must really contains a `T`. If not, this function will panic in
+    /// debug build, cause undefined behavior in release build.
+    pub unsafe fn clone_assume_init(&self) -> Self {
+        Self::new(self.get_ref().clone())
+    }
+}
+
+/// Clone every `UncheckedOption` in `slice`, assuming all of them containing a value
+///
+/// # Safety
+/// Every `UncheckedOption` in `slice` must really contains a `T`. If not, this function will
+/// panic in debug build, cause undefined behavior in release build.
+#[cfg(feature = "alloc")]
+pub unsafe fn clone_slice_assume_init<T: Clone>(
+    slice: &[UncheckedOption<T>]
+) -> Vec<UncheckedOption<T>> {
+    slice.iter().map(|option| option.clone_assume_init()).collect()
+}
+
+/// Occupancy is not tracked in release build, so the content is never printed
+#[cfg(not(debug_assertions))]
+impl<T> Debug for UncheckedOption<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.write_str("UncheckedOption(<opaque>)")
     }
 }
 
@@ -153,10 +481,107 @@ pub trait UncheckedCellOps {
     /// let mut_ref: &mut T = unsafe { &mut *ptr };
     /// ```
     ///
+    /// Prefer `update_unchecked` where possible, which keeps the mutable reference from escaping.
+    ///
     /// # Safety
     /// If another mutable reference or immutable reference already exists, calling this function
     /// would immediately trigger undefined behavior.
+    #[allow(clippy::mut_from_ref)]
     unsafe fn get_mut_ref_unchecked(&self) -> &mut Self::Target;
+
+    /// Call `f` with an immutable reference obtained by `get_ref_unchecked`, which cannot outlive
+    /// the call.
+    ///
+    /// # Safety
+    /// No mutable reference may exist during the call, including ones created inside `f`.
+    #[inline]
+    unsafe fn with_ref_unchecked<R>(&self, f: impl FnOnce(&Self::Target) -> R) -> R {
+        f(self.get_ref_unchecked())
+    }
+
+    /// Call `f` with a mutable reference obtained by `get_mut_ref_unchecked`, which cannot outlive
+    /// the call.
+    ///
+    /// # Safety
+    /// No other reference may exist during the call, including ones created inside `f`, so `f`
+    /// must not access the cell again.
+    #[inline]
+    unsafe fn update_unchecked<R>(&self, f: impl FnOnce(&mut Self::Target) -> R) -> R {
+        f(self.get_mut_ref_unchecked())
+    }
+}
+
+/// Unchecked operations added to slices of `MaybeUninit`
+///
+/// These are counterparts to the slice methods of `MaybeUninit` which are not yet stable.
+pub trait UncheckedMaybeUninitOps {
+    type Item;
+
+    /// Assuming all elements are initialized, get an immutable slice of them.
+    ///
+    /// # Safety
+    /// All elements must be initialized. This cannot be checked, even in debug build.
+    unsafe fn assume_init_ref_slice(&self) -> &[Self::Item];
+
+    /// Assuming all elements are initialized, get a mutable slice of them.
+    ///
+    /// # Safety
+    /// All elements must be initialized. This cannot be checked, even in debug build.
+    unsafe fn assume_init_mut_slice(&mut self) -> &mut [Self::Item];
+
+    /// Initialize all elements with clones of elements from `src`, returning the initialized
+    /// slice.
+    ///
+    /// Previous contents are overwritten without being dropped. If a `clone` panics, the elements
+    /// cloned so far are dropped, and the slice is left uninitialized.
+    ///
+    /// # Safety
+    /// `src` must have the same length as this slice. Checked with a panic in debug build.
+    unsafe fn write_slice_cloned(&mut self, src: &[Self::Item]) -> &mut [Self::Item]
+        where Self::Item: Clone;
+}
+
+impl<T> UncheckedMaybeUninitOps for [MaybeUninit<T>] {
+    type Item = T;
+
+    #[inline]
+    unsafe fn assume_init_ref_slice(&self) -> &[T] {
+        &*(self as *const [MaybeUninit<T>] as *const [T])
+    }
+
+    #[inline]
+    unsafe fn assume_init_mut_slice(&mut self) -> &mut [T] {
+        &mut *(self as *mut [MaybeUninit<T>] as *mut [T])
+    }
+
+    unsafe fn write_slice_cloned(&mut self, src: &[T]) -> &mut [T]
+        where T: Clone
+    {
+        /// Drops the initialized prefix if a `clone` panics
+        struct Guard<'a, T> {
+            slice: &'a mut [MaybeUninit<T>],
+            initialized: usize
+        }
+
+        impl<T> Drop for Guard<'_, T> {
+            fn drop(&mut self) {
+                unsafe {
+                    core::ptr::drop_in_place(
+                        self.slice.get_unchecked_mut(..self.initialized).assume_init_mut_slice()
+                    );
+                }
+            }
+        }
+
+        debug_assert_eq!(self.len(), src.len(), "slice lengths mismatch");
+        let mut guard: Guard<'_, T> = Guard { slice: self, initialized: 0 };
+        for item in src {
+            guard.slice.get_unchecked_mut(guard.initialized).write(item.clone());
+            guard.initialized += 1;
+        }
+        core::mem::forget(guard);
+        self.assume_init_mut_slice()
+    }
 }
 
 impl<T> UncheckedCellOps for UnsafeCell<T> {
@@ -164,11 +589,442 @@ impl<T> UncheckedCellOps for UnsafeCell<T> {
 
     #[inline]
     unsafe fn get_ref_unchecked(&self) -> &Self::Target {
-        &*(self.get() as *const T)
+        &*self.get()
     }
 
     #[inline]
+    #[allow(clippy::mut_from_ref)]
     unsafe fn get_mut_ref_unchecked(&self) -> &mut Self::Target {
         &mut *self.get()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::cell::{Cell, UnsafeCell};
+    use std::panic::{AssertUnwindSafe, catch_unwind};
+    use std::rc::Rc;
+
+    use crate::test_intern::DropCounter;
+    use crate::unchecked_intern::{UncheckedCellOps, UncheckedOption};
+
+    #[test]
+    fn test_unchecked_cell_ops() {
+        let cell: UnsafeCell<Vec<i32>> = UnsafeCell::new(vec![114]);
+        unsafe {
+            cell.get_mut_ref_unchecked().push(514);
+            let r1: &Vec<i32> = cell.get_ref_unchecked();
+            let r2: &Vec<i32> = cell.get_ref_unchecked();
+            assert_eq!(r1, r2);
+            assert_eq!(r1.len(), 2);
+
+            // a fresh mutable reference is fine once the shared ones are no longer used
+            let m: &mut Vec<i32> = cell.get_mut_ref_unchecked();
+            m.push(1919);
+            *cell.get() = vec![810];
+            assert_eq!(cell.get_ref_unchecked(), &[810]);
+        }
+    }
+
+    #[test]
+    fn test_unchecked_cell_closures() {
+        let cell: UnsafeCell<Vec<i32>> = UnsafeCell::new(vec![114]);
+        unsafe {
+            let len: usize = cell.update_unchecked(|v| {
+                v.push(514);
+                v.len()
+            });
+            assert_eq!(len, 2);
+            let sum: i32 = cell.with_ref_unchecked(|v| v.iter().sum());
+            assert_eq!(sum, 628);
+            // nested shared access is fine
+            cell.with_ref_unchecked(|outer| {
+                cell.with_ref_unchecked(|inner| assert!(std::ptr::eq(outer, inner)))
+            });
+            cell.update_unchecked(|v| v.clear());
+            assert!(cell.get_ref_unchecked().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_unchecked_option() {
+        let mut option: UncheckedOption<String> = UncheckedOption::new("114514".into());
+        unsafe {
+            option.get_mut().push_str("1919810");
+            assert_eq!(option.get_ref(), "1145141919810");
+            let s: String = option.take();
+            assert_eq!(s, "1145141919810");
+            option.set(s);
+            drop(option.take());
+        }
+    }
+
+    #[test]
+    fn test_unchecked_option_replace() {
+        let drops: Rc<Cell<usize>> = Rc::new(Cell::new(0));
+        let mut option: UncheckedOption<DropCounter> =
+            UncheckedOption::new(DropCounter(drops.clone(), 114));
+        unsafe {
+            let old: DropCounter = option.replace(DropCounter(drops.clone(), 514));
+            assert_eq!(old.1, 114);
+            assert_eq!(option.get_ref().1, 514);
+            assert_eq!(drops.get(), 0);
+            drop(old);
+            assert_eq!(drops.get(), 1);
+
+            let old: DropCounter = option.replace(DropCounter(drops.clone(), 1919));
+            assert_eq!(old.1, 514);
+            drop(old);
+            assert_eq!(drops.get(), 2);
+
+            assert_eq!(option.take().1, 1919);
+        }
+        assert_eq!(drops.get(), 3);
+    }
+
+    #[test]
+    fn test_unchecked_option_swap() {
+        let drops: Rc<Cell<usize>> = Rc::new(Cell::new(0));
+        let mut a: UncheckedOption<DropCounter> =
+            UncheckedOption::new(DropCounter(drops.clone(), 114));
+        let mut b: UncheckedOption<DropCounter> =
+            UncheckedOption::new(DropCounter(drops.clone(), 514));
+        unsafe {
+            a.swap(&mut b);
+            assert_eq!((a.get_ref().1, b.get_ref().1), (514, 114));
+            b.swap(&mut a);
+            assert_eq!((a.get_ref().1, b.get_ref().1), (114, 514));
+            assert_eq!(drops.get(), 0);
+
+            drop(a.take());
+            drop(b.take());
+        }
+        assert_eq!(drops.get(), 2);
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic]
+    fn test_unchecked_option_swap_empty() {
+        let mut a: UncheckedOption<String> = UncheckedOption::new("893".into());
+        let mut b: UncheckedOption<String> = UncheckedOption::new_none();
+        unsafe { a.swap(&mut b); }
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic]
+    fn test_unchecked_option_replace_empty() {
+        let mut option: UncheckedOption<String> = UncheckedOption::new_none();
+        unsafe { option.replace("893".into()); }
+    }
+
+    #[test]
+    fn test_unchecked_option_map() {
+        let drops: Rc<Cell<usize>> = Rc::new(Cell::new(0));
+        let mut option: UncheckedOption<DropCounter> =
+            UncheckedOption::new(DropCounter(drops.clone(), 114));
+        unsafe {
+            option.map_in_place(|mut counter| {
+                counter.1 += 400;
+                counter
+            });
+            assert_eq!(option.get_ref().1, 514);
+            assert_eq!(drops.get(), 0);
+
+            let mapped: UncheckedOption<i32> = option.map_into(|counter| counter.1 * 2);
+            assert_eq!(drops.get(), 1);
+            let mut mapped: UncheckedOption<String> = mapped.map_into(|x| x.to_string());
+            assert_eq!(mapped.take(), "1028");
+        }
+    }
+
+    #[test]
+    fn test_unchecked_option_map_panic() {
+        let drops: Rc<Cell<usize>> = Rc::new(Cell::new(0));
+        let mut option: UncheckedOption<DropCounter> =
+            UncheckedOption::new(DropCounter(drops.clone(), 1919));
+        let result = catch_unwind(AssertUnwindSafe(|| unsafe {
+            option.map_in_place(|_| panic!("810"));
+        }));
+        assert!(result.is_err());
+        // the value was dropped exactly once during unwinding, and the slot is left empty
+        assert_eq!(drops.get(), 1);
+        unsafe {
+            option.set(DropCounter(drops.clone(), 893));
+            assert_eq!(option.take().1, 893);
+        }
+        assert_eq!(drops.get(), 2);
+    }
+
+    #[test]
+    fn test_unchecked_option_debug() {
+        #[derive(Debug)]
+        struct Frame {
+            #[allow(dead_code)]
+            slot: UncheckedOption<i32>
+        }
+
+        let mut full: Frame = Frame { slot: UncheckedOption::new(114) };
+        let empty: UncheckedOption<i32> = UncheckedOption::new_none();
+        #[cfg(debug_assertions)]
+        {
+            assert_eq!(format!("{:?}", full), "Frame { slot: UncheckedOption(Some(114)) }");
+            assert_eq!(format!("{:?}", empty), "UncheckedOption(None)");
+        }
+        #[cfg(not(debug_assertions))]
+        {
+            assert_eq!(format!("{:?}", full), "Frame { slot: UncheckedOption(<opaque>) }");
+            assert_eq!(format!("{:?}", empty), "UncheckedOption(<opaque>)");
+        }
+        unsafe { full.slot.take(); }
+    }
+
+    #[test]
+    fn test_unchecked_option_clone() {
+        let mut option: UncheckedOption<String> = UncheckedOption::new("114514".into());
+        unsafe {
+            let mut cloned: UncheckedOption<String> = option.clone_assume_init();
+            cloned.get_mut().push_str("1919810");
+            assert_eq!(option.take(), "114514");
+            assert_eq!(cloned.take(), "1145141919810");
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_clone_slice_assume_init() {
+        use crate::unchecked_intern::clone_slice_assume_init;
+
+        let mut registers: Vec<UncheckedOption<String>> =
+            (0..4).map(|i| UncheckedOption::new(i.to_string())).collect();
+        unsafe {
+            let mut cloned: Vec<UncheckedOption<String>> = clone_slice_assume_init(&registers);
+            assert_eq!(cloned.len(), 4);
+            for (i, (a, b)) in registers.iter_mut().zip(cloned.iter_mut()).enumerate() {
+                assert_eq!(a.take(), i.to_string());
+                assert_eq!(b.take(), i.to_string());
+            }
+        }
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic]
+    fn test_unchecked_option_clone_empty() {
+        let option: UncheckedOption<String> = UncheckedOption::new_none();
+        unsafe { option.clone_assume_init(); }
+    }
+
+    #[test]
+    fn test_unchecked_option_ptr() {
+        let mut option: UncheckedOption<String> = UncheckedOption::new("114".into());
+        let ptr: *mut String = option.as_mut_ptr();
+        unsafe {
+            (*ptr).push_str("514");
+            *ptr = format!("{}1919810", *ptr);
+            assert_eq!(&*option.as_ptr(), "1145141919810");
+            assert_eq!(option.take(), "1145141919810");
+        }
+
+        // the storage is still there when empty, and can be written through
+        assert_eq!(option.as_ptr(), ptr as *const String);
+        let slot: *mut String = option.as_mut_ptr();
+        assert_eq!(slot, ptr);
+        unsafe {
+            slot.write("893".into());
+            let mut other: UncheckedOption<String> = UncheckedOption::new_none();
+            other.set(option.as_ptr().read());
+            assert_eq!(other.take(), "893");
+        }
+    }
+
+    #[test]
+    fn test_unchecked_option_into_inner() {
+        use crate::diag_intern::test::capture_diagnostics;
+
+        struct Frame {
+            slot: UncheckedOption<DropCounter>,
+            extra: i32
+        }
+
+        let drops: Rc<Cell<usize>> = Rc::new(Cell::new(0));
+        let frame: Frame = Frame {
+            slot: UncheckedOption::new(DropCounter(drops.clone(), 114)),
+            extra: 514
+        };
+
+        let ((counter, extra), messages) = capture_diagnostics(|| {
+            let Frame { slot, extra } = frame;
+            let counter: DropCounter = unsafe { slot.into_inner() };
+            (counter, extra)
+        });
+
+        assert_eq!((counter.1, extra), (114, 514));
+        assert_eq!(drops.get(), 0);
+        drop(counter);
+        assert_eq!(drops.get(), 1);
+        assert!(messages.is_empty());
+    }
+
+    #[cfg(all(debug_assertions, feature = "unchecked-backtrace"))]
+    #[test]
+    #[should_panic(expected = "UncheckedOption accessed while empty, last take or set at:")]
+    fn test_unchecked_option_backtrace_misuse() {
+        let mut option: UncheckedOption<String> = UncheckedOption::new("114514".into());
+        unsafe {
+            drop(option.take());
+            drop(option.take());
+        }
+    }
+
+    #[cfg(all(debug_assertions, feature = "unchecked-backtrace"))]
+    #[test]
+    fn test_unchecked_option_backtrace_leak() {
+        use crate::diag_intern::test::capture_diagnostics;
+
+        #[inline(never)]
+        fn forgetful_setter(option: &mut UncheckedOption<i32>) {
+            unsafe { option.set(1919810); }
+        }
+
+        let mut option: UncheckedOption<i32> = UncheckedOption::new_none();
+        forgetful_setter(&mut option);
+
+        let ((), messages) = capture_diagnostics(|| drop(option));
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].starts_with(
+            "UncheckedOption dropped with value, potential resource leak, last take or set at:"
+        ));
+        assert!(messages[0].contains("forgetful_setter"));
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    fn test_leak_policy() {
+        use std::panic::{AssertUnwindSafe, catch_unwind};
+        use std::sync::Mutex;
+        use std::thread::{self, ThreadId};
+
+        use crate::diag_intern::test::HANDLER_LOCK;
+        use crate::unchecked_intern::{LeakPolicy, leak_policy, set_leak_policy};
+
+        // other tests may leak options concurrently, so leaks are recorded with their thread
+        static LEAKED: Mutex<Vec<(ThreadId, &'static str)>> = Mutex::new(Vec::new());
+
+        let _guard = HANDLER_LOCK.lock().unwrap();
+        assert!(matches!(leak_policy(), LeakPolicy::Warn));
+
+        set_leak_policy(LeakPolicy::Panic);
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            drop(UncheckedOption::new(String::from("114514")));
+        }));
+        let payload: String = *result.unwrap_err().downcast::<String>().unwrap();
+        assert_eq!(
+            payload,
+            "UncheckedOption dropped with value, potential resource leak, \
+             type `alloc::string::String`"
+        );
+        // empty options never trigger the policy
+        drop(UncheckedOption::<String>::new_none());
+
+        set_leak_policy(LeakPolicy::Hook(|type_name| {
+            LEAKED.lock().unwrap().push((thread::current().id(), type_name));
+        }));
+        drop(UncheckedOption::new(1919810_u64));
+        assert!(matches!(leak_policy(), LeakPolicy::Hook(_)));
+
+        set_leak_policy(LeakPolicy::Silent);
+        drop(UncheckedOption::new(893_i32));
+
+        set_leak_policy(LeakPolicy::Warn);
+        let current: ThreadId = thread::current().id();
+        let leaked: Vec<&'static str> = LEAKED.lock().unwrap()
+            .iter()
+            .filter(|(thread, _)| *thread == current)
+            .map(|(_, type_name)| *type_name)
+            .collect();
+        assert_eq!(leaked, ["u64"]);
+    }
+
+    #[test]
+    fn test_maybe_uninit_ops() {
+        use std::mem::MaybeUninit;
+
+        use crate::unchecked_intern::UncheckedMaybeUninitOps;
+
+        let mut slots: [MaybeUninit<String>; 3] = [
+            MaybeUninit::uninit(),
+            MaybeUninit::uninit(),
+            MaybeUninit::uninit()
+        ];
+        let src: [String; 3] = ["114".into(), "514".into(), "1919".into()];
+        unsafe {
+            let written: &mut [String] = slots.write_slice_cloned(&src);
+            written[2].push_str("810");
+            assert_eq!(slots.assume_init_ref_slice(), ["114", "514", "1919810"]);
+            slots.assume_init_mut_slice()[0].push('!');
+            assert_eq!(slots[0].assume_init_ref(), "114!");
+            std::ptr::drop_in_place(slots.assume_init_mut_slice());
+        }
+
+        let mut empty: [MaybeUninit<String>; 0] = [];
+        assert!(unsafe { empty.write_slice_cloned(&[]) }.is_empty());
+    }
+
+    #[test]
+    fn test_write_slice_cloned_panic() {
+        use std::mem::MaybeUninit;
+
+        use crate::unchecked_intern::UncheckedMaybeUninitOps;
+
+        struct PanicOnClone {
+            id: i32,
+            drops: Rc<Cell<usize>>
+        }
+
+        impl Clone for PanicOnClone {
+            fn clone(&self) -> Self {
+                if self.id == 893 {
+                    panic!("clone failed");
+                }
+                PanicOnClone { id: self.id, drops: self.drops.clone() }
+            }
+        }
+
+        impl Drop for PanicOnClone {
+            fn drop(&mut self) {
+                self.drops.set(self.drops.get() + 1);
+            }
+        }
+
+        let drops: Rc<Cell<usize>> = Rc::new(Cell::new(0));
+        let src: Vec<PanicOnClone> = [114, 514, 893, 1919].iter()
+            .map(|id| PanicOnClone { id: *id, drops: drops.clone() })
+            .collect();
+        let mut slots: Vec<MaybeUninit<PanicOnClone>> =
+            (0..4).map(|_| MaybeUninit::uninit()).collect();
+        let result = catch_unwind(AssertUnwindSafe(|| unsafe {
+            slots.write_slice_cloned(&src);
+        }));
+        assert!(result.is_err());
+        // the two clones made before the panic are dropped exactly once
+        assert_eq!(drops.get(), 2);
+        drop(slots);
+        assert_eq!(drops.get(), 2);
+        drop(src);
+        assert_eq!(drops.get(), 6);
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "slice lengths mismatch")]
+    fn test_write_slice_cloned_mismatch() {
+        use std::mem::MaybeUninit;
+
+        use crate::unchecked_intern::UncheckedMaybeUninitOps;
+
+        let mut slots: [MaybeUninit<i32>; 2] = [MaybeUninit::uninit(); 2];
+        unsafe { slots.write_slice_cloned(&[114, 514, 1919]); }
+    }
+}
@@ -14,60 +14,32 @@ pub enum Value {
 }
 
 impl Value {
-    pub fn is_nil(&self) -> bool {
-        if let Value::Nil = self {
-            true
-        } else {
-            false
-        }
+    pub const fn is_nil(&self) -> bool {
+        matches!(self, Value::Nil)
     }
 
-    pub fn is_bool(&self) -> bool {
-        if let Value::Bool(_) = self {
-            true
-        } else {
-            false
-        }
+    pub const fn is_bool(&self) -> bool {
+        matches!(self, Value::Bool(_))
     }
 
-    pub fn is_int(&self) -> bool {
-        if let Value::Int(_) = self {
-            true
-        } else {
-            false
-        }
+    pub const fn is_int(&self) -> bool {
+        matches!(self, Value::Int(_))
     }
 
-    pub fn is_float(&self) -> bool {
-        if let Value::Float(_) = self {
-            true
-        } else {
-            false
-        }
+    pub const fn is_float(&self) -> bool {
+        matches!(self, Value::Float(_))
     }
 
-    pub fn is_string(&self) -> bool {
-        if let Value::String(_) = self {
-            true
-        } else {
-            false
-        }
+    pub const fn is_string(&self) -> bool {
+        matches!(self, Value::String(_))
     }
 
-    pub fn is_array(&self) -> bool {
-        if let Value::Array(_) = self {
-            true
-        } else {
-            false
-        }
+    pub const fn is_array(&self) -> bool {
+        matches!(self, Value::Array(_))
     }
 
-    pub fn is_object(&self) -> bool {
-        if let Value::Object(_) = self {
-            true
-        } else {
-            false
-        }
+    pub const fn is_object(&self) -> bool {
+        matches!(self, Value::Object(_))
     }
 }
 
@@ -348,7 +320,7 @@ impl Serialize for Value {
             Value::Bool(b) => serializer.serialize_bool(*b),
             Value::Int(i) => serializer.serialize_i64(*i),
             Value::Float(n) => serializer.serialize_f64(*n),
-            Value::String(s) => serializer.serialize_str(&s),
+            Value::String(s) => serializer.serialize_str(s),
             Value::Array(a) => a.serialize(serializer),
             Value::Object(o) => o.serialize(serializer),
         }
@@ -1,9 +1,152 @@
 //! Provides a un-instantiable `Void` type for plugging traits
+//!
+//! `Void` is handy for expressing errors that cannot happen. Since it has no values, matching
+//! on it needs no arm at all:
+//!
+//! ```
+//! # use xjbutil::void::{Void, VoidResultExt};
+//! fn parse_infallible(s: &str) -> Result<String, Void> {
+//!     Ok(s.to_uppercase())
+//! }
+//!
+//! let s: String = match parse_infallible("ace") {
+//!     Ok(s) => s,
+//!     Err(void) => match void {}
+//! };
+//! assert_eq!(s, "ACE");
+//! assert_eq!(parse_infallible("ace").unwrap_void(), "ACE");
+//! ```
+
+use core::convert::Infallible;
+use core::fmt::{Debug, Display, Formatter};
+
+#[cfg(feature = "either")] use crate::either::Either;
 
 /// An un-instantiable `Void` type.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Void {}
 
+/// Turn a `Void` into anything, since it can never exist
+#[inline(always)]
+pub const fn absurd(v: Void) -> ! {
+    match v {}
+}
+
+impl Debug for Void {
+    fn fmt(&self, _: &mut Formatter<'_>) -> core::fmt::Result {
+        match *self {}
+    }
+}
+
+impl Display for Void {
+    fn fmt(&self, _: &mut Formatter<'_>) -> core::fmt::Result {
+        match *self {}
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Void {}
+
+impl From<Infallible> for Void {
+    fn from(v: Infallible) -> Self {
+        match v {}
+    }
+}
+
+impl From<Void> for Infallible {
+    fn from(v: Void) -> Self {
+        match v {}
+    }
+}
+
+/// Extension methods for `Result`s which cannot fail
+pub trait VoidResultExt<T> {
+    /// Get the success value, without any panicking path
+    fn unwrap_void(self) -> T;
+}
+
+impl<T> VoidResultExt<T> for Result<T, Void> {
+    #[inline(always)]
+    fn unwrap_void(self) -> T {
+        match self {
+            Ok(t) => t,
+            Err(v) => match v {}
+        }
+    }
+}
+
+#[cfg(feature = "either")]
+impl<T> Either<T, Void> {
+    /// Get the left value, the only possible one
+    #[inline(always)]
+    pub fn into_left(self) -> T {
+        match self {
+            Either::Left(t) => t,
+            Either::Right(v) => match v {}
+        }
+    }
+}
+
+#[cfg(feature = "either")]
+impl<T> Either<Void, T> {
+    /// Get the right value, the only possible one
+    #[inline(always)]
+    pub fn into_right(self) -> T {
+        match self {
+            Either::Left(v) => match v {},
+            Either::Right(t) => t
+        }
+    }
+}
+
 /// 这个 trait 没有任何实质性作用，但它表明任何类型都应该是我老婆
 pub trait Wife {}
 
 impl<T: ?Sized> Wife for T {}
+
+#[cfg(test)]
+mod test {
+    use std::convert::Infallible;
+    use std::str::FromStr;
+
+    use crate::void::{Void, VoidResultExt, absurd};
+
+    #[test]
+    fn test_unwrap_void() {
+        let r: Result<i32, Void> = Ok(114514);
+        assert_eq!(r.unwrap_void(), 114514);
+
+        let r: Result<String, Infallible> = String::from_str("1919810");
+        assert_eq!(r.map_err(Void::from).unwrap_void(), "1919810");
+    }
+
+    #[test]
+    fn test_absurd() {
+        fn error_to_anything<T>(r: Result<T, Void>) -> Result<T, std::io::Error> {
+            r.map_err(|v| absurd(v))
+        }
+
+        assert_eq!(error_to_anything(Ok(893)).unwrap(), 893);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_void_error() {
+        fn boxed(r: Result<i32, Void>) -> Result<i32, Box<dyn std::error::Error>> {
+            Ok(r?)
+        }
+
+        assert_eq!(boxed(Ok(114)).unwrap(), 114);
+    }
+
+    #[cfg(feature = "either")]
+    #[test]
+    fn test_either_void() {
+        use crate::either::Either;
+
+        let e: Either<&str, Void> = Either::Left("114514");
+        assert_eq!(e.into_left(), "114514");
+        let e: Either<Void, i32> = Either::Right(1919810);
+        assert_eq!(e.into_right(), 1919810);
+    }
+}
@@ -1,35 +1,398 @@
 //! Yet another wide pointer.
 
-use std::fmt::Debug;
-use std::marker::PhantomData;
+use core::fmt::Debug;
+use core::marker::PhantomData;
+use core::mem::{size_of, transmute_copy};
+use core::ptr::NonNull;
 
+#[cfg(feature = "nightly-metadata")] use core::ptr::Pointee;
+
+mod atomic;
+pub use atomic::AtomicWidePointer;
+
+/// Raw representation of a wide pointer
+///
+/// Both words are kept as pointers rather than integers, so that the provenance of the data
+/// pointer, and of the vtable of a trait object, survives the round trip. Use `addr` and
+/// `with_addr` to inspect or modify the data address. For slices and `str`, `trivia` holds the
+/// length as an address without provenance, see `new_slice` and `len`.
 #[repr(C)]
 #[derive(Clone, Copy, Eq, Hash, PartialEq)]
 pub struct WidePointer {
-    pub ptr: usize,
-    pub trivia: usize,
-    _phantom: PhantomData<*const ()>
+    pub ptr: *const (),
+    pub trivia: *const ()
 }
 
 impl WidePointer {
-    pub const fn new(ptr: usize, trivia: usize) -> Self {
-        Self {
-            ptr,
-            trivia,
-            _phantom: PhantomData
+    pub const fn new(ptr: *const (), trivia: *const ()) -> Self {
+        Self { ptr, trivia }
+    }
+
+    /// Creates a slice or `str` pointer with given data pointer and length
+    pub const fn new_slice(ptr: *const (), len: usize) -> Self {
+        Self::new(ptr, without_provenance(len))
+    }
+
+    /// The null wide pointer, with null `trivia`
+    ///
+    /// Nullness is decided by the data pointer alone, see `is_null`.
+    pub const fn null() -> Self {
+        Self::new(core::ptr::null(), core::ptr::null())
+    }
+
+    /// A dangling but well-aligned placeholder for `T`, with null `trivia`
+    pub const fn dangling_for<T>() -> Self {
+        Self::new(NonNull::<T>::dangling().as_ptr() as *const (), core::ptr::null())
+    }
+
+    /// Whether the data pointer is null, regardless of `trivia`
+    ///
+    /// `(0, len)` is null even if `len` is not zero, just like a null `*const [T]` with non-zero
+    /// length.
+    #[inline] pub const fn is_null(&self) -> bool {
+        self.ptr.is_null()
+    }
+
+    /// Return `self`, checking that it is not null in debug builds
+    #[track_caller]
+    #[inline] pub fn expect_non_null(self) -> Self {
+        debug_assert!(
+            !self.is_null(),
+            "unexpected null `WidePointer` with trivia {:#x}",
+            self.len()
+        );
+        self
+    }
+
+    /// Address of the data pointer, without exposing its provenance
+    #[inline] pub const fn addr(&self) -> usize {
+        ptr_addr(self.ptr)
+    }
+
+    /// Creates a new wide pointer with given data address, keeping the provenance of the data
+    /// pointer and the trivia
+    #[inline] pub fn with_addr(self, addr: usize) -> Self {
+        Self::new(ptr_with_addr(self.ptr, addr), self.trivia)
+    }
+
+    /// Creates a new wide pointer with the data address mapped by `f`, keeping the provenance of
+    /// the data pointer and the trivia
+    #[inline] pub fn map_addr(self, f: impl FnOnce(usize) -> usize) -> Self {
+        self.with_addr(f(self.addr()))
+    }
+
+    /// Advance the data pointer by `n` bytes, keeping the trivia
+    ///
+    /// Overflowing the address space panics in debug builds and wraps around in release builds.
+    #[track_caller]
+    #[inline] pub fn byte_add(self, n: usize) -> Self {
+        debug_assert!(self.addr().checked_add(n).is_some(), "`WidePointer::byte_add` overflowed");
+        Self::new((self.ptr as *const u8).wrapping_add(n) as *const (), self.trivia)
+    }
+
+    /// Distance in bytes from the data pointer of `other` to the one of `self`
+    ///
+    /// Only addresses are compared, so the pointers need not be derived from the same object.
+    /// The difference wraps around if it does not fit in an `isize`.
+    #[inline] pub fn byte_offset_from(self, other: Self) -> isize {
+        self.addr().wrapping_sub(other.addr()) as isize
+    }
+
+    /// Round the data address up to a multiple of `align`, keeping the trivia
+    ///
+    /// Panics if `align` is not a power of two. Rounding past the end of the address space panics
+    /// in debug builds and wraps around to zero in release builds.
+    #[track_caller]
+    #[inline] pub fn align_up(self, align: usize) -> Self {
+        assert!(align.is_power_of_two(), "alignment {} is not a power of two", align);
+        let mask: usize = align - 1;
+        debug_assert!(
+            self.addr().checked_add(mask).is_some(),
+            "`WidePointer::align_up` overflowed"
+        );
+        self.map_addr(|addr: usize| addr.wrapping_add(mask) & !mask)
+    }
+
+    /// Whether both pointers have the same `trivia` address, that is the same length or vtable
+    ///
+    /// For trait objects, see `likely_same_concrete_type` for why this is only a heuristic.
+    #[inline] pub const fn same_metadata(&self, other: &WidePointer) -> bool {
+        ptr_addr(self.trivia) == ptr_addr(other.trivia)
+    }
+
+    /// Whether both the data address and the `trivia` are equal
+    #[inline] pub const fn same_object(&self, other: &WidePointer) -> bool {
+        self.addr() == other.addr() && self.same_metadata(other)
+    }
+
+    /// Guess whether two trait object pointers refer to the same concrete type, by comparing their
+    /// vtables
+    ///
+    /// This is a heuristic. Vtables are not guaranteed to be unique: the same type may get one
+    /// vtable per codegen unit, giving false negatives, and identical vtables of different types
+    /// may be merged, giving false positives. Use `TypeId` where correctness depends on it.
+    #[inline] pub const fn likely_same_concrete_type(&self, other: &WidePointer) -> bool {
+        self.same_metadata(other)
+    }
+
+    /// Run the destructor of the `T` this pointer refers to
+    ///
+    /// # Safety
+    /// `self` must satisfy the requirements of `to_ptr::<T>`, and the result must satisfy those of
+    /// `core::ptr::drop_in_place`. The object must not be used after this call.
+    #[inline] pub unsafe fn drop_in_place_as<T: ?Sized>(self) {
+        core::ptr::drop_in_place(self.to_mut_ptr::<T>())
+    }
+
+    /// Set the bits of the data address selected by `mask` to `tag`, keeping the trivia
+    ///
+    /// In debug builds, panics if those bits are not zero yet, or if `tag` has bits outside
+    /// `mask`.
+    #[track_caller]
+    #[inline] pub fn with_tag(self, tag: usize, mask: usize) -> Self {
+        debug_assert!(
+            self.addr() & mask == 0,
+            "tagging `WidePointer` with address {:#x}, which already has bits in mask {:#x}",
+            self.addr(),
+            mask
+        );
+        debug_assert!(tag & !mask == 0, "tag {:#x} does not fit in mask {:#x}", tag, mask);
+        self.map_addr(|addr: usize| addr | (tag & mask))
+    }
+
+    /// Bits of the data address selected by `mask`
+    #[inline] pub fn tag(self, mask: usize) -> usize {
+        self.addr() & mask
+    }
+
+    /// Clear the bits of the data address selected by `mask`, keeping the trivia
+    #[inline] pub fn untagged(self, mask: usize) -> Self {
+        self.map_addr(|addr: usize| addr & !mask)
+    }
+
+    /// `with_tag` using the lowest `BITS` bits, panics if `tag` does not fit
+    #[track_caller]
+    #[inline] pub fn with_tag_bits<const BITS: u32>(self, tag: usize) -> Self {
+        let mask: usize = TagMask::<BITS>::MASK;
+        assert!(tag <= mask, "tag {:#x} does not fit in {} bits", tag, BITS);
+        self.with_tag(tag, mask)
+    }
+
+    /// `tag` using the lowest `BITS` bits
+    #[inline] pub fn tag_bits<const BITS: u32>(self) -> usize {
+        self.tag(TagMask::<BITS>::MASK)
+    }
+
+    /// `untagged` using the lowest `BITS` bits
+    #[inline] pub fn untagged_bits<const BITS: u32>(self) -> Self {
+        self.untagged(TagMask::<BITS>::MASK)
+    }
+
+    /// Native-endian bytes of the data address followed by the trivia
+    ///
+    /// The provenance of the data pointer is stripped, not exposed.
+    pub fn as_bytes(&self) -> [u8; 2 * size_of::<usize>()] {
+        let mut bytes: [u8; 2 * size_of::<usize>()] = [0; 2 * size_of::<usize>()];
+        let (addr, trivia) = bytes.split_at_mut(size_of::<usize>());
+        addr.copy_from_slice(&self.addr().to_ne_bytes());
+        trivia.copy_from_slice(&self.len().to_ne_bytes());
+        bytes
+    }
+
+    /// Inverse of `as_bytes`
+    ///
+    /// Neither pointer has provenance. The data pointer must not be dereferenced: use `with_addr`
+    /// on a pointer to the original object, such as the base of a shared memory arena, to get one
+    /// that can be. Likewise, a vtable read back this way must not be used to rebuild a trait
+    /// object pointer.
+    pub fn from_bytes(bytes: [u8; 2 * size_of::<usize>()]) -> Self {
+        let mut addr: [u8; size_of::<usize>()] = [0; size_of::<usize>()];
+        let mut trivia: [u8; size_of::<usize>()] = [0; size_of::<usize>()];
+        addr.copy_from_slice(&bytes[..size_of::<usize>()]);
+        trivia.copy_from_slice(&bytes[size_of::<usize>()..]);
+        Self::new(
+            without_provenance(usize::from_ne_bytes(addr)),
+            without_provenance(usize::from_ne_bytes(trivia))
+        )
+    }
+
+    /// Split a pointer into its data pointer and metadata
+    ///
+    /// Thin pointers get null `trivia`. Pointers of any other size fail to compile.
+    #[inline] pub fn from_ptr<T: ?Sized>(ptr: *const T) -> Self {
+        if PtrKind::<T>::IS_WIDE {
+            // SAFETY: both are two words, and `WidePointer` has the layout of a fat pointer
+            unsafe { transmute_copy::<*const T, WidePointer>(&ptr) }
+        } else {
+            Self::new(ptr as *const (), core::ptr::null())
         }
     }
+
+    #[inline] pub fn from_mut_ptr<T: ?Sized>(ptr: *mut T) -> Self {
+        Self::from_ptr(ptr as *const T)
+    }
+
+    /// Re-assemble a pointer from the data pointer and metadata
+    ///
+    /// For thin pointers `trivia` is ignored. Pointers of any other size fail to compile.
+    ///
+    /// # Safety
+    /// If `T` is unsized, `trivia` must be valid metadata for `T`: a vtable of the same trait
+    /// object type for `dyn Trait`, carrying the provenance it got from `from_ptr`, or a length
+    /// for slices and `str`.
+    #[inline] pub unsafe fn to_ptr<T: ?Sized>(self) -> *const T {
+        if PtrKind::<T>::IS_WIDE {
+            transmute_copy::<WidePointer, *const T>(&self)
+        } else {
+            transmute_copy::<*const (), *const T>(&self.ptr)
+        }
+    }
+
+    /// Mutable counterpart of `to_ptr`
+    ///
+    /// # Safety
+    /// See `to_ptr`.
+    #[inline] pub unsafe fn to_mut_ptr<T: ?Sized>(self) -> *mut T {
+        self.to_ptr::<T>() as *mut T
+    }
+
+    #[inline] pub fn from_slice<T>(slice: &[T]) -> Self {
+        Self::from_ptr(slice as *const [T])
+    }
+
+    #[inline] pub fn from_mut_slice<T>(slice: &mut [T]) -> Self {
+        Self::from_mut_ptr(slice as *mut [T])
+    }
+
+    /// Length of the slice this pointer was created from
+    ///
+    /// This is simply the address of `trivia`, which is only meaningful for pointers created from
+    /// slices or `str`. For trait objects it is the address of a vtable.
+    #[inline] pub const fn len(&self) -> usize {
+        ptr_addr(self.trivia)
+    }
+
+    #[inline] pub const fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Reconstruct the slice this pointer was created from
+    ///
+    /// # Safety
+    /// `self` must come from a `[T]` slice which is still alive and not mutably borrowed. The
+    /// lifetime `'a` is unbounded and picked by the caller, so nothing ties it to the original
+    /// borrow: the caller must make sure the returned slice does not outlive the data.
+    #[inline] pub unsafe fn as_slice<'a, T>(self) -> &'a [T] {
+        core::slice::from_raw_parts(self.ptr as *const T, self.len())
+    }
+
+    /// Reconstruct the mutable slice this pointer was created from
+    ///
+    /// # Safety
+    /// `self` must come from `from_mut_slice` on a `[T]` slice which is still alive, and no other
+    /// reference to the slice may exist while the returned one is in use. As with `as_slice`, the
+    /// lifetime `'a` is picked by the caller and not tied to the original borrow.
+    #[inline] pub unsafe fn as_mut_slice<'a, T>(self) -> &'a mut [T] {
+        core::slice::from_raw_parts_mut(self.ptr as *mut T, self.len())
+    }
+}
+
+struct PtrKind<T: ?Sized>(PhantomData<*const T>);
+
+impl<T: ?Sized> PtrKind<T> {
+    const IS_WIDE: bool = {
+        let size: usize = size_of::<*const T>();
+        assert!(
+            size == size_of::<WidePointer>() || size == size_of::<*const ()>(),
+            "pointer is neither thin nor of the size of `WidePointer`"
+        );
+        size == size_of::<WidePointer>()
+    };
+}
+
+struct TagMask<const BITS: u32>;
+
+impl<const BITS: u32> TagMask<BITS> {
+    const MASK: usize = {
+        assert!(BITS < usize::BITS, "too many tag bits");
+        (1 << BITS) - 1
+    };
+}
+
+/// Interop with the unstable pointer metadata API
+#[cfg(feature = "nightly-metadata")]
+impl WidePointer {
+    pub fn from_raw_parts<T: ?Sized>(data: *const (), meta: <T as Pointee>::Metadata) -> Self {
+        Self::from_ptr(core::ptr::from_raw_parts::<T>(data, meta))
+    }
+
+    /// # Safety
+    /// See `to_ptr`.
+    pub unsafe fn metadata<T: ?Sized>(self) -> <T as Pointee>::Metadata {
+        core::ptr::metadata(self.to_ptr::<T>())
+    }
+
+    /// # Safety
+    /// See `to_ptr`.
+    pub unsafe fn to_raw_parts<T: ?Sized>(self) -> (*const (), <T as Pointee>::Metadata) {
+        self.to_ptr::<T>().to_raw_parts()
+    }
+}
+
+// polyfills of `<*const T>::addr`, `<*const T>::with_addr` and `core::ptr::without_provenance`,
+// not available on older compilers
+#[inline]
+#[allow(clippy::transmutes_expressible_as_ptr_casts, ptr_to_integer_transmute_in_consts)]
+const fn ptr_addr(ptr: *const ()) -> usize {
+    // SAFETY: pointer-to-integer transmutes simply strip the provenance, while an `as` cast
+    // would expose it. During const eval this only succeeds for pointers without provenance
+    // (null pointers and slice lengths)
+    unsafe { core::mem::transmute::<*const (), usize>(ptr) }
+}
+
+#[inline] fn ptr_with_addr(ptr: *const (), addr: usize) -> *const () {
+    let offset: isize = addr.wrapping_sub(ptr_addr(ptr)) as isize;
+    (ptr as *const u8).wrapping_offset(offset) as *const ()
+}
+
+#[inline] const fn without_provenance(addr: usize) -> *const () {
+    core::ptr::null::<u8>().wrapping_add(addr) as *const ()
+}
+
+// SAFETY: two words without padding, and every bit pattern is a valid raw pointer. Pointers read
+// from bytes carry no provenance, as documented on `from_bytes`.
+#[cfg(feature = "bytemuck")]
+unsafe impl bytemuck::Zeroable for WidePointer {}
+
+#[cfg(feature = "bytemuck")]
+unsafe impl bytemuck::Pod for WidePointer {}
+
+/// Generates arbitrary addresses and trivia values
+///
+/// The generated pointers carry no provenance and are never dereferenceable; they are only meant
+/// for exercising code that stores or compares wide pointers.
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for WidePointer {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let addr: usize = u.arbitrary()?;
+        let trivia: usize = u.arbitrary()?;
+        Ok(Self::new(without_provenance(addr), without_provenance(trivia)))
+    }
+
+    fn size_hint(depth: usize) -> (usize, Option<usize>) {
+        <(usize, usize) as arbitrary::Arbitrary<'a>>::size_hint(depth)
+    }
 }
 
 impl Debug for WidePointer {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "WidePointer(0x{:X}, 0x{:X})", self.ptr, self.trivia)
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "WidePointer(0x{:X}, 0x{:X})", self.addr(), self.len())
     }
 }
 
 impl Default for WidePointer {
     fn default() -> Self {
-        Self::new(0, 0)
+        Self::null()
     }
 }
 
@@ -57,9 +420,9 @@ mod test {
         let s = MeinStrukt();
         let ptr: *const MeinStrukt = &s as *const MeinStrukt;
         let wide_ptr: *const dyn UselessTrait = &s as &dyn UselessTrait as *const dyn UselessTrait;
-        let wide_ptr: WidePointer = unsafe { std::mem::transmute::<>(wide_ptr) };
+        let wide_ptr: WidePointer = WidePointer::from_ptr(wide_ptr);
 
-        assert_eq!(wide_ptr.ptr, ptr as usize);
+        assert_eq!(wide_ptr.ptr, ptr as *const ());
     }
 
     #[test]
@@ -67,9 +430,363 @@ mod test {
         let slice: &[i32; 4] = &[114, 514, 1919, 810];
         let ptr: *const i32 = &slice[0] as *const i32;
         let wide_ptr: *const [i32] = slice as *const [i32];
-        let wide_ptr: WidePointer = unsafe { std::mem::transmute::<>(wide_ptr) };
+        let wide_ptr: WidePointer = WidePointer::from_ptr(wide_ptr);
+
+        assert_eq!(wide_ptr.ptr, ptr as *const ());
+        assert_eq!(wide_ptr.len(), 4);
+
+        let from_slice: WidePointer = WidePointer::from_slice(&slice[..]);
+        assert_eq!(from_slice, wide_ptr);
+        assert_eq!(from_slice.len(), 4);
+        assert_eq!(unsafe { from_slice.as_slice::<i32>() }, &[114, 514, 1919, 810]);
+
+        let empty: WidePointer = WidePointer::from_slice::<i32>(&[]);
+        assert_eq!(empty.len(), 0);
+        assert!(empty.is_empty());
+        assert_eq!(unsafe { empty.as_slice::<i32>() }, &[] as &[i32]);
+
+        let mut array: [u8; 3] = [11, 45, 14];
+        let mutable: WidePointer = WidePointer::from_mut_slice(&mut array[1..]);
+        assert_eq!(mutable.len(), 2);
+        unsafe { mutable.as_mut_slice::<u8>()[1] = 19 };
+        assert_eq!(array, [11, 45, 19]);
+    }
+
+    #[test]
+    fn test_wide_pointer_provenance() {
+        let mut array: [i32; 4] = [114, 514, 1919, 810];
+        let wide_ptr: *mut [i32] = &mut array as *mut [i32];
+        let wide_ptr: WidePointer = WidePointer::from_mut_ptr(wide_ptr);
+
+        let moved: WidePointer = wide_ptr.with_addr(wide_ptr.addr() + 2 * std::mem::size_of::<i32>());
+        assert_eq!(moved.len(), 4);
+        assert_eq!(moved.addr() - wide_ptr.addr(), 8);
+
+        // the moved pointer still carries provenance of `array`, so accessing through it is fine
+        let elem: *mut i32 = moved.ptr as *mut i32;
+        unsafe { *elem = 1 };
+
+        let back: *mut [i32] = unsafe { wide_ptr.to_mut_ptr() };
+        assert_eq!(unsafe { &*back }, &[114, 514, 1, 810]);
+        assert_eq!(array, [114, 514, 1, 810]);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        use std::fmt::Display;
+
+        let mut array: [u16; 3] = [114, 514, 1919];
+        let slice: *mut [u16] = &mut array as *mut [u16];
+        let wide_ptr: WidePointer = WidePointer::from_mut_ptr(slice);
+        assert_eq!((wide_ptr.ptr, wide_ptr.len()), (slice as *const (), 3));
+        let back: *mut [u16] = unsafe { wide_ptr.to_mut_ptr() };
+        unsafe { (*back)[2] = 810 };
+        assert_eq!(array, [114, 514, 810]);
+
+        let s: &str = "1919810";
+        let wide_ptr: WidePointer = WidePointer::from_ptr(s as *const str);
+        assert_eq!(unsafe { &*wide_ptr.to_ptr::<str>() }, "1919810");
+
+        let value: i32 = 893;
+        let thin: WidePointer = WidePointer::from_ptr(&value as *const i32);
+        assert_eq!((thin.ptr, thin.len()), (&value as *const i32 as *const (), 0));
+        assert_eq!(unsafe { *thin.to_ptr::<i32>() }, 893);
+
+        let display: *const dyn Display = &value as &dyn Display as *const dyn Display;
+        let wide_ptr: WidePointer = WidePointer::from_ptr(display);
+        assert_eq!(wide_ptr.ptr, &value as *const i32 as *const ());
+        let back: *const dyn Display = unsafe { wide_ptr.to_ptr() };
+        assert_eq!(unsafe { &*back }.to_string(), "893");
+    }
+
+    #[test]
+    fn test_null_dangling() {
+        assert!(WidePointer::null().is_null());
+        assert_eq!(WidePointer::default(), WidePointer::null());
+
+        // only the data pointer decides nullness
+        let null_with_len: WidePointer = WidePointer::new_slice(std::ptr::null(), 4);
+        assert!(null_with_len.is_null());
+        assert_ne!(null_with_len, WidePointer::null());
+        let slice: *const [u8] = unsafe { null_with_len.to_ptr() };
+        assert!(slice.is_null());
+
+        let dangling: WidePointer = WidePointer::dangling_for::<u64>();
+        assert!(!dangling.is_null());
+        assert_eq!(dangling.addr(), std::mem::align_of::<u64>());
+        assert_eq!(dangling.len(), 0);
+        assert_eq!(dangling.expect_non_null(), dangling);
+        assert_eq!(WidePointer::dangling_for::<()>().addr(), 1);
+
+        const DANGLING: WidePointer = WidePointer::dangling_for::<u32>();
+        assert_eq!(unsafe { DANGLING.as_slice::<u32>() }, &[] as &[u32]);
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "unexpected null `WidePointer` with trivia 0x4")]
+    fn test_expect_non_null() {
+        WidePointer::new_slice(std::ptr::null(), 4).expect_non_null();
+    }
+
+    #[test]
+    fn test_addr_arithmetic() {
+        let mut array: [u8; 16] = [0; 16];
+        let base: WidePointer = WidePointer::new_slice(array.as_mut_ptr() as *const (), 893);
+
+        let moved: WidePointer = base.byte_add(5);
+        assert_eq!(moved.len(), 893);
+        assert_eq!(moved.byte_offset_from(base), 5);
+        assert_eq!(base.byte_offset_from(moved), -5);
+        assert_eq!(base.byte_add(0), base);
+
+        let tagged: WidePointer = base.map_addr(|addr: usize| addr | 1);
+        assert_eq!(tagged.len(), 893);
+        assert_eq!(tagged.map_addr(|addr: usize| addr & !1).addr(), base.addr() & !1);
+
+        let aligned: WidePointer = moved.align_up(4);
+        assert_eq!(aligned.addr() % 4, 0);
+        assert!(aligned.addr() >= moved.addr() && aligned.addr() < moved.addr() + 4);
+        assert_eq!(aligned.align_up(4), aligned);
+        assert_eq!(aligned.len(), 893);
+        assert_eq!(moved.align_up(1), moved);
+
+        // provenance is kept through all of the above
+        let target: WidePointer = base.byte_add(3).map_addr(|addr: usize| addr + 1).align_up(1);
+        unsafe { *(target.ptr as *mut u8) = 114 };
+        assert_eq!(array[4], 114);
+    }
+
+    #[test]
+    #[should_panic(expected = "alignment 3 is not a power of two")]
+    fn test_align_up_non_power_of_two() {
+        WidePointer::dangling_for::<u8>().align_up(3);
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "`WidePointer::byte_add` overflowed")]
+    fn test_byte_add_overflow() {
+        WidePointer::null().with_addr(usize::MAX).byte_add(1);
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "`WidePointer::align_up` overflowed")]
+    fn test_align_up_overflow() {
+        WidePointer::null().with_addr(usize::MAX - 2).align_up(8);
+    }
+
+    #[cfg(not(debug_assertions))]
+    #[test]
+    fn test_addr_arithmetic_wrapping() {
+        let last: WidePointer = WidePointer::null().with_addr(usize::MAX);
+        assert_eq!(last.byte_add(2).addr(), 1);
+        assert_eq!(last.align_up(8).addr(), 0);
+        assert_eq!(WidePointer::null().byte_offset_from(last), 1);
+    }
+
+    #[test]
+    fn test_same_metadata() {
+        use std::fmt::Debug;
+
+        let (a, b, c): (i32, i32, &str) = (114, 514, "1919");
+        let erase = |value: &dyn Debug| WidePointer::from_ptr(value as *const dyn Debug);
+        let (a, b, c): (WidePointer, WidePointer, WidePointer) = (erase(&a), erase(&b), erase(&c));
+
+        // Miri deliberately hands out distinct vtables for the same type, which is exactly why
+        // this is only a heuristic
+        if !cfg!(miri) {
+            assert!(a.same_metadata(&b) && a.likely_same_concrete_type(&b));
+        }
+        assert!(!a.same_metadata(&c) && !a.likely_same_concrete_type(&c));
+        assert!(!a.same_object(&b));
+        assert!(a.same_object(&a));
+
+        let array: [u8; 4] = [11, 45, 14, 19];
+        let head: WidePointer = WidePointer::from_slice(&array[..2]);
+        let tail: WidePointer = WidePointer::from_slice(&array[2..]);
+        assert!(head.same_metadata(&tail) && !head.same_object(&tail));
+        assert!(!head.same_object(&WidePointer::from_slice(&array[..3])));
+    }
+
+    #[test]
+    fn test_drop_in_place_as() {
+        use std::cell::Cell;
+        use std::mem::ManuallyDrop;
+
+        trait Shape {}
+
+        struct Square<'a>(&'a Cell<u32>);
+        struct Circle<'a>(&'a Cell<u32>);
+
+        impl Shape for Square<'_> {}
+        impl Shape for Circle<'_> {}
+
+        impl Drop for Square<'_> {
+            fn drop(&mut self) {
+                self.0.set(self.0.get() + 1);
+            }
+        }
+
+        impl Drop for Circle<'_> {
+            fn drop(&mut self) {
+                self.0.set(self.0.get() + 100);
+            }
+        }
+
+        let counter: Cell<u32> = Cell::new(0);
+        let mut squares: ManuallyDrop<[Square; 2]> =
+            ManuallyDrop::new([Square(&counter), Square(&counter)]);
+        let slice: WidePointer = WidePointer::from_mut_slice(&mut squares[..]);
+        unsafe { slice.drop_in_place_as::<[Square]>() };
+        assert_eq!(counter.get(), 2);
+
+        let mut square: ManuallyDrop<Square> = ManuallyDrop::new(Square(&counter));
+        let mut circle: ManuallyDrop<Circle> = ManuallyDrop::new(Circle(&counter));
+        let square: WidePointer =
+            WidePointer::from_mut_ptr(&mut *square as &mut dyn Shape as *mut dyn Shape);
+        let circle: WidePointer =
+            WidePointer::from_mut_ptr(&mut *circle as &mut dyn Shape as *mut dyn Shape);
+        assert!(!square.likely_same_concrete_type(&circle));
+
+        unsafe { circle.drop_in_place_as::<dyn Shape>() };
+        assert_eq!(counter.get(), 102);
+        unsafe { square.drop_in_place_as::<dyn Shape>() };
+        assert_eq!(counter.get(), 103);
+    }
+
+    #[test]
+    fn test_tag() {
+        let mut array: [u64; 3] = [114, 514, 1919];
+        let wide_ptr: WidePointer = WidePointer::from_mut_slice(&mut array[..]);
+
+        let tagged: WidePointer = wide_ptr.with_tag(0b101, 0b111);
+        assert_eq!(tagged.tag(0b111), 0b101);
+        assert_eq!(tagged.tag(0b100), 0b100);
+        assert_eq!(tagged.len(), 3);
+        assert_eq!(tagged.addr(), wide_ptr.addr() | 0b101);
+        assert_eq!(tagged.untagged(0b111), wide_ptr);
+
+        let tagged: WidePointer = wide_ptr.with_tag_bits::<3>(6);
+        assert_eq!(tagged.tag_bits::<3>(), 6);
+        assert_eq!(tagged.tag_bits::<1>(), 0);
+        assert_eq!(wide_ptr.with_tag_bits::<0>(0), wide_ptr);
+
+        // nothing was corrupted on the way
+        let untagged: WidePointer = tagged.untagged_bits::<3>();
+        unsafe { untagged.as_mut_slice::<u64>()[2] = 810 };
+        assert_eq!(unsafe { untagged.as_slice::<u64>() }, &[114, 514, 810]);
+    }
+
+    #[test]
+    #[should_panic(expected = "tag 0x8 does not fit in 3 bits")]
+    fn test_tag_too_large() {
+        WidePointer::dangling_for::<u64>().untagged_bits::<3>().with_tag_bits::<3>(8);
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "which already has bits in mask 0x3")]
+    fn test_tag_occupied() {
+        WidePointer::dangling_for::<u8>().with_addr(0x1001).with_tag(2, 0b11);
+    }
+
+    #[cfg(feature = "nightly-metadata")]
+    #[test]
+    fn test_metadata() {
+        use std::fmt::Display;
+        use std::ptr::DynMetadata;
+
+        let array: [u16; 3] = [114, 514, 1919];
+        let slice: *const [u16] = &array as *const [u16];
+        let wide_ptr: WidePointer = WidePointer::from_raw_parts::<[u16]>(array.as_ptr().cast(), 3);
+        assert_eq!(wide_ptr, WidePointer::from_ptr(slice));
+        assert_eq!(unsafe { wide_ptr.metadata::<[u16]>() }, 3);
+        assert_eq!(unsafe { wide_ptr.to_raw_parts::<[u16]>() }, (slice as *const (), 3));
+
+        let value: i32 = 893;
+        let display: *const dyn Display = &value as &dyn Display as *const dyn Display;
+        let meta: DynMetadata<dyn Display> = std::ptr::metadata(display);
+        let wide_ptr: WidePointer =
+            WidePointer::from_raw_parts::<dyn Display>(display.cast(), meta);
+        assert_eq!(wide_ptr, WidePointer::from_ptr(display));
+        assert_eq!(wide_ptr.ptr, &value as *const i32 as *const ());
+        assert_eq!(meta.size_of(), std::mem::size_of::<i32>());
+
+        assert_eq!(unsafe { wide_ptr.metadata::<dyn Display>() }, meta);
+        let (data, meta2) = unsafe { wide_ptr.to_raw_parts::<dyn Display>() };
+        assert_eq!((data, meta2), (display as *const (), meta));
+
+        let thin_ptr: *const i32 = &value as *const i32;
+        let thin: WidePointer = WidePointer::from_raw_parts::<i32>(thin_ptr.cast(), ());
+        assert_eq!(thin, WidePointer::from_ptr(thin_ptr));
+        assert_eq!(unsafe { thin.metadata::<i32>() }, ());
+    }
+
+    #[test]
+    fn test_bytes_round_trip() {
+        let mut array: [u32; 4] = [114, 514, 1919, 810];
+        let values: [WidePointer; 3] = [
+            WidePointer::from_slice(&array[1..]),
+            WidePointer::null(),
+            WidePointer::new_slice(std::ptr::null(), usize::MAX)
+        ];
+
+        const SIZE: usize = 2 * std::mem::size_of::<usize>();
+        let mut buffer: Vec<u8> = Vec::new();
+        for value in values.iter() {
+            buffer.extend_from_slice(&value.as_bytes());
+        }
+        assert_eq!(buffer.len(), 3 * SIZE);
+        assert_eq!(buffer[SIZE / 2..SIZE], 3_usize.to_ne_bytes());
+
+        for (chunk, value) in buffer.chunks_exact(SIZE).zip(values.iter()) {
+            let mut bytes: [u8; SIZE] = [0; SIZE];
+            bytes.copy_from_slice(chunk);
+            let read: WidePointer = WidePointer::from_bytes(bytes);
+            assert_eq!((read.addr(), read.len()), (value.addr(), value.len()));
+        }
+
+        // provenance has to be restored from the original object before dereferencing
+        let read: WidePointer = WidePointer::from_bytes(values[0].as_bytes());
+        let base: WidePointer = WidePointer::from_mut_slice(&mut array[..]);
+        let restored: WidePointer = base.with_addr(read.addr());
+        let restored: WidePointer = WidePointer::new_slice(restored.ptr, read.len());
+        assert_eq!(unsafe { restored.as_slice::<u32>() }, &[514, 1919, 810]);
+    }
+
+    #[cfg(feature = "bytemuck")]
+    #[test]
+    fn test_bytemuck() {
+        let array: [u8; 3] = [11, 45, 14];
+        let values: [WidePointer; 2] = [WidePointer::from_slice(&array[..]), WidePointer::null()];
+
+        let bytes: &[u8] = bytemuck::cast_slice(&values[..]);
+        assert_eq!(bytes[..2 * std::mem::size_of::<usize>()], values[0].as_bytes());
+        let read: &[WidePointer] = bytemuck::cast_slice(bytes);
+        assert_eq!(read.len(), 2);
+        assert_eq!((read[0].addr(), read[0].len()), (values[0].addr(), 3));
+        assert!(read[1].is_null());
+
+        let zeroed: WidePointer = bytemuck::Zeroable::zeroed();
+        assert_eq!(zeroed, WidePointer::null());
+    }
+
+    #[cfg(feature = "arbitrary")]
+    #[test]
+    fn test_arbitrary() {
+        use arbitrary::{Arbitrary, Unstructured};
+
+        let bytes: Vec<u8> = (1..=32).collect();
+        let mut u: Unstructured = Unstructured::new(&bytes);
+        let wide_ptr: WidePointer = WidePointer::arbitrary(&mut u).unwrap();
+        let mut u: Unstructured = Unstructured::new(&bytes);
+        let (addr, trivia): (usize, usize) = Arbitrary::arbitrary(&mut u).unwrap();
+        assert_eq!(wide_ptr.addr(), addr);
+        assert_eq!(wide_ptr.len(), trivia);
 
-        assert_eq!(wide_ptr.ptr, ptr as usize);
-        assert_eq!(wide_ptr.trivia, 4);
+        let mut u: Unstructured = Unstructured::new(&[]);
+        assert_eq!(WidePointer::arbitrary(&mut u).unwrap(), WidePointer::null());
     }
 }
@@ -0,0 +1,412 @@
+//! Atomic `WidePointer`
+
+use core::fmt::{Debug, Formatter};
+use core::sync::atomic::Ordering;
+
+use crate::wide_ptr::WidePointer;
+
+/// A `WidePointer` which can be safely shared between threads
+///
+/// On x86_64 with the `cmpxchg16b` target feature enabled (for example through
+/// `-C target-cpu=native`) and on aarch64, operations are lock-free double-word atomics. Elsewhere,
+/// and under Miri, the value is protected by a spin lock. `IS_LOCK_FREE` tells which
+/// implementation is in use.
+///
+/// All operations are sequentially consistent. `Ordering` arguments are accepted for parity with
+/// `core::sync::atomic` and never weaken the guarantees.
+pub struct AtomicWidePointer {
+    cell: imp::Cell
+}
+
+unsafe impl Send for AtomicWidePointer {}
+unsafe impl Sync for AtomicWidePointer {}
+
+impl AtomicWidePointer {
+    pub const IS_LOCK_FREE: bool = imp::IS_LOCK_FREE;
+
+    pub const fn new(value: WidePointer) -> Self {
+        Self { cell: imp::Cell::new(value) }
+    }
+
+    pub fn into_inner(self) -> WidePointer {
+        self.cell.into_inner()
+    }
+
+    pub fn get_mut(&mut self) -> &mut WidePointer {
+        self.cell.get_mut()
+    }
+
+    pub fn compare_exchange(
+        &self,
+        current: WidePointer,
+        new: WidePointer,
+        _success: Ordering,
+        _failure: Ordering
+    ) -> Result<WidePointer, WidePointer> {
+        self.cell.compare_exchange(current, new)
+    }
+
+    /// Never fails spuriously, this is the same as `compare_exchange`
+    #[inline] pub fn compare_exchange_weak(
+        &self,
+        current: WidePointer,
+        new: WidePointer,
+        success: Ordering,
+        failure: Ordering
+    ) -> Result<WidePointer, WidePointer> {
+        self.compare_exchange(current, new, success, failure)
+    }
+
+    pub fn load(&self, order: Ordering) -> WidePointer {
+        // writing back the same value makes the exchange observably a pure load
+        let guess: WidePointer = WidePointer::null();
+        match self.compare_exchange(guess, guess, order, order) {
+            Ok(previous) | Err(previous) => previous
+        }
+    }
+
+    pub fn swap(&self, value: WidePointer, order: Ordering) -> WidePointer {
+        let mut current: WidePointer = WidePointer::null();
+        loop {
+            match self.compare_exchange(current, value, order, order) {
+                Ok(previous) => return previous,
+                Err(previous) => current = previous
+            }
+        }
+    }
+
+    pub fn store(&self, value: WidePointer, order: Ordering) {
+        self.swap(value, order);
+    }
+}
+
+impl Debug for AtomicWidePointer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        Debug::fmt(&self.load(Ordering::SeqCst), f)
+    }
+}
+
+impl Default for AtomicWidePointer {
+    fn default() -> Self {
+        Self::new(WidePointer::null())
+    }
+}
+
+impl From<WidePointer> for AtomicWidePointer {
+    fn from(value: WidePointer) -> Self {
+        Self::new(value)
+    }
+}
+
+#[cfg(all(not(miri), target_arch = "x86_64", target_feature = "cmpxchg16b"))]
+mod imp {
+    use core::arch::asm;
+    use core::cell::UnsafeCell;
+
+    use crate::wide_ptr::WidePointer;
+
+    pub(super) const IS_LOCK_FREE: bool = true;
+
+    #[repr(C, align(16))]
+    pub(super) struct Cell(UnsafeCell<WidePointer>);
+
+    impl Cell {
+        pub(super) const fn new(value: WidePointer) -> Self {
+            Self(UnsafeCell::new(value))
+        }
+
+        pub(super) fn into_inner(self) -> WidePointer {
+            self.0.into_inner()
+        }
+
+        pub(super) fn get_mut(&mut self) -> &mut WidePointer {
+            self.0.get_mut()
+        }
+
+        pub(super) fn compare_exchange(
+            &self,
+            current: WidePointer,
+            new: WidePointer
+        ) -> Result<WidePointer, WidePointer> {
+            // SAFETY: the cell is 16-byte aligned and only accessed atomically while shared
+            let (previous, ok): (WidePointer, bool) = unsafe {
+                compare_exchange(self.0.get(), current, new)
+            };
+            if ok { Ok(previous) } else { Err(previous) }
+        }
+    }
+
+    /// # Safety
+    /// `dst` must be valid for reads and writes, 16-byte aligned and only accessed atomically.
+    #[inline] unsafe fn compare_exchange(
+        dst: *mut WidePointer,
+        current: WidePointer,
+        new: WidePointer
+    ) -> (WidePointer, bool) {
+        let previous_ptr: *const ();
+        let previous_trivia: *const ();
+        let ok: u8;
+        // `rbx` is reserved by LLVM, so the low word of `new` is swapped in and out of it
+        asm!(
+            "xchg {new_ptr}, rbx",
+            "lock cmpxchg16b xmmword ptr [{dst}]",
+            "sete {ok}",
+            "mov rbx, {new_ptr}",
+            dst = in(reg) dst,
+            new_ptr = inout(reg) new.ptr => _,
+            ok = out(reg_byte) ok,
+            inout("rax") current.ptr => previous_ptr,
+            inout("rdx") current.trivia => previous_trivia,
+            in("rcx") new.trivia,
+            options(nostack)
+        );
+        (WidePointer::new(previous_ptr, previous_trivia), ok != 0)
+    }
+}
+
+#[cfg(all(not(miri), target_arch = "aarch64", target_pointer_width = "64"))]
+mod imp {
+    use core::arch::asm;
+    use core::cell::UnsafeCell;
+
+    use crate::wide_ptr::WidePointer;
+
+    pub(super) const IS_LOCK_FREE: bool = true;
+
+    #[repr(C, align(16))]
+    pub(super) struct Cell(UnsafeCell<WidePointer>);
+
+    impl Cell {
+        pub(super) const fn new(value: WidePointer) -> Self {
+            Self(UnsafeCell::new(value))
+        }
+
+        pub(super) fn into_inner(self) -> WidePointer {
+            self.0.into_inner()
+        }
+
+        pub(super) fn get_mut(&mut self) -> &mut WidePointer {
+            self.0.get_mut()
+        }
+
+        pub(super) fn compare_exchange(
+            &self,
+            current: WidePointer,
+            new: WidePointer
+        ) -> Result<WidePointer, WidePointer> {
+            // SAFETY: the cell is 16-byte aligned and only accessed atomically while shared
+            let (previous, ok): (WidePointer, bool) = unsafe {
+                compare_exchange(self.0.get(), current, new)
+            };
+            if ok { Ok(previous) } else { Err(previous) }
+        }
+    }
+
+    /// # Safety
+    /// `dst` must be valid for reads and writes, 16-byte aligned and only accessed atomically.
+    #[inline] unsafe fn compare_exchange(
+        dst: *mut WidePointer,
+        current: WidePointer,
+        new: WidePointer
+    ) -> (WidePointer, bool) {
+        let previous_ptr: *const ();
+        let previous_trivia: *const ();
+        let ok: usize;
+        // a pair load is only single-copy atomic once the following store-exclusive succeeds, so
+        // the loaded value is written back when the comparison fails
+        asm!(
+            "2:",
+            "ldaxp {prev_ptr}, {prev_trivia}, [{dst}]",
+            "cmp {prev_ptr}, {cur_ptr}",
+            "ccmp {prev_trivia}, {cur_trivia}, #0, eq",
+            "csel {tmp_ptr}, {new_ptr}, {prev_ptr}, eq",
+            "csel {tmp_trivia}, {new_trivia}, {prev_trivia}, eq",
+            "cset {ok}, eq",
+            "stlxp {status:w}, {tmp_ptr}, {tmp_trivia}, [{dst}]",
+            "cbnz {status:w}, 2b",
+            dst = in(reg) dst,
+            cur_ptr = in(reg) current.ptr,
+            cur_trivia = in(reg) current.trivia,
+            new_ptr = in(reg) new.ptr,
+            new_trivia = in(reg) new.trivia,
+            prev_ptr = out(reg) previous_ptr,
+            prev_trivia = out(reg) previous_trivia,
+            tmp_ptr = out(reg) _,
+            tmp_trivia = out(reg) _,
+            ok = out(reg) ok,
+            status = out(reg) _,
+            options(nostack)
+        );
+        (WidePointer::new(previous_ptr, previous_trivia), ok != 0)
+    }
+}
+
+#[cfg(not(all(
+    not(miri),
+    any(
+        all(target_arch = "x86_64", target_feature = "cmpxchg16b"),
+        all(target_arch = "aarch64", target_pointer_width = "64")
+    )
+)))]
+mod imp {
+    use crate::spin::{SpinGuard, SpinLock};
+    use crate::wide_ptr::WidePointer;
+
+    pub(super) const IS_LOCK_FREE: bool = false;
+
+    pub(super) struct Cell(SpinLock<WidePointer>);
+
+    impl Cell {
+        pub(super) const fn new(value: WidePointer) -> Self {
+            Self(SpinLock::new(value))
+        }
+
+        pub(super) fn into_inner(self) -> WidePointer {
+            self.0.into_inner()
+        }
+
+        pub(super) fn get_mut(&mut self) -> &mut WidePointer {
+            self.0.get_mut()
+        }
+
+        pub(super) fn compare_exchange(
+            &self,
+            current: WidePointer,
+            new: WidePointer
+        ) -> Result<WidePointer, WidePointer> {
+            let mut value: SpinGuard<WidePointer> = self.0.lock();
+            let previous: WidePointer = *value;
+            if previous == current {
+                *value = new;
+                Ok(previous)
+            } else {
+                Err(previous)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::sync::atomic::Ordering;
+    use std::thread;
+
+    use crate::wide_ptr::{AtomicWidePointer, WidePointer};
+
+    #[test]
+    fn test_atomic_wide_pointer_basic() {
+        let array: [u8; 4] = [11, 45, 14, 19];
+        let a: WidePointer = WidePointer::from_slice(&array[..]);
+        let b: WidePointer = WidePointer::from_slice(&array[1..3]);
+
+        let atomic: AtomicWidePointer = AtomicWidePointer::new(a);
+        assert_eq!(atomic.load(Ordering::SeqCst), a);
+        assert_eq!(atomic.swap(b, Ordering::AcqRel), a);
+        assert_eq!(atomic.load(Ordering::Acquire), b);
+
+        assert_eq!(atomic.compare_exchange(a, a, Ordering::SeqCst, Ordering::SeqCst), Err(b));
+        assert_eq!(atomic.compare_exchange(b, a, Ordering::SeqCst, Ordering::SeqCst), Ok(b));
+        assert_eq!(atomic.compare_exchange_weak(a, b, Ordering::AcqRel, Ordering::Relaxed), Ok(a));
+
+        // same data pointer, different length
+        let c: WidePointer = WidePointer::new_slice(b.ptr, 1);
+        assert_eq!(atomic.compare_exchange(c, a, Ordering::SeqCst, Ordering::SeqCst), Err(b));
+
+        atomic.store(WidePointer::null(), Ordering::Release);
+        assert_eq!(atomic.load(Ordering::SeqCst), WidePointer::null());
+        let mut atomic: AtomicWidePointer = AtomicWidePointer::default();
+        *atomic.get_mut() = c;
+        assert_eq!(atomic.into_inner(), c);
+    }
+
+    /// Only compared against, never dereferenced
+    #[derive(Clone, Copy)]
+    struct Values(WidePointer, WidePointer);
+
+    unsafe impl Send for Values {}
+
+    #[test]
+    fn test_atomic_wide_pointer_no_tearing() {
+        const THREADS: usize = 4;
+        const ROUNDS: usize = if cfg!(miri) { 50 } else { 100000 };
+
+        // every word differs between the two values, so a torn read matches neither
+        let array: [u64; 8] = [0; 8];
+        let a: WidePointer = WidePointer::from_slice(&array[..2]);
+        let b: WidePointer = WidePointer::from_slice(&array[4..]);
+        let values: Values =
+            Values(WidePointer::new_slice(a.ptr, usize::MAX), WidePointer::new_slice(b.ptr, 0));
+
+        let atomic: Arc<AtomicWidePointer> = Arc::new(AtomicWidePointer::new(values.0));
+        let handles: Vec<thread::JoinHandle<()>> = (0..THREADS).map(|i| {
+            let atomic: Arc<AtomicWidePointer> = atomic.clone();
+            thread::spawn(move || {
+                let Values(a, b) = values;
+                for round in 0..ROUNDS {
+                    let value: WidePointer = atomic.load(Ordering::Acquire);
+                    assert!(value == a || value == b, "torn read: {:?}", value);
+                    let other: WidePointer = if value == a { b } else { a };
+                    match (i + round) % 3 {
+                        0 => atomic.store(other, Ordering::Release),
+                        1 => {
+                            let previous: WidePointer = atomic.swap(other, Ordering::AcqRel);
+                            assert!(previous == a || previous == b, "torn read: {:?}", previous);
+                        },
+                        _ => {
+                            let result: Result<WidePointer, WidePointer> = atomic.compare_exchange(
+                                value,
+                                other,
+                                Ordering::AcqRel,
+                                Ordering::Acquire
+                            );
+                            let previous: WidePointer = result.unwrap_or_else(|p| p);
+                            assert!(previous == a || previous == b, "torn read: {:?}", previous);
+                        }
+                    }
+                }
+            })
+        }).collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_atomic_wide_pointer_cas_counter() {
+        const THREADS: usize = 4;
+        const ROUNDS: usize = if cfg!(miri) { 50 } else { 10000 };
+
+        let value: u32 = 893;
+        let base: WidePointer = WidePointer::from_ptr(&value as *const u32);
+        let atomic: Arc<AtomicWidePointer> = Arc::new(AtomicWidePointer::new(base));
+        let handles: Vec<thread::JoinHandle<()>> = (0..THREADS).map(|_| {
+            let atomic: Arc<AtomicWidePointer> = atomic.clone();
+            thread::spawn(move || {
+                for _ in 0..ROUNDS {
+                    let mut current: WidePointer = atomic.load(Ordering::Relaxed);
+                    loop {
+                        let next: WidePointer =
+                            WidePointer::new_slice(current.ptr, current.len() + 1);
+                        match atomic.compare_exchange_weak(
+                            current,
+                            next,
+                            Ordering::AcqRel,
+                            Ordering::Relaxed
+                        ) {
+                            Ok(_) => break,
+                            Err(previous) => current = previous
+                        }
+                    }
+                }
+            })
+        }).collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let result: WidePointer = atomic.load(Ordering::SeqCst);
+        assert_eq!(result.ptr, base.ptr);
+        assert_eq!(result.len(), THREADS * ROUNDS);
+    }
+}
@@ -0,0 +1,239 @@
+//! Compile-time checks that `const fn` APIs stay usable in `const` contexts
+//!
+//! Each item below only compiles if the functions it calls are `const`, so accidentally removing
+//! `const` from any of them breaks the build of this test.
+
+#[cfg(feature = "either")]
+mod either {
+    use xjbutil::either::Either;
+
+    const LEFT: Either<i32, &str> = Either::Left(114);
+
+    const _: () = assert!(LEFT.is_left() && !LEFT.is_right());
+    const _: () = assert!(matches!(LEFT.as_ref(), Either::Left(&114)));
+
+    #[cfg(feature = "const-mut")]
+    const _: () = {
+        let mut either: Either<i32, &str> = Either::Left(114);
+        if let Either::Left(value) = either.as_mut() {
+            *value += 400;
+        }
+        assert!(matches!(either, Either::Left(514)));
+    };
+}
+
+#[cfg(feature = "void")]
+mod void {
+    use xjbutil::void::{Void, absurd};
+
+    #[allow(dead_code)]
+    const fn never(v: Void) -> i32 {
+        absurd(v)
+    }
+}
+
+#[cfg(feature = "value")]
+mod value {
+    use xjbutil::value::Value;
+
+    const NIL: &Value = &Value::Nil;
+
+    const _: () = assert!(NIL.is_nil() && !NIL.is_bool() && !NIL.is_int() && !NIL.is_float());
+    const _: () = assert!(!NIL.is_string() && !NIL.is_array() && !NIL.is_object());
+}
+
+#[cfg(feature = "display2")]
+mod display2 {
+    use std::fmt::Formatter;
+
+    use xjbutil::display2::{Display2, Display2Wrapper};
+
+    struct Unit;
+
+    impl Display2 for Unit {
+        fn fmt2(&self, fmt: &mut Formatter<'_>) -> std::fmt::Result {
+            fmt.write_str("unit")
+        }
+    }
+
+    const _: Display2Wrapper<'static, Unit> = Display2Wrapper::new(&Unit);
+}
+
+#[cfg(feature = "std-ext")]
+mod std_ext {
+    use xjbutil::std_ext::extend_lifetime;
+
+    const _: &str = unsafe { extend_lifetime("114514") };
+}
+
+#[cfg(feature = "korobka")]
+mod korobka {
+    use xjbutil::korobka::SmallKorobka;
+
+    const fn is_inline(korobka: &SmallKorobka<u32, 8>) -> bool {
+        korobka.is_inline()
+    }
+
+    #[cfg(feature = "const-mut")]
+    const fn as_mut_ptr(korobka: &mut xjbutil::korobka::Korobka<u32>) -> *mut u32 {
+        korobka.as_mut_ptr()
+    }
+
+    #[test]
+    fn test_korobka_const_fn() {
+        assert!(is_inline(&SmallKorobka::new(893)));
+        #[cfg(feature = "const-mut")]
+        {
+            let mut korobka: xjbutil::korobka::Korobka<u32> = xjbutil::korobka::Korobka::new(893);
+            assert_eq!(unsafe { *as_mut_ptr(&mut korobka) }, 893);
+        }
+    }
+}
+
+#[cfg(feature = "maybe-owned")]
+mod maybe_owned {
+    use xjbutil::maybe_owned::MaybeOwned;
+
+    const BORROWED: MaybeOwned<'static, i32> = MaybeOwned::Borrowed(&114);
+
+    const _: () = assert!(BORROWED.is_borrowed() && !BORROWED.is_owned());
+}
+
+#[cfg(feature = "unchecked")]
+mod unchecked {
+    use xjbutil::unchecked::{UncheckedSend, UncheckedSendSync};
+
+    #[cfg(feature = "const-mut")] use xjbutil::unchecked::UncheckedOption;
+
+    const SEND: UncheckedSend<i32> = unsafe { UncheckedSend::new(114) };
+    const SEND_SYNC: UncheckedSendSync<i32> = unsafe { UncheckedSendSync::new(514) };
+
+    const _: () = assert!(*SEND.as_ref() == 114 && *SEND_SYNC.as_ref() == 514);
+    const _: *const i32 = SEND.as_ptr();
+    const _: *const i32 = SEND_SYNC.as_ptr();
+
+    #[cfg(feature = "const-mut")]
+    const _: () = {
+        let mut send: UncheckedSend<i32> = unsafe { UncheckedSend::new(114) };
+        *send.as_mut() += 400;
+        let _: *mut i32 = send.as_mut_ptr();
+        let mut send_sync: UncheckedSendSync<i32> = unsafe { UncheckedSendSync::new(1919) };
+        *send_sync.as_mut() -= 1109;
+        let _: *mut i32 = send_sync.as_mut_ptr();
+        assert!(*send.as_ref() == 514 && *send_sync.as_ref() == 810);
+    };
+
+    #[cfg(feature = "const-mut")]
+    static TABLE: [UncheckedOption<i32>; 3] = {
+        let mut table: [UncheckedOption<i32>; 3] = [
+            UncheckedOption::new(114),
+            UncheckedOption::new_none(),
+            UncheckedOption::new_none()
+        ];
+        unsafe {
+            let value: i32 = table[0].take();
+            table[1].set(value + 400);
+            table[2].set(1919);
+            *table[2].get_mut() -= 1109;
+            assert!(*table[1].get_ref() == 514);
+        }
+        table
+    };
+
+    #[cfg(feature = "const-mut")]
+    #[test]
+    fn test_unchecked_option_const_fn() {
+        assert_eq!(unsafe { *TABLE[1].get_ref() }, 514);
+        assert_eq!(unsafe { *TABLE[2].get_ref() }, 810);
+    }
+}
+
+#[cfg(feature = "handle-map")]
+mod handle_map {
+    use xjbutil::handle_map::{Handle, HandleMap};
+
+    const HANDLE: Handle = Handle::from_raw((893 << 32) | 114);
+
+    const _: () = assert!(HANDLE.index() == 114 && HANDLE.generation() == 893);
+    const _: () = assert!(HANDLE.to_raw() == (893 << 32) | 114);
+
+    const fn check(map: &HandleMap<i32>) -> (bool, usize) {
+        (map.is_empty(), map.len())
+    }
+
+    #[test]
+    fn test_handle_map_const_fn() {
+        assert_eq!(check(&HandleMap::new()), (true, 0));
+    }
+}
+
+#[cfg(feature = "intrusive")]
+mod intrusive {
+    use xjbutil::intrusive::{Adapter, SList, SListLink};
+
+    struct Node {
+        link: SListLink<Node>
+    }
+
+    struct NodeAdapter;
+
+    unsafe impl Adapter<Node> for NodeAdapter {
+        fn link(node: &Node) -> &SListLink<Node> {
+            &node.link
+        }
+    }
+
+    const fn check(list: &SList<Node, NodeAdapter>) -> (bool, usize) {
+        (list.is_empty() && list.front().is_none(), list.len())
+    }
+
+    #[test]
+    fn test_slist_const_fn() {
+        assert_eq!(check(&SList::new()), (true, 0));
+    }
+}
+
+#[cfg(all(feature = "spin", feature = "const-mut"))]
+mod spin {
+    use xjbutil::spin::{SeqLock, SpinLock, SpinRwLock};
+
+    const _: () = {
+        let mut lock: SpinLock<i32> = SpinLock::new(114);
+        *lock.get_mut() += 400;
+        let mut rw_lock: SpinRwLock<i32> = SpinRwLock::new(1919);
+        *rw_lock.get_mut() -= 1109;
+        let mut seq_lock: SeqLock<i32> = SeqLock::new(0);
+        *seq_lock.get_mut() = 893;
+        assert!(*lock.get_mut() == 514);
+        assert!(*rw_lock.get_mut() == 810);
+        assert!(seq_lock.into_inner() == 893);
+    };
+}
+
+#[cfg(feature = "defer")]
+mod defer {
+    use xjbutil::defer::DropBomb;
+
+    const fn check(bomb: &DropBomb) -> bool {
+        !bomb.is_defused()
+    }
+
+    #[test]
+    fn test_drop_bomb_const_fn() {
+        let mut bomb: DropBomb = DropBomb::new("114514");
+        assert!(check(&bomb));
+        bomb.defuse();
+    }
+}
+
+#[cfg(feature = "wide_ptr")]
+mod wide_ptr {
+    use xjbutil::wide_ptr::WidePointer;
+
+    const NULL: WidePointer = WidePointer::null();
+    const SLICE: WidePointer = WidePointer::new_slice(core::ptr::null(), 3);
+
+    const _: () = assert!(NULL.is_null() && NULL.is_empty() && NULL.addr() == 0);
+    const _: () = assert!(SLICE.is_null() && SLICE.len() == 3 && !SLICE.is_empty());
+    const _: () = assert!(SLICE.same_object(&SLICE) && !NULL.same_metadata(&SLICE));
+}
@@ -0,0 +1,30 @@
+#![no_std]
+
+extern crate alloc;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use xjbutil::either::Either;
+use xjbutil::korobka::Korobka;
+
+#[test]
+fn test_korobka_no_std() {
+    let korobka: Korobka<String> = Korobka::new(String::from("114514"));
+    assert_eq!(korobka.as_ref(), "114514");
+
+    let boxed: Korobka<[i32]> = Korobka::from(alloc::vec![1919, 810].into_boxed_slice());
+    assert_eq!(boxed.len(), 2);
+
+    let v: Vec<Korobka<String>> = alloc::vec![korobka];
+    assert_eq!(v[0].as_str(), "114514");
+}
+
+#[test]
+fn test_either_no_std() {
+    let values: [Either<i32, &str>; 2] = [Either::Left(893), Either::Right("893")];
+    let lefts: i32 = values.iter()
+        .map(|e| if let Either::Left(x) = e { *x } else { 0 })
+        .sum();
+    assert_eq!(lefts, 893);
+}